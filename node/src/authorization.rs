@@ -0,0 +1,81 @@
+//! Authorized nodes of `pallet_node_authorization`, followed by the node itself when its offchain
+//! workers, which otherwise restrict the peers to them, are disabled.
+
+use crate::{events::storage_value_key, service::FullClient};
+use codec::{Decode, Encode};
+use futures::StreamExt;
+use node_template_runtime::Hash;
+use sc_client_api::{BlockchainEvents, StorageProvider};
+use sc_network::{NetworkPeers, NetworkStateInfo, PeerId};
+use sp_blockchain::HeaderBackend;
+use sp_core::{
+	hashing::{blake2_128, twox_128},
+	storage::StorageKey,
+};
+use std::{
+	collections::{BTreeSet, HashSet},
+	sync::Arc,
+};
+
+/// Name of the node authorization pallet in `construct_runtime!`.
+const PALLET: &[u8] = b"NodeAuthorization";
+
+fn read<T: Decode + Default>(
+	client: &FullClient,
+	hash: Hash,
+	key: &StorageKey,
+) -> sp_blockchain::Result<T> {
+	match client.storage(hash, key)? {
+		Some(data) => T::decode(&mut &data.0[..]).map_err(|e| {
+			sp_blockchain::Error::Backend(format!("Undecodable authorized nodes: {}", e))
+		}),
+		None => Ok(T::default()),
+	}
+}
+
+/// Peers of the node `local` at the block `hash`, as the offchain worker of the pallet computes
+/// them: its additional connections and, if it is a well-known node, the other well-known nodes.
+pub fn authorized_peers(
+	client: &FullClient,
+	hash: Hash,
+	local: &PeerId,
+) -> sp_blockchain::Result<HashSet<PeerId>> {
+	let local = local.to_bytes();
+	let connections = StorageKey(
+		[
+			&twox_128(PALLET)[..],
+			&twox_128(b"AdditionalConnections"),
+			&blake2_128(&local.encode()),
+			&local.encode(),
+		]
+		.concat(),
+	);
+	let mut peers: BTreeSet<Vec<u8>> = read(client, hash, &connections)?;
+	let well_known: BTreeSet<Vec<u8>> =
+		read(client, hash, &storage_value_key(PALLET, b"WellKnownNodes"))?;
+	if well_known.contains(&local) {
+		peers.extend(well_known.into_iter().filter(|node| *node != local));
+	}
+	Ok(peers.iter().filter_map(|node| PeerId::from_bytes(node).ok()).collect())
+}
+
+/// Restrict the peers of `network` to the authorized nodes of the best block, after every new
+/// best block.
+pub async fn follow<N: NetworkPeers + NetworkStateInfo>(client: Arc<FullClient>, network: Arc<N>) {
+	let local = network.local_peer_id();
+	let apply = |hash| match authorized_peers(&client, hash, &local) {
+		Ok(peers) => {
+			network.set_authorized_peers(peers);
+			network.set_authorized_only(true);
+		},
+		Err(e) => log::warn!("Failed to read the authorized nodes at {}: {}", hash, e),
+	};
+
+	let mut imported = client.import_notification_stream();
+	apply(client.info().best_hash);
+	while let Some(block) = imported.next().await {
+		if block.is_new_best {
+			apply(block.hash);
+		}
+	}
+}
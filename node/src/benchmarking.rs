@@ -2,10 +2,16 @@
 //!
 //! Should only be used for benchmarking as it may break in other contexts.
 
-use crate::service::FullClient;
+use crate::{service::FullClient, storage};
 
 use node_template_runtime as runtime;
-use runtime::{AccountId, Balance, BalancesCall, SystemCall};
+use runtime::{
+	pallet_fedecom_ps_demo::{
+		self, FlexibilitySellingData, FLEXIBILITY_SELLING_STATE_NOT_DECIDED, PARTICIPANT_ROLE_DSO,
+		PARTICIPANT_ROLE_MARKET_OPERATOR, PARTICIPANT_ROLE_PROSUMER,
+	},
+	AccountId, Balance, BalancesCall, FedecomPSDemoConfig, Runtime, SystemCall,
+};
 use sc_cli::Result;
 use sc_client_api::BlockBackend;
 use sp_core::{Encode, Pair};
@@ -15,6 +21,9 @@ use sp_runtime::{OpaqueExtrinsic, SaturatedConversion};
 
 use std::{sync::Arc, time::Duration};
 
+/// Calls of the default instance of the flexibility market pallet.
+type FedecomPSDemoCall = pallet_fedecom_ps_demo::Call<Runtime>;
+
 /// Generates extrinsics for the `benchmark overhead` command.
 ///
 /// Note: Should only be used for benchmarking.
@@ -92,6 +101,221 @@ impl frame_benchmarking_cli::ExtrinsicBuilder for TransferKeepAliveBuilder {
 	}
 }
 
+/// Flexibility market identifier used by the fedecom extrinsic builders.
+const BENCHMARK_MARKET_IDENTIFIER: u32 = 0;
+
+/// Asset that Bob offers in the extrinsics of [`FlexibilitySellingBuilder`].
+const BENCHMARK_SELLING_ASSET: u32 = 1;
+
+/// Asset of the offers seeded for [`FlexibilityPurchaseDecisionBuilder`].
+const BENCHMARK_DECISION_ASSET: u32 = 2;
+
+/// Number of offers seeded for [`FlexibilityPurchaseDecisionBuilder`], one per market period from
+/// timestamp 0, more than the purchase decisions fitting in a block.
+const BENCHMARK_OFFERS: u32 = 10_000;
+
+/// Market participants of the `benchmark` chain: Alice is a DSO buying the flexibility of Bob, a
+/// prosumer owning the benchmark assets, on the market operated by Charlie.
+pub fn benchmark_genesis() -> FedecomPSDemoConfig {
+	let (alice, bob, charlie) = (
+		Sr25519Keyring::Alice.to_account_id(),
+		Sr25519Keyring::Bob.to_account_id(),
+		Sr25519Keyring::Charlie.to_account_id(),
+	);
+
+	FedecomPSDemoConfig {
+		participants: vec![
+			(alice, PARTICIPANT_ROLE_DSO),
+			(bob.clone(), PARTICIPANT_ROLE_PROSUMER),
+			(charlie.clone(), PARTICIPANT_ROLE_MARKET_OPERATOR),
+		],
+		markets: vec![(BENCHMARK_MARKET_IDENTIFIER, charlie)],
+		assets: vec![(BENCHMARK_SELLING_ASSET, bob.clone()), (BENCHMARK_DECISION_ASSET, bob)],
+		..Default::default()
+	}
+}
+
+/// Storage of the offers of Bob to Alice that [`FlexibilityPurchaseDecisionBuilder`] decides,
+/// with the number of offers of their periods, to add to the genesis storage of the `benchmark`
+/// chain.
+pub fn benchmark_offers() -> Vec<(Vec<u8>, Vec<u8>)> {
+	let (alice, bob) = (Sr25519Keyring::Alice.to_account_id(), Sr25519Keyring::Bob.to_account_id());
+	let data = FlexibilitySellingData {
+		sold_power: 10,
+		change_fct_w: 2,
+		state: FLEXIBILITY_SELLING_STATE_NOT_DECIDED,
+	};
+
+	(0..BENCHMARK_OFFERS)
+		.flat_map(|timestamp| {
+			let key = (
+				bob.clone(),
+				alice.clone(),
+				BENCHMARK_MARKET_IDENTIFIER,
+				timestamp,
+				BENCHMARK_DECISION_ASSET,
+			);
+			[
+				(storage::offer(&key), data.encode()),
+				(storage::period_offers(BENCHMARK_MARKET_IDENTIFIER, timestamp), 1u32.encode()),
+				(
+					storage::seller_offers(BENCHMARK_MARKET_IDENTIFIER, timestamp, &bob),
+					1u32.encode(),
+				),
+			]
+		})
+		.collect()
+}
+
+/// Generates `FedecomPSDemo::AddPayment` extrinsics for the benchmarks.
+///
+/// Every extrinsic is sent by Bob to `receiver` and uses its nonce as payment timestamp, so
+/// that no two extrinsics of a block touch the same payment.
+///
+/// Note: Should only be used for benchmarking.
+pub struct AddPaymentBuilder {
+	client: Arc<FullClient>,
+	receiver: AccountId,
+	value: u32,
+}
+
+impl AddPaymentBuilder {
+	/// Creates a new [`Self`] from the given client.
+	pub fn new(client: Arc<FullClient>, receiver: AccountId, value: u32) -> Self {
+		Self { client, receiver, value }
+	}
+}
+
+impl frame_benchmarking_cli::ExtrinsicBuilder for AddPaymentBuilder {
+	fn pallet(&self) -> &str {
+		"fedecom_ps_demo"
+	}
+
+	fn extrinsic(&self) -> &str {
+		"add_payment"
+	}
+
+	fn build(&self, nonce: u32) -> std::result::Result<OpaqueExtrinsic, &'static str> {
+		let acc = Sr25519Keyring::Bob.pair();
+		let extrinsic: OpaqueExtrinsic = create_benchmark_extrinsic(
+			self.client.as_ref(),
+			acc,
+			FedecomPSDemoCall::add_payment {
+				key_receiver: self.receiver.clone(),
+				ts: nonce,
+				value: self.value,
+			}
+			.into(),
+			nonce,
+		)
+		.into();
+
+		Ok(extrinsic)
+	}
+}
+
+/// Generates `FedecomPSDemo::FlexibilitySelling` extrinsics for the benchmarks.
+///
+/// Bob offers the flexibility of his benchmark asset to `buyer`, using the nonce as market
+/// timestamp so that every extrinsic creates a new offer in its own period, below the per-seller
+/// quota. The participants, market and asset are registered by the `benchmark` chain.
+///
+/// Note: Should only be used for benchmarking.
+pub struct FlexibilitySellingBuilder {
+	client: Arc<FullClient>,
+	buyer: AccountId,
+}
+
+impl FlexibilitySellingBuilder {
+	/// Creates a new [`Self`] from the given client.
+	pub fn new(client: Arc<FullClient>, buyer: AccountId) -> Self {
+		Self { client, buyer }
+	}
+}
+
+impl frame_benchmarking_cli::ExtrinsicBuilder for FlexibilitySellingBuilder {
+	fn pallet(&self) -> &str {
+		"fedecom_ps_demo"
+	}
+
+	fn extrinsic(&self) -> &str {
+		"flexibility_selling"
+	}
+
+	fn build(&self, nonce: u32) -> std::result::Result<OpaqueExtrinsic, &'static str> {
+		let acc = Sr25519Keyring::Bob.pair();
+		let extrinsic: OpaqueExtrinsic = create_benchmark_extrinsic(
+			self.client.as_ref(),
+			acc,
+			FedecomPSDemoCall::flexibility_selling {
+				buyer: self.buyer.clone(),
+				flexibility_market_identifier: BENCHMARK_MARKET_IDENTIFIER,
+				flexibility_market_timestamp: nonce,
+				asset_identifier: BENCHMARK_SELLING_ASSET,
+				sold_power: 10,
+				change_fct_w: 2,
+			}
+			.into(),
+			nonce,
+		)
+		.into();
+
+		Ok(extrinsic)
+	}
+}
+
+/// Generates `FedecomPSDemo::FlexibilityPurchaseDecision` extrinsics for the benchmarks.
+///
+/// Alice confirms the offer of `seller` of the period starting at the nonce, one of the offers
+/// seeded by the `benchmark` chain, so that every extrinsic takes the successful path: the offer
+/// is confirmed and its payment added.
+///
+/// Note: Should only be used for benchmarking.
+pub struct FlexibilityPurchaseDecisionBuilder {
+	client: Arc<FullClient>,
+	seller: AccountId,
+}
+
+impl FlexibilityPurchaseDecisionBuilder {
+	/// Creates a new [`Self`] from the given client.
+	pub fn new(client: Arc<FullClient>, seller: AccountId) -> Self {
+		Self { client, seller }
+	}
+}
+
+impl frame_benchmarking_cli::ExtrinsicBuilder for FlexibilityPurchaseDecisionBuilder {
+	fn pallet(&self) -> &str {
+		"fedecom_ps_demo"
+	}
+
+	fn extrinsic(&self) -> &str {
+		"flexibility_purchase_decision"
+	}
+
+	fn build(&self, nonce: u32) -> std::result::Result<OpaqueExtrinsic, &'static str> {
+		if nonce >= BENCHMARK_OFFERS {
+			return Err("Not enough offers seeded by the benchmark chain")
+		}
+		let acc = Sr25519Keyring::Alice.pair();
+		let extrinsic: OpaqueExtrinsic = create_benchmark_extrinsic(
+			self.client.as_ref(),
+			acc,
+			FedecomPSDemoCall::flexibility_purchase_decision {
+				seller: self.seller.clone(),
+				flexibility_market_identifier: BENCHMARK_MARKET_IDENTIFIER,
+				flexibility_market_timestamp: nonce,
+				asset_identifier: BENCHMARK_DECISION_ASSET,
+				new_state: pallet_fedecom_ps_demo::FLEXIBILITY_SELLING_STATE_CONFIRMED,
+			}
+			.into(),
+			nonce,
+		)
+		.into();
+
+		Ok(extrinsic)
+	}
+}
+
 /// Create a transaction using the given `call`.
 ///
 /// Note: Should only be used for benchmarking.
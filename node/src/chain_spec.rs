@@ -1,12 +1,26 @@
+use crate::{
+	benchmarking,
+	onboarding::{Authorities, Pilot},
+};
 use node_template_runtime::{
-	AccountId, AuraConfig, BalancesConfig, GrandpaConfig, RuntimeGenesisConfig, Signature,
-	SudoConfig, SystemConfig, WASM_BINARY,
+	opaque::SessionKeys,
+	pallet_fedecom_ps_demo::{
+		PARTICIPANT_ROLE_AGGREGATOR, PARTICIPANT_ROLE_DSO, PARTICIPANT_ROLE_MARKET_OPERATOR,
+		PARTICIPANT_ROLE_PROSUMER,
+	},
+	AccountId, AuraConfig, Balance, BalancesConfig, CouncilConfig, FedecomPSDemoConfig,
+	GrandpaConfig, NodeAuthorizationConfig, RuntimeGenesisConfig, SessionConfig, Signature,
+	SystemConfig, SS58Prefix, ValidatorSetConfig, WASM_BINARY,
 };
-use sc_service::ChainType;
+use sc_network::PeerId;
+use sc_service::{ChainSpec as _, ChainType, Properties};
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use sp_consensus_grandpa::AuthorityId as GrandpaId;
-use sp_core::{sr25519, Pair, Public};
-use sp_runtime::traits::{IdentifyAccount, Verify};
+use sp_core::{sr25519, OpaquePeerId, Pair, Public};
+use sp_runtime::{
+	traits::{IdentifyAccount, Verify},
+	BuildStorage,
+};
 
 // The URL for the telemetry server.
 // const STAGING_TELEMETRY_URL: &str = "wss://telemetry.polkadot.io/submit/";
@@ -31,9 +45,28 @@ where
 	AccountPublic::from(get_from_seed::<TPublic>(seed)).into_account()
 }
 
-/// Generate an Aura authority key.
-pub fn authority_keys_from_seed(s: &str) -> (AuraId, GrandpaId) {
-	(get_from_seed::<AuraId>(s), get_from_seed::<GrandpaId>(s))
+/// Generate a validator account with its Aura and GRANDPA authority keys.
+pub fn authority_keys_from_seed(s: &str) -> (AccountId, AuraId, GrandpaId) {
+	(
+		get_account_id_from_seed::<sr25519::Public>(s),
+		get_from_seed::<AuraId>(s),
+		get_from_seed::<GrandpaId>(s),
+	)
+}
+
+fn session_keys(aura: AuraId, grandpa: GrandpaId) -> SessionKeys {
+	SessionKeys { aura, grandpa }
+}
+
+/// Peer id of Alice's node, started with a `--node-key` of 31 zero bytes followed by `01`.
+const ALICE_NODE: &str = "12D3KooWEyoppNCUx8Yx66oV9fJnriXwCcXwDDUA2kj6vnc6iDEp";
+/// Peer id of Bob's node, started with a `--node-key` of 31 zero bytes followed by `02`.
+const BOB_NODE: &str = "12D3KooWHdiAxVd8uMQR1hGWXccidmfCwLqcMpGwR6QcTP6QRMuD";
+
+/// Generate a well-known node owned by the account of `owner_seed`.
+pub fn well_known_node(peer_id: &str, owner_seed: &str) -> (OpaquePeerId, AccountId) {
+	let peer_id: PeerId = peer_id.parse().expect("static values are valid; qed");
+	(OpaquePeerId(peer_id.to_bytes()), get_account_id_from_seed::<sr25519::Public>(owner_seed))
 }
 
 pub fn development_config() -> Result<ChainSpec, String> {
@@ -50,15 +83,18 @@ pub fn development_config() -> Result<ChainSpec, String> {
 				wasm_binary,
 				// Initial PoA authorities
 				vec![authority_keys_from_seed("Alice")],
-				// Sudo account
-				get_account_id_from_seed::<sr25519::Public>("Alice"),
+				// Well-known nodes
+				vec![well_known_node(ALICE_NODE, "Alice")],
+				// Council members
+				vec![get_account_id_from_seed::<sr25519::Public>("Alice")],
 				// Pre-funded accounts
-				vec![
+				endowed(vec![
 					get_account_id_from_seed::<sr25519::Public>("Alice"),
 					get_account_id_from_seed::<sr25519::Public>("Bob"),
 					get_account_id_from_seed::<sr25519::Public>("Alice//stash"),
 					get_account_id_from_seed::<sr25519::Public>("Bob//stash"),
-				],
+				]),
+				Default::default(),
 				true,
 			)
 		},
@@ -90,12 +126,17 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
 				wasm_binary,
 				// Initial PoA authorities
 				vec![authority_keys_from_seed("Alice"), authority_keys_from_seed("Bob")],
-				// Sudo account
-				get_account_id_from_seed::<sr25519::Public>("Alice"),
-				// Pre-funded accounts
+				// Well-known nodes
+				vec![well_known_node(ALICE_NODE, "Alice"), well_known_node(BOB_NODE, "Bob")],
+				// Council members
 				vec![
 					get_account_id_from_seed::<sr25519::Public>("Alice"),
 					get_account_id_from_seed::<sr25519::Public>("Bob"),
+				],
+				// Pre-funded accounts
+				endowed(vec![
+					get_account_id_from_seed::<sr25519::Public>("Alice"),
+					get_account_id_from_seed::<sr25519::Public>("Bob"),
 					get_account_id_from_seed::<sr25519::Public>("Charlie"),
 					get_account_id_from_seed::<sr25519::Public>("Dave"),
 					get_account_id_from_seed::<sr25519::Public>("Eve"),
@@ -106,7 +147,132 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
 					get_account_id_from_seed::<sr25519::Public>("Dave//stash"),
 					get_account_id_from_seed::<sr25519::Public>("Eve//stash"),
 					get_account_id_from_seed::<sr25519::Public>("Ferdie//stash"),
+				]),
+				Default::default(),
+				true,
+			)
+		},
+		// Bootnodes
+		vec![],
+		// Telemetry
+		None,
+		// Protocol ID
+		None,
+		// Properties
+		None,
+		None,
+		// Extensions
+		None,
+	))
+}
+
+/// Token properties of the fedecom chains.
+fn fedecom_properties() -> Properties {
+	let mut properties = Properties::new();
+	properties.insert("tokenSymbol".into(), "FCT".into());
+	properties.insert("tokenDecimals".into(), 12.into());
+	properties.insert("ss58Format".into(), SS58Prefix::get().into());
+	properties
+}
+
+/// Market participants of the fedecom demo chains, with well-known keyring accounts.
+///
+/// Alice is the DSO, Bob the aggregator, Charlie the market operator and Dave, Eve and Ferdie are
+/// prosumers. Charlie operates a day-ahead (1) and an intraday (2) flexibility market, every
+/// prosumer owns one asset and the aggregator owns a pooled asset.
+fn fedecom_demo_genesis() -> FedecomPSDemoConfig {
+	let account = get_account_id_from_seed::<sr25519::Public>;
+
+	FedecomPSDemoConfig {
+		participants: vec![
+			(account("Alice"), PARTICIPANT_ROLE_DSO),
+			(account("Bob"), PARTICIPANT_ROLE_AGGREGATOR),
+			(account("Charlie"), PARTICIPANT_ROLE_MARKET_OPERATOR),
+			(account("Dave"), PARTICIPANT_ROLE_PROSUMER),
+			(account("Eve"), PARTICIPANT_ROLE_PROSUMER),
+			(account("Ferdie"), PARTICIPANT_ROLE_PROSUMER),
+		],
+		markets: vec![(1, account("Charlie")), (2, account("Charlie"))],
+		assets: vec![
+			(101, account("Dave")),
+			(102, account("Eve")),
+			(103, account("Ferdie")),
+			(201, account("Bob")),
+		],
+		..Default::default()
+	}
+}
+
+/// Pre-funded accounts of the fedecom demo chains.
+fn fedecom_endowed_accounts() -> Vec<AccountId> {
+	["Alice", "Bob", "Charlie", "Dave", "Eve", "Ferdie", "Alice//stash", "Bob//stash"]
+		.iter()
+		.map(|seed| get_account_id_from_seed::<sr25519::Public>(seed))
+		.collect()
+}
+
+pub fn fedecom_demo_config() -> Result<ChainSpec, String> {
+	let wasm_binary = WASM_BINARY.ok_or_else(|| "Development wasm not available".to_string())?;
+
+	Ok(ChainSpec::from_genesis(
+		// Name
+		"FEDECOM Demo",
+		// ID
+		"fedecom_demo",
+		ChainType::Development,
+		move || {
+			testnet_genesis(
+				wasm_binary,
+				// Initial PoA authorities
+				vec![authority_keys_from_seed("Alice")],
+				// Well-known nodes
+				vec![well_known_node(ALICE_NODE, "Alice")],
+				// Council members
+				vec![get_account_id_from_seed::<sr25519::Public>("Alice")],
+				// Pre-funded accounts
+				endowed(fedecom_endowed_accounts()),
+				fedecom_demo_genesis(),
+				true,
+			)
+		},
+		// Bootnodes
+		vec![],
+		// Telemetry
+		None,
+		// Protocol ID
+		Some("fedecom"),
+		None,
+		// Properties
+		Some(fedecom_properties()),
+		// Extensions
+		None,
+	))
+}
+
+pub fn fedecom_local_config() -> Result<ChainSpec, String> {
+	let wasm_binary = WASM_BINARY.ok_or_else(|| "Development wasm not available".to_string())?;
+
+	Ok(ChainSpec::from_genesis(
+		// Name
+		"FEDECOM Local Testnet",
+		// ID
+		"fedecom_local",
+		ChainType::Local,
+		move || {
+			testnet_genesis(
+				wasm_binary,
+				// Initial PoA authorities
+				vec![authority_keys_from_seed("Alice"), authority_keys_from_seed("Bob")],
+				// Well-known nodes
+				vec![well_known_node(ALICE_NODE, "Alice"), well_known_node(BOB_NODE, "Bob")],
+				// Council members
+				vec![
+					get_account_id_from_seed::<sr25519::Public>("Alice"),
+					get_account_id_from_seed::<sr25519::Public>("Bob"),
 				],
+				// Pre-funded accounts
+				endowed(fedecom_endowed_accounts()),
+				fedecom_demo_genesis(),
 				true,
 			)
 		},
@@ -115,21 +281,169 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
 		// Telemetry
 		None,
 		// Protocol ID
+		Some("fedecom"),
 		None,
 		// Properties
+		Some(fedecom_properties()),
+		// Extensions
 		None,
+	))
+}
+
+/// Chain spec of the `benchmark extrinsic` command: a dev chain registering the market
+/// participants of the fedecom extrinsic builders, with the offers decided by the purchase
+/// decision builder in its genesis storage.
+pub fn benchmark_config() -> Result<ChainSpec, String> {
+	let wasm_binary = WASM_BINARY.ok_or_else(|| "Development wasm not available".to_string())?;
+
+	let mut spec = ChainSpec::from_genesis(
+		// Name
+		"Benchmark",
+		// ID
+		"benchmark",
+		ChainType::Development,
+		move || {
+			testnet_genesis(
+				wasm_binary,
+				// Initial PoA authorities
+				vec![authority_keys_from_seed("Alice")],
+				// Well-known nodes
+				vec![well_known_node(ALICE_NODE, "Alice")],
+				// Council members
+				vec![get_account_id_from_seed::<sr25519::Public>("Alice")],
+				// Pre-funded accounts
+				endowed(vec![
+					get_account_id_from_seed::<sr25519::Public>("Alice"),
+					get_account_id_from_seed::<sr25519::Public>("Bob"),
+					get_account_id_from_seed::<sr25519::Public>("Charlie"),
+				]),
+				benchmarking::benchmark_genesis(),
+				true,
+			)
+		},
+		// Bootnodes
+		vec![],
+		// Telemetry
+		None,
+		// Protocol ID
+		None,
+		None,
+		// Properties
 		None,
 		// Extensions
 		None,
+	);
+
+	// The offers are not part of the genesis config of the pallet, they are added to its storage
+	let mut storage = spec.build_storage()?;
+	storage.top.extend(benchmarking::benchmark_offers());
+	spec.set_storage(storage);
+	Ok(spec)
+}
+
+/// Initial balance of the pre-funded accounts of the testnets.
+const ENDOWMENT: Balance = 1 << 60;
+
+/// Pre-fund `accounts` with the initial balance of the testnets.
+fn endowed(accounts: Vec<AccountId>) -> Vec<(AccountId, Balance)> {
+	accounts.into_iter().map(|account| (account, ENDOWMENT)).collect()
+}
+
+/// Validators, well-known nodes and council of the `template` chain, `fedecom-demo` or
+/// `fedecom-local`, with its chain type.
+///
+/// They are the public development accounts, only fit for simulations and local tests.
+pub fn template_authorities(template: &str) -> Result<(ChainType, Authorities), String> {
+	let (chain_type, seeds, nodes) = match template {
+		"fedecom-demo" => (ChainType::Development, vec!["Alice"], vec![(ALICE_NODE, "Alice")]),
+		"fedecom-local" => {
+			let nodes = vec![(ALICE_NODE, "Alice"), (BOB_NODE, "Bob")];
+			(ChainType::Local, vec!["Alice", "Bob"], nodes)
+		},
+		_ => return Err(format!("Unknown template chain `{}`", template)),
+	};
+	let authorities = Authorities {
+		validators: seeds.iter().map(|seed| authority_keys_from_seed(seed)).collect(),
+		nodes: nodes.iter().map(|(peer_id, owner)| well_known_node(peer_id, owner)).collect(),
+		council: seeds
+			.iter()
+			.map(|seed| get_account_id_from_seed::<sr25519::Public>(seed))
+			.collect(),
+	};
+	Ok((chain_type, authorities))
+}
+
+/// Chain spec of a pilot with the validators, well-known nodes and council of `authorities` and
+/// the market participants of `pilot`.
+///
+/// The validators and council members not funded by the pilot are pre-funded like in the
+/// testnets, so that they can pay the fees of their transactions.
+pub fn fedecom_pilot_config(
+	chain_type: ChainType,
+	name: &str,
+	id: &str,
+	authorities: Authorities,
+	pilot: Pilot,
+) -> Result<ChainSpec, String> {
+	let wasm_binary = WASM_BINARY.ok_or_else(|| "Development wasm not available".to_string())?;
+
+	let mut balances = pilot.balances;
+	let validators = authorities.validators.iter().map(|(account, ..)| account);
+	for account in validators.chain(&authorities.council) {
+		if !balances.iter().any(|(funded, _)| funded == account) {
+			balances.push((account.clone(), ENDOWMENT));
+		}
+	}
+	let (participants, markets, assets) = (pilot.participants, pilot.markets, pilot.assets);
+
+	Ok(ChainSpec::from_genesis(
+		// Name
+		name,
+		// ID
+		id,
+		chain_type,
+		move || {
+			testnet_genesis(
+				wasm_binary,
+				// Initial PoA authorities
+				authorities.validators.clone(),
+				// Well-known nodes
+				authorities.nodes.clone(),
+				// Council members
+				authorities.council.clone(),
+				// Pre-funded accounts
+				balances.clone(),
+				FedecomPSDemoConfig {
+					participants: participants.clone(),
+					markets: markets.clone(),
+					assets: assets.clone(),
+					..Default::default()
+				},
+				true,
+			)
+		},
+		// Bootnodes
+		vec![],
+		// Telemetry
+		None,
+		// Protocol ID
+		Some("fedecom"),
+		None,
+		// Properties
+		Some(fedecom_properties()),
+		// Extensions
+		None,
 	))
 }
 
 /// Configure initial storage state for FRAME modules.
 fn testnet_genesis(
 	wasm_binary: &[u8],
-	initial_authorities: Vec<(AuraId, GrandpaId)>,
-	root_key: AccountId,
-	endowed_accounts: Vec<AccountId>,
+	initial_authorities: Vec<(AccountId, AuraId, GrandpaId)>,
+	well_known_nodes: Vec<(OpaquePeerId, AccountId)>,
+	council_members: Vec<AccountId>,
+	endowed_accounts: Vec<(AccountId, Balance)>,
+	fedecom_ps_demo: FedecomPSDemoConfig,
 	_enable_println: bool,
 ) -> RuntimeGenesisConfig {
 	RuntimeGenesisConfig {
@@ -138,21 +452,26 @@ fn testnet_genesis(
 			code: wasm_binary.to_vec(),
 			..Default::default()
 		},
-		balances: BalancesConfig {
-			// Configure endowed accounts with initial balance of 1 << 60.
-			balances: endowed_accounts.iter().cloned().map(|k| (k, 1 << 60)).collect(),
-		},
-		aura: AuraConfig {
-			authorities: initial_authorities.iter().map(|x| (x.0.clone())).collect(),
-		},
-		grandpa: GrandpaConfig {
-			authorities: initial_authorities.iter().map(|x| (x.1.clone(), 1)).collect(),
-			..Default::default()
+		balances: BalancesConfig { balances: endowed_accounts },
+		// The authorities are set by `pallet_session` from the validator set.
+		aura: AuraConfig { authorities: vec![] },
+		grandpa: GrandpaConfig { authorities: vec![], ..Default::default() },
+		validator_set: ValidatorSetConfig {
+			initial_validators: initial_authorities.iter().map(|x| x.0.clone()).collect(),
 		},
-		sudo: SudoConfig {
-			// Assign network admin rights.
-			key: Some(root_key),
+		session: SessionConfig {
+			keys: initial_authorities
+				.iter()
+				.map(|x| (x.0.clone(), x.0.clone(), session_keys(x.1.clone(), x.2.clone())))
+				.collect(),
 		},
+		// Privileged operations are voted by the council, there is no sudo key.
+		council: CouncilConfig { members: council_members, ..Default::default() },
 		transaction_payment: Default::default(),
+		fedecom_ps_demo,
+		// The second flexibility market is set up by the market council, appointed by the council.
+		fedecom_ps_demo2: Default::default(),
+		market_council: Default::default(),
+		node_authorization: NodeAuthorizationConfig { nodes: well_known_nodes },
 	}
 }
@@ -0,0 +1,417 @@
+//! Activation and settlement market documents, in an XML modeled on IEC 62325-451.
+//!
+//! Both documents have a time series per confirmed offer, over the market timestamp of the offer.
+//! Their schemas are bundled in `node/res/cim`. They borrow the element names and codes of the
+//! ENTSO-E documents, but are in fedecom namespaces and are not validated against the official
+//! ENTSO-E schemas, so they make no claim of IEC 62325-451 conformance.
+
+use super::eic::EicCodes;
+use node_template_runtime::{pallet_fedecom_ps_demo::CONFIRMATION_OK, AccountId};
+
+/// Namespace of the activation documents.
+pub const ACTIVATION_NAMESPACE: &str = "urn:fedecom:activationdocument:1:0";
+
+/// Namespace of the settlement documents.
+pub const SETTLEMENT_NAMESPACE: &str = "urn:fedecom:settlementdocument:1:0";
+
+/// Coding scheme of the EIC codes.
+const EIC_CODING_SCHEME: &str = "A01";
+
+/// Document types: activation history and financial situation.
+const ACTIVATION_TYPE: &str = "A41";
+const SETTLEMENT_TYPE: &str = "A87";
+
+/// Process type: realised.
+const PROCESS_TYPE: &str = "A16";
+
+/// Market roles of the sender (market operator) and of the receiver (system operator).
+const SENDER_ROLE: &str = "A11";
+const RECEIVER_ROLE: &str = "A04";
+
+/// Business type of the time series: manual frequency restoration reserve.
+const BUSINESS_TYPE: &str = "A97";
+
+/// Unit of the quantities (MW), currency of the prices, and direction of the flexibility (up).
+///
+/// The prices and amounts are in FCT, the token of the chain, which has no ISO 4217 code: they
+/// take `XXX`, the code of the transactions involving no currency.
+const MEASUREMENT_UNIT: &str = "MAW";
+const CURRENCY: &str = "XXX";
+const FLOW_DIRECTION: &str = "A01";
+
+/// Reason codes of the settlement status of the payments.
+const SETTLED: (&str, &str) = ("Z01", "settled");
+const DISPUTED: (&str, &str) = ("Z02", "disputed");
+const UNCONFIRMED: (&str, &str) = ("Z03", "unconfirmed");
+
+/// Confirmed flexibility offer with the payment of the buyer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settlement {
+	/// Seller of the flexibility
+	pub seller: AccountId,
+	/// Buyer of the flexibility
+	pub buyer: AccountId,
+	/// Flexibility market identifier
+	pub market: u32,
+	/// Flexibility market timestamp, in seconds
+	pub market_timestamp: u32,
+	/// Asset/flexibility identifier
+	pub asset: u32,
+	/// Sold power, in W
+	pub sold_power: u32,
+	/// Change, in FCT/W
+	pub change_fct_w: u32,
+	/// Value paid by the buyer to the seller, if any
+	pub payment: Option<u32>,
+	/// Confirmation status of the payment by the seller, if any
+	pub confirmation: Option<u32>,
+}
+
+impl Settlement {
+	/// Reason code and text of the settlement status.
+	fn reason(&self) -> (&'static str, &'static str) {
+		match (self.payment, self.confirmation) {
+			(Some(_), Some(CONFIRMATION_OK)) => SETTLED,
+			(Some(_), Some(_)) => DISPUTED,
+			_ => UNCONFIRMED,
+		}
+	}
+}
+
+/// Header of a market document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+	/// Start of the period of the document, in seconds, included
+	pub start: u32,
+	/// End of the period of the document, in seconds, excluded
+	pub end: u32,
+	/// Creation time of the document, in seconds
+	pub created: u64,
+}
+
+/// Escape the XML special characters of `text`.
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+		.replace('\'', "&apos;")
+}
+
+/// Date of the Unix timestamp `seconds`, in the `chrono` `format`.
+fn date(seconds: u64, format: &str) -> String {
+	chrono::DateTime::from_timestamp(seconds as i64, 0)
+		.map(|time| time.format(format).to_string())
+		.unwrap_or_default()
+}
+
+/// Date of a time interval boundary, to the minute.
+fn minute(seconds: u64) -> String {
+	date(seconds, "%Y-%m-%dT%H:%MZ")
+}
+
+/// Decimal number of MW of `watts`.
+fn megawatts(watts: u64) -> String {
+	let fraction = format!("{:06}", watts % 1_000_000);
+	let fraction = fraction.trim_end_matches('0');
+	if fraction.is_empty() {
+		(watts / 1_000_000).to_string()
+	} else {
+		format!("{}.{}", watts / 1_000_000, fraction)
+	}
+}
+
+/// Indented XML document.
+struct Xml {
+	out: String,
+	open: Vec<&'static str>,
+}
+
+impl Xml {
+	fn new(root: &'static str, namespace: &str) -> Self {
+		let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+		out.push_str(&format!("<{} xmlns=\"{}\">\n", root, escape(namespace)));
+		Self { out, open: vec![root] }
+	}
+
+	fn indent(&mut self) {
+		self.out.push_str(&"\t".repeat(self.open.len()));
+	}
+
+	fn open(&mut self, name: &'static str) {
+		self.indent();
+		self.out.push_str(&format!("<{}>\n", name));
+		self.open.push(name);
+	}
+
+	fn close(&mut self) {
+		let name = self.open.pop().expect("Closed more elements than opened");
+		self.indent();
+		self.out.push_str(&format!("</{}>\n", name));
+	}
+
+	fn leaf(&mut self, name: &str, text: impl ToString) {
+		self.indent();
+		self.out.push_str(&format!("<{0}>{1}</{0}>\n", name, escape(&text.to_string())));
+	}
+
+	/// Element holding an EIC code.
+	fn eic(&mut self, name: &str, code: &str) {
+		self.indent();
+		self.out.push_str(&format!(
+			"<{0} codingScheme=\"{1}\">{2}</{0}>\n",
+			name,
+			EIC_CODING_SCHEME,
+			escape(code)
+		));
+	}
+
+	fn interval(&mut self, name: &'static str, start: u64, end: u64) {
+		self.open(name);
+		self.leaf("start", minute(start));
+		self.leaf("end", minute(end));
+		self.close();
+	}
+
+	fn finish(mut self) -> String {
+		while !self.open.is_empty() {
+			self.close();
+		}
+		self.out
+	}
+}
+
+/// Document of `root` in `namespace`, with the time series written by `time_series` for every
+/// settlement.
+fn document(
+	root: &'static str,
+	namespace: &str,
+	(prefix, kind): (&str, &str),
+	codes: &EicCodes,
+	header: &Header,
+	settlements: &[Settlement],
+	time_series: impl Fn(&mut Xml, &Settlement),
+) -> Result<String, String> {
+	let mut xml = Xml::new(root, namespace);
+	xml.leaf("mRID", format!("{}-{}-{}", prefix, header.start, header.end));
+	xml.leaf("revisionNumber", 1);
+	xml.leaf("type", kind);
+	xml.leaf("process.processType", PROCESS_TYPE);
+	xml.eic("sender_MarketParticipant.mRID", &codes.sender);
+	xml.leaf("sender_MarketParticipant.marketRole.type", SENDER_ROLE);
+	xml.eic("receiver_MarketParticipant.mRID", &codes.receiver);
+	xml.leaf("receiver_MarketParticipant.marketRole.type", RECEIVER_ROLE);
+	xml.leaf("createdDateTime", date(header.created, "%Y-%m-%dT%H:%M:%SZ"));
+	xml.interval("period.timeInterval", header.start.into(), header.end.into());
+	xml.eic("domain.mRID", &codes.domain);
+
+	for (index, settlement) in settlements.iter().enumerate() {
+		xml.open("TimeSeries");
+		xml.leaf("mRID", index + 1);
+		xml.leaf("businessType", BUSINESS_TYPE);
+		xml.eic("acquiring_Domain.mRID", codes.market(settlement.market));
+		xml.eic("connecting_Domain.mRID", codes.market(settlement.market));
+		xml.leaf("marketAgreement.mRID", settlement.market);
+		xml.eic("registeredResource.mRID", codes.asset(settlement.asset)?);
+		xml.eic("provider_MarketParticipant.mRID", codes.participant(&settlement.seller)?);
+		xml.eic("acquirer_MarketParticipant.mRID", codes.participant(&settlement.buyer)?);
+		time_series(&mut xml, settlement);
+		xml.close();
+	}
+
+	Ok(xml.finish())
+}
+
+/// Open the period of the time series of `settlement`, with its first point.
+fn period(xml: &mut Xml, codes: &EicCodes, settlement: &Settlement) {
+	let start = u64::from(settlement.market_timestamp);
+	let resolution = u64::from(codes.resolution);
+	xml.open("Period");
+	xml.interval("timeInterval", start, start + resolution * 60);
+	xml.leaf("resolution", format!("PT{}M", resolution));
+	xml.open("Point");
+	xml.leaf("position", 1);
+	xml.leaf("quantity", megawatts(settlement.sold_power.into()));
+}
+
+/// Activation document of the `settlements`, the activated power of the confirmed offers.
+pub fn activation(
+	codes: &EicCodes,
+	header: &Header,
+	settlements: &[Settlement],
+) -> Result<String, String> {
+	document(
+		"Activation_MarketDocument",
+		ACTIVATION_NAMESPACE,
+		("ACT", ACTIVATION_TYPE),
+		codes,
+		header,
+		settlements,
+		|xml, settlement| {
+			xml.leaf("measurement_Unit.name", MEASUREMENT_UNIT);
+			xml.leaf("flowDirection.direction", FLOW_DIRECTION);
+			period(xml, codes, settlement);
+			// Point and Period
+			xml.close();
+			xml.close();
+		},
+	)
+}
+
+/// Settlement document of the `settlements`, the prices of the confirmed offers with the amounts
+/// paid and their settlement status.
+pub fn settlement(
+	codes: &EicCodes,
+	header: &Header,
+	settlements: &[Settlement],
+) -> Result<String, String> {
+	document(
+		"Settlement_MarketDocument",
+		SETTLEMENT_NAMESPACE,
+		("SET", SETTLEMENT_TYPE),
+		codes,
+		header,
+		settlements,
+		|xml, settlement| {
+			xml.leaf("currency_Unit.name", CURRENCY);
+			xml.leaf("price_Measure_Unit.name", MEASUREMENT_UNIT);
+			period(xml, codes, settlement);
+			xml.leaf("price.amount", u64::from(settlement.change_fct_w) * 1_000_000);
+			if let Some(payment) = settlement.payment {
+				xml.leaf("settlement.amount", payment);
+			}
+			// Point and Period
+			xml.close();
+			xml.close();
+			let (code, text) = settlement.reason();
+			xml.open("Reason");
+			xml.leaf("code", code);
+			xml.leaf("text", text);
+			xml.close();
+		},
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use libxml::{
+		parser::Parser,
+		schemas::{SchemaParserContext, SchemaValidationContext},
+	};
+	use node_template_runtime::pallet_fedecom_ps_demo::CONFIRMATION_NOK_UNDERESTIMATION;
+	use sp_keyring::Sr25519Keyring::{Alice, Bob, Charlie};
+
+	const CODES: &str = r#"
+		sender = "10X1001A1001A450"
+		receiver = "10X1001A1001A361"
+		domain = "10YDE-VE-------2"
+
+		[participants]
+		"5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY" = "11XALICE-------1"
+		"5FHneW46xGXgs5mUiveU4sbTyGBzmstUspZC92UhjJM694ty" = "11XBOB---------2"
+
+		[assets]
+		3 = "11WASSET-------3"
+		4 = "11WASSET-------4"
+	"#;
+
+	const HEADER: Header = Header { start: 1_700_000_000 - 800, end: 1_700_086_400, created: 0 };
+
+	fn settlement(asset: u32, payment: Option<u32>, confirmation: Option<u32>) -> Settlement {
+		Settlement {
+			seller: Alice.to_account_id(),
+			buyer: Bob.to_account_id(),
+			market: 1,
+			market_timestamp: 1_700_000_100,
+			asset,
+			sold_power: 1_500_000,
+			change_fct_w: 2,
+			payment,
+			confirmation,
+		}
+	}
+
+	fn settlements() -> Vec<Settlement> {
+		vec![
+			settlement(3, Some(3_000_000), Some(CONFIRMATION_OK)),
+			settlement(4, Some(3_000_000), Some(CONFIRMATION_NOK_UNDERESTIMATION)),
+			settlement(4, None, None),
+		]
+	}
+
+	/// Validate `xml` against the bundled schema `xsd`.
+	fn validate(xsd: &str, xml: &str) {
+		let path = format!("{}/res/cim/{}", env!("CARGO_MANIFEST_DIR"), xsd);
+		let mut parser = SchemaParserContext::from_file(&path);
+		let mut schema = SchemaValidationContext::from_parser(&mut parser)
+			.unwrap_or_else(|errors| panic!("Invalid schema {}: {:?}", xsd, errors));
+		let document = Parser::default().parse_string(xml).expect("Malformed document");
+		if let Err(errors) = schema.validate_document(&document) {
+			let messages: Vec<_> = errors.into_iter().filter_map(|e| e.message).collect();
+			panic!("Document invalid against {}: {:?}\n{}", xsd, messages, xml);
+		}
+	}
+
+	#[test]
+	fn activation_document_is_valid() {
+		let codes = EicCodes::parse(CODES).unwrap();
+		let xml = activation(&codes, &HEADER, &settlements()).unwrap();
+
+		validate("activation.xsd", &xml);
+		assert!(xml.contains("<mRID>ACT-1699999200-1700086400</mRID>"));
+		assert!(xml.contains("<createdDateTime>1970-01-01T00:00:00Z</createdDateTime>"));
+		assert!(xml.contains("codingScheme=\"A01\">11WASSET-------3</registeredResource.mRID>"));
+		assert!(xml.contains("<start>2023-11-14T22:15Z</start>"));
+		assert!(xml.contains("<end>2023-11-14T22:30Z</end>"));
+		assert!(xml.contains("<quantity>1.5</quantity>"));
+		assert_eq!(xml.matches("<TimeSeries>").count(), 3);
+	}
+
+	#[test]
+	fn settlement_document_is_valid() {
+		let codes = EicCodes::parse(CODES).unwrap();
+		let xml = settlement(&codes, &HEADER, &settlements()).unwrap();
+
+		validate("settlement.xsd", &xml);
+		assert!(xml.contains("<price.amount>2000000</price.amount>"));
+		assert_eq!(xml.matches("<settlement.amount>3000000</settlement.amount>").count(), 2);
+		for (code, _) in [SETTLED, DISPUTED, UNCONFIRMED] {
+			assert_eq!(xml.matches(&format!("<code>{}</code>", code)).count(), 1);
+		}
+	}
+
+	#[test]
+	fn empty_documents_are_valid() {
+		let codes = EicCodes::parse(CODES).unwrap();
+
+		validate("activation.xsd", &activation(&codes, &HEADER, &[]).unwrap());
+		validate("settlement.xsd", &settlement(&codes, &HEADER, &[]).unwrap());
+	}
+
+	#[test]
+	fn unmapped_participants_are_rejected() {
+		let codes = EicCodes::parse(CODES).unwrap();
+		let mut unmapped = settlement(3, None, None);
+		unmapped.buyer = Charlie.to_account_id();
+
+		let error = activation(&codes, &HEADER, &[unmapped]).unwrap_err();
+		assert_eq!(
+			error,
+			format!("No EIC code of the participant {}", Charlie.to_account_id())
+		);
+	}
+
+	#[test]
+	fn quantities_are_megawatts() {
+		assert_eq!(megawatts(0), "0");
+		assert_eq!(megawatts(500), "0.0005");
+		assert_eq!(megawatts(2_000_000), "2");
+		assert_eq!(megawatts(2_250_000), "2.25");
+	}
+
+	#[test]
+	fn text_is_escaped() {
+		assert_eq!(escape("a<b & \"c\""), "a&lt;b &amp; &quot;c&quot;");
+	}
+}
@@ -0,0 +1,183 @@
+//! Table of the EIC codes of the market participants, assets and areas, read from a TOML file.
+
+use node_template_runtime::AccountId;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+/// Length of an EIC code.
+const EIC_LENGTH: usize = 16;
+
+/// Default duration of a market timestamp, in minutes.
+const DEFAULT_RESOLUTION: u32 = 15;
+
+/// EIC code table, as written in the TOML file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+	sender: String,
+	receiver: String,
+	domain: String,
+	resolution: Option<u32>,
+	#[serde(default)]
+	participants: BTreeMap<AccountId, String>,
+	#[serde(default)]
+	assets: BTreeMap<String, String>,
+	#[serde(default)]
+	markets: BTreeMap<String, String>,
+}
+
+/// EIC codes (coding scheme `A01`) of the parties and areas of the market documents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EicCodes {
+	/// Market operator issuing the documents
+	pub sender: String,
+	/// System operator or regulator receiving the documents
+	pub receiver: String,
+	/// Area of the flexibility markets
+	pub domain: String,
+	/// Duration of a market timestamp, in minutes
+	pub resolution: u32,
+	/// Market participants
+	pub participants: BTreeMap<AccountId, String>,
+	/// Assets/flexibilities, as registered resources
+	pub assets: BTreeMap<u32, String>,
+	/// Area of the flexibility markets differing from `domain`
+	pub markets: BTreeMap<u32, String>,
+}
+
+/// Whether `code` has the format of an EIC code: 16 digits, capital letters or `-`.
+fn is_eic(code: &str) -> bool {
+	code.len() == EIC_LENGTH &&
+		code.chars().all(|c| c.is_ascii_digit() || c.is_ascii_uppercase() || c == '-')
+}
+
+fn check(code: String, what: impl fmt::Display) -> Result<String, String> {
+	if is_eic(&code) {
+		Ok(code)
+	} else {
+		Err(format!("invalid EIC code `{}` of {}", code, what))
+	}
+}
+
+fn identifiers(
+	codes: BTreeMap<String, String>,
+	what: &str,
+) -> Result<BTreeMap<u32, String>, String> {
+	codes
+		.into_iter()
+		.map(|(id, code)| {
+			let id: u32 =
+				id.parse().map_err(|_| format!("invalid {} identifier `{}`", what, id))?;
+			Ok((id, check(code, format!("{} {}", what, id))?))
+		})
+		.collect()
+}
+
+impl EicCodes {
+	/// Read the table from the TOML file at `path`.
+	pub fn load(path: &Path) -> Result<Self, String> {
+		let content = fs::read_to_string(path)
+			.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+		Self::parse(&content).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+	}
+
+	/// Parse and validate a TOML table.
+	pub fn parse(content: &str) -> Result<Self, String> {
+		let file: File = toml::from_str(content).map_err(|e| e.to_string())?;
+
+		let resolution = file.resolution.unwrap_or(DEFAULT_RESOLUTION);
+		if resolution == 0 {
+			return Err("`resolution` must be at least 1 minute".into())
+		}
+
+		Ok(Self {
+			sender: check(file.sender, "the sender")?,
+			receiver: check(file.receiver, "the receiver")?,
+			domain: check(file.domain, "the domain")?,
+			resolution,
+			participants: file
+				.participants
+				.into_iter()
+				.map(|(account, code)| {
+					let code = check(code, format!("participant {}", account))?;
+					Ok((account, code))
+				})
+				.collect::<Result<_, String>>()?,
+			assets: identifiers(file.assets, "asset")?,
+			markets: identifiers(file.markets, "market")?,
+		})
+	}
+
+	/// EIC code of the market participant `account`.
+	pub fn participant(&self, account: &AccountId) -> Result<&str, String> {
+		self.participants
+			.get(account)
+			.map(String::as_str)
+			.ok_or_else(|| format!("No EIC code of the participant {}", account))
+	}
+
+	/// EIC code of the asset/flexibility `asset`.
+	pub fn asset(&self, asset: u32) -> Result<&str, String> {
+		self.assets
+			.get(&asset)
+			.map(String::as_str)
+			.ok_or_else(|| format!("No EIC code of the asset {}", asset))
+	}
+
+	/// EIC code of the area of the flexibility market `market`.
+	pub fn market(&self, market: u32) -> &str {
+		self.markets.get(&market).unwrap_or(&self.domain)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_keyring::Sr25519Keyring::{Alice, Bob};
+
+	const TABLE: &str = r#"
+		sender = "10X1001A1001A450"
+		receiver = "10X1001A1001A361"
+		domain = "10YDE-VE-------2"
+
+		[participants]
+		"5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY" = "11XALICE-------1"
+
+		[assets]
+		3 = "11WASSET-------3"
+
+		[markets]
+		2 = "10YAT-APG------L"
+	"#;
+
+	#[test]
+	fn table_is_parsed() {
+		let codes = EicCodes::parse(TABLE).unwrap();
+
+		assert_eq!(codes.resolution, DEFAULT_RESOLUTION);
+		assert_eq!(codes.participant(&Alice.to_account_id()).unwrap(), "11XALICE-------1");
+		assert!(codes.participant(&Bob.to_account_id()).is_err());
+		assert_eq!(codes.asset(3).unwrap(), "11WASSET-------3");
+		assert!(codes.asset(4).is_err());
+		assert_eq!(codes.market(1), "10YDE-VE-------2");
+		assert_eq!(codes.market(2), "10YAT-APG------L");
+	}
+
+	#[test]
+	fn invalid_codes_are_rejected() {
+		let short = TABLE.replace("11WASSET-------3", "11WASSET-3");
+		assert_eq!(
+			EicCodes::parse(&short).unwrap_err(),
+			"invalid EIC code `11WASSET-3` of asset 3"
+		);
+
+		let lowercase = TABLE.replace("10X1001A1001A450", "10x1001a1001a450");
+		assert!(EicCodes::parse(&lowercase).is_err());
+
+		let identifier = TABLE.replace("3 = ", "three = ");
+		assert_eq!(EicCodes::parse(&identifier).unwrap_err(), "invalid asset identifier `three`");
+
+		let resolution = format!("resolution = 0\n{}", TABLE);
+		assert!(EicCodes::parse(&resolution).is_err());
+	}
+}
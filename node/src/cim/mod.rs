@@ -0,0 +1,209 @@
+//! Export of the flexibility settlement as market documents modeled on IEC 62325-451 (ENTSO-E CIM).
+//!
+//! The flexibility offers confirmed in a period, with the payments of their buyers, are written as
+//! an activation document and a settlement document (see [`document`]), whose participants,
+//! assets and areas are identified by their EIC codes, mapped from the on-chain identifiers by the
+//! table of [`eic`]. The documents are exported by the `export-settlement` subcommand, from the
+//! local database, and by the `fedecom_settlementDocuments` RPC method of [`rpc`], enabled with
+//! `--fedecom-eic-codes <path>` and unsafe, as it reads the whole ledger.
+
+pub mod document;
+pub mod eic;
+pub mod rpc;
+
+use crate::{events::block_timestamp, service::FullClient, storage};
+use codec::Decode;
+use document::{Header, Settlement};
+use eic::EicCodes;
+use node_template_runtime::{
+	opaque::Block,
+	pallet_fedecom_ps_demo::{FlexibilitySellingData, FLEXIBILITY_SELLING_STATE_CONFIRMED},
+	Hash,
+};
+use sc_cli::{BlockNumberOrHash, CliConfiguration, DatabaseParams, PruningParams, SharedParams};
+use sc_client_api::{Backend, StorageProvider};
+use serde::Serialize;
+use sp_blockchain::HeaderBackend;
+use sp_core::storage::StorageKey;
+use std::{fs, path::PathBuf, sync::Arc};
+
+/// Activation and settlement documents of a period.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Documents {
+	/// Activation document, in XML
+	pub activation: String,
+	/// Settlement document, in XML
+	pub settlement: String,
+}
+
+fn decode<T: Decode>(data: &[u8], what: &str) -> Result<T, String> {
+	T::decode(&mut &data[..]).map_err(|e| format!("Undecodable {}: {}", what, e))
+}
+
+/// Flexibility offers of the block `hash` confirmed with a market timestamp from `start` included
+/// to `end` excluded, with their payments, ordered by market timestamp.
+pub fn settlements<C, BE>(
+	client: &C,
+	hash: Hash,
+	start: u32,
+	end: u32,
+) -> Result<Vec<Settlement>, String>
+where
+	BE: Backend<Block>,
+	C: StorageProvider<Block, BE>,
+{
+	let read_error = |e: sp_blockchain::Error| format!("Failed to read the state: {}", e);
+	let value = |key: Vec<u8>, what: &str| -> Result<Option<u32>, String> {
+		match client.storage(hash, &StorageKey(key)).map_err(read_error)? {
+			Some(data) => decode(&data.0, what).map(Some),
+			None => Ok(None),
+		}
+	};
+
+	// The confirmed offers of the closed periods are kept in `SettledOffers`, with the same keys
+	let mut pairs = Vec::new();
+	for item in [storage::OFFERS, storage::SETTLED_OFFERS] {
+		let prefix = storage::prefix(item);
+		let item_pairs = client
+			.storage_pairs(hash, Some(&StorageKey(prefix.clone())), None)
+			.map_err(read_error)?;
+		pairs.extend(item_pairs.map(|(key, data)| (key.0[prefix.len()..].to_vec(), data)));
+	}
+
+	let mut settlements = Vec::new();
+	for (key, data) in pairs {
+		let (seller, buyer, market, market_timestamp, asset) = storage::offer_key(&mut &key[..])
+			.ok_or_else(|| format!("Undecodable offer key 0x{}", hex::encode(&key)))?;
+		let data: FlexibilitySellingData = decode(&data.0, "offer")?;
+		if data.state != FLEXIBILITY_SELLING_STATE_CONFIRMED ||
+			!(start..end).contains(&market_timestamp)
+		{
+			continue
+		}
+
+		// The buyer pays the seller at the market timestamp when confirming the offer
+		let payment_key = (buyer.clone(), seller.clone(), market_timestamp);
+		settlements.push(Settlement {
+			payment: value(storage::payment(&payment_key), "payment")?,
+			confirmation: value(storage::confirmation(&payment_key), "confirmation")?,
+			seller,
+			buyer,
+			market,
+			market_timestamp,
+			asset,
+			sold_power: data.sold_power,
+			change_fct_w: data.change_fct_w,
+		});
+	}
+	settlements.sort_by_key(|s| (s.market_timestamp, s.market, s.asset));
+
+	Ok(settlements)
+}
+
+/// Documents of the offers confirmed from `start` included to `end` excluded, in the state of the
+/// block `hash`, created at the timestamp of the block.
+pub fn documents<C, BE>(
+	client: &C,
+	hash: Hash,
+	codes: &EicCodes,
+	start: u32,
+	end: u32,
+) -> Result<Documents, String>
+where
+	BE: Backend<Block>,
+	C: StorageProvider<Block, BE>,
+{
+	if start >= end {
+		return Err("The start of the period must precede its end".into())
+	}
+	let settlements = settlements(client, hash, start, end)?;
+	let created = block_timestamp(client, hash)
+		.map_err(|e| format!("Failed to read the state: {}", e))? /
+		1000;
+	let header = Header { start, end, created };
+
+	Ok(Documents {
+		activation: document::activation(codes, &header, &settlements)?,
+		settlement: document::settlement(codes, &header, &settlements)?,
+	})
+}
+
+/// Unix timestamp, in seconds, of an RFC 3339 date.
+fn parse_time(date: &str) -> Result<u32, String> {
+	let time = chrono::DateTime::parse_from_rfc3339(date).map_err(|e| e.to_string())?;
+	u32::try_from(time.timestamp()).map_err(|_| format!("{} is out of the market timestamps", date))
+}
+
+/// Export the flexibility offers confirmed in a period as activation and settlement documents
+/// modeled on IEC 62325-451.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct ExportSettlementCmd {
+	/// Start of the period, an RFC 3339 date, e.g. `2023-11-14T00:00:00Z`.
+	#[arg(long, value_parser = parse_time)]
+	pub start: u32,
+
+	/// End of the period, excluded, an RFC 3339 date.
+	#[arg(long, value_parser = parse_time)]
+	pub end: u32,
+
+	/// Block hash or number of the exported state, the best block if not given.
+	#[arg(long, value_name = "HASH or NUMBER")]
+	pub at: Option<BlockNumberOrHash>,
+
+	/// TOML table of the EIC codes of the participants, assets and areas.
+	#[arg(long, value_name = "PATH")]
+	pub eic_codes: PathBuf,
+
+	/// Directory of the `activation.xml` and `settlement.xml` documents, created if missing.
+	#[arg(long, value_name = "PATH", default_value = ".")]
+	pub output: PathBuf,
+
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+
+	#[clap(flatten)]
+	pub pruning_params: PruningParams,
+
+	#[clap(flatten)]
+	pub database_params: DatabaseParams,
+}
+
+impl ExportSettlementCmd {
+	/// Export the documents at the selected block of `client`.
+	pub fn run(&self, client: Arc<FullClient>) -> sc_cli::Result<()> {
+		let codes = EicCodes::load(&self.eic_codes)?;
+		let hash = match &self.at {
+			Some(at) => client.expect_block_hash_from_id(&at.parse::<Block>()?)?,
+			None => client.info().best_hash,
+		};
+
+		let documents = documents(&*client, hash, &codes, self.start, self.end)?;
+
+		fs::create_dir_all(&self.output)
+			.map_err(|e| format!("Failed to create {}: {}", self.output.display(), e))?;
+		for (name, xml) in
+			[("activation.xml", &documents.activation), ("settlement.xml", &documents.settlement)]
+		{
+			let path = self.output.join(name);
+			fs::write(&path, xml)
+				.map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+			eprintln!("{} written", path.display());
+		}
+		Ok(())
+	}
+}
+
+impl CliConfiguration for ExportSettlementCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		Some(&self.pruning_params)
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
@@ -0,0 +1,63 @@
+//! RPC method exporting the settlement documents.
+
+use super::{documents, eic::EicCodes, Documents};
+use crate::rpc::DenyUnsafe;
+use jsonrpsee::{
+	core::RpcResult,
+	proc_macros::rpc,
+	types::error::{CallError, ErrorObject},
+};
+use node_template_runtime::{opaque::Block, Hash};
+use sc_client_api::{Backend, StorageProvider};
+use sp_blockchain::HeaderBackend;
+use std::{marker::PhantomData, sync::Arc};
+
+/// Error code of the export failures.
+const EXPORT_ERROR: i32 = 1;
+
+/// Export of the flexibility settlement as market documents modeled on IEC 62325-451.
+#[rpc(server)]
+pub trait FedecomSettlementApi {
+	/// Activation and settlement documents of the flexibility offers confirmed with a market
+	/// timestamp from `start` included to `end` excluded (Unix seconds), in the state of the block
+	/// `at`, the best block if not given.
+	///
+	/// Unsafe, as every call reads the whole ledger.
+	#[method(name = "fedecom_settlementDocuments")]
+	fn documents(&self, start: u32, end: u32, at: Option<Hash>) -> RpcResult<Documents>;
+}
+
+/// Implementation of [`FedecomSettlementApiServer`] reading the state of the client.
+pub struct FedecomSettlement<C, BE> {
+	client: Arc<C>,
+	codes: Arc<EicCodes>,
+	deny_unsafe: DenyUnsafe,
+	_backend: PhantomData<BE>,
+}
+
+impl<C, BE> FedecomSettlement<C, BE> {
+	/// Create a new instance of the settlement RPC method, identifying the parties with `codes`.
+	pub fn new(client: Arc<C>, codes: Arc<EicCodes>, deny_unsafe: DenyUnsafe) -> Self {
+		Self { client, codes, deny_unsafe, _backend: PhantomData }
+	}
+}
+
+impl<C, BE> FedecomSettlementApiServer for FedecomSettlement<C, BE>
+where
+	BE: Backend<Block> + 'static,
+	C: HeaderBackend<Block> + StorageProvider<Block, BE>,
+	C: Send + Sync + 'static,
+{
+	fn documents(&self, start: u32, end: u32, at: Option<Hash>) -> RpcResult<Documents> {
+		self.deny_unsafe.check_if_safe()?;
+		let hash = at.unwrap_or_else(|| self.client.info().best_hash);
+		documents(&*self.client, hash, &self.codes, start, end).map_err(|e| {
+			CallError::Custom(ErrorObject::owned(
+				EXPORT_ERROR,
+				"Unable to export the settlement documents.",
+				Some(e),
+			))
+			.into()
+		})
+	}
+}
@@ -1,4 +1,6 @@
+use crate::service::Sealing;
 use sc_cli::RunCmd;
+use std::path::PathBuf;
 
 #[derive(Debug, clap::Parser)]
 pub struct Cli {
@@ -7,6 +9,34 @@ pub struct Cli {
 
 	#[clap(flatten)]
 	pub run: RunCmd,
+
+	/// Replace Aura and GRANDPA with manual seal: `instant`, `manual` or `interval=<ms>`.
+	///
+	/// Meant for development and CI only. In `manual` mode blocks are created and finalized
+	/// through the `engine_createBlock` and `engine_finalizeBlock` RPC methods.
+	#[arg(long)]
+	pub sealing: Option<Sealing>,
+
+	/// Index the fedecom events and extrinsics in the SQLite database at the given path.
+	///
+	/// The index is queried through the `fedecom_index_*` RPC methods. It requires the state of
+	/// the finalized blocks, kept with `--state-pruning archive-canonical`.
+	#[arg(long, value_name = "PATH")]
+	pub fedecom_index: Option<PathBuf>,
+
+	/// POST the finalized fedecom events to the webhooks of the given TOML configuration.
+	///
+	/// The delivery cursors and dead-letter logs are kept in the `fedecom-webhooks` directory of
+	/// the chain data.
+	#[arg(long, value_name = "PATH")]
+	pub fedecom_webhooks: Option<PathBuf>,
+
+	/// Serve the settlement documents of the flexibility market, identifying its participants,
+	/// assets and areas with the EIC codes of the given TOML table.
+	///
+	/// The documents are exported through the `fedecom_settlementDocuments` RPC method.
+	#[arg(long, value_name = "PATH")]
+	pub fedecom_eic_codes: Option<PathBuf>,
 }
 
 #[derive(Debug, clap::Subcommand)]
@@ -19,6 +49,10 @@ pub enum Subcommand {
 	/// Build a chain specification.
 	BuildSpec(sc_cli::BuildSpecCmd),
 
+	/// Build the chain specification of a pilot from CSV or JSON files of its accounts, assets
+	/// and flexibility markets.
+	BuildFedecomSpec(crate::onboarding::BuildFedecomSpecCmd),
+
 	/// Validate blocks.
 	CheckBlock(sc_cli::CheckBlockCmd),
 
@@ -28,12 +62,27 @@ pub enum Subcommand {
 	/// Export the state of a given block into a chain spec.
 	ExportState(sc_cli::ExportStateCmd),
 
+	/// Export the fedecom payments, confirmations and flexibility offers of a given block as
+	/// CSV, JSON or Parquet tables.
+	ExportLedger(crate::ledger::ExportLedgerCmd),
+
+	/// Export the flexibility offers confirmed in a period as IEC 62325-451 activation and
+	/// settlement documents.
+	ExportSettlement(crate::cim::ExportSettlementCmd),
+
+	/// Create a local chain from the state of a production chain, run by the keyring accounts of
+	/// a dev chain.
+	ForkOff(crate::fork::ForkOffCmd),
+
 	/// Import blocks.
 	ImportBlocks(sc_cli::ImportBlocksCmd),
 
 	/// Remove the whole chain.
 	PurgeChain(sc_cli::PurgeChainCmd),
 
+	/// Simulate flexibility market participants trading against a node, from a seeded scenario.
+	Simulate(crate::simulator::SimulateCmd),
+
 	/// Revert the chain to a previous state.
 	Revert(sc_cli::RevertCmd),
 
@@ -51,4 +100,7 @@ pub enum Subcommand {
 
 	/// Db meta columns information.
 	ChainInfo(sc_cli::ChainInfoCmd),
+
+	/// Submit and query the operations of the flexibility market.
+	Fedecom(crate::fedecom::FedecomCmd),
 }
@@ -1,5 +1,8 @@
 use crate::{
-	benchmarking::{inherent_benchmark_data, RemarkBuilder, TransferKeepAliveBuilder},
+	benchmarking::{
+		inherent_benchmark_data, AddPaymentBuilder, FlexibilityPurchaseDecisionBuilder,
+		FlexibilitySellingBuilder, RemarkBuilder, TransferKeepAliveBuilder,
+	},
 	chain_spec,
 	cli::{Cli, Subcommand},
 	service,
@@ -42,6 +45,9 @@ impl SubstrateCli for Cli {
 		Ok(match id {
 			"dev" => Box::new(chain_spec::development_config()?),
 			"" | "local" => Box::new(chain_spec::local_testnet_config()?),
+			"fedecom-demo" => Box::new(chain_spec::fedecom_demo_config()?),
+			"fedecom-local" => Box::new(chain_spec::fedecom_local_config()?),
+			"benchmark" => Box::new(chain_spec::benchmark_config()?),
 			path =>
 				Box::new(chain_spec::ChainSpec::from_json_file(std::path::PathBuf::from(path))?),
 		})
@@ -58,6 +64,10 @@ pub fn run() -> sc_cli::Result<()> {
 			let runner = cli.create_runner(cmd)?;
 			runner.sync_run(|config| cmd.run(config.chain_spec, config.network))
 		},
+		Some(Subcommand::BuildFedecomSpec(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| cmd.run(config.network))
+		},
 		Some(Subcommand::CheckBlock(cmd)) => {
 			let runner = cli.create_runner(cmd)?;
 			runner.async_run(|config| {
@@ -80,6 +90,30 @@ pub fn run() -> sc_cli::Result<()> {
 				Ok((cmd.run(client, config.chain_spec), task_manager))
 			})
 		},
+		Some(Subcommand::ExportLedger(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| {
+				let PartialComponents { client, .. } = service::new_partial(&config)?;
+				cmd.run(client)
+			})
+		},
+		Some(Subcommand::ExportSettlement(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| {
+				let PartialComponents { client, .. } = service::new_partial(&config)?;
+				cmd.run(client)
+			})
+		},
+		Some(Subcommand::ForkOff(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| {
+				if cmd.state.is_some() {
+					return cmd.run(None)
+				}
+				let PartialComponents { client, .. } = service::new_partial(&config)?;
+				cmd.run(Some(client))
+			})
+		},
 		Some(Subcommand::ImportBlocks(cmd)) => {
 			let runner = cli.create_runner(cmd)?;
 			runner.async_run(|config| {
@@ -154,7 +188,7 @@ pub fn run() -> sc_cli::Result<()> {
 					},
 					BenchmarkCmd::Extrinsic(cmd) => {
 						let PartialComponents { client, .. } = service::new_partial(&config)?;
-						// Register the *Remark* and *TKA* builders.
+						// Register the *Remark*, *TKA* and fedecom market builders.
 						let ext_factory = ExtrinsicFactory(vec![
 							Box::new(RemarkBuilder::new(client.clone())),
 							Box::new(TransferKeepAliveBuilder::new(
@@ -162,6 +196,19 @@ pub fn run() -> sc_cli::Result<()> {
 								Sr25519Keyring::Alice.to_account_id(),
 								EXISTENTIAL_DEPOSIT,
 							)),
+							Box::new(AddPaymentBuilder::new(
+								client.clone(),
+								Sr25519Keyring::Alice.to_account_id(),
+								100,
+							)),
+							Box::new(FlexibilitySellingBuilder::new(
+								client.clone(),
+								Sr25519Keyring::Alice.to_account_id(),
+							)),
+							Box::new(FlexibilityPurchaseDecisionBuilder::new(
+								client.clone(),
+								Sr25519Keyring::Bob.to_account_id(),
+							)),
 						]);
 
 						cmd.run(client, inherent_benchmark_data()?, Vec::new(), &ext_factory)
@@ -202,10 +249,25 @@ pub fn run() -> sc_cli::Result<()> {
 			let runner = cli.create_runner(cmd)?;
 			runner.sync_run(|config| cmd.run::<Block>(&config))
 		},
+		Some(Subcommand::Fedecom(cmd)) if cmd.local => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| {
+				let PartialComponents { client, .. } = service::new_partial(&config)?;
+				cmd.run_local(client)
+			})
+		},
+		Some(Subcommand::Fedecom(cmd)) => sc_cli::build_runtime()?.block_on(cmd.run()),
+		Some(Subcommand::Simulate(cmd)) => sc_cli::build_runtime()?.block_on(cmd.run()),
 		None => {
 			let runner = cli.create_runner(&cli.run)?;
+			let sealing = cli.sealing;
+			let fedecom = service::FedecomOptions {
+				index: cli.fedecom_index.clone(),
+				webhooks: cli.fedecom_webhooks.clone(),
+				eic_codes: cli.fedecom_eic_codes.clone(),
+			};
 			runner.run_node_until_exit(|config| async move {
-				service::new_full(config).map_err(sc_cli::Error::Service)
+				service::new_full(config, sealing, fedecom).map_err(sc_cli::Error::Service)
 			})
 		},
 	}
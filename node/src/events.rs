@@ -0,0 +1,44 @@
+//! Events and timestamp of the blocks, read from the state of the runtime.
+
+use codec::Decode;
+use node_template_runtime::{opaque::Block, Hash, RuntimeEvent};
+use sc_client_api::{Backend, StorageProvider};
+use sp_core::{hashing::twox_128, storage::StorageKey};
+
+/// Event of a block, with the phase of the block it was deposited in.
+pub type EventRecord = frame_system::EventRecord<RuntimeEvent, Hash>;
+
+/// Key of the plain storage value `storage` of `pallet`.
+pub fn storage_value_key(pallet: &[u8], storage: &[u8]) -> StorageKey {
+	StorageKey([twox_128(pallet), twox_128(storage)].concat())
+}
+
+fn decode<T: Decode>(data: &[u8], what: &str) -> sp_blockchain::Result<T> {
+	T::decode(&mut &data[..])
+		.map_err(|e| sp_blockchain::Error::Backend(format!("Undecodable {}: {}", what, e)))
+}
+
+/// Events deposited in the block `hash`.
+pub fn block_events<C, BE>(client: &C, hash: Hash) -> sp_blockchain::Result<Vec<EventRecord>>
+where
+	BE: Backend<Block>,
+	C: StorageProvider<Block, BE>,
+{
+	match client.storage(hash, &storage_value_key(b"System", b"Events"))? {
+		Some(data) => decode(&data.0, "events"),
+		None => Ok(Vec::new()),
+	}
+}
+
+/// Timestamp of the block `hash`, in milliseconds.
+pub fn block_timestamp<C, BE>(client: &C, hash: Hash) -> sp_blockchain::Result<u64>
+where
+	BE: Backend<Block>,
+	C: StorageProvider<Block, BE>,
+{
+	match client.storage(hash, &storage_value_key(b"Timestamp", b"Now"))? {
+		Some(data) => decode(&data.0, "timestamp"),
+		// The genesis block has no timestamp
+		None => Ok(0),
+	}
+}
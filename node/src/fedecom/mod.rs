@@ -0,0 +1,356 @@
+//! `fedecom` subcommands, submitting and querying the operations of the flexibility market.
+//!
+//! The submissions are signed with `--suri` or with an sr25519 key of a keystore, sent to the RPC
+//! endpoint of a node and awaited until their inclusion in a block. They can also be prepared,
+//! signed and submitted in three steps, see [`offline`]. The queries read the state of the best
+//! block, either through the RPC endpoint or, with `--local`, from the database of the chain
+//! selected by `--chain` and `--base-path`, which must not be used by a running node.
+
+pub(crate) mod offline;
+pub(crate) mod query;
+pub(crate) mod remote;
+pub(crate) mod signer;
+
+use crate::service::FullClient;
+use codec::Encode;
+use node_template_runtime::{
+	pallet_fedecom_ps_demo::{
+		Call as FedecomPSDemoCall, CONFIRMATION_NOK_OVERESTIMATION,
+		CONFIRMATION_NOK_UNDERESTIMATION, CONFIRMATION_OK, FLEXIBILITY_SELLING_STATE_CONFIRMED,
+		FLEXIBILITY_SELLING_STATE_REJECTED,
+	},
+	AccountId, RuntimeCall,
+};
+use query::{OfferFilter, OfferState, State};
+use sc_cli::{CliConfiguration, Result, SharedParams};
+use serde::Serialize;
+use signer::SignerParams;
+use std::{fmt, path::PathBuf, sync::Arc};
+
+/// Submit and query the operations of the flexibility market.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct FedecomCmd {
+	#[command(subcommand)]
+	pub operation: Operation,
+
+	/// WebSocket URL of the RPC endpoint of the node.
+	#[arg(long, value_name = "URL", default_value = "ws://127.0.0.1:9944")]
+	pub url: String,
+
+	/// Read the queries from the local database instead of the RPC endpoint of a node.
+	#[arg(long)]
+	pub local: bool,
+
+	/// Print the output as JSON.
+	#[arg(long)]
+	pub json: bool,
+
+	#[clap(flatten)]
+	pub signer: SignerParams,
+
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+}
+
+/// Status of a payment confirmation.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum ConfirmationStatus {
+	/// The payment is correct
+	Ok,
+	/// The payment overestimates the delivered flexibility
+	Overestimation,
+	/// The payment underestimates the delivered flexibility
+	Underestimation,
+}
+
+/// Decision of a buyer on a flexibility offer.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Decision {
+	/// Buy the flexibility, paying the seller
+	Confirm,
+	/// Decline the offer
+	Reject,
+}
+
+/// Call of the flexibility market.
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum MarketCall {
+	/// Add a payment of the signer to a receiver.
+	AddPayment {
+		/// Receiver of the payment.
+		#[arg(long)]
+		receiver: AccountId,
+		/// Payment timestamp.
+		#[arg(long)]
+		ts: u32,
+		/// Paid value, in FCT.
+		#[arg(long)]
+		value: u32,
+	},
+
+	/// Confirm, as receiver, the payment of a sender.
+	Confirm {
+		/// Sender of the payment.
+		#[arg(long)]
+		sender: AccountId,
+		/// Payment timestamp.
+		#[arg(long)]
+		ts: u32,
+		/// Confirmation status.
+		#[arg(long, value_enum, default_value = "ok")]
+		status: ConfirmationStatus,
+	},
+
+	/// Offer flexibility, as seller, to a buyer.
+	Sell {
+		/// Buyer of the flexibility.
+		#[arg(long)]
+		buyer: AccountId,
+		/// Flexibility market identifier.
+		#[arg(long)]
+		market: u32,
+		/// Flexibility market timestamp.
+		#[arg(long)]
+		market_timestamp: u32,
+		/// Asset/flexibility identifier.
+		#[arg(long)]
+		asset: u32,
+		/// Sold power, in W.
+		#[arg(long)]
+		power: u32,
+		/// Change, in FCT/W.
+		#[arg(long)]
+		change: u32,
+	},
+
+	/// Confirm or reject, as buyer, the flexibility offer of a seller.
+	Decide {
+		/// Seller of the flexibility.
+		#[arg(long)]
+		seller: AccountId,
+		/// Flexibility market identifier.
+		#[arg(long)]
+		market: u32,
+		/// Flexibility market timestamp.
+		#[arg(long)]
+		market_timestamp: u32,
+		/// Asset/flexibility identifier.
+		#[arg(long)]
+		asset: u32,
+		/// Decision on the offer.
+		#[arg(long, value_enum)]
+		decision: Decision,
+	},
+}
+
+/// Operation of the flexibility market.
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum Operation {
+	#[command(flatten)]
+	Submit(MarketCall),
+
+	/// Show a payment and its confirmation.
+	GetPayment {
+		/// Sender of the payment.
+		#[arg(long)]
+		sender: AccountId,
+		/// Receiver of the payment.
+		#[arg(long)]
+		receiver: AccountId,
+		/// Payment timestamp.
+		#[arg(long)]
+		ts: u32,
+	},
+
+	/// List the offers of the flexibility market ledger.
+	ListOffers {
+		/// Only list the offers of this seller.
+		#[arg(long)]
+		seller: Option<AccountId>,
+		/// Only list the offers to this buyer.
+		#[arg(long)]
+		buyer: Option<AccountId>,
+		/// Only list the offers of this flexibility market.
+		#[arg(long)]
+		market: Option<u32>,
+		/// Only list the offers in this state.
+		#[arg(long, value_enum)]
+		state: Option<OfferState>,
+	},
+
+	/// Export the unsigned transaction of a call, to be signed offline with `sign`.
+	#[command(args_conflicts_with_subcommands = true)]
+	Prepare {
+		#[command(subcommand)]
+		call: Option<MarketCall>,
+
+		#[clap(flatten)]
+		params: offline::PrepareParams,
+	},
+
+	/// Verify and sign, without any connection, an unsigned transaction exported by `prepare`.
+	Sign {
+		/// Unsigned transaction, as hex or as the path of a file holding it.
+		payload: String,
+
+		/// File written with the signed extrinsic, printed if not given.
+		#[arg(long, value_name = "PATH")]
+		output: Option<PathBuf>,
+	},
+
+	/// Submit an extrinsic signed with `sign`.
+	SubmitSigned {
+		/// Signed extrinsic, as hex or as the path of a file holding it.
+		extrinsic: String,
+	},
+}
+
+impl MarketCall {
+	/// Runtime call of the market call.
+	fn call(self) -> RuntimeCall {
+		let call = match self {
+			Self::AddPayment { receiver, ts, value } =>
+				FedecomPSDemoCall::add_payment { key_receiver: receiver, ts, value },
+			Self::Confirm { sender, ts, status } => FedecomPSDemoCall::add_confirmation {
+				key_sender: sender,
+				ts,
+				status: match status {
+					ConfirmationStatus::Ok => CONFIRMATION_OK,
+					ConfirmationStatus::Overestimation => CONFIRMATION_NOK_OVERESTIMATION,
+					ConfirmationStatus::Underestimation => CONFIRMATION_NOK_UNDERESTIMATION,
+				},
+			},
+			Self::Sell { buyer, market, market_timestamp, asset, power, change } =>
+				FedecomPSDemoCall::flexibility_selling {
+					buyer,
+					flexibility_market_identifier: market,
+					flexibility_market_timestamp: market_timestamp,
+					asset_identifier: asset,
+					sold_power: power,
+					change_fct_w: change,
+				},
+			Self::Decide { seller, market, market_timestamp, asset, decision } =>
+				FedecomPSDemoCall::flexibility_purchase_decision {
+					seller,
+					flexibility_market_identifier: market,
+					flexibility_market_timestamp: market_timestamp,
+					asset_identifier: asset,
+					new_state: match decision {
+						Decision::Confirm => FLEXIBILITY_SELLING_STATE_CONFIRMED,
+						Decision::Reject => FLEXIBILITY_SELLING_STATE_REJECTED,
+					},
+				},
+		};
+		RuntimeCall::FedecomPSDemo(call)
+	}
+}
+
+/// Offers printed one per line.
+#[derive(Serialize)]
+#[serde(transparent)]
+struct Offers(Vec<query::Offer>);
+
+impl fmt::Display for Offers {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if self.0.is_empty() {
+			return write!(f, "No offers")
+		}
+		for (index, offer) in self.0.iter().enumerate() {
+			if index > 0 {
+				writeln!(f)?;
+			}
+			write!(f, "{}", offer)?;
+		}
+		Ok(())
+	}
+}
+
+impl FedecomCmd {
+	/// Whether the operation only reads the state, and can run against the local database.
+	pub fn is_query(&self) -> bool {
+		matches!(self.operation, Operation::GetPayment { .. } | Operation::ListOffers { .. })
+	}
+
+	fn print(&self, output: &(impl Serialize + fmt::Display)) -> Result<()> {
+		if self.json {
+			let json = serde_json::to_string_pretty(output)
+				.map_err(|e| format!("Failed to serialize the output: {}", e))?;
+			println!("{}", json);
+		} else {
+			println!("{}", output);
+		}
+		Ok(())
+	}
+
+	async fn query(&self, state: &impl State) -> Result<()> {
+		match &self.operation {
+			Operation::GetPayment { sender, receiver, ts } => {
+				let payment = query::payment(state, sender.clone(), receiver.clone(), *ts).await?;
+				self.print(&payment)
+			},
+			Operation::ListOffers { seller, buyer, market, state: offer_state } => {
+				let filter = OfferFilter {
+					seller: seller.clone(),
+					buyer: buyer.clone(),
+					market: *market,
+					state: *offer_state,
+				};
+				self.print(&Offers(query::offers(state, &filter).await?))
+			},
+			_ => Err("Not a query".into()),
+		}
+	}
+
+	fn print_submission(&self, submission: remote::Submission) -> Result<()> {
+		self.print(&submission)?;
+		match submission.error {
+			Some(error) => Err(format!("The extrinsic failed: {}", error).into()),
+			None => Ok(()),
+		}
+	}
+
+	/// Run the operation, against the RPC endpoint of the node at `--url` unless it is offline.
+	pub async fn run(&self) -> Result<()> {
+		match &self.operation {
+			Operation::Submit(call) => {
+				let signer = self.signer.signer()?;
+				let node = remote::Node::connect(&self.url).await?;
+				self.print_submission(node.submit(&signer, call.clone().call()).await?)
+			},
+			Operation::Prepare { call, params } => {
+				let transaction = params.transaction(call.clone().map(MarketCall::call))?;
+				eprintln!("{}", transaction);
+				offline::write_hex(&transaction.encode(), params.output.as_ref())
+			},
+			Operation::Sign { payload, output } => {
+				let transaction = offline::decode_transaction(&offline::read_hex(payload)?)?;
+				transaction.verify()?;
+				let signer = self.signer.signer()?;
+				eprintln!("{}", transaction);
+				eprintln!("Signer: {}", signer.account());
+				offline::write_hex(&transaction.sign(&signer)?.encode(), output.as_ref())
+			},
+			Operation::SubmitSigned { extrinsic } => {
+				let extrinsic = offline::decode_extrinsic(&offline::read_hex(extrinsic)?)?;
+				let node = remote::Node::connect(&self.url).await?;
+				self.print_submission(node.submit_extrinsic(extrinsic).await?)
+			},
+			Operation::GetPayment { .. } | Operation::ListOffers { .. } =>
+				self.query(&remote::Node::connect(&self.url).await?).await,
+		}
+	}
+
+	/// Run the query against the state of the best block of the local `client`.
+	pub fn run_local(&self, client: Arc<FullClient>) -> Result<()> {
+		if !self.is_query() {
+			return Err("Only the queries can run against the local database".into())
+		}
+		futures::executor::block_on(self.query(&query::LocalState::new(client)))
+	}
+}
+
+impl CliConfiguration for FedecomCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+}
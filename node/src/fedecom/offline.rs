@@ -0,0 +1,542 @@
+//! Offline construction and signing of the fedecom extrinsics, for air-gapped signers.
+//!
+//! `prepare` exports an [`UnsignedTransaction`]: the call and every parameter of its signed
+//! payload, given explicitly, with the hash of the metadata of the runtime of the target chain.
+//! `sign` checks that the runtime built in the binary has the same metadata, so that the call is
+//! decoded as the chain will, verifies the call against it and signs it, without any connection,
+//! and `submit-signed` submits the signed extrinsic.
+//! Both files hold the SCALE encoding as a single line of `0x`-prefixed hex, which also fits a QR
+//! code.
+
+use super::signer::Signer;
+use codec::{Decode, DecodeAll, Encode};
+use frame_metadata::{RuntimeMetadata, RuntimeMetadataPrefixed};
+use frame_support::dispatch::GetCallName;
+use node_template_runtime::{
+	self as runtime, Balance, Hash, Runtime, RuntimeCall, SignedExtra, SignedPayload,
+	UncheckedExtrinsic, VERSION,
+};
+use sc_cli::Result;
+use scale_info::TypeDef;
+use sp_core::Bytes;
+use sp_runtime::{
+	generic::Era,
+	traits::{BlakeTwo256, Hash as _},
+};
+use std::{fmt, fs, path::PathBuf};
+
+/// Version of the encoding of [`UnsignedTransaction`].
+const PAYLOAD_VERSION: u8 = 2;
+
+/// Leading bytes of encoded runtime metadata, `meta`.
+const METADATA_MAGIC: &[u8] = b"meta";
+
+/// Identifiers of the signed extensions of [`SignedExtra`], in order.
+const SIGNED_EXTENSIONS: &[&str] = &[
+	"CheckNonZeroSender",
+	"CheckSpecVersion",
+	"CheckTxVersion",
+	"CheckGenesis",
+	"CheckMortality",
+	"CheckNonce",
+	"CheckWeight",
+	"ChargeTransactionPayment",
+];
+
+/// Call and parameters of a transaction, to be signed offline.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+pub struct UnsignedTransaction {
+	/// Version of the encoding, [`PAYLOAD_VERSION`]
+	pub version: u8,
+	/// Call of the transaction
+	pub call: RuntimeCall,
+	/// Nonce of the signer
+	pub nonce: u32,
+	/// Validity period of the transaction
+	pub era: Era,
+	/// Tip of the block author
+	pub tip: Balance,
+	/// Spec version of the runtime
+	pub spec_version: u32,
+	/// Transaction version of the runtime
+	pub transaction_version: u32,
+	/// Hash of the genesis block
+	pub genesis_hash: Hash,
+	/// Hash of the block the era starts at, the genesis hash for immortal transactions
+	pub era_block_hash: Hash,
+	/// Hash of the metadata of the runtime of the chain, see [`metadata_hash`]
+	pub metadata_hash: Hash,
+}
+
+/// Hash of the encoded runtime `metadata`, as returned by `state_getMetadata`.
+pub fn metadata_hash(metadata: &[u8]) -> Hash {
+	BlakeTwo256::hash(metadata)
+}
+
+/// Hash of the metadata of the runtime built in the binary.
+fn runtime_metadata_hash() -> Hash {
+	metadata_hash(&Runtime::metadata().encode())
+}
+
+impl fmt::Display for UnsignedTransaction {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "Call: {:?}", self.call)?;
+		writeln!(f, "Nonce: {}", self.nonce)?;
+		match self.era {
+			Era::Immortal => writeln!(f, "Era: immortal")?,
+			Era::Mortal(period, phase) => writeln!(
+				f,
+				"Era: mortal, {} blocks from {:?} (phase {})",
+				period, self.era_block_hash, phase
+			)?,
+		}
+		writeln!(f, "Tip: {}", self.tip)?;
+		writeln!(
+			f,
+			"Runtime: spec version {}, transaction version {}",
+			self.spec_version, self.transaction_version
+		)?;
+		writeln!(f, "Metadata: {:?}", self.metadata_hash)?;
+		write!(f, "Genesis: {:?}", self.genesis_hash)
+	}
+}
+
+/// Signed extensions of a transaction.
+fn signed_extra(nonce: u32, era: Era, tip: Balance) -> SignedExtra {
+	(
+		frame_system::CheckNonZeroSender::<Runtime>::new(),
+		frame_system::CheckSpecVersion::<Runtime>::new(),
+		frame_system::CheckTxVersion::<Runtime>::new(),
+		frame_system::CheckGenesis::<Runtime>::new(),
+		frame_system::CheckEra::<Runtime>::from(era),
+		frame_system::CheckNonce::<Runtime>::from(nonce),
+		frame_system::CheckWeight::<Runtime>::new(),
+		pallet_transaction_payment::ChargeTransactionPayment::<Runtime>::from(tip),
+	)
+}
+
+impl UnsignedTransaction {
+	/// Signed extensions of the transaction.
+	fn extra(&self) -> SignedExtra {
+		signed_extra(self.nonce, self.era, self.tip)
+	}
+
+	/// Payload signed by the signer of the transaction.
+	pub fn signed_payload(&self) -> SignedPayload {
+		SignedPayload::from_raw(
+			self.call.clone(),
+			self.extra(),
+			(
+				(),
+				self.spec_version,
+				self.transaction_version,
+				self.genesis_hash,
+				self.era_block_hash,
+				(),
+				(),
+				(),
+			),
+		)
+	}
+
+	/// Check that the metadata of the runtime of the chain is the metadata of the runtime of the
+	/// binary, which encodes the call and the signed extensions.
+	fn check_metadata(&self) -> Result<()> {
+		let runtime_hash = runtime_metadata_hash();
+		if self.metadata_hash != runtime_hash {
+			return Err(format!(
+				"The payload targets a runtime with the metadata {:?}, but the runtime of this \
+				binary has the metadata {:?}",
+				self.metadata_hash, runtime_hash
+			)
+			.into())
+		}
+		Ok(())
+	}
+
+	/// Sign the transaction with `signer`, if it targets the runtime of the binary.
+	pub fn sign(&self, signer: &Signer) -> Result<UncheckedExtrinsic> {
+		self.check_metadata()?;
+		let signature = self.signed_payload().using_encoded(|payload| signer.sign(payload))?;
+
+		Ok(UncheckedExtrinsic::new_signed(
+			self.call.clone(),
+			signer.account().into(),
+			signature,
+			self.extra(),
+		))
+	}
+
+	/// Check that the transaction targets the runtime of the binary, and that its call and signed
+	/// extensions match the metadata of that runtime.
+	pub fn verify(&self) -> Result<()> {
+		if self.version != PAYLOAD_VERSION {
+			return Err(format!("Unsupported payload version {}", self.version).into())
+		}
+		self.check_metadata()?;
+		if (self.spec_version, self.transaction_version) !=
+			(VERSION.spec_version, VERSION.transaction_version)
+		{
+			return Err(format!(
+				"The payload targets the spec version {} and transaction version {}, but the \
+				runtime of this binary has {} and {}",
+				self.spec_version,
+				self.transaction_version,
+				VERSION.spec_version,
+				VERSION.transaction_version
+			)
+			.into())
+		}
+		let (pallet_name, call_name) = match &self.call {
+			RuntimeCall::FedecomPSDemo(call) => ("FedecomPSDemo", call.get_call_name()),
+			RuntimeCall::FedecomPSDemo2(call) => ("FedecomPSDemo2", call.get_call_name()),
+			_ => return Err("Only the fedecom calls can be signed".into()),
+		};
+
+		let RuntimeMetadataPrefixed(_, RuntimeMetadata::V14(metadata)) = Runtime::metadata() else {
+			return Err("Unsupported runtime metadata version".into())
+		};
+		let encoded = self.call.encode();
+		let pallet = metadata
+			.pallets
+			.iter()
+			.find(|pallet| pallet.index == encoded[0])
+			.ok_or("The pallet of the call is missing from the metadata")?;
+		let variant = pallet
+			.calls
+			.as_ref()
+			.and_then(|calls| metadata.types.resolve(calls.ty.id))
+			.and_then(|ty| match &ty.type_def {
+				TypeDef::Variant(calls) => calls.variants.iter().find(|v| v.index == encoded[1]),
+				_ => None,
+			})
+			.ok_or("The call is missing from the metadata")?;
+		if pallet.name != pallet_name || variant.name != call_name {
+			return Err(format!(
+				"The call {}::{} does not match {}::{} in the metadata",
+				pallet_name, call_name, pallet.name, variant.name
+			)
+			.into())
+		}
+
+		let extensions: Vec<_> =
+			metadata.extrinsic.signed_extensions.iter().map(|e| e.identifier.as_str()).collect();
+		if extensions != SIGNED_EXTENSIONS {
+			return Err(format!(
+				"The signed extensions of the metadata {:?} differ from the payload",
+				extensions
+			)
+			.into())
+		}
+
+		Ok(())
+	}
+}
+
+/// Era parameters of `prepare`.
+#[derive(Debug, Clone, clap::Args)]
+pub struct EraParams {
+	/// Validity period of a mortal transaction, in blocks (a power of two, at least 4).
+	///
+	/// Transactions are immortal by default.
+	#[arg(long, requires_all = ["era_block_number", "era_block_hash"])]
+	pub era_period: Option<u64>,
+
+	/// Number of the block the validity period of a mortal transaction starts at.
+	#[arg(long, requires = "era_period")]
+	pub era_block_number: Option<u64>,
+
+	/// Hash of the block the validity period of a mortal transaction starts at.
+	#[arg(long, requires = "era_period")]
+	pub era_block_hash: Option<Hash>,
+}
+
+/// Parameters of `prepare`, besides the call.
+#[derive(Debug, Clone, clap::Args)]
+pub struct PrepareParams {
+	/// Encoded fedecom call, instead of an operation.
+	#[arg(long, value_name = "HEX")]
+	pub call_data: Option<Bytes>,
+
+	/// Nonce of the signer.
+	#[arg(long)]
+	pub nonce: u32,
+
+	/// Hash of the genesis block of the chain.
+	#[arg(long)]
+	pub genesis_hash: Hash,
+
+	/// Spec version of the runtime.
+	#[arg(long)]
+	pub spec_version: u32,
+
+	/// Transaction version of the runtime.
+	#[arg(long)]
+	pub transaction_version: u32,
+
+	/// Metadata of the runtime, as returned by `state_getMetadata`: hex, or the path of a file
+	/// holding it.
+	#[arg(long, value_name = "HEX|PATH")]
+	pub metadata: String,
+
+	/// Tip of the block author.
+	#[arg(long, default_value_t = 0)]
+	pub tip: Balance,
+
+	#[clap(flatten)]
+	pub era: EraParams,
+
+	/// File written with the payload, printed if not given.
+	#[arg(long, value_name = "PATH")]
+	pub output: Option<PathBuf>,
+}
+
+impl PrepareParams {
+	/// Unsigned transaction of `call`, or of `--call-data` if not given.
+	pub fn transaction(&self, call: Option<RuntimeCall>) -> Result<UnsignedTransaction> {
+		let call = match (call, &self.call_data) {
+			(Some(call), None) => call,
+			(None, Some(data)) => RuntimeCall::decode_all(&mut &data[..])
+				.map_err(|e| format!("Undecodable call: {}", e))?,
+			_ => return Err("Give either an operation or `--call-data`".into()),
+		};
+		if !matches!(call, RuntimeCall::FedecomPSDemo(_) | RuntimeCall::FedecomPSDemo2(_)) {
+			return Err("Only the fedecom calls can be prepared".into())
+		}
+		let metadata = read_hex(&self.metadata)?;
+		if !metadata.starts_with(METADATA_MAGIC) {
+			return Err("The metadata is not encoded runtime metadata".into())
+		}
+
+		let (era, era_block_hash) = match &self.era {
+			EraParams {
+				era_period: Some(period),
+				era_block_number: Some(number),
+				era_block_hash: Some(hash),
+			} => (Era::mortal(*period, *number), *hash),
+			_ => (Era::Immortal, self.genesis_hash),
+		};
+
+		Ok(UnsignedTransaction {
+			version: PAYLOAD_VERSION,
+			call,
+			nonce: self.nonce,
+			era,
+			tip: self.tip,
+			spec_version: self.spec_version,
+			transaction_version: self.transaction_version,
+			genesis_hash: self.genesis_hash,
+			era_block_hash,
+			metadata_hash: metadata_hash(&metadata),
+		})
+	}
+}
+
+/// Hex given as argument, or read from the file at the given path.
+pub fn read_hex(input: &str) -> Result<Vec<u8>> {
+	let hex = match input.starts_with("0x") {
+		true => input.to_owned(),
+		false => fs::read_to_string(input)
+			.map_err(|e| format!("Failed to read {}: {}", input, e))?
+			.trim()
+			.to_owned(),
+	};
+	hex::decode(hex.trim_start_matches("0x"))
+		.map_err(|e| format!("Invalid hex in {}: {}", input, e).into())
+}
+
+/// Write `data` as hex to `output`, or print it.
+pub fn write_hex(data: &[u8], output: Option<&PathBuf>) -> Result<()> {
+	let hex = format!("0x{}", hex::encode(data));
+	match output {
+		Some(path) => fs::write(path, hex + "\n")
+			.map_err(|e| format!("Failed to write {}: {}", path.display(), e).into()),
+		None => {
+			println!("{}", hex);
+			Ok(())
+		},
+	}
+}
+
+/// Decode the unsigned transaction `data`.
+pub fn decode_transaction(data: &[u8]) -> Result<UnsignedTransaction> {
+	UnsignedTransaction::decode_all(&mut &data[..])
+		.map_err(|e| format!("Undecodable unsigned transaction: {}", e).into())
+}
+
+/// Decode the signed extrinsic `data`, checking that it is signed.
+pub fn decode_extrinsic(data: &[u8]) -> Result<UncheckedExtrinsic> {
+	let extrinsic = UncheckedExtrinsic::decode_all(&mut &data[..])
+		.map_err(|e| format!("Undecodable extrinsic: {}", e))?;
+	match extrinsic.signature {
+		Some(_) => Ok(extrinsic),
+		None => Err("The extrinsic is not signed".into()),
+	}
+}
+
+/// Transaction of `call` signed online, valid for half the block hash count from the best block.
+pub fn online_transaction(
+	call: RuntimeCall,
+	nonce: u32,
+	genesis_hash: Hash,
+	(best_number, best_hash): (u32, Hash),
+	(spec_version, transaction_version): (u32, u32),
+	metadata_hash: Hash,
+) -> UnsignedTransaction {
+	let period = runtime::BlockHashCount::get()
+		.checked_next_power_of_two()
+		.map(|c| c / 2)
+		.unwrap_or(2);
+
+	UnsignedTransaction {
+		version: PAYLOAD_VERSION,
+		call,
+		nonce,
+		era: Era::mortal(period as u64, best_number as u64),
+		tip: 0,
+		spec_version,
+		transaction_version,
+		genesis_hash,
+		era_block_hash: best_hash,
+		metadata_hash,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use node_template_runtime::{pallet_fedecom_ps_demo, SystemCall};
+	use pallet_fedecom_ps_demo::Instance2;
+	use sp_keyring::AccountKeyring::{Alice, Bob};
+	use sp_runtime::traits::Verify;
+
+	fn transaction_params() -> PrepareParams {
+		PrepareParams {
+			call_data: None,
+			nonce: 3,
+			genesis_hash: Hash::repeat_byte(1),
+			spec_version: VERSION.spec_version,
+			transaction_version: VERSION.transaction_version,
+			metadata: format!("0x{}", hex::encode(Runtime::metadata().encode())),
+			tip: 0,
+			era: EraParams { era_period: None, era_block_number: None, era_block_hash: None },
+			output: None,
+		}
+	}
+
+	fn transaction(call: RuntimeCall) -> UnsignedTransaction {
+		let params = PrepareParams {
+			era: EraParams {
+				era_period: Some(64),
+				era_block_number: Some(100),
+				era_block_hash: Some(Hash::repeat_byte(2)),
+			},
+			..transaction_params()
+		};
+		params.transaction(Some(call)).unwrap()
+	}
+
+	fn add_payment() -> RuntimeCall {
+		RuntimeCall::FedecomPSDemo(pallet_fedecom_ps_demo::Call::add_payment {
+			key_receiver: Bob.into(),
+			ts: 20,
+			value: 1_000,
+		})
+	}
+
+	fn add_payment_2() -> RuntimeCall {
+		let call = pallet_fedecom_ps_demo::Call::<Runtime, Instance2>::add_payment {
+			key_receiver: Bob.into(),
+			ts: 20,
+			value: 1_000,
+		};
+		RuntimeCall::FedecomPSDemo2(call)
+	}
+
+	#[test]
+	fn prepared_transactions_round_trip() {
+		let transaction = transaction(add_payment());
+		assert_eq!(transaction.era, Era::mortal(64, 100));
+		assert_eq!(transaction.era_block_hash, Hash::repeat_byte(2));
+
+		assert_eq!(decode_transaction(&transaction.encode()).unwrap(), transaction);
+		// Trailing bytes are rejected
+		assert!(decode_transaction(&[transaction.encode(), vec![0]].concat()).is_err());
+	}
+
+	#[test]
+	fn prepares_encoded_calls() {
+		let params = PrepareParams {
+			call_data: Some(Bytes(add_payment().encode())),
+			..transaction_params()
+		};
+		assert_eq!(params.transaction(None).unwrap().call, add_payment());
+		assert_eq!(params.transaction(None).unwrap().era, Era::Immortal);
+		assert!(params.transaction(Some(add_payment())).is_err());
+
+		let remark = RuntimeCall::System(SystemCall::remark { remark: vec![] });
+		let params =
+			PrepareParams { call_data: Some(Bytes(remark.encode())), ..transaction_params() };
+		assert!(params.transaction(None).is_err());
+	}
+
+	#[test]
+	fn prepares_with_the_hash_of_the_metadata() {
+		let metadata = Runtime::metadata().encode();
+		let transaction = transaction(add_payment_2());
+		assert_eq!(transaction.metadata_hash, metadata_hash(&metadata));
+
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("metadata");
+		fs::write(&path, format!("0x{}\n", hex::encode(&metadata))).unwrap();
+		let params = PrepareParams {
+			metadata: path.to_str().unwrap().to_owned(),
+			..transaction_params()
+		};
+		let prepared = params.transaction(Some(add_payment())).unwrap();
+		assert_eq!(prepared.metadata_hash, transaction.metadata_hash);
+
+		let params = PrepareParams { metadata: "0x00".into(), ..transaction_params() };
+		assert!(params.transaction(Some(add_payment())).is_err());
+	}
+
+	#[test]
+	fn verifies_against_the_runtime() {
+		assert!(transaction(add_payment()).verify().is_ok());
+		assert!(transaction(add_payment_2()).verify().is_ok());
+
+		// Another runtime, e.g. upgraded since the binary was built, may encode the call otherwise
+		let upgraded = UnsignedTransaction {
+			metadata_hash: Hash::repeat_byte(3),
+			..transaction(add_payment())
+		};
+		assert!(upgraded.verify().unwrap_err().to_string().contains("metadata"));
+		assert!(upgraded.sign(&Signer::Pair(Alice.pair())).is_err());
+
+		let outdated = UnsignedTransaction {
+			spec_version: VERSION.spec_version - 1,
+			..transaction(add_payment())
+		};
+		assert!(outdated.verify().unwrap_err().to_string().contains("spec version"));
+
+		let remark = UnsignedTransaction {
+			call: RuntimeCall::System(SystemCall::remark { remark: vec![] }),
+			..transaction(add_payment())
+		};
+		assert!(remark.verify().is_err());
+	}
+
+	#[test]
+	fn signs_the_signed_payload() {
+		let transaction = transaction(add_payment());
+		let signer = Signer::Pair(Alice.pair());
+
+		let extrinsic = decode_extrinsic(&transaction.sign(&signer).unwrap().encode()).unwrap();
+		let (address, signature, extra) = extrinsic.signature.unwrap();
+		assert_eq!(address, Alice.to_account_id().into());
+		assert_eq!(extra, transaction.extra());
+		assert!(transaction
+			.signed_payload()
+			.using_encoded(|payload| signature.verify(payload, &Alice.to_account_id())));
+	}
+}
@@ -0,0 +1,361 @@
+//! Queries of the state of the flexibility market.
+
+use crate::{
+	service::{FullBackend, FullClient},
+	storage::{self, OfferKey},
+};
+use codec::Decode;
+use node_template_runtime::{
+	opaque::Block,
+	pallet_fedecom_ps_demo::{
+		FlexibilitySellingData, CONFIRMATION_NOK_OVERESTIMATION, CONFIRMATION_NOK_UNDERESTIMATION,
+		CONFIRMATION_OK, FLEXIBILITY_SELLING_STATE_CONFIRMED, FLEXIBILITY_SELLING_STATE_NOT_DECIDED,
+		FLEXIBILITY_SELLING_STATE_REJECTED,
+	},
+	AccountId, Hash,
+};
+use sc_cli::Result;
+use sc_client_api::StorageProvider;
+use serde::Serialize;
+use sp_blockchain::HeaderBackend;
+use sp_core::storage::StorageKey;
+use std::{fmt, sync::Arc};
+
+/// State of the best block of a node.
+#[async_trait::async_trait]
+pub trait State: Sync {
+	/// Value stored at `key`, if any.
+	async fn storage(&self, key: Vec<u8>) -> Result<Option<Vec<u8>>>;
+
+	/// Keys starting with `prefix`, with their values.
+	async fn pairs(&self, prefix: Vec<u8>) -> Result<Vec<(Vec<u8>, Vec<u8>)>>;
+}
+
+/// State of the best block of the local database.
+pub struct LocalState {
+	client: Arc<FullClient>,
+	at: Hash,
+}
+
+impl LocalState {
+	/// State of the best block of `client`.
+	pub fn new(client: Arc<FullClient>) -> Self {
+		let at = client.info().best_hash;
+		Self { client, at }
+	}
+}
+
+#[async_trait::async_trait]
+impl State for LocalState {
+	async fn storage(&self, key: Vec<u8>) -> Result<Option<Vec<u8>>> {
+		let data = StorageProvider::<Block, FullBackend>::storage(
+			&*self.client,
+			self.at,
+			&StorageKey(key),
+		)
+		.map_err(|e| format!("Failed to read the state: {}", e))?;
+		Ok(data.map(|data| data.0))
+	}
+
+	async fn pairs(&self, prefix: Vec<u8>) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+		let pairs = StorageProvider::<Block, FullBackend>::storage_pairs(
+			&*self.client,
+			self.at,
+			Some(&StorageKey(prefix)),
+			None,
+		)
+		.map_err(|e| format!("Failed to read the state: {}", e))?;
+		Ok(pairs.map(|(key, data)| (key.0, data.0)).collect())
+	}
+}
+
+fn decode<T: Decode>(data: &[u8], what: &str) -> Result<T> {
+	T::decode(&mut &data[..]).map_err(|e| format!("Undecodable {}: {}", what, e).into())
+}
+
+/// Name of a payment confirmation status.
+pub(crate) fn confirmation_status(status: u32) -> &'static str {
+	match status {
+		CONFIRMATION_OK => "ok",
+		CONFIRMATION_NOK_OVERESTIMATION => "overestimation",
+		CONFIRMATION_NOK_UNDERESTIMATION => "underestimation",
+		_ => "unknown",
+	}
+}
+
+/// Payment and its confirmation.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Payment {
+	/// Sender of the payment
+	pub sender: AccountId,
+	/// Receiver of the payment
+	pub receiver: AccountId,
+	/// Payment timestamp
+	pub ts: u32,
+	/// Paid value, if the payment exists
+	pub value: Option<u32>,
+	/// Confirmation status of the receiver, if confirmed
+	pub confirmation: Option<u32>,
+}
+
+impl fmt::Display for Payment {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let Some(value) = self.value else {
+			return write!(f, "No payment from {} to {} at {}", self.sender, self.receiver, self.ts)
+		};
+		write!(
+			f,
+			"Payment of {} FCT from {} to {} at {}",
+			value, self.sender, self.receiver, self.ts
+		)?;
+		match self.confirmation {
+			Some(status) => write!(f, ", confirmed: {}", confirmation_status(status)),
+			None => write!(f, ", not confirmed"),
+		}
+	}
+}
+
+/// Payment of `sender` to `receiver` at `ts`.
+pub async fn payment(
+	state: &impl State,
+	sender: AccountId,
+	receiver: AccountId,
+	ts: u32,
+) -> Result<Payment> {
+	let key = (sender, receiver, ts);
+	let value = match state.storage(storage::payment(&key)).await? {
+		Some(data) => Some(decode(&data, "payment")?),
+		None => None,
+	};
+	let confirmation = match state.storage(storage::confirmation(&key)).await? {
+		Some(data) => Some(decode(&data, "confirmation")?),
+		None => None,
+	};
+	let (sender, receiver, ts) = key;
+
+	Ok(Payment { sender, receiver, ts, value, confirmation })
+}
+
+/// State of a flexibility offer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum OfferState {
+	/// Not decided by the buyer yet
+	Pending,
+	/// Confirmed by the buyer
+	Confirmed,
+	/// Rejected by the buyer
+	Rejected,
+}
+
+impl OfferState {
+	/// State of the `state` value of the ledger, `None` if unknown.
+	pub(crate) fn from_state(state: u32) -> Option<Self> {
+		match state {
+			FLEXIBILITY_SELLING_STATE_NOT_DECIDED => Some(Self::Pending),
+			FLEXIBILITY_SELLING_STATE_CONFIRMED => Some(Self::Confirmed),
+			FLEXIBILITY_SELLING_STATE_REJECTED => Some(Self::Rejected),
+			_ => None,
+		}
+	}
+
+	/// Name of the state, `unknown` if `None`.
+	pub(crate) fn name(state: Option<Self>) -> &'static str {
+		match state {
+			Some(Self::Pending) => "pending",
+			Some(Self::Confirmed) => "confirmed",
+			Some(Self::Rejected) => "rejected",
+			None => "unknown",
+		}
+	}
+}
+
+/// Flexibility offer of the market ledger.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Offer {
+	/// Seller of the flexibility
+	pub seller: AccountId,
+	/// Buyer of the flexibility
+	pub buyer: AccountId,
+	/// Flexibility market identifier
+	pub market: u32,
+	/// Flexibility market timestamp
+	pub market_timestamp: u32,
+	/// Asset/flexibility identifier
+	pub asset: u32,
+	/// Sold power, in W
+	pub sold_power: u32,
+	/// Change, in FCT/W
+	pub change_fct_w: u32,
+	/// State of the offer, `None` if unknown
+	pub state: Option<OfferState>,
+}
+
+impl fmt::Display for Offer {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"{} -> {}: market {} at {}, asset {}, {} W at {} FCT/W, {}",
+			self.seller,
+			self.buyer,
+			self.market,
+			self.market_timestamp,
+			self.asset,
+			self.sold_power,
+			self.change_fct_w,
+			OfferState::name(self.state)
+		)
+	}
+}
+
+/// Filter of the listed offers, every field is optional.
+#[derive(Debug, Clone, Default)]
+pub struct OfferFilter {
+	/// Seller of the offers
+	pub seller: Option<AccountId>,
+	/// Buyer of the offers
+	pub buyer: Option<AccountId>,
+	/// Flexibility market of the offers
+	pub market: Option<u32>,
+	/// State of the offers
+	pub state: Option<OfferState>,
+}
+
+impl OfferFilter {
+	fn matches(&self, (seller, buyer, market, ..): &OfferKey, state: Option<OfferState>) -> bool {
+		self.seller.as_ref().map_or(true, |s| s == seller) &&
+			self.buyer.as_ref().map_or(true, |b| b == buyer) &&
+			self.market.map_or(true, |m| m == *market) &&
+			self.state.map_or(true, |s| Some(s) == state)
+	}
+}
+
+/// Offers of the flexibility market ledger matching `filter`.
+pub async fn offers(state: &impl State, filter: &OfferFilter) -> Result<Vec<Offer>> {
+	let ledger = storage::prefix(storage::OFFERS);
+	// Only the leading key of the ledger, the market, can narrow the read
+	let prefix = match filter.market {
+		Some(market) => storage::offers_prefix(market, None),
+		None => ledger.clone(),
+	};
+
+	let mut offers = Vec::new();
+	for (key, data) in state.pairs(prefix).await? {
+		let offer = storage::offer_key(&mut &key[ledger.len()..])
+			.ok_or_else(|| format!("Undecodable offer key 0x{}", hex::encode(&key)))?;
+		let data: FlexibilitySellingData = decode(&data, "offer")?;
+		let offer_state = OfferState::from_state(data.state);
+
+		if filter.matches(&offer, offer_state) {
+			let (seller, buyer, market, market_timestamp, asset) = offer;
+			offers.push(Offer {
+				seller,
+				buyer,
+				market,
+				market_timestamp,
+				asset,
+				sold_power: data.sold_power,
+				change_fct_w: data.change_fct_w,
+				state: offer_state,
+			});
+		}
+	}
+
+	Ok(offers)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Encode;
+	use sp_keyring::AccountKeyring::{Alice, Bob, Charlie};
+	use std::collections::BTreeMap;
+
+	/// State held in memory.
+	#[derive(Default)]
+	struct MemoryState(BTreeMap<Vec<u8>, Vec<u8>>);
+
+	#[async_trait::async_trait]
+	impl State for MemoryState {
+		async fn storage(&self, key: Vec<u8>) -> Result<Option<Vec<u8>>> {
+			Ok(self.0.get(&key).cloned())
+		}
+
+		async fn pairs(&self, prefix: Vec<u8>) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+			let pairs = self.0.iter().filter(|(key, _)| key.starts_with(&prefix));
+			Ok(pairs.map(|(key, data)| (key.clone(), data.clone())).collect())
+		}
+	}
+
+	impl MemoryState {
+		fn offer(&mut self, seller: AccountId, market: u32, asset: u32, state: u32) {
+			let key = (seller, Bob.to_account_id(), market, 10, asset);
+			let data = FlexibilitySellingData { sold_power: 500, change_fct_w: 2, state };
+			self.0.insert(storage::offer(&key), data.encode());
+		}
+	}
+
+	#[tokio::test]
+	async fn payments_come_with_their_confirmation() {
+		let mut state = MemoryState::default();
+		let key = (Bob.to_account_id(), Alice.to_account_id(), 20);
+		state.0.insert(storage::payment(&key), 1_000u32.encode());
+		state.0.insert(storage::confirmation(&key), CONFIRMATION_NOK_OVERESTIMATION.encode());
+
+		let (sender, receiver, ts) = key;
+		let confirmed = payment(&state, sender.clone(), receiver.clone(), ts).await.unwrap();
+		assert_eq!(confirmed.value, Some(1_000));
+		assert_eq!(confirmed.confirmation, Some(CONFIRMATION_NOK_OVERESTIMATION));
+		assert!(confirmed.to_string().ends_with("confirmed: overestimation"));
+
+		let missing = payment(&state, receiver, sender, ts).await.unwrap();
+		assert_eq!(missing.value, None);
+		assert!(missing.to_string().starts_with("No payment"));
+	}
+
+	#[tokio::test]
+	async fn offers_are_filtered() {
+		let mut state = MemoryState::default();
+		state.offer(Alice.to_account_id(), 1, 1, FLEXIBILITY_SELLING_STATE_NOT_DECIDED);
+		state.offer(Alice.to_account_id(), 1, 2, FLEXIBILITY_SELLING_STATE_CONFIRMED);
+		state.offer(Charlie.to_account_id(), 1, 3, FLEXIBILITY_SELLING_STATE_REJECTED);
+		state.offer(Alice.to_account_id(), 2, 4, FLEXIBILITY_SELLING_STATE_NOT_DECIDED);
+
+		let assets = |offers: Vec<Offer>| {
+			let mut assets: Vec<_> = offers.iter().map(|offer| offer.asset).collect();
+			assets.sort();
+			assets
+		};
+
+		let all = offers(&state, &OfferFilter::default()).await.unwrap();
+		assert_eq!(assets(all), vec![1, 2, 3, 4]);
+
+		let filter = OfferFilter { market: Some(1), ..Default::default() };
+		let market = offers(&state, &filter).await.unwrap();
+		assert_eq!(market[0].sold_power, 500);
+		assert_eq!(assets(market), vec![1, 2, 3]);
+
+		let filter = OfferFilter {
+			seller: Some(Alice.to_account_id()),
+			state: Some(OfferState::Pending),
+			..Default::default()
+		};
+		assert_eq!(assets(offers(&state, &filter).await.unwrap()), vec![1, 4]);
+
+		let filter = OfferFilter { buyer: Some(Alice.to_account_id()), ..Default::default() };
+		assert!(offers(&state, &filter).await.unwrap().is_empty());
+	}
+
+	#[tokio::test]
+	async fn undecodable_offers_are_errors() {
+		let mut state = MemoryState::default();
+		let mut key = storage::prefix(storage::OFFERS);
+		key.extend([0; 4]);
+		state.0.insert(key, Vec::new());
+
+		let error = offers(&state, &OfferFilter::default()).await.unwrap_err();
+		assert!(error.to_string().contains("Undecodable offer key"));
+	}
+}
@@ -0,0 +1,370 @@
+//! Connection to the RPC endpoint of a node, to read its state and submit extrinsics.
+
+use super::{offline, query::State, signer::Signer};
+use crate::events::{storage_value_key, EventRecord};
+use codec::{Decode, Encode};
+use futures::StreamExt;
+use jsonrpsee::{
+	core::{
+		client::{ClientT, SubscriptionClientT},
+		params::ArrayParams,
+	},
+	rpc_params,
+	ws_client::{WsClient, WsClientBuilder},
+};
+use node_template_runtime::{
+	opaque, Hash, RuntimeCall, RuntimeError, RuntimeEvent, UncheckedExtrinsic,
+};
+use sc_cli::Result;
+use sc_transaction_pool_api::TransactionStatus;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sp_core::{
+	crypto::Ss58Codec,
+	storage::{StorageChangeSet, StorageKey},
+	Bytes,
+};
+use sp_runtime::{
+	generic::SignedBlock,
+	traits::{BlakeTwo256, Hash as _, Header as _},
+};
+use std::fmt;
+
+/// Number of keys requested at once when listing the storage, whose values are then read at once.
+const KEYS_PAGE: u32 = 1000;
+
+/// Versions of the runtime, as returned by `state_getRuntimeVersion`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RuntimeVersion {
+	pub spec_version: u32,
+	pub transaction_version: u32,
+}
+
+/// Outcome of a submitted extrinsic.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Submission {
+	/// Hash of the extrinsic
+	pub extrinsic_hash: Hash,
+	/// Hash of the block including the extrinsic
+	pub block_hash: Hash,
+	/// Index of the extrinsic in the block
+	pub extrinsic_index: u32,
+	/// Dispatch error of the extrinsic, if it failed
+	pub error: Option<String>,
+	/// Events deposited by the extrinsic
+	pub events: Vec<String>,
+}
+
+impl fmt::Display for Submission {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(
+			f,
+			"Extrinsic {:?} included in block {:?} at index {}",
+			self.extrinsic_hash, self.block_hash, self.extrinsic_index
+		)?;
+		match &self.error {
+			Some(error) => writeln!(f, "Failed: {}", error)?,
+			None => writeln!(f, "Succeeded")?,
+		}
+		for event in &self.events {
+			writeln!(f, "  {}", event)?;
+		}
+		Ok(())
+	}
+}
+
+fn rpc_error(e: jsonrpsee::core::Error) -> sc_cli::Error {
+	format!("RPC error: {}", e).into()
+}
+
+/// Node connected through its RPC endpoint.
+pub struct Node {
+	client: WsClient,
+	/// Best block at the connection, whose state is read
+	best: Hash,
+}
+
+impl Node {
+	/// Connect to the node at the WebSocket `url`.
+	pub async fn connect(url: &str) -> Result<Self> {
+		let client = WsClientBuilder::default()
+			.build(url)
+			.await
+			.map_err(|e| format!("Failed to connect to {}: {}", url, e))?;
+		let best = client.request("chain_getBlockHash", rpc_params![]).await.map_err(rpc_error)?;
+
+		Ok(Self { client, best })
+	}
+
+	/// Call the RPC `method` of the node.
+	pub(crate) async fn request<R: DeserializeOwned>(
+		&self,
+		method: &str,
+		params: ArrayParams,
+	) -> Result<R> {
+		self.client.request(method, params).await.map_err(rpc_error)
+	}
+
+	/// Sign `call` with `signer`, valid on top of the best block, if the runtime of the node is the
+	/// runtime of the binary.
+	async fn sign(&self, signer: &Signer, call: RuntimeCall) -> Result<UncheckedExtrinsic> {
+		let genesis: Hash = self.request("chain_getBlockHash", rpc_params![0u32]).await?;
+		let best: opaque::Header = self.request("chain_getHeader", rpc_params![self.best]).await?;
+		let version: RuntimeVersion =
+			self.request("state_getRuntimeVersion", rpc_params![self.best]).await?;
+		let account = signer.account().to_ss58check();
+		let nonce: u32 = self.request("system_accountNextIndex", rpc_params![account]).await?;
+		let metadata: Bytes = self.request("state_getMetadata", rpc_params![self.best]).await?;
+
+		offline::online_transaction(
+			call,
+			nonce,
+			genesis,
+			(*best.number(), self.best),
+			(version.spec_version, version.transaction_version),
+			offline::metadata_hash(&metadata),
+		)
+		.sign(signer)
+	}
+
+	/// Sign and submit `call`, then wait for its inclusion in a block.
+	pub async fn submit(&self, signer: &Signer, call: RuntimeCall) -> Result<Submission> {
+		let extrinsic = self.sign(signer, call).await?;
+		self.submit_extrinsic(extrinsic).await
+	}
+
+	/// Submit the signed `extrinsic`, then wait for its inclusion in a block.
+	pub async fn submit_extrinsic(&self, extrinsic: UncheckedExtrinsic) -> Result<Submission> {
+		let extrinsic = extrinsic.encode();
+		let extrinsic_hash = BlakeTwo256::hash(&extrinsic);
+
+		let mut statuses = self
+			.client
+			.subscribe::<TransactionStatus<Hash, Hash>, _>(
+				"author_submitAndWatchExtrinsic",
+				rpc_params![Bytes(extrinsic.clone())],
+				"author_unwatchExtrinsic",
+			)
+			.await
+			.map_err(rpc_error)?;
+
+		let block_hash = loop {
+			match statuses.next().await {
+				Some(Ok(TransactionStatus::InBlock(hash) | TransactionStatus::Finalized(hash))) =>
+					break hash,
+				Some(Ok(
+					TransactionStatus::Future |
+					TransactionStatus::Ready |
+					TransactionStatus::Broadcast(_) |
+					TransactionStatus::Retracted(_),
+				)) => continue,
+				Some(Ok(status)) =>
+					return Err(format!("Extrinsic not included: {:?}", status).into()),
+				Some(Err(e)) => return Err(rpc_error(e)),
+				None => return Err("The node closed the subscription".into()),
+			}
+		};
+
+		let block: SignedBlock<opaque::Block> =
+			self.request("chain_getBlock", rpc_params![block_hash]).await?;
+		let extrinsic_index = block
+			.block
+			.extrinsics
+			.iter()
+			.position(|e| e.encode() == extrinsic)
+			.ok_or("The extrinsic is missing from its block")? as u32;
+
+		let events_key = storage_value_key(b"System", b"Events");
+		let events: Option<Bytes> =
+			self.request("state_getStorage", rpc_params![events_key, block_hash]).await?;
+		let events = match events {
+			Some(events) => Vec::<EventRecord>::decode(&mut &events[..])
+				.map_err(|e| format!("Undecodable events: {}", e))?,
+			None => Vec::new(),
+		};
+
+		let mut error = None;
+		let events = events
+			.into_iter()
+			.filter(|record| {
+				record.phase == frame_system::Phase::ApplyExtrinsic(extrinsic_index)
+			})
+			.map(|record| {
+				if let RuntimeEvent::System(frame_system::Event::ExtrinsicFailed {
+					dispatch_error,
+					..
+				}) = &record.event
+				{
+					error = Some(match RuntimeError::from_dispatch_error(*dispatch_error) {
+						Some(e) => format!("{:?}", e),
+						None => format!("{:?}", dispatch_error),
+					});
+				}
+				format!("{:?}", record.event)
+			})
+			.collect();
+
+		Ok(Submission { extrinsic_hash, block_hash, extrinsic_index, error, events })
+	}
+}
+
+#[async_trait::async_trait]
+impl State for Node {
+	async fn storage(&self, key: Vec<u8>) -> Result<Option<Vec<u8>>> {
+		let data: Option<Bytes> =
+			self.request("state_getStorage", rpc_params![StorageKey(key), self.best]).await?;
+		Ok(data.map(|data| data.0))
+	}
+
+	async fn pairs(&self, prefix: Vec<u8>) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+		let prefix = StorageKey(prefix);
+		let mut pairs = Vec::new();
+		let mut start_key: Option<StorageKey> = None;
+
+		loop {
+			let keys: Vec<StorageKey> = self
+				.request(
+					"state_getKeysPaged",
+					rpc_params![&prefix, KEYS_PAGE, &start_key, self.best],
+				)
+				.await?;
+			if !keys.is_empty() {
+				let changes: Vec<StorageChangeSet<Hash>> = self
+					.request("state_queryStorageAt", rpc_params![&keys, self.best])
+					.await?;
+				pairs.extend(changes.into_iter().flat_map(|set| set.changes).filter_map(
+					|(key, data)| data.map(|data| (key.0, data.0)),
+				));
+			}
+			if keys.len() < KEYS_PAGE as usize {
+				return Ok(pairs)
+			}
+			start_key = keys.last().cloned();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{fedecom::query, storage};
+	use jsonrpsee::{
+		server::{ServerBuilder, ServerHandle},
+		RpcModule,
+	};
+	use sp_core::storage::StorageData;
+	use sp_keyring::AccountKeyring::{Alice, Bob};
+	use std::{
+		collections::BTreeMap,
+		sync::{Arc, Mutex},
+	};
+
+	/// State of a stand-in node, with the methods called on it.
+	#[derive(Default)]
+	struct StandIn {
+		storage: BTreeMap<Vec<u8>, Vec<u8>>,
+		requests: Mutex<Vec<&'static str>>,
+	}
+
+	impl StandIn {
+		fn called(&self, method: &'static str) {
+			self.requests.lock().unwrap().push(method);
+		}
+
+		fn requests(&self, method: &str) -> usize {
+			self.requests.lock().unwrap().iter().filter(|m| **m == method).count()
+		}
+	}
+
+	/// Serve the state methods of `stand_in` and connect to it.
+	async fn connect(stand_in: Arc<StandIn>) -> (Node, ServerHandle) {
+		let mut module = RpcModule::new(stand_in);
+		module
+			.register_method("chain_getBlockHash", |_, stand_in| {
+				stand_in.called("chain_getBlockHash");
+				Ok(Hash::repeat_byte(1))
+			})
+			.unwrap();
+		module
+			.register_method("state_getStorage", |params, stand_in| {
+				stand_in.called("state_getStorage");
+				let (key, _): (StorageKey, Hash) = params.parse()?;
+				Ok(stand_in.storage.get(&key.0).cloned().map(Bytes))
+			})
+			.unwrap();
+		module
+			.register_method("state_getKeysPaged", |params, stand_in| {
+				stand_in.called("state_getKeysPaged");
+				let (prefix, count, start_key, _): (StorageKey, u32, Option<StorageKey>, Hash) =
+					params.parse()?;
+				let keys: Vec<_> = stand_in
+					.storage
+					.keys()
+					.filter(|key| key.starts_with(&prefix.0))
+					.filter(|key| start_key.as_ref().map_or(true, |start| **key > start.0))
+					.take(count as usize)
+					.map(|key| StorageKey(key.clone()))
+					.collect();
+				Ok(keys)
+			})
+			.unwrap();
+		module
+			.register_method("state_queryStorageAt", |params, stand_in| {
+				stand_in.called("state_queryStorageAt");
+				let (keys, block): (Vec<StorageKey>, Hash) = params.parse()?;
+				let changes = keys
+					.into_iter()
+					.map(|key| {
+						let data = stand_in.storage.get(&key.0).cloned().map(StorageData);
+						(key, data)
+					})
+					.collect();
+				Ok(vec![StorageChangeSet { block, changes }])
+			})
+			.unwrap();
+
+		let server = ServerBuilder::default().build("127.0.0.1:0").await.unwrap();
+		let url = format!("ws://{}", server.local_addr().unwrap());
+		let handle = server.start(module).unwrap();
+		(Node::connect(&url).await.unwrap(), handle)
+	}
+
+	#[tokio::test]
+	async fn pairs_are_read_a_page_of_keys_at_a_time() {
+		let mut stand_in = StandIn::default();
+		let market = storage::offers_prefix(1, None);
+		for asset in 0..1500u32 {
+			let key = (Alice.to_account_id(), Bob.to_account_id(), 1, 10, asset);
+			stand_in.storage.insert(storage::offer(&key), asset.encode());
+		}
+		// Another market, outside of the prefix
+		let key = (Alice.to_account_id(), Bob.to_account_id(), 2, 10, 0);
+		stand_in.storage.insert(storage::offer(&key), 0u32.encode());
+
+		let stand_in = Arc::new(stand_in);
+		let (node, _server) = connect(stand_in.clone()).await;
+		let pairs = node.pairs(market.clone()).await.unwrap();
+
+		assert_eq!(pairs.len(), 1500);
+		assert!(pairs.iter().all(|(key, _)| key.starts_with(&market)));
+		assert_eq!(pairs[0].1, stand_in.storage[&pairs[0].0]);
+		// A request for every page of keys and one for their values, none per key
+		assert_eq!(stand_in.requests("state_getKeysPaged"), 2);
+		assert_eq!(stand_in.requests("state_queryStorageAt"), 2);
+		assert_eq!(stand_in.requests("state_getStorage"), 0);
+	}
+
+	#[tokio::test]
+	async fn payments_are_read_from_the_best_block() {
+		let mut stand_in = StandIn::default();
+		let key = (Bob.to_account_id(), Alice.to_account_id(), 20);
+		stand_in.storage.insert(storage::payment(&key), 1_000u32.encode());
+
+		let (node, _server) = connect(Arc::new(stand_in)).await;
+		let payment = query::payment(&node, key.0, key.1, key.2).await.unwrap();
+
+		assert_eq!(payment.value, Some(1_000));
+		assert_eq!(payment.confirmation, None);
+	}
+}
@@ -0,0 +1,197 @@
+//! Signers of the submitted extrinsics: a secret URI or a key of a local keystore.
+
+use node_template_runtime::{AccountId, Signature};
+use sc_cli::Result;
+use sc_keystore::LocalKeystore;
+use sp_core::{
+	crypto::{ExposeSecret, KeyTypeId, SecretString},
+	sr25519, Pair,
+};
+use sp_keystore::Keystore;
+use std::{fs, path::PathBuf};
+
+/// Environment variable holding the password, when neither `--password-filename` nor
+/// `--password-interactive` is given.
+pub const PASSWORD_ENV: &str = "FEDECOM_PASSWORD";
+
+/// Parameters of the signer of the submitted extrinsics.
+#[derive(Debug, Clone, clap::Args)]
+pub struct SignerParams {
+	/// Secret URI of the signing sr25519 key, e.g. `//Alice` or a mnemonic phrase.
+	#[arg(long, conflicts_with = "account")]
+	pub suri: Option<String>,
+
+	/// SS58 address of the account signing with its sr25519 key of the keystore.
+	#[arg(long, requires = "keystore_path")]
+	pub account: Option<AccountId>,
+
+	/// Path of the keystore holding the key of `--account`.
+	#[arg(long, value_name = "PATH")]
+	pub keystore_path: Option<PathBuf>,
+
+	/// Type of the key of `--account` in the keystore.
+	#[arg(long, value_name = "TYPE", default_value = "acco")]
+	pub key_type: String,
+
+	/// File holding the password of the secret URI or of the keystore, otherwise it is read from
+	/// the `FEDECOM_PASSWORD` environment variable, if set.
+	#[arg(long, value_name = "PATH", conflicts_with = "password_interactive")]
+	pub password_filename: Option<PathBuf>,
+
+	/// Prompt for the password of the secret URI or of the keystore.
+	#[arg(long)]
+	pub password_interactive: bool,
+}
+
+/// Signer of the submitted extrinsics.
+pub enum Signer {
+	/// Key pair derived from a secret URI
+	Pair(sr25519::Pair),
+	/// Key of a local keystore
+	Keystore { keystore: LocalKeystore, key_type: KeyTypeId, public: sr25519::Public },
+}
+
+impl SignerParams {
+	/// Password of the secret URI or of the keystore, kept off the command line so that it does not
+	/// show up in the process list.
+	fn password(&self) -> Result<Option<SecretString>> {
+		let password = if let Some(path) = &self.password_filename {
+			let password = fs::read_to_string(path)
+				.map_err(|e| format!("Failed to read the password file: {}", e))?;
+			Some(password.trim_end_matches(&['\r', '\n'][..]).to_owned())
+		} else if self.password_interactive {
+			Some(rpassword::prompt_password("Password: ")?)
+		} else {
+			std::env::var(PASSWORD_ENV).ok()
+		};
+		Ok(password.map(SecretString::new))
+	}
+
+	/// Signer selected by the parameters.
+	pub fn signer(&self) -> Result<Signer> {
+		match (&self.suri, &self.account, &self.keystore_path) {
+			(Some(suri), _, _) => {
+				let password = self.password()?;
+				let password = password.as_ref().map(|p| p.expose_secret().as_str());
+				sr25519::Pair::from_string(suri, password)
+					.map(Signer::Pair)
+					.map_err(|e| format!("Invalid secret URI: {:?}", e).into())
+			},
+			(None, Some(account), Some(path)) => {
+				let password = self.password()?;
+				let keystore = LocalKeystore::open(path, password)
+					.map_err(|e| format!("Failed to open the keystore: {}", e))?;
+				let key_type = KeyTypeId::try_from(self.key_type.as_str())
+					.map_err(|_| format!("Invalid key type `{}`", self.key_type))?;
+				let public = sr25519::Public::from_raw(*account.as_ref());
+				if !keystore.has_keys(&[(public.to_vec(), key_type)]) {
+					return Err(format!(
+						"No `{}` key of {} in the keystore",
+						self.key_type, account
+					)
+					.into())
+				}
+				Ok(Signer::Keystore { keystore, key_type, public })
+			},
+			_ => Err("Submissions must be signed with `--suri` or `--account`".into()),
+		}
+	}
+}
+
+impl Signer {
+	/// Account of the signer.
+	pub fn account(&self) -> AccountId {
+		match self {
+			Self::Pair(pair) => pair.public().into(),
+			Self::Keystore { public, .. } => (*public).into(),
+		}
+	}
+
+	/// Sign `payload`.
+	pub fn sign(&self, payload: &[u8]) -> Result<Signature> {
+		let signature = match self {
+			Self::Pair(pair) => pair.sign(payload),
+			Self::Keystore { keystore, key_type, public } => keystore
+				.sr25519_sign(*key_type, public, payload)
+				.map_err(|e| format!("Failed to sign with the keystore: {}", e))?
+				.ok_or("The key vanished from the keystore")?,
+		};
+		Ok(Signature::Sr25519(signature))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_keyring::AccountKeyring::{Alice, Bob};
+	use sp_runtime::traits::Verify;
+
+	fn params() -> SignerParams {
+		SignerParams {
+			suri: None,
+			account: None,
+			keystore_path: None,
+			key_type: "acco".into(),
+			password_filename: None,
+			password_interactive: false,
+		}
+	}
+
+	fn signs_for(signer: &Signer, account: &AccountId) -> bool {
+		signer.sign(b"payload").unwrap().verify(&b"payload"[..], account)
+	}
+
+	#[test]
+	fn secret_uris_sign_for_their_account() {
+		let signer = SignerParams { suri: Some("//Alice".into()), ..params() }.signer().unwrap();
+
+		assert_eq!(signer.account(), Alice.to_account_id());
+		assert!(signs_for(&signer, &Alice.to_account_id()));
+		assert!(!signs_for(&signer, &Bob.to_account_id()));
+	}
+
+	#[test]
+	fn passwords_are_read_from_a_file() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("password");
+		fs::write(&path, "secret\n").unwrap();
+
+		let params = SignerParams {
+			suri: Some("//Alice".into()),
+			password_filename: Some(path),
+			..params()
+		};
+		let expected = sr25519::Pair::from_string("//Alice///secret", None).unwrap();
+		assert_eq!(params.signer().unwrap().account(), expected.public().into());
+
+		let missing = SignerParams { password_filename: Some(dir.path().join("none")), ..params };
+		assert!(missing.signer().is_err());
+	}
+
+	#[test]
+	fn keystore_keys_sign_for_their_account() {
+		let dir = tempfile::tempdir().unwrap();
+		let keystore = LocalKeystore::open(dir.path(), None).unwrap();
+		let key_type = KeyTypeId(*b"acco");
+		keystore.sr25519_generate_new(key_type, Some("//Bob")).unwrap();
+
+		let params = SignerParams {
+			account: Some(Bob.to_account_id()),
+			keystore_path: Some(dir.path().to_path_buf()),
+			..params()
+		};
+		let signer = params.signer().unwrap();
+		assert_eq!(signer.account(), Bob.to_account_id());
+		assert!(signs_for(&signer, &Bob.to_account_id()));
+
+		let other_type = SignerParams { key_type: "babe".into(), ..params.clone() };
+		assert!(other_type.signer().is_err());
+		let other_account = SignerParams { account: Some(Alice.to_account_id()), ..params };
+		assert!(other_account.signer().is_err());
+	}
+
+	#[test]
+	fn submissions_need_a_signer() {
+		assert!(params().signer().is_err());
+	}
+}
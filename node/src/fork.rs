@@ -0,0 +1,390 @@
+//! `fork-off` subcommand, creating a local chain from the state of a production chain.
+//!
+//! The state, read from a chain spec written by `export-state` or from the local database, keeps
+//! its runtime, balances, fedecom ledger and market participants. The storage of the consensus,
+//! of the validators and session keys, of the well-known nodes and of the council is replaced by
+//! the genesis storage of a dev chain, so that its keyring accounts produce blocks and govern the
+//! fork from its first block. The market timestamps of the ledger can be shifted, e.g. to replay
+//! the markets of a past day today.
+
+use crate::{chain_spec, service::FullClient, storage};
+use codec::{Decode, Encode};
+use node_template_runtime::{opaque::Block, AccountId, Balance, Nonce};
+use sc_cli::{BlockNumberOrHash, CliConfiguration, DatabaseParams, PruningParams, SharedParams};
+use sc_service::ChainSpec as _;
+use sp_blockchain::HeaderBackend;
+use sp_core::{hashing::twox_128, storage::Storage};
+use sp_runtime::BuildStorage;
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::Arc};
+
+/// Balances of an account, as stored by `frame_system`.
+type AccountInfo = frame_system::AccountInfo<Nonce, pallet_balances::AccountData<Balance>>;
+
+/// Pallets whose storage is taken from the dev chain.
+const REPLACED_PALLETS: &[&[u8]] = &[
+	b"Timestamp",
+	b"Aura",
+	b"Grandpa",
+	b"ValidatorSet",
+	b"Session",
+	b"Historical",
+	b"Offences",
+	b"NodeAuthorization",
+	b"Council",
+	b"MarketCouncil",
+];
+
+/// Storage items of `System` kept from the production state, the others being those of a genesis
+/// block. The last runtime upgrade is kept so that the migrations of the runtime are not run again.
+const KEPT_SYSTEM_ITEMS: &[&[u8]] =
+	&[b"Account", b"LastRuntimeUpgrade", b"UpgradedToU32RefCount", b"UpgradedToTripleRefCount"];
+
+/// Well-known key of the GRANDPA authorities.
+const GRANDPA_AUTHORITIES_KEY: &[u8] = b":grandpa_authorities";
+
+/// Whether `key` is taken from the dev chain rather than from the production state.
+fn is_replaced(key: &[u8]) -> bool {
+	let pallet = |name: &[u8]| key.starts_with(&twox_128(name));
+
+	key == GRANDPA_AUTHORITIES_KEY ||
+		REPLACED_PALLETS.iter().any(|name| pallet(name)) ||
+		(pallet(b"System") &&
+			!KEPT_SYSTEM_ITEMS
+				.iter()
+				.any(|item| key[16..].starts_with(&twox_128(item))))
+}
+
+fn key_error(key: &[u8]) -> String {
+	format!("Undecodable fedecom key 0x{}", hex::encode(key))
+}
+
+/// Market timestamp shifted by `offset` seconds.
+fn shift(timestamp: u32, offset: i64) -> Result<u32, String> {
+	u32::try_from(i64::from(timestamp) + offset).map_err(|_| {
+		format!("Market timestamp {} shifted by {} s is out of range", timestamp, offset)
+	})
+}
+
+/// Key of a payment, confirmation, offer or market period with its market timestamp shifted by
+/// `offset` seconds, or `None` if `key` is not one of them.
+fn shifted_key(key: &[u8], offset: i64) -> Result<Option<Vec<u8>>, String> {
+	for (item, storage_key) in [
+		(storage::OFFERS, storage::offer as fn(&storage::OfferKey) -> Vec<u8>),
+		(storage::SETTLED_OFFERS, storage::settled_offer),
+	] {
+		if let Some(mut suffix) = key.strip_prefix(&storage::prefix(item)[..]) {
+			let (seller, buyer, market, timestamp, asset) =
+				storage::offer_key(&mut suffix).ok_or_else(|| key_error(key))?;
+			let timestamp = shift(timestamp, offset)?;
+			return Ok(Some(storage_key(&(seller, buyer, market, timestamp, asset))))
+		}
+	}
+
+	for (item, storage_key) in [
+		(storage::PERIOD_OFFERS, storage::period_offers as fn(u32, u32) -> Vec<u8>),
+		(storage::CLOSED_PERIODS, storage::closed_period),
+	] {
+		if let Some(mut suffix) = key.strip_prefix(&storage::prefix(item)[..]) {
+			let (market, timestamp) =
+				storage::period_key(&mut suffix).ok_or_else(|| key_error(key))?;
+			return Ok(Some(storage_key(market, shift(timestamp, offset)?)))
+		}
+	}
+
+	let sellers = storage::prefix(storage::SELLER_OFFERS);
+	if let Some(mut suffix) = key.strip_prefix(&sellers[..]) {
+		let (market, timestamp, seller) =
+			storage::seller_offers_key(&mut suffix).ok_or_else(|| key_error(key))?;
+		return Ok(Some(storage::seller_offers(market, shift(timestamp, offset)?, &seller)))
+	}
+
+	for (item, storage_key) in [
+		(&b"Payments"[..], storage::payment as fn(&storage::ConfirmationKey) -> Vec<u8>),
+		(&b"Confirmations"[..], storage::confirmation),
+	] {
+		if let Some(mut suffix) = key.strip_prefix(&storage::prefix(item)[..]) {
+			let (sender, receiver, timestamp) =
+				storage::confirmation_key(&mut suffix).ok_or_else(|| key_error(key))?;
+			return Ok(Some(storage_key(&(sender, receiver, shift(timestamp, offset)?))))
+		}
+	}
+	Ok(None)
+}
+
+/// What a fork kept and changed of the production state.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+	/// Storage entries kept from the production state
+	pub kept: usize,
+	/// Storage entries taken from the dev chain
+	pub replaced: usize,
+	/// Dev accounts added to the production accounts
+	pub dev_accounts: usize,
+	/// Payments, confirmations, offers and market periods whose market timestamp was shifted
+	pub shifted: usize,
+}
+
+/// Storage of the fork of the `production` state, with the consensus, validators, well-known nodes
+/// and council of the genesis storage of the `dev` chain, and the market timestamps shifted by
+/// `offset` seconds.
+///
+/// The dev accounts missing from the production state are added with their balance, which is
+/// added to the total issuance.
+pub fn fork(production: Storage, dev: Storage, offset: i64) -> Result<(Storage, Summary), String> {
+	let mut summary = Summary::default();
+	let mut top = BTreeMap::new();
+
+	for (key, value) in production.top {
+		if is_replaced(&key) {
+			continue
+		}
+		let key = match offset {
+			0 => key,
+			offset => match shifted_key(&key, offset)? {
+				Some(shifted) => {
+					summary.shifted += 1;
+					shifted
+				},
+				None => key,
+			},
+		};
+		summary.kept += 1;
+		top.insert(key, value);
+	}
+
+	let accounts = [twox_128(b"System"), twox_128(b"Account")].concat();
+	let issuance_key = [twox_128(b"Balances"), twox_128(b"TotalIssuance")].concat();
+	let mut issuance = match top.get(&issuance_key) {
+		Some(value) => Balance::decode(&mut &value[..])
+			.map_err(|e| format!("Undecodable total issuance: {}", e))?,
+		None => 0,
+	};
+	for (key, value) in dev.top {
+		if is_replaced(&key) {
+			summary.replaced += 1;
+			top.insert(key, value);
+		} else if key.starts_with(&accounts) && !top.contains_key(&key) {
+			let account = AccountInfo::decode(&mut &value[..])
+				.map_err(|e| format!("Undecodable dev account: {}", e))?;
+			issuance = issuance.saturating_add(account.data.free + account.data.reserved);
+			summary.dev_accounts += 1;
+			top.insert(key, value);
+		}
+	}
+	top.insert(issuance_key, issuance.encode());
+
+	Ok((Storage { top, children_default: production.children_default }, summary))
+}
+
+/// Create a local chain from the state of a production chain, replacing its authorities and
+/// council with the keyring accounts of a dev chain.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct ForkOffCmd {
+	/// Chain spec written by `export-state`, whose state is forked instead of the state of the
+	/// local database.
+	#[arg(long, value_name = "PATH")]
+	pub state: Option<PathBuf>,
+
+	/// Block hash or number of the state forked from the local database, the best block if not
+	/// given.
+	#[arg(long, value_name = "HASH or NUMBER", conflicts_with = "state")]
+	pub at: Option<BlockNumberOrHash>,
+
+	/// Dev chain whose authorities, well-known nodes and council run the fork: `fedecom-demo`,
+	/// with Alice, or `fedecom-local`, with Alice and Bob.
+	#[arg(long, default_value = "fedecom-demo")]
+	pub template: String,
+
+	/// Seconds added to the market timestamps of the payments, confirmations and offers, which
+	/// may be negative.
+	#[arg(long, default_value_t = 0, allow_hyphen_values = true)]
+	pub market_time_offset: i64,
+
+	/// Name of the forked chain.
+	#[arg(long, default_value = "FEDECOM Fork")]
+	pub name: String,
+
+	/// Identifier of the forked chain.
+	#[arg(long, default_value = "fedecom_fork")]
+	pub id: String,
+
+	/// File of the raw chain spec of the fork, written to stdout if not given.
+	#[arg(long, value_name = "PATH")]
+	pub output: Option<PathBuf>,
+
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+
+	#[clap(flatten)]
+	pub pruning_params: PruningParams,
+
+	#[clap(flatten)]
+	pub database_params: DatabaseParams,
+}
+
+impl ForkOffCmd {
+	/// Fork the state of the `--state` chain spec, or else the state of the local database of
+	/// `client`.
+	pub fn run(&self, client: Option<Arc<FullClient>>) -> sc_cli::Result<()> {
+		let production = match (&self.state, client) {
+			(Some(path), _) => chain_spec::ChainSpec::from_json_file(path.clone())?
+				.build_storage()
+				.map_err(|e| format!("Invalid state in {}: {}", path.display(), e))?,
+			(None, Some(client)) => {
+				let hash = match &self.at {
+					Some(at) => client.expect_block_hash_from_id(&at.parse::<Block>()?)?,
+					None => client.info().best_hash,
+				};
+				sc_service::chain_ops::export_raw_state(client, hash)?
+			},
+			(None, None) => return Err("No state to fork".into()),
+		};
+
+		let mut spec = match self.template.as_str() {
+			"fedecom-demo" => chain_spec::fedecom_demo_config()?,
+			"fedecom-local" => chain_spec::fedecom_local_config()?,
+			template => return Err(format!("Unknown template chain `{}`", template).into()),
+		};
+		let dev = spec.build_storage()?;
+
+		let (storage, summary) = fork(production, dev, self.market_time_offset)?;
+		spec.set_storage(storage);
+
+		let mut json: serde_json::Value = serde_json::from_str(&spec.as_json(true)?)
+			.map_err(|e| format!("Invalid chain spec: {}", e))?;
+		json["name"] = self.name.clone().into();
+		json["id"] = self.id.clone().into();
+		let json = serde_json::to_string_pretty(&json)
+			.map_err(|e| format!("Invalid chain spec: {}", e))?;
+
+		match &self.output {
+			Some(path) => fs::write(path, json)
+				.map_err(|e| format!("Failed to write {}: {}", path.display(), e))?,
+			None => println!("{}", json),
+		}
+		eprintln!(
+			"{} entries kept, {} replaced, {} dev accounts added, {} market timestamps shifted",
+			summary.kept, summary.replaced, summary.dev_accounts, summary.shifted
+		);
+		Ok(())
+	}
+}
+
+impl CliConfiguration for ForkOffCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		Some(&self.pruning_params)
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::hashing::blake2_128;
+	use sp_keyring::Sr25519Keyring::{Alice, Bob, Charlie};
+
+	fn key(pallet: &[u8], item: &[u8]) -> Vec<u8> {
+		[twox_128(pallet), twox_128(item)].concat()
+	}
+
+	fn account_key(account: &AccountId) -> Vec<u8> {
+		let account = account.encode();
+		[key(b"System", b"Account"), blake2_128(&account).to_vec(), account].concat()
+	}
+
+	fn account(free: Balance) -> Vec<u8> {
+		let data = pallet_balances::AccountData { free, ..Default::default() };
+		AccountInfo { providers: 1, data, ..Default::default() }.encode()
+	}
+
+	fn storage(top: Vec<(Vec<u8>, Vec<u8>)>) -> Storage {
+		Storage { top: top.into_iter().collect(), children_default: Default::default() }
+	}
+
+	#[test]
+	fn consensus_and_council_are_replaced() {
+		let production = storage(vec![
+			(b":code".to_vec(), b"production runtime".to_vec()),
+			(GRANDPA_AUTHORITIES_KEY.to_vec(), b"production".to_vec()),
+			(key(b"Aura", b"Authorities"), b"production".to_vec()),
+			(key(b"Aura", b"CurrentSlot"), 1_000u64.encode()),
+			(key(b"Council", b"Members"), b"production".to_vec()),
+			(key(b"System", b"Number"), 1_200u32.encode()),
+			(key(b"System", b"LastRuntimeUpgrade"), b"production".to_vec()),
+			(account_key(&Bob.to_account_id()), account(5_000)),
+			(key(b"Balances", b"TotalIssuance"), 5_000u128.encode()),
+			(key(b"FedecomPSDemo", b"PalletPaused"), true.encode()),
+		]);
+		let dev = storage(vec![
+			(b":code".to_vec(), b"dev runtime".to_vec()),
+			(GRANDPA_AUTHORITIES_KEY.to_vec(), b"dev".to_vec()),
+			(key(b"Aura", b"Authorities"), b"dev".to_vec()),
+			(key(b"Council", b"Members"), b"dev".to_vec()),
+			(key(b"System", b"LastRuntimeUpgrade"), b"dev".to_vec()),
+			(account_key(&Alice.to_account_id()), account(1_000)),
+			(account_key(&Bob.to_account_id()), account(1_000)),
+			(key(b"Balances", b"TotalIssuance"), 2_000u128.encode()),
+			(key(b"FedecomPSDemo", b"PalletPaused"), false.encode()),
+		]);
+
+		let (forked, summary) = fork(production, dev, 0).unwrap();
+		let value = |key: &[u8]| forked.top.get(key).map(|value| value.as_slice());
+
+		assert_eq!(value(b":code"), Some(&b"production runtime"[..]));
+		assert_eq!(value(GRANDPA_AUTHORITIES_KEY), Some(&b"dev"[..]));
+		assert_eq!(value(&key(b"Aura", b"Authorities")), Some(&b"dev"[..]));
+		assert_eq!(value(&key(b"Aura", b"CurrentSlot")), None);
+		assert_eq!(value(&key(b"Council", b"Members")), Some(&b"dev"[..]));
+		assert_eq!(value(&key(b"System", b"Number")), None);
+		assert_eq!(value(&key(b"System", b"LastRuntimeUpgrade")), Some(&b"production"[..]));
+		assert_eq!(value(&key(b"FedecomPSDemo", b"PalletPaused")), Some(&true.encode()[..]));
+		// Alice is added, Bob keeps his production balance
+		assert_eq!(value(&account_key(&Alice.to_account_id())), Some(&account(1_000)[..]));
+		assert_eq!(value(&account_key(&Bob.to_account_id())), Some(&account(5_000)[..]));
+		assert_eq!(value(&key(b"Balances", b"TotalIssuance")), Some(&6_000u128.encode()[..]));
+		assert_eq!(summary, Summary { kept: 5, replaced: 3, dev_accounts: 1, shifted: 0 });
+	}
+
+	#[test]
+	fn market_timestamps_are_shifted() {
+		let (alice, bob) = (Alice.to_account_id(), Bob.to_account_id());
+		let production = storage(vec![
+			(storage::payment(&(bob.clone(), alice.clone(), 1_000)), 50u32.encode()),
+			(storage::confirmation(&(bob.clone(), alice.clone(), 1_000)), 1u32.encode()),
+			(storage::offer(&(alice.clone(), bob.clone(), 1, 1_000, 7)), b"offer".to_vec()),
+			(storage::period_offers(1, 1_000), 1u32.encode()),
+			(storage::seller_offers(1, 1_000, &alice), 1u32.encode()),
+			(storage::settled_offer(&(alice.clone(), bob.clone(), 1, 500, 7)), b"offer".to_vec()),
+			(storage::closed_period(1, 500), ().encode()),
+			(key(b"FedecomPSDemo", b"Participants"), b"participants".to_vec()),
+		]);
+
+		let (forked, summary) = fork(production, storage(vec![]), 86_400).unwrap();
+
+		let payment_key = (bob.clone(), alice.clone(), 87_400);
+		assert_eq!(summary.shifted, 7);
+		assert_eq!(forked.top.get(&storage::payment(&payment_key)), Some(&50u32.encode()));
+		assert!(forked.top.contains_key(&storage::confirmation(&payment_key)));
+		let offer_key = (alice.clone(), bob.clone(), 1, 87_400, 7);
+		assert!(forked.top.contains_key(&storage::offer(&offer_key)));
+		assert_eq!(forked.top.get(&storage::period_offers(1, 87_400)), Some(&1u32.encode()));
+		let seller_offers = forked.top.get(&storage::seller_offers(1, 87_400, &alice));
+		assert_eq!(seller_offers, Some(&1u32.encode()));
+		assert!(forked.top.contains_key(&storage::settled_offer(&(alice, bob, 1, 86_900, 7))));
+		assert!(forked.top.contains_key(&storage::closed_period(1, 86_900)));
+		assert!(forked.top.contains_key(&key(b"FedecomPSDemo", b"Participants")));
+
+		let production = storage(vec![(
+			storage::payment(&(Charlie.to_account_id(), Bob.to_account_id(), 10)),
+			50u32.encode(),
+		)]);
+		assert!(fork(production, storage(vec![]), -11).is_err());
+	}
+}
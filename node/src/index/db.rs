@@ -0,0 +1,595 @@
+//! SQLite database of the fedecom index.
+
+use node_template_runtime::{AccountId, Hash};
+use rusqlite::{params, params_from_iter, types::Value, Connection, Row};
+use serde::{Deserialize, Serialize};
+use sp_core::crypto::Ss58Codec;
+use std::{
+	path::Path,
+	sync::{Mutex, MutexGuard},
+};
+
+pub use rusqlite::Error;
+
+/// Result of the index database operations.
+pub type Result<T> = rusqlite::Result<T>;
+
+/// Default number of rows returned by a query.
+const DEFAULT_LIMIT: u32 = 100;
+
+/// Maximum number of rows returned by a query.
+pub const MAX_LIMIT: u32 = 1000;
+
+const SCHEMA: &str = "
+	PRAGMA foreign_keys = ON;
+	PRAGMA journal_mode = WAL;
+
+	CREATE TABLE IF NOT EXISTS blocks (
+		hash BLOB PRIMARY KEY,
+		number INTEGER NOT NULL,
+		parent_hash BLOB NOT NULL,
+		timestamp INTEGER NOT NULL,
+		finalized INTEGER NOT NULL
+	);
+	CREATE INDEX IF NOT EXISTS blocks_number ON blocks (number);
+	CREATE INDEX IF NOT EXISTS blocks_timestamp ON blocks (timestamp);
+
+	CREATE TABLE IF NOT EXISTS extrinsics (
+		block_hash BLOB NOT NULL REFERENCES blocks (hash) ON DELETE CASCADE,
+		extrinsic_index INTEGER NOT NULL,
+		signer TEXT,
+		call TEXT NOT NULL,
+		args TEXT NOT NULL,
+		success INTEGER NOT NULL,
+		PRIMARY KEY (block_hash, extrinsic_index)
+	);
+	CREATE INDEX IF NOT EXISTS extrinsics_signer ON extrinsics (signer);
+	CREATE INDEX IF NOT EXISTS extrinsics_call ON extrinsics (call);
+
+	CREATE TABLE IF NOT EXISTS events (
+		block_hash BLOB NOT NULL REFERENCES blocks (hash) ON DELETE CASCADE,
+		event_index INTEGER NOT NULL,
+		extrinsic_index INTEGER,
+		name TEXT NOT NULL,
+		account TEXT,
+		counterparty TEXT,
+		market INTEGER,
+		data TEXT NOT NULL,
+		PRIMARY KEY (block_hash, event_index)
+	);
+	CREATE INDEX IF NOT EXISTS events_name ON events (name);
+	CREATE INDEX IF NOT EXISTS events_account ON events (account);
+	CREATE INDEX IF NOT EXISTS events_counterparty ON events (counterparty);
+	CREATE INDEX IF NOT EXISTS events_market ON events (market);
+";
+
+/// Block to write in the index.
+pub struct BlockRow {
+	pub hash: Hash,
+	pub parent_hash: Hash,
+	pub number: u32,
+	/// Timestamp of the block, in milliseconds
+	pub timestamp: u64,
+	pub extrinsics: Vec<ExtrinsicRow>,
+	pub events: Vec<EventRow>,
+}
+
+/// Fedecom extrinsic to write in the index.
+pub struct ExtrinsicRow {
+	pub index: u32,
+	pub signer: Option<AccountId>,
+	/// Name of the call, e.g. `flexibility_selling`
+	pub call: &'static str,
+	/// Arguments of the call, as formatted by `Debug`
+	pub args: String,
+	pub success: bool,
+}
+
+/// Fedecom event to write in the index.
+pub struct EventRow {
+	pub index: u32,
+	/// Index of the extrinsic which deposited the event, if any
+	pub extrinsic_index: Option<u32>,
+	/// Name of the event, e.g. `FlexibilitySellingConfirmed`
+	pub name: &'static str,
+	/// Main account of the event: seller, payment sender, participant, ...
+	pub account: Option<AccountId>,
+	/// Other account of the event: buyer, payment receiver, ...
+	pub counterparty: Option<AccountId>,
+	pub market: Option<u32>,
+	/// Fields of the event
+	pub data: serde_json::Value,
+}
+
+/// Filter of the index queries.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Filter {
+	/// Names of the events or calls, all of them when empty.
+	pub names: Vec<String>,
+	/// Account involved in the events, or signer of the extrinsics.
+	pub account: Option<AccountId>,
+	/// Flexibility market of the events, ignored for the extrinsics.
+	pub market: Option<u32>,
+	/// Earliest block timestamp in milliseconds, inclusive.
+	pub from: Option<u64>,
+	/// Latest block timestamp in milliseconds, inclusive.
+	pub to: Option<u64>,
+	/// Only return the data of finalized blocks.
+	pub finalized_only: bool,
+	/// Maximum number of rows, 100 by default and at most 1000.
+	pub limit: Option<u32>,
+	/// Number of rows to skip.
+	pub offset: Option<u32>,
+}
+
+/// Block of an indexed event or extrinsic.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedBlock {
+	pub number: u32,
+	pub hash: Hash,
+	/// Timestamp of the block, in milliseconds.
+	pub timestamp: u64,
+	/// Whether the block is finalized, otherwise it may still be retracted.
+	pub finalized: bool,
+}
+
+/// Indexed fedecom event.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedEvent {
+	pub block: IndexedBlock,
+	pub event_index: u32,
+	pub extrinsic_index: Option<u32>,
+	pub name: String,
+	pub account: Option<String>,
+	pub counterparty: Option<String>,
+	pub market: Option<u32>,
+	pub data: serde_json::Value,
+}
+
+/// Indexed fedecom extrinsic.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedExtrinsic {
+	pub block: IndexedBlock,
+	pub extrinsic_index: u32,
+	pub signer: Option<String>,
+	pub call: String,
+	pub args: String,
+	pub success: bool,
+}
+
+/// Blocks covered by the index.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexStatus {
+	/// Highest indexed block, finalized or not.
+	pub best_number: Option<u32>,
+	/// Highest indexed finalized block.
+	pub finalized_number: Option<u32>,
+	/// Ranges of finalized blocks below `finalized_number` missing from the index, first and last
+	/// included, e.g. because their state was pruned or could not be read. They are indexed again
+	/// when the node restarts.
+	pub gaps: Vec<[u32; 2]>,
+}
+
+fn hash_from_row(row: &Row, index: usize) -> Result<Hash> {
+	let bytes: Vec<u8> = row.get(index)?;
+	if bytes.len() != Hash::len_bytes() {
+		return Err(Error::InvalidColumnType(index, "hash".into(), rusqlite::types::Type::Blob))
+	}
+	Ok(Hash::from_slice(&bytes))
+}
+
+fn block_from_row(row: &Row) -> Result<IndexedBlock> {
+	Ok(IndexedBlock {
+		number: row.get(0)?,
+		hash: hash_from_row(row, 1)?,
+		timestamp: row.get::<_, i64>(2)? as u64,
+		finalized: row.get(3)?,
+	})
+}
+
+/// Append the conditions on the blocks of `filter` to the query `sql`.
+fn filter_blocks(filter: &Filter, sql: &mut String, values: &mut Vec<Value>) {
+	if let Some(from) = filter.from {
+		sql.push_str(" AND b.timestamp >= ?");
+		values.push(Value::Integer(from as i64));
+	}
+	if let Some(to) = filter.to {
+		sql.push_str(" AND b.timestamp <= ?");
+		values.push(Value::Integer(to as i64));
+	}
+	if filter.finalized_only {
+		sql.push_str(" AND b.finalized = 1");
+	}
+}
+
+/// Append the `names` condition of `filter` on `column` to the query `sql`.
+fn filter_names(filter: &Filter, column: &str, sql: &mut String, values: &mut Vec<Value>) {
+	if !filter.names.is_empty() {
+		let placeholders = vec!["?"; filter.names.len()].join(", ");
+		sql.push_str(&format!(" AND {} IN ({})", column, placeholders));
+		values.extend(filter.names.iter().cloned().map(Value::Text));
+	}
+}
+
+/// Append the ordering and pagination of `filter` to the query `sql`.
+fn paginate(filter: &Filter, order: &str, sql: &mut String, values: &mut Vec<Value>) {
+	sql.push_str(&format!(" ORDER BY b.number, {} LIMIT ? OFFSET ?", order));
+	values.push(Value::Integer(filter.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT).into()));
+	values.push(Value::Integer(filter.offset.unwrap_or(0).into()));
+}
+
+/// Index of the fedecom events and extrinsics.
+pub struct Index {
+	connection: Mutex<Connection>,
+}
+
+impl Index {
+	/// Open the index at `path`, creating it if needed.
+	pub fn open(path: &Path) -> Result<Self> {
+		let connection = Connection::open(path)?;
+		connection.execute_batch(SCHEMA)?;
+		Ok(Self { connection: Mutex::new(connection) })
+	}
+
+	fn connection(&self) -> MutexGuard<Connection> {
+		// The connection is left consistent by transactions, even if a holder panicked
+		self.connection.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+	}
+
+	/// Whether the block `hash` is indexed.
+	pub fn contains(&self, hash: &Hash) -> Result<bool> {
+		self.connection()
+			.prepare_cached("SELECT 1 FROM blocks WHERE hash = ?")?
+			.exists(params![hash.as_bytes()])
+	}
+
+	/// Write `block`, or only mark it as `finalized` if it is already indexed.
+	pub fn insert(&self, block: &BlockRow, finalized: bool) -> Result<()> {
+		let mut connection = self.connection();
+		let transaction = connection.transaction()?;
+
+		let inserted = transaction.execute(
+			"INSERT OR IGNORE INTO blocks (hash, number, parent_hash, timestamp, finalized)
+			VALUES (?, ?, ?, ?, ?)",
+			params![
+				block.hash.as_bytes(),
+				block.number,
+				block.parent_hash.as_bytes(),
+				block.timestamp as i64,
+				finalized
+			],
+		)? == 1;
+
+		if inserted {
+			for extrinsic in &block.extrinsics {
+				transaction.execute(
+					"INSERT INTO extrinsics (block_hash, extrinsic_index, signer, call, args,
+						success)
+					VALUES (?, ?, ?, ?, ?, ?)",
+					params![
+						block.hash.as_bytes(),
+						extrinsic.index,
+						extrinsic.signer.as_ref().map(|signer| signer.to_ss58check()),
+						extrinsic.call,
+						extrinsic.args,
+						extrinsic.success
+					],
+				)?;
+			}
+			for event in &block.events {
+				transaction.execute(
+					"INSERT INTO events (block_hash, event_index, extrinsic_index, name, account,
+						counterparty, market, data)
+					VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+					params![
+						block.hash.as_bytes(),
+						event.index,
+						event.extrinsic_index,
+						event.name,
+						event.account.as_ref().map(|account| account.to_ss58check()),
+						event.counterparty.as_ref().map(|account| account.to_ss58check()),
+						event.market,
+						event.data.to_string()
+					],
+				)?;
+			}
+		} else if finalized {
+			transaction.execute(
+				"UPDATE blocks SET finalized = 1 WHERE hash = ?",
+				params![block.hash.as_bytes()],
+			)?;
+		}
+
+		transaction.commit()
+	}
+
+	/// Mark the blocks `hashes` as finalized, and remove the data of the blocks of the other forks
+	/// up to the height `number` of the last one.
+	pub fn finalize(&self, hashes: &[Hash], number: u32) -> Result<()> {
+		let mut connection = self.connection();
+		let transaction = connection.transaction()?;
+
+		for hash in hashes {
+			transaction.execute(
+				"UPDATE blocks SET finalized = 1 WHERE hash = ?",
+				params![hash.as_bytes()],
+			)?;
+		}
+		transaction
+			.execute("DELETE FROM blocks WHERE finalized = 0 AND number <= ?", params![number])?;
+
+		transaction.commit()
+	}
+
+	/// Remove the data of the blocks `hashes`, retracted by a re-organisation, unless finalized.
+	pub fn retract(&self, hashes: &[Hash]) -> Result<()> {
+		let mut connection = self.connection();
+		let transaction = connection.transaction()?;
+
+		for hash in hashes {
+			transaction.execute(
+				"DELETE FROM blocks WHERE hash = ? AND finalized = 0",
+				params![hash.as_bytes()],
+			)?;
+		}
+
+		transaction.commit()
+	}
+
+	/// Remove the data of all the blocks that are not finalized.
+	pub fn discard_unfinalized(&self) -> Result<()> {
+		self.connection().execute("DELETE FROM blocks WHERE finalized = 0", []).map(|_| ())
+	}
+
+	/// Blocks covered by the index.
+	pub fn status(&self) -> Result<IndexStatus> {
+		let (best_number, finalized_number) = self.connection().query_row(
+			"SELECT MAX(number), MAX(CASE WHEN finalized = 1 THEN number END) FROM blocks",
+			[],
+			|row| Ok((row.get(0)?, row.get(1)?)),
+		)?;
+		Ok(IndexStatus { best_number, finalized_number, gaps: self.gaps()? })
+	}
+
+	/// Ranges of finalized blocks missing below the highest indexed finalized block, first and
+	/// last included.
+	pub fn gaps(&self) -> Result<Vec<[u32; 2]>> {
+		let connection = self.connection();
+		let first: Option<u32> = connection.query_row(
+			"SELECT MIN(number) FROM blocks WHERE finalized = 1",
+			[],
+			|row| row.get(0),
+		)?;
+		let mut gaps: Vec<[u32; 2]> = match first {
+			Some(first) if first > 0 => vec![[0, first - 1]],
+			_ => Vec::new(),
+		};
+
+		let mut statement = connection.prepare_cached(
+			"SELECT number + 1, next - 1 FROM (
+				SELECT number, LEAD(number) OVER (ORDER BY number) AS next
+				FROM blocks WHERE finalized = 1
+			) WHERE next > number + 1",
+		)?;
+		let rows = statement.query_map([], |row| Ok([row.get(0)?, row.get(1)?]))?;
+		for gap in rows {
+			gaps.push(gap?);
+		}
+		Ok(gaps)
+	}
+
+	/// Events matching `filter`, oldest first.
+	pub fn events(&self, filter: &Filter) -> Result<Vec<IndexedEvent>> {
+		let mut sql = String::from(
+			"SELECT b.number, b.hash, b.timestamp, b.finalized, e.event_index, e.extrinsic_index,
+				e.name, e.account, e.counterparty, e.market, e.data
+			FROM events e JOIN blocks b ON b.hash = e.block_hash
+			WHERE 1 = 1",
+		);
+		let mut values = Vec::new();
+
+		filter_names(filter, "e.name", &mut sql, &mut values);
+		if let Some(account) = &filter.account {
+			let account = account.to_ss58check();
+			sql.push_str(" AND (e.account = ? OR e.counterparty = ?)");
+			values.extend([Value::Text(account.clone()), Value::Text(account)]);
+		}
+		if let Some(market) = filter.market {
+			sql.push_str(" AND e.market = ?");
+			values.push(Value::Integer(market.into()));
+		}
+		filter_blocks(filter, &mut sql, &mut values);
+		paginate(filter, "e.event_index", &mut sql, &mut values);
+
+		let connection = self.connection();
+		let mut statement = connection.prepare(&sql)?;
+		let rows = statement.query_map(params_from_iter(values), |row| {
+			let data: String = row.get(10)?;
+			Ok(IndexedEvent {
+				block: block_from_row(row)?,
+				event_index: row.get(4)?,
+				extrinsic_index: row.get(5)?,
+				name: row.get(6)?,
+				account: row.get(7)?,
+				counterparty: row.get(8)?,
+				market: row.get(9)?,
+				data: serde_json::from_str(&data).unwrap_or(serde_json::Value::String(data)),
+			})
+		})?;
+		rows.collect()
+	}
+
+	/// Extrinsics matching `filter`, oldest first.
+	pub fn extrinsics(&self, filter: &Filter) -> Result<Vec<IndexedExtrinsic>> {
+		let mut sql = String::from(
+			"SELECT b.number, b.hash, b.timestamp, b.finalized, x.extrinsic_index, x.signer, x.call,
+				x.args, x.success
+			FROM extrinsics x JOIN blocks b ON b.hash = x.block_hash
+			WHERE 1 = 1",
+		);
+		let mut values = Vec::new();
+
+		filter_names(filter, "x.call", &mut sql, &mut values);
+		if let Some(account) = &filter.account {
+			sql.push_str(" AND x.signer = ?");
+			values.push(Value::Text(account.to_ss58check()));
+		}
+		filter_blocks(filter, &mut sql, &mut values);
+		paginate(filter, "x.extrinsic_index", &mut sql, &mut values);
+
+		let connection = self.connection();
+		let mut statement = connection.prepare(&sql)?;
+		let rows = statement.query_map(params_from_iter(values), |row| {
+			Ok(IndexedExtrinsic {
+				block: block_from_row(row)?,
+				extrinsic_index: row.get(4)?,
+				signer: row.get(5)?,
+				call: row.get(6)?,
+				args: row.get(7)?,
+				success: row.get(8)?,
+			})
+		})?;
+		rows.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_keyring::AccountKeyring::{Alice, Bob, Charlie};
+
+	fn index() -> (tempfile::TempDir, Index) {
+		let dir = tempfile::tempdir().unwrap();
+		let index = Index::open(&dir.path().join("index.sqlite")).unwrap();
+		(dir, index)
+	}
+
+	/// Block `number` of the fork `fork`, with a confirmation of an offer of `seller` to Bob.
+	fn block(number: u32, fork: u8, seller: &AccountId) -> BlockRow {
+		let hash = |number: u32| {
+			let mut hash = Hash::repeat_byte(fork);
+			hash.as_bytes_mut()[..4].copy_from_slice(&number.to_be_bytes());
+			hash
+		};
+		BlockRow {
+			hash: hash(number),
+			parent_hash: hash(number.wrapping_sub(1)),
+			number,
+			timestamp: number as u64 * 6_000,
+			extrinsics: vec![ExtrinsicRow {
+				index: 1,
+				signer: Some(Bob.to_account_id()),
+				call: "flexibility_purchase_decision",
+				args: String::new(),
+				success: true,
+			}],
+			events: vec![EventRow {
+				index: 0,
+				extrinsic_index: Some(1),
+				name: "FlexibilitySellingConfirmed",
+				account: Some(seller.clone()),
+				counterparty: Some(Bob.to_account_id()),
+				market: Some(1),
+				data: serde_json::json!({ "marketTimestamp": 900 }),
+			}],
+		}
+	}
+
+	fn numbers(events: Vec<IndexedEvent>) -> Vec<u32> {
+		events.into_iter().map(|event| event.block.number).collect()
+	}
+
+	#[test]
+	fn events_and_extrinsics_are_filtered_and_paginated() {
+		let (_dir, index) = index();
+		let (alice, charlie) = (Alice.to_account_id(), Charlie.to_account_id());
+		for number in 0..5 {
+			let seller = if number % 2 == 0 { &alice } else { &charlie };
+			index.insert(&block(number, 0, seller), true).unwrap();
+		}
+
+		let filter = Filter { account: Some(alice.clone()), ..Default::default() };
+		assert_eq!(numbers(index.events(&filter).unwrap()), vec![0, 2, 4]);
+		// The counterparty of the events is matched as well
+		let filter = Filter { account: Some(Bob.to_account_id()), ..Default::default() };
+		assert_eq!(index.events(&filter).unwrap().len(), 5);
+
+		let filter = Filter { from: Some(6_000), to: Some(18_000), ..Default::default() };
+		assert_eq!(numbers(index.events(&filter).unwrap()), vec![1, 2, 3]);
+		let filter = Filter { limit: Some(2), offset: Some(2), ..Default::default() };
+		assert_eq!(numbers(index.events(&filter).unwrap()), vec![2, 3]);
+
+		let filter = Filter { names: vec!["AddedToConfirmations".into()], ..Default::default() };
+		assert!(index.events(&filter).unwrap().is_empty());
+		let filter = Filter { market: Some(2), ..Default::default() };
+		assert!(index.events(&filter).unwrap().is_empty());
+
+		let filter =
+			Filter { account: Some(Bob.to_account_id()), limit: Some(1), ..Default::default() };
+		let extrinsics = index.extrinsics(&filter).unwrap();
+		assert_eq!(extrinsics.len(), 1);
+		assert_eq!(extrinsics[0].call, "flexibility_purchase_decision");
+		assert_eq!(extrinsics[0].signer, Some(Bob.to_account_id().to_ss58check()));
+	}
+
+	#[test]
+	fn reorganisations_keep_the_events_of_the_best_chain() {
+		let (_dir, index) = index();
+		let (alice, charlie) = (Alice.to_account_id(), Charlie.to_account_id());
+		index.insert(&block(0, 0, &alice), true).unwrap();
+		// Fork 1 is imported first
+		index.insert(&block(1, 1, &alice), false).unwrap();
+		index.insert(&block(2, 1, &alice), false).unwrap();
+		assert_eq!(index.status().unwrap().best_number, Some(2));
+
+		// Fork 2 becomes the best chain
+		index.retract(&[block(1, 1, &alice).hash, block(2, 1, &alice).hash]).unwrap();
+		for number in 1..4 {
+			index.insert(&block(number, 2, &charlie), false).unwrap();
+		}
+		let filter = Filter { account: Some(alice.clone()), ..Default::default() };
+		assert_eq!(numbers(index.events(&filter).unwrap()), vec![0]);
+		let filter = Filter { account: Some(charlie.clone()), ..Default::default() };
+		assert_eq!(numbers(index.events(&filter).unwrap()), vec![1, 2, 3]);
+
+		let filter = Filter { finalized_only: true, ..Default::default() };
+		assert_eq!(numbers(index.events(&filter).unwrap()), vec![0]);
+
+		// A block of fork 1 left behind is removed once fork 2 is finalized above it
+		index.insert(&block(1, 1, &alice), false).unwrap();
+		let finalized = [block(1, 2, &charlie).hash, block(2, 2, &charlie).hash];
+		index.finalize(&finalized, 2).unwrap();
+		let filter = Filter { account: Some(alice), ..Default::default() };
+		assert_eq!(numbers(index.events(&filter).unwrap()), vec![0]);
+
+		let status = index.status().unwrap();
+		assert_eq!((status.best_number, status.finalized_number), (Some(3), Some(2)));
+		index.discard_unfinalized().unwrap();
+		assert_eq!(index.status().unwrap().best_number, Some(2));
+	}
+
+	#[test]
+	fn missing_finalized_blocks_are_reported_as_gaps() {
+		let (_dir, index) = index();
+		let alice = Alice.to_account_id();
+		for number in [2, 3, 6, 8] {
+			index.insert(&block(number, 0, &alice), true).unwrap();
+		}
+		// Not finalized yet, hence not a gap
+		index.insert(&block(10, 0, &alice), false).unwrap();
+
+		let status = index.status().unwrap();
+		assert_eq!((status.best_number, status.finalized_number), (Some(10), Some(8)));
+		assert_eq!(status.gaps, vec![[0, 1], [4, 5], [7, 7]]);
+
+		for number in [0, 1, 4, 5, 7] {
+			index.insert(&block(number, 0, &alice), true).unwrap();
+		}
+		assert!(index.gaps().unwrap().is_empty());
+	}
+}
@@ -0,0 +1,484 @@
+//! Index of the fedecom events and extrinsics, in a local SQLite database.
+//!
+//! Enabled with `--fedecom-index <path>`, the indexer writes the `FedecomPSDemo` events and
+//! extrinsics of every new best block, and marks them as finalized once their block is. The data of
+//! blocks retracted by a re-organisation, or left on a fork when a competing block is finalized, is
+//! removed, so that non-finalized results are always those of the current best chain.
+//!
+//! When the node starts, the unfinalized data of the previous run is discarded and the finalized
+//! blocks missing from the index are indexed again. The finalized blocks that could not be indexed
+//! are reported as gaps by `fedecom_index_status`. Since the events are read from the state of the
+//! blocks, the index requires a node keeping the state of the finalized blocks.
+//!
+//! The index is queried through the `fedecom_index_*` RPC methods of [`rpc`].
+
+pub mod db;
+pub mod rpc;
+
+use crate::events::{block_events, block_timestamp};
+use codec::{Decode, Encode};
+use db::{BlockRow, EventRow, ExtrinsicRow};
+use frame_support::dispatch::GetCallName;
+use futures::{stream, StreamExt};
+use node_template_runtime::{
+	opaque::Block, pallet_fedecom_ps_demo, AccountId, Hash, Runtime, RuntimeCall, RuntimeEvent,
+	UncheckedExtrinsic,
+};
+use sc_client_api::{
+	Backend, BlockBackend, BlockImportNotification, BlockchainEvents, FinalityNotification,
+	StorageProvider,
+};
+use serde_json::json;
+use sp_blockchain::HeaderBackend;
+use sp_core::crypto::Ss58Codec;
+use sp_runtime::{traits::Header, MultiAddress};
+use std::{collections::HashSet, fmt, iter, sync::Arc};
+
+pub use db::Index;
+
+const LOG_TARGET: &str = "fedecom-index";
+
+/// Error of the indexer.
+#[derive(Debug)]
+pub enum Error {
+	/// The block could not be read from the client.
+	Client(sp_blockchain::Error),
+	/// The block could not be written in the database.
+	Database(db::Error),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Client(e) => write!(f, "Client error: {}", e),
+			Self::Database(e) => write!(f, "Database error: {}", e),
+		}
+	}
+}
+
+impl From<sp_blockchain::Error> for Error {
+	fn from(e: sp_blockchain::Error) -> Self {
+		Self::Client(e)
+	}
+}
+
+impl From<db::Error> for Error {
+	fn from(e: db::Error) -> Self {
+		Self::Database(e)
+	}
+}
+
+enum Notification {
+	Imported(BlockImportNotification<Block>),
+	Finalized(FinalityNotification<Block>),
+}
+
+/// Row of a fedecom event, `None` for the events that are not worth indexing.
+fn event_row(
+	index: u32,
+	extrinsic_index: Option<u32>,
+	event: pallet_fedecom_ps_demo::Event<Runtime>,
+) -> Option<EventRow> {
+	use pallet_fedecom_ps_demo::Event::*;
+
+	let new_row =
+		|name, account: Option<&AccountId>, counterparty: Option<&AccountId>, market, data| {
+			EventRow {
+				index,
+				extrinsic_index,
+				name,
+				account: account.cloned(),
+				counterparty: counterparty.cloned(),
+				market,
+				data,
+			}
+		};
+	let ss58 = |account: &AccountId| account.to_ss58check();
+
+	let row = match &event {
+		GotFromPayments { key_sender, key_receiver, ts, value } => new_row(
+			"GotFromPayments",
+			Some(key_sender),
+			Some(key_receiver),
+			None,
+			json!({ "ts": ts, "value": value }),
+		),
+		AddedToPayments { key_sender, key_receiver, ts, value } => new_row(
+			"AddedToPayments",
+			Some(key_sender),
+			Some(key_receiver),
+			None,
+			json!({ "ts": ts, "value": value }),
+		),
+		RemovedFromPayments { key_sender, key_receiver, ts } => new_row(
+			"RemovedFromPayments",
+			Some(key_sender),
+			Some(key_receiver),
+			None,
+			json!({ "ts": ts }),
+		),
+		SetInPayments { key_sender, key_receiver, ts } => new_row(
+			"SetInPayments",
+			Some(key_sender),
+			Some(key_receiver),
+			None,
+			json!({ "ts": ts }),
+		),
+		AddedToConfirmations { key_confirmer, key_sender, key_receiver, ts, status } => new_row(
+			"AddedToConfirmations",
+			Some(key_sender),
+			Some(key_receiver),
+			None,
+			json!({ "confirmer": ss58(key_confirmer), "ts": ts, "status": status }),
+		),
+		RemovedFromConfirmations { key_sender, key_receiver, ts } => new_row(
+			"RemovedFromConfirmations",
+			Some(key_sender),
+			Some(key_receiver),
+			None,
+			json!({ "ts": ts }),
+		),
+		SuccessfullySoldFlexibility {
+			seller,
+			buyer,
+			flexibility_market_identifier,
+			flexibility_market_timestamp,
+			asset_identifier,
+			sold_power,
+			change_fct_w,
+		} => new_row(
+			"SuccessfullySoldFlexibility",
+			Some(seller),
+			Some(buyer),
+			Some(*flexibility_market_identifier),
+			json!({
+				"marketTimestamp": flexibility_market_timestamp,
+				"asset": asset_identifier,
+				"soldPower": sold_power,
+				"changeFctW": change_fct_w,
+			}),
+		),
+		AlreadySoldFlexibility {
+			seller,
+			buyer,
+			flexibility_market_identifier,
+			flexibility_market_timestamp,
+			asset_identifier,
+		} => new_row(
+			"AlreadySoldFlexibility",
+			Some(seller),
+			Some(buyer),
+			Some(*flexibility_market_identifier),
+			json!({ "marketTimestamp": flexibility_market_timestamp, "asset": asset_identifier }),
+		),
+		FlexibilitySellingNotExisting {
+			seller,
+			buyer,
+			flexibility_market_identifier,
+			flexibility_market_timestamp,
+			asset_identifier,
+		} => new_row(
+			"FlexibilitySellingNotExisting",
+			Some(seller),
+			Some(buyer),
+			Some(*flexibility_market_identifier),
+			json!({ "marketTimestamp": flexibility_market_timestamp, "asset": asset_identifier }),
+		),
+		FlexibilitySellingConfirmed {
+			seller,
+			buyer,
+			flexibility_market_identifier,
+			flexibility_market_timestamp,
+			asset_identifier,
+		} => new_row(
+			"FlexibilitySellingConfirmed",
+			Some(seller),
+			Some(buyer),
+			Some(*flexibility_market_identifier),
+			json!({ "marketTimestamp": flexibility_market_timestamp, "asset": asset_identifier }),
+		),
+		FlexibilitySellingRejected {
+			seller,
+			buyer,
+			flexibility_market_identifier,
+			flexibility_market_timestamp,
+			asset_identifier,
+		} => new_row(
+			"FlexibilitySellingRejected",
+			Some(seller),
+			Some(buyer),
+			Some(*flexibility_market_identifier),
+			json!({ "marketTimestamp": flexibility_market_timestamp, "asset": asset_identifier }),
+		),
+		ParticipantRegistered { participant, role } => new_row(
+			"ParticipantRegistered",
+			Some(participant),
+			None,
+			None,
+			json!({ "role": role }),
+		),
+		ParticipantRemoved { participant } =>
+			new_row("ParticipantRemoved", Some(participant), None, None, json!({})),
+		MarketRegistered { flexibility_market_identifier, operator } => new_row(
+			"MarketRegistered",
+			Some(operator),
+			None,
+			Some(*flexibility_market_identifier),
+			json!({}),
+		),
+		MarketRemoved { flexibility_market_identifier } =>
+			new_row("MarketRemoved", None, None, Some(*flexibility_market_identifier), json!({})),
+		AssetRegistered { asset_identifier, owner } => new_row(
+			"AssetRegistered",
+			Some(owner),
+			None,
+			None,
+			json!({ "asset": asset_identifier }),
+		),
+		PalletPaused() => new_row("PalletPaused", None, None, None, json!({})),
+		PalletUnpaused() => new_row("PalletUnpaused", None, None, None, json!({})),
+		CallPaused { call_name } => new_row(
+			"CallPaused",
+			None,
+			None,
+			None,
+			json!({ "call": String::from_utf8_lossy(call_name) }),
+		),
+		CallUnpaused { call_name } => new_row(
+			"CallUnpaused",
+			None,
+			None,
+			None,
+			json!({ "call": String::from_utf8_lossy(call_name) }),
+		),
+		MarketPaused { flexibility_market_identifier } =>
+			new_row("MarketPaused", None, None, Some(*flexibility_market_identifier), json!({})),
+		MarketUnpaused { flexibility_market_identifier } =>
+			new_row("MarketUnpaused", None, None, Some(*flexibility_market_identifier), json!({})),
+		MarketPeriodClosed {
+			flexibility_market_identifier,
+			flexibility_market_timestamp,
+			removed,
+			settled,
+			remaining,
+		} => new_row(
+			"MarketPeriodClosed",
+			None,
+			None,
+			Some(*flexibility_market_identifier),
+			json!({
+				"marketTimestamp": flexibility_market_timestamp,
+				"removed": removed,
+				"settled": settled,
+				"remaining": remaining,
+			}),
+		),
+		// `InPayments`, `ConfirmationOK`, ... carry no data, they only make sense along with the
+		// extrinsic or event preceding them
+		_ => return None,
+	};
+
+	Some(row)
+}
+
+/// Read the fedecom events and extrinsics of the block `hash`.
+fn block_row<C, BE>(client: &C, hash: Hash) -> Result<BlockRow, Error>
+where
+	BE: Backend<Block>,
+	C: HeaderBackend<Block> + BlockBackend<Block> + StorageProvider<Block, BE>,
+{
+	let header = client
+		.header(hash)?
+		.ok_or_else(|| sp_blockchain::Error::UnknownBlock(hash.to_string()))?;
+	let body = client.block_body(hash)?.unwrap_or_default();
+
+	let mut events = Vec::new();
+	let mut succeeded = HashSet::new();
+	for (index, record) in block_events(client, hash)?.into_iter().enumerate() {
+		let extrinsic_index = match record.phase {
+			frame_system::Phase::ApplyExtrinsic(index) => Some(index),
+			_ => None,
+		};
+		match record.event {
+			RuntimeEvent::System(frame_system::Event::ExtrinsicSuccess { .. }) => {
+				succeeded.extend(extrinsic_index);
+			},
+			RuntimeEvent::FedecomPSDemo(event) =>
+				events.extend(event_row(index as u32, extrinsic_index, event)),
+			_ => {},
+		}
+	}
+
+	let mut extrinsics = Vec::new();
+	for (index, opaque) in body.iter().enumerate() {
+		let index = index as u32;
+		let extrinsic = match UncheckedExtrinsic::decode(&mut &opaque.encode()[..]) {
+			Ok(extrinsic) => extrinsic,
+			Err(e) => {
+				log::warn!(target: LOG_TARGET, "Undecodable extrinsic {}-{}: {}", hash, index, e);
+				continue
+			},
+		};
+		let RuntimeCall::FedecomPSDemo(call) = extrinsic.function else { continue };
+
+		extrinsics.push(ExtrinsicRow {
+			index,
+			signer: extrinsic.signature.and_then(|(address, _, _)| match address {
+				MultiAddress::Id(account) => Some(account),
+				_ => None,
+			}),
+			call: call.get_call_name(),
+			args: format!("{:?}", call),
+			success: succeeded.contains(&index),
+		});
+	}
+
+	Ok(BlockRow {
+		hash,
+		parent_hash: *header.parent_hash(),
+		number: *header.number(),
+		timestamp: block_timestamp(client, hash)?,
+		extrinsics,
+		events,
+	})
+}
+
+/// Index the finalized block `number`.
+fn index_finalized<C, BE>(client: &C, index: &Index, number: u32) -> Result<(), Error>
+where
+	BE: Backend<Block>,
+	C: HeaderBackend<Block> + BlockBackend<Block> + StorageProvider<Block, BE>,
+{
+	let hash = client
+		.hash(number)?
+		.ok_or_else(|| sp_blockchain::Error::UnknownBlock(number.to_string()))?;
+	Ok(index.insert(&block_row(client, hash)?, true)?)
+}
+
+/// Index the finalized blocks missing from `index`, in its gaps and above its last finalized
+/// block, discarding its unfinalized data.
+///
+/// The blocks that cannot be indexed are left as gaps, reported by the index status and tried
+/// again at the next start.
+fn catch_up<C, BE>(client: &C, index: &Index) -> Result<(), Error>
+where
+	BE: Backend<Block>,
+	C: HeaderBackend<Block> + BlockBackend<Block> + StorageProvider<Block, BE>,
+{
+	index.discard_unfinalized()?;
+
+	let status = index.status()?;
+	let first = status.finalized_number.map_or(0, |number| number + 1);
+	let last = client.info().finalized_number;
+	let ranges = status.gaps.into_iter().chain((first <= last).then_some([first, last]));
+
+	for [first, last] in ranges {
+		log::info!(target: LOG_TARGET, "Indexing the finalized blocks #{} to #{}", first, last);
+
+		let mut failed = 0;
+		for number in first..=last {
+			if let Err(e) = index_finalized(client, index, number) {
+				if failed == 0 {
+					log::error!(target: LOG_TARGET, "Failed to index the block #{}: {}", number, e);
+				}
+				failed += 1;
+			}
+		}
+		if failed > 0 {
+			log::error!(
+				target: LOG_TARGET,
+				"{} of the finalized blocks #{} to #{} are missing from the index",
+				failed,
+				first,
+				last
+			);
+		}
+	}
+
+	Ok(())
+}
+
+/// Index the new best block of `notification`, and the blocks it enacts in place of the retracted
+/// ones.
+fn on_best_block<C, BE>(
+	client: &C,
+	index: &Index,
+	notification: &BlockImportNotification<Block>,
+) -> Result<(), Error>
+where
+	BE: Backend<Block>,
+	C: HeaderBackend<Block> + BlockBackend<Block> + StorageProvider<Block, BE>,
+{
+	if let Some(tree_route) = &notification.tree_route {
+		let retracted: Vec<_> = tree_route.retracted().iter().map(|block| block.hash).collect();
+		index.retract(&retracted)?;
+
+		for block in tree_route.enacted() {
+			index.insert(&block_row(client, block.hash)?, false)?;
+		}
+	}
+
+	index.insert(&block_row(client, notification.hash)?, false)
+}
+
+/// Index the blocks finalized by `notification`, and remove the data of the other forks.
+fn on_finalized_block<C, BE>(
+	client: &C,
+	index: &Index,
+	notification: &FinalityNotification<Block>,
+) -> Result<(), Error>
+where
+	BE: Backend<Block>,
+	C: HeaderBackend<Block> + BlockBackend<Block> + StorageProvider<Block, BE>,
+{
+	// The tree route holds the blocks finalized along with `hash`, oldest first
+	let hashes: Vec<Hash> =
+		notification.tree_route.iter().chain(iter::once(&notification.hash)).copied().collect();
+
+	for hash in &hashes {
+		// Usually indexed when it became the best block
+		if !index.contains(hash)? {
+			index.insert(&block_row(client, *hash)?, true)?;
+		}
+	}
+
+	Ok(index.finalize(&hashes, *notification.header.number())?)
+}
+
+/// Follow the best and finalized blocks of `client` and write their fedecom events and extrinsics
+/// in `index`.
+///
+/// The blocks are read and written synchronously, so the task must be spawned as a blocking task,
+/// on a thread of its own, not to hold up the other tasks of the node.
+pub async fn run<C, BE>(client: Arc<C>, index: Arc<Index>)
+where
+	BE: Backend<Block> + 'static,
+	C: BlockchainEvents<Block>
+		+ HeaderBackend<Block>
+		+ BlockBackend<Block>
+		+ StorageProvider<Block, BE>
+		+ 'static,
+{
+	// Subscribe before catching up, so that no block is missed
+	let imported = client.import_notification_stream().map(Notification::Imported);
+	let finalized = client.finality_notification_stream().map(Notification::Finalized);
+	let mut notifications = stream::select(imported, finalized);
+
+	if let Err(e) = catch_up(&*client, &index) {
+		log::error!(target: LOG_TARGET, "Failed to index the finalized blocks: {}", e);
+	}
+
+	while let Some(notification) = notifications.next().await {
+		let (hash, result) = match &notification {
+			Notification::Imported(notification) if notification.is_new_best =>
+				(notification.hash, on_best_block(&*client, &index, notification)),
+			Notification::Imported(_) => continue,
+			Notification::Finalized(notification) =>
+				(notification.hash, on_finalized_block(&*client, &index, notification)),
+		};
+
+		if let Err(e) = result {
+			log::warn!(target: LOG_TARGET, "Failed to index the block {}: {}", hash, e);
+		}
+	}
+}
@@ -0,0 +1,100 @@
+//! RPC methods querying the fedecom index.
+
+use super::db::{Filter, Index, IndexStatus, IndexedEvent, IndexedExtrinsic, MAX_LIMIT};
+use jsonrpsee::{
+	core::RpcResult,
+	proc_macros::rpc,
+	types::error::{CallError, ErrorObject},
+};
+use node_template_runtime::AccountId;
+use std::sync::Arc;
+
+/// Error code of the index query failures.
+const INDEX_ERROR: i32 = 1;
+
+/// Historical queries of the fedecom events and extrinsics.
+#[rpc(server)]
+pub trait FedecomIndexApi {
+	/// Blocks covered by the index.
+	#[method(name = "fedecom_index_status")]
+	fn status(&self) -> RpcResult<IndexStatus>;
+
+	/// Events matching `filter`, oldest first.
+	#[method(name = "fedecom_index_events")]
+	fn events(&self, filter: Filter) -> RpcResult<Vec<IndexedEvent>>;
+
+	/// Extrinsics matching `filter`, oldest first.
+	#[method(name = "fedecom_index_extrinsics")]
+	fn extrinsics(&self, filter: Filter) -> RpcResult<Vec<IndexedExtrinsic>>;
+
+	/// Payment confirmations and confirmed flexibility sellings involving `account`, in the
+	/// range of block timestamps `from` to `to` (milliseconds, inclusive), oldest first.
+	///
+	/// At most `limit` of them are returned (1000 by default and at most), after skipping `offset`
+	/// of them.
+	#[method(name = "fedecom_index_confirmations")]
+	fn confirmations(
+		&self,
+		account: AccountId,
+		from: Option<u64>,
+		to: Option<u64>,
+		limit: Option<u32>,
+		offset: Option<u32>,
+	) -> RpcResult<Vec<IndexedEvent>>;
+}
+
+fn index_error(e: super::db::Error) -> jsonrpsee::core::Error {
+	CallError::Custom(ErrorObject::owned(
+		INDEX_ERROR,
+		"Unable to query the fedecom index.",
+		Some(e.to_string()),
+	))
+	.into()
+}
+
+/// Implementation of [`FedecomIndexApiServer`] on top of the index database.
+pub struct FedecomIndex {
+	index: Arc<Index>,
+}
+
+impl FedecomIndex {
+	/// Create a new instance of the index RPC methods.
+	pub fn new(index: Arc<Index>) -> Self {
+		Self { index }
+	}
+}
+
+impl FedecomIndexApiServer for FedecomIndex {
+	fn status(&self) -> RpcResult<IndexStatus> {
+		self.index.status().map_err(index_error)
+	}
+
+	fn events(&self, filter: Filter) -> RpcResult<Vec<IndexedEvent>> {
+		self.index.events(&filter).map_err(index_error)
+	}
+
+	fn extrinsics(&self, filter: Filter) -> RpcResult<Vec<IndexedExtrinsic>> {
+		self.index.extrinsics(&filter).map_err(index_error)
+	}
+
+	fn confirmations(
+		&self,
+		account: AccountId,
+		from: Option<u64>,
+		to: Option<u64>,
+		limit: Option<u32>,
+		offset: Option<u32>,
+	) -> RpcResult<Vec<IndexedEvent>> {
+		let filter = Filter {
+			names: vec!["AddedToConfirmations".into(), "FlexibilitySellingConfirmed".into()],
+			account: Some(account),
+			from,
+			to,
+			limit: Some(limit.unwrap_or(MAX_LIMIT)),
+			offset,
+			..Default::default()
+		};
+
+		self.index.events(&filter).map_err(index_error)
+	}
+}
@@ -0,0 +1,358 @@
+//! `export-ledger` subcommand, writing the payments, confirmations and flexibility offers of the
+//! fedecom pallet at a block as tables for audits and regulatory reporting.
+//!
+//! Unlike `export-state`, which dumps the raw storage into a chain spec, the tables are decoded:
+//! accounts are SS58 addresses, timestamps are given both raw and as RFC 3339 UTC dates, and
+//! every payment carries its settlement status. The state is read from the local database, which
+//! must not be used by a running node, and older blocks need a node run with `--state-pruning
+//! archive`.
+
+pub mod table;
+
+use crate::{
+	events::block_timestamp,
+	fedecom::query::{confirmation_status, OfferState},
+	service::{FullBackend, FullClient},
+	storage,
+};
+use codec::Decode;
+use node_template_runtime::{
+	opaque::Block,
+	pallet_fedecom_ps_demo::{FlexibilitySellingData, CONFIRMATION_OK},
+	Hash,
+};
+use sc_cli::{
+	BlockNumberOrHash, CliConfiguration, DatabaseParams, PruningParams, Result, SharedParams,
+};
+use sc_client_api::StorageProvider;
+use sp_blockchain::HeaderBackend;
+use sp_core::{crypto::Ss58Codec, storage::StorageKey};
+use sp_runtime::generic::BlockId;
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
+use table::{Format, Kind, Table};
+
+const PAYMENTS: &[(&str, Kind)] = &[
+	("sender", Kind::Text),
+	("receiver", Kind::Text),
+	("ts", Kind::Integer),
+	("time", Kind::Text),
+	("value_fct", Kind::Integer),
+	("confirmation", Kind::Text),
+	("settlement", Kind::Text),
+];
+
+const CONFIRMATIONS: &[(&str, Kind)] = &[
+	("sender", Kind::Text),
+	("receiver", Kind::Text),
+	("ts", Kind::Integer),
+	("time", Kind::Text),
+	("status", Kind::Text),
+];
+
+const OFFERS: &[(&str, Kind)] = &[
+	("seller", Kind::Text),
+	("buyer", Kind::Text),
+	("market", Kind::Integer),
+	("market_timestamp", Kind::Integer),
+	("market_time", Kind::Text),
+	("asset", Kind::Integer),
+	("sold_power_w", Kind::Integer),
+	("change_fct_w", Kind::Integer),
+	("value_fct", Kind::Integer),
+	("state", Kind::Text),
+];
+
+/// Export the fedecom payments, confirmations and flexibility offers at a block as tables.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct ExportLedgerCmd {
+	/// Block hash or number of the exported state, the best block if not given.
+	#[arg(long, value_name = "HASH or NUMBER")]
+	pub at: Option<BlockNumberOrHash>,
+
+	/// Format of the tables.
+	#[arg(long, value_enum, default_value = "csv")]
+	pub format: Format,
+
+	/// Directory of the `payments`, `confirmations` and `offers` files, created if missing.
+	#[arg(long, value_name = "PATH", default_value = ".")]
+	pub output: PathBuf,
+
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+
+	#[clap(flatten)]
+	pub pruning_params: PruningParams,
+
+	#[clap(flatten)]
+	pub database_params: DatabaseParams,
+}
+
+/// RFC 3339 UTC date of the Unix timestamp `seconds`.
+fn time(seconds: u64) -> String {
+	chrono::DateTime::from_timestamp(seconds as i64, 0)
+		.map(|time| time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+		.unwrap_or_default()
+}
+
+/// Settlement of a payment with the `confirmation` of its receiver.
+fn settlement(confirmation: Option<u32>) -> &'static str {
+	match confirmation {
+		None => "unconfirmed",
+		Some(CONFIRMATION_OK) => "settled",
+		Some(_) => "disputed",
+	}
+}
+
+/// Tables of the fedecom ledger.
+pub struct Ledger {
+	/// Payments, with their confirmation and settlement status
+	pub payments: Table,
+	/// Payment confirmations of the receivers
+	pub confirmations: Table,
+	/// Offers of the flexibility market ledger
+	pub offers: Table,
+}
+
+impl Ledger {
+	/// Ledger of the storage `pairs` of the `Payments`, `Confirmations` and `FlexibilityOffers`
+	/// items, given without their storage prefix. The offers kept in `SettledOffers` have the same
+	/// keys and are given along with the `FlexibilityOffers` ones.
+	pub fn decode(
+		payments: Vec<(Vec<u8>, Vec<u8>)>,
+		confirmations: Vec<(Vec<u8>, Vec<u8>)>,
+		offers: Vec<(Vec<u8>, Vec<u8>)>,
+	) -> Result<Self> {
+		let confirmations = confirmations
+			.into_iter()
+			.map(|(key, data)| {
+				let status = decode::<u32>(&data, "confirmation")?;
+				storage::confirmation_key(&mut &key[..])
+					.map(|key| (key, status))
+					.ok_or_else(|| undecodable_key("confirmation", &key))
+			})
+			.collect::<Result<BTreeMap<_, _>>>()?;
+
+		let mut ledger = Self {
+			payments: Table::new("payments", PAYMENTS),
+			confirmations: Table::new("confirmations", CONFIRMATIONS),
+			offers: Table::new("offers", OFFERS),
+		};
+
+		for (key, data) in payments {
+			let (sender, receiver, ts) = storage::confirmation_key(&mut &key[..])
+				.ok_or_else(|| undecodable_key("payment", &key))?;
+			let value: u32 = decode(&data, "payment")?;
+			let confirmation = confirmations.get(&(sender.clone(), receiver.clone(), ts)).copied();
+			ledger.payments.push(vec![
+				sender.to_ss58check().into(),
+				receiver.to_ss58check().into(),
+				ts.into(),
+				time(ts.into()).into(),
+				value.into(),
+				confirmation.map_or("none", confirmation_status).into(),
+				settlement(confirmation).into(),
+			]);
+		}
+
+		for ((sender, receiver, ts), status) in confirmations {
+			ledger.confirmations.push(vec![
+				sender.to_ss58check().into(),
+				receiver.to_ss58check().into(),
+				ts.into(),
+				time(ts.into()).into(),
+				confirmation_status(status).into(),
+			]);
+		}
+
+		for (key, data) in offers {
+			let (seller, buyer, market, market_timestamp, asset) =
+				storage::offer_key(&mut &key[..]).ok_or_else(|| undecodable_key("offer", &key))?;
+			let data: FlexibilitySellingData = decode(&data, "offer")?;
+			ledger.offers.push(vec![
+				seller.to_ss58check().into(),
+				buyer.to_ss58check().into(),
+				market.into(),
+				market_timestamp.into(),
+				time(market_timestamp.into()).into(),
+				asset.into(),
+				data.sold_power.into(),
+				data.change_fct_w.into(),
+				(u64::from(data.sold_power) * u64::from(data.change_fct_w)).into(),
+				OfferState::name(OfferState::from_state(data.state)).into(),
+			]);
+		}
+
+		Ok(ledger)
+	}
+
+	/// Tables of the ledger.
+	pub fn tables(&self) -> [&Table; 3] {
+		[&self.payments, &self.confirmations, &self.offers]
+	}
+}
+
+fn decode<T: Decode>(data: &[u8], what: &str) -> Result<T> {
+	T::decode(&mut &data[..]).map_err(|e| format!("Undecodable {}: {}", what, e).into())
+}
+
+fn undecodable_key(what: &str, key: &[u8]) -> sc_cli::Error {
+	format!("Undecodable {} key 0x{}", what, hex::encode(key)).into()
+}
+
+/// Pairs of the `item` storage map of the fedecom pallet at `hash`, with the keys stripped of
+/// their storage prefix.
+fn pairs(client: &FullClient, hash: Hash, item: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+	let prefix = storage::prefix(item);
+	let pairs = StorageProvider::<Block, FullBackend>::storage_pairs(
+		client,
+		hash,
+		Some(&StorageKey(prefix.clone())),
+		None,
+	)
+	.map_err(|e| format!("Failed to read the state: {}", e))?;
+	Ok(pairs.map(|(key, data)| (key.0[prefix.len()..].to_vec(), data.0)).collect())
+}
+
+impl ExportLedgerCmd {
+	/// Export the ledger at the selected block of `client`.
+	pub fn run(&self, client: Arc<FullClient>) -> Result<()> {
+		let hash = match &self.at {
+			Some(at) => client.expect_block_hash_from_id(&at.parse::<Block>()?)?,
+			None => client.info().best_hash,
+		};
+		let number = client.expect_block_number_from_id(&BlockId::Hash(hash))?;
+		let timestamp = block_timestamp(&*client, hash)?;
+
+		let ledger = Ledger::decode(
+			pairs(&client, hash, b"Payments")?,
+			pairs(&client, hash, b"Confirmations")?,
+			[pairs(&client, hash, storage::OFFERS)?, pairs(&client, hash, storage::SETTLED_OFFERS)?]
+				.concat(),
+		)?;
+
+		std::fs::create_dir_all(&self.output)
+			.map_err(|e| format!("Failed to create {}: {}", self.output.display(), e))?;
+		eprintln!("Ledger at block #{} ({:?}) of {}", number, hash, time(timestamp / 1000));
+		for table in ledger.tables() {
+			let path = table.write(&self.output, self.format)?;
+			eprintln!("  {} rows written to {}", table.rows.len(), path.display());
+		}
+		Ok(())
+	}
+}
+
+impl CliConfiguration for ExportLedgerCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		Some(&self.pruning_params)
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Encode;
+	use table::Cell;
+	use node_template_runtime::{
+		pallet_fedecom_ps_demo::{
+			CONFIRMATION_NOK_OVERESTIMATION, FLEXIBILITY_SELLING_STATE_CONFIRMED,
+		},
+		AccountId,
+	};
+	use sp_keyring::Sr25519Keyring::{Alice, Bob, Charlie};
+
+	fn strip(storage: &[u8], key: Vec<u8>) -> Vec<u8> {
+		key[storage::prefix(storage).len()..].to_vec()
+	}
+
+	fn payment(sender: AccountId, receiver: AccountId, ts: u32, value: u32) -> (Vec<u8>, Vec<u8>) {
+		(strip(b"Payments", storage::payment(&(sender, receiver, ts))), value.encode())
+	}
+
+	fn confirmation(
+		sender: AccountId,
+		receiver: AccountId,
+		ts: u32,
+		status: u32,
+	) -> (Vec<u8>, Vec<u8>) {
+		(strip(b"Confirmations", storage::confirmation(&(sender, receiver, ts))), status.encode())
+	}
+
+	#[test]
+	fn timestamps_are_dates() {
+		assert_eq!(time(0), "1970-01-01T00:00:00Z");
+		assert_eq!(time(1_700_000_000), "2023-11-14T22:13:20Z");
+	}
+
+	#[test]
+	fn payments_carry_their_settlement() {
+		let (alice, bob, charlie) =
+			(Alice.to_account_id(), Bob.to_account_id(), Charlie.to_account_id());
+		let ledger = Ledger::decode(
+			vec![
+				payment(alice.clone(), bob.clone(), 10, 100),
+				payment(alice.clone(), charlie.clone(), 20, 200),
+				payment(bob.clone(), charlie.clone(), 30, 300),
+			],
+			vec![
+				confirmation(alice.clone(), bob.clone(), 10, CONFIRMATION_OK),
+				confirmation(alice.clone(), charlie.clone(), 20, CONFIRMATION_NOK_OVERESTIMATION),
+			],
+			Vec::new(),
+		)
+		.unwrap();
+
+		let settlement = |ts: u32| {
+			let row = ledger.payments.rows.iter().find(|row| row[2] == ts.into()).unwrap();
+			(row[5].clone(), row[6].clone())
+		};
+		assert_eq!(settlement(10), ("ok".into(), "settled".into()));
+		assert_eq!(settlement(20), ("overestimation".into(), "disputed".into()));
+		assert_eq!(settlement(30), ("none".into(), "unconfirmed".into()));
+		assert_eq!(ledger.confirmations.rows.len(), 2);
+		let row = ledger.payments.rows.iter().find(|row| row[2] == 10u32.into()).unwrap();
+		assert_eq!(row[0], alice.to_ss58check().into());
+		assert_eq!(row[3], "1970-01-01T00:00:10Z".into());
+	}
+
+	#[test]
+	fn offers_are_decoded() {
+		let (alice, bob) = (Alice.to_account_id(), Bob.to_account_id());
+		let key = strip(storage::OFFERS, storage::offer(&(alice.clone(), bob.clone(), 1, 60, 3)));
+		let data = FlexibilitySellingData {
+			sold_power: 500,
+			change_fct_w: 2,
+			state: FLEXIBILITY_SELLING_STATE_CONFIRMED,
+		};
+		let ledger = Ledger::decode(Vec::new(), Vec::new(), vec![(key, data.encode())]).unwrap();
+
+		assert_eq!(
+			ledger.offers.rows,
+			[vec![
+				Cell::from(alice.to_ss58check()),
+				bob.to_ss58check().into(),
+				1u32.into(),
+				60u32.into(),
+				"1970-01-01T00:01:00Z".into(),
+				3u32.into(),
+				500u32.into(),
+				2u32.into(),
+				1000u32.into(),
+				"confirmed".into(),
+			]]
+		);
+	}
+
+	#[test]
+	fn undecodable_keys_are_rejected() {
+		let result = Ledger::decode(vec![(vec![1, 2, 3], 1u32.encode())], Vec::new(), Vec::new());
+		assert!(result.is_err());
+	}
+}
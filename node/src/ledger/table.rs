@@ -0,0 +1,311 @@
+//! Tables of the exported ledger and their CSV, JSON and Parquet writers.
+
+use parquet::{
+	basic::Compression,
+	data_type::{ByteArray, ByteArrayType, Int64Type},
+	file::{properties::WriterProperties, writer::SerializedFileWriter},
+	schema::parser::parse_message_type,
+};
+use serde_json::{Map, Value};
+use std::{
+	fs::File,
+	io::{self, BufWriter, Write},
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+
+/// Type of the values of a column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+	/// UTF-8 text
+	Text,
+	/// Unsigned integer
+	Integer,
+}
+
+/// Value of a cell, of the kind of its column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cell {
+	/// UTF-8 text
+	Text(String),
+	/// Unsigned integer
+	Integer(u64),
+}
+
+impl Cell {
+	fn kind(&self) -> Kind {
+		match self {
+			Self::Text(_) => Kind::Text,
+			Self::Integer(_) => Kind::Integer,
+		}
+	}
+}
+
+impl From<String> for Cell {
+	fn from(text: String) -> Self {
+		Self::Text(text)
+	}
+}
+
+impl From<&str> for Cell {
+	fn from(text: &str) -> Self {
+		Self::Text(text.into())
+	}
+}
+
+impl From<u32> for Cell {
+	fn from(value: u32) -> Self {
+		Self::Integer(value.into())
+	}
+}
+
+impl From<u64> for Cell {
+	fn from(value: u64) -> Self {
+		Self::Integer(value)
+	}
+}
+
+/// Output format of the tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+	/// Comma-separated values, with a header row
+	Csv,
+	/// Array of JSON objects
+	Json,
+	/// Apache Parquet
+	Parquet,
+}
+
+impl Format {
+	/// Extension of the files written in the format.
+	pub fn extension(self) -> &'static str {
+		match self {
+			Self::Csv => "csv",
+			Self::Json => "json",
+			Self::Parquet => "parquet",
+		}
+	}
+}
+
+/// Table with named and typed columns.
+#[derive(Debug)]
+pub struct Table {
+	/// Name of the table, and of its file
+	pub name: &'static str,
+	/// Name and kind of the columns
+	pub columns: &'static [(&'static str, Kind)],
+	/// Rows, with a cell per column
+	pub rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+	/// Empty table.
+	pub fn new(name: &'static str, columns: &'static [(&'static str, Kind)]) -> Self {
+		Self { name, columns, rows: Vec::new() }
+	}
+
+	/// Append a row, whose cells must match the columns.
+	pub fn push(&mut self, row: Vec<Cell>) {
+		debug_assert!(
+			row.len() == self.columns.len() &&
+				row.iter().zip(self.columns).all(|(cell, (_, kind))| cell.kind() == *kind),
+			"Row mismatching the columns of {}",
+			self.name
+		);
+		self.rows.push(row);
+	}
+
+	/// Write the table in `format` to the file `<name>.<extension>` of `directory`, and return its
+	/// path.
+	pub fn write(&self, directory: &Path, format: Format) -> Result<PathBuf, String> {
+		let path = directory.join(format!("{}.{}", self.name, format.extension()));
+		let file = File::create(&path)
+			.map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+		let result = match format {
+			Format::Csv => self.write_csv(BufWriter::new(file)).map_err(|e| e.to_string()),
+			Format::Json => self.write_json(BufWriter::new(file)).map_err(|e| e.to_string()),
+			Format::Parquet => self.write_parquet(file).map_err(|e| e.to_string()),
+		};
+		result.map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+		Ok(path)
+	}
+
+	/// Write the table as CSV, following RFC 4180.
+	pub fn write_csv(&self, mut out: impl Write) -> io::Result<()> {
+		let header: Vec<_> = self.columns.iter().map(|(name, _)| csv_field(name)).collect();
+		write!(out, "{}\r\n", header.join(","))?;
+		for row in &self.rows {
+			let fields: Vec<_> = row
+				.iter()
+				.map(|cell| match cell {
+					Cell::Text(text) => csv_field(text),
+					Cell::Integer(value) => value.to_string(),
+				})
+				.collect();
+			write!(out, "{}\r\n", fields.join(","))?;
+		}
+		out.flush()
+	}
+
+	/// Write the table as a JSON array of objects keyed by the column names.
+	pub fn write_json(&self, mut out: impl Write) -> io::Result<()> {
+		let rows: Vec<_> = self
+			.rows
+			.iter()
+			.map(|row| {
+				let object: Map<_, _> = self
+					.columns
+					.iter()
+					.zip(row)
+					.map(|((name, _), cell)| {
+						let value = match cell {
+							Cell::Text(text) => Value::from(text.as_str()),
+							Cell::Integer(value) => Value::from(*value),
+						};
+						(name.to_string(), value)
+					})
+					.collect();
+				Value::Object(object)
+			})
+			.collect();
+		serde_json::to_writer_pretty(&mut out, &rows)?;
+		writeln!(out)?;
+		out.flush()
+	}
+
+	/// Write the table as a Parquet file with a single row group.
+	pub fn write_parquet(&self, out: impl Write + Send) -> parquet::errors::Result<()> {
+		let fields: Vec<_> = self
+			.columns
+			.iter()
+			.map(|(name, kind)| match kind {
+				Kind::Text => format!("REQUIRED BYTE_ARRAY {} (UTF8);", name),
+				Kind::Integer => format!("REQUIRED INT64 {} (UINT_64);", name),
+			})
+			.collect();
+		let schema = parse_message_type(&format!(
+			"message {} {{ {} }}",
+			self.name,
+			fields.join(" ")
+		))?;
+		let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+		let mut writer = SerializedFileWriter::new(out, Arc::new(schema), Arc::new(properties))?;
+
+		let mut row_group = writer.next_row_group()?;
+		let mut index = 0;
+		while let Some(mut column) = row_group.next_column()? {
+			let cells = self.rows.iter().map(|row| &row[index]);
+			match self.columns[index].1 {
+				Kind::Text => {
+					let values: Vec<_> = cells
+						.map(|cell| match cell {
+							Cell::Text(text) => ByteArray::from(text.as_str()),
+							Cell::Integer(value) => ByteArray::from(value.to_string().as_str()),
+						})
+						.collect();
+					column.typed::<ByteArrayType>().write_batch(&values, None, None)?;
+				},
+				Kind::Integer => {
+					let values: Vec<_> = cells
+						.map(|cell| match cell {
+							Cell::Integer(value) => *value as i64,
+							Cell::Text(_) => 0,
+						})
+						.collect();
+					column.typed::<Int64Type>().write_batch(&values, None, None)?;
+				},
+			}
+			column.close()?;
+			index += 1;
+		}
+		row_group.close()?;
+		writer.close()?;
+		Ok(())
+	}
+}
+
+/// Field of a CSV row, quoted if needed.
+fn csv_field(text: &str) -> String {
+	if text.contains([',', '"', '\r', '\n']) {
+		format!("\"{}\"", text.replace('"', "\"\""))
+	} else {
+		text.into()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use parquet::{
+		file::reader::{FileReader, SerializedFileReader},
+		record::RowAccessor,
+	};
+
+	const COLUMNS: &[(&str, Kind)] = &[("account", Kind::Text), ("value", Kind::Integer)];
+
+	fn table() -> Table {
+		let mut table = Table::new("payments", COLUMNS);
+		table.push(vec!["alice".into(), 100u32.into()]);
+		table.push(vec!["bob, \"the builder\"".into(), u64::from(u32::MAX).into()]);
+		table
+	}
+
+	#[test]
+	fn csv_is_quoted() {
+		let mut out = Vec::new();
+		table().write_csv(&mut out).unwrap();
+
+		assert_eq!(
+			String::from_utf8(out).unwrap(),
+			"account,value\r\nalice,100\r\n\"bob, \"\"the builder\"\"\",4294967295\r\n"
+		);
+	}
+
+	#[test]
+	fn json_keeps_the_kinds() {
+		let mut out = Vec::new();
+		table().write_json(&mut out).unwrap();
+
+		let json: Value = serde_json::from_slice(&out).unwrap();
+		assert_eq!(
+			json,
+			serde_json::json!([
+				{ "account": "alice", "value": 100 },
+				{ "account": "bob, \"the builder\"", "value": 4294967295u64 },
+			])
+		);
+	}
+
+	#[test]
+	fn parquet_round_trips() {
+		let directory = tempfile::tempdir().unwrap();
+		let path = table().write(directory.path(), Format::Parquet).unwrap();
+		assert_eq!(path, directory.path().join("payments.parquet"));
+
+		let reader = SerializedFileReader::new(File::open(path).unwrap()).unwrap();
+		let metadata = reader.metadata().file_metadata();
+		assert_eq!(metadata.num_rows(), 2);
+		let columns: Vec<_> =
+			metadata.schema_descr().columns().iter().map(|c| c.name().to_string()).collect();
+		assert_eq!(columns, ["account", "value"]);
+
+		let rows: Vec<_> = reader.get_row_iter(None).unwrap().map(|row| row.unwrap()).collect();
+		assert_eq!(rows[0].get_string(0).unwrap(), "alice");
+		assert_eq!(rows[1].get_string(0).unwrap(), "bob, \"the builder\"");
+		assert_eq!(rows[1].get_ulong(1).unwrap(), u64::from(u32::MAX));
+	}
+
+	#[test]
+	fn empty_tables_are_written() {
+		let directory = tempfile::tempdir().unwrap();
+		let table = Table::new("payments", COLUMNS);
+		for format in [Format::Csv, Format::Json, Format::Parquet] {
+			table.write(directory.path(), format).unwrap();
+		}
+
+		let csv = std::fs::read_to_string(directory.path().join("payments.csv")).unwrap();
+		assert_eq!(csv, "account,value\r\n");
+		let json = std::fs::read_to_string(directory.path().join("payments.json")).unwrap();
+		assert_eq!(json.trim(), "[]");
+	}
+}
@@ -1,3 +1,15 @@
+pub mod authorization;
 pub mod chain_spec;
+pub mod cim;
+pub mod events;
+pub mod fedecom;
+pub mod fork;
+pub mod index;
+pub mod ledger;
+pub mod metrics;
+pub mod onboarding;
 pub mod rpc;
 pub mod service;
+pub mod simulator;
+pub mod storage;
+pub mod webhook;
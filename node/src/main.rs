@@ -1,13 +1,25 @@
 //! Substrate Node Template CLI library.
 #![warn(missing_docs)]
 
+mod authorization;
 mod chain_spec;
 #[macro_use]
 mod service;
 mod benchmarking;
+mod cim;
 mod cli;
 mod command;
+mod events;
+mod fedecom;
+mod fork;
+mod index;
+mod ledger;
+mod metrics;
+mod onboarding;
 mod rpc;
+mod simulator;
+mod storage;
+mod webhook;
 
 fn main() -> sc_cli::Result<()> {
 	command::run()
@@ -0,0 +1,531 @@
+//! Prometheus metrics of the flexibility market activity.
+//!
+//! A task follows the imported and finalized blocks, decodes the events of `FedecomPSDemo` and
+//! exports:
+//!
+//! - `fedecom_offers_total`, `fedecom_confirmations_total` and `fedecom_rejections_total`, the
+//!   flexibility offers and the decisions of the buyers,
+//! - `fedecom_traded_power_total` and `fedecom_traded_value_total`, the power (W) and value (FCT)
+//!   of the confirmed offers.
+//!
+//! These counters are labelled with the flexibility `market` and the `block` they were read from:
+//! `best` for imported best blocks, counted again when a re-organisation imports the same events on
+//! another fork, and `finalized` for finalized blocks.
+//!
+//! The `fedecom_pending_offers` gauge (per market) and the `fedecom_open_disputes` gauge are read
+//! from the state of the last finalized block when the node starts, then kept up to date from the
+//! events of the finalized blocks. A dispute is a payment confirmation reporting an over or under
+//! estimation, until it is removed; confirmations of any other status are not counted.
+
+use crate::{
+	events::{block_events, EventRecord},
+	storage::{self, confirmation_key, offer_key, ConfirmationKey, OfferKey},
+};
+use codec::Decode;
+use futures::{stream, StreamExt};
+use node_template_runtime::{
+	opaque::Block,
+	pallet_fedecom_ps_demo::{
+		self, FlexibilitySellingData, CONFIRMATION_NOK_OVERESTIMATION,
+		CONFIRMATION_NOK_UNDERESTIMATION, FLEXIBILITY_SELLING_STATE_NOT_DECIDED,
+	},
+	Hash, Runtime, RuntimeEvent,
+};
+use sc_client_api::{
+	Backend, BlockImportNotification, BlockchainEvents, FinalityNotification, StorageProvider,
+};
+use sp_blockchain::HeaderBackend;
+use sp_core::storage::StorageKey;
+use sp_runtime::traits::Header;
+use std::{collections::HashSet, iter, marker::PhantomData, sync::Arc};
+use substrate_prometheus_endpoint::{
+	register, CounterVec, Gauge, GaugeVec, Opts, PrometheusError, Registry, U64,
+};
+
+const LOG_TARGET: &str = "fedecom-metrics";
+
+/// Prometheus metrics of the flexibility market.
+#[derive(Clone)]
+pub struct Metrics {
+	offers: CounterVec<U64>,
+	confirmations: CounterVec<U64>,
+	rejections: CounterVec<U64>,
+	traded_power: CounterVec<U64>,
+	traded_value: CounterVec<U64>,
+	pending_offers: GaugeVec<U64>,
+	open_disputes: Gauge<U64>,
+}
+
+impl Metrics {
+	/// Register the metrics of the flexibility market in `registry`.
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		let counter = |name: &str, help: &str| {
+			register(CounterVec::new(Opts::new(name, help), &["market", "block"])?, registry)
+		};
+
+		Ok(Self {
+			offers: counter("fedecom_offers_total", "Number of flexibility offers")?,
+			confirmations: counter(
+				"fedecom_confirmations_total",
+				"Number of flexibility offers confirmed by the buyer",
+			)?,
+			rejections: counter(
+				"fedecom_rejections_total",
+				"Number of flexibility offers rejected by the buyer",
+			)?,
+			traded_power: counter(
+				"fedecom_traded_power_total",
+				"Power of the confirmed flexibility offers, in W",
+			)?,
+			traded_value: counter(
+				"fedecom_traded_value_total",
+				"Value of the confirmed flexibility offers, in FCT",
+			)?,
+			pending_offers: register(
+				GaugeVec::new(
+					Opts::new(
+						"fedecom_pending_offers",
+						"Number of undecided flexibility offers at the last finalized block",
+					),
+					&["market"],
+				)?,
+				registry,
+			)?,
+			open_disputes: register(
+				Gauge::new(
+					"fedecom_open_disputes",
+					"Number of disputed payment confirmations at the last finalized block",
+				)?,
+				registry,
+			)?,
+		})
+	}
+}
+
+/// Kind of block the events are read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockKind {
+	Best,
+	Finalized,
+}
+
+impl BlockKind {
+	fn label(&self) -> &'static str {
+		match self {
+			Self::Best => "best",
+			Self::Finalized => "finalized",
+		}
+	}
+}
+
+enum Notification {
+	Imported(BlockImportNotification<Block>),
+	Finalized(FinalityNotification<Block>),
+}
+
+/// Metrics kept up to date from the events of the blocks.
+struct Tracker {
+	metrics: Metrics,
+	/// Offers not decided at the last processed finalized block
+	pending_offers: HashSet<OfferKey>,
+	/// Disputed confirmations at the last processed finalized block
+	open_disputes: HashSet<ConfirmationKey>,
+}
+
+impl Tracker {
+	fn new(metrics: Metrics) -> Self {
+		Self { metrics, pending_offers: HashSet::new(), open_disputes: HashSet::new() }
+	}
+
+	fn open_offer(&mut self, offer: OfferKey) {
+		let market = offer.2.to_string();
+		if self.pending_offers.insert(offer) {
+			self.metrics.pending_offers.with_label_values(&[&market]).inc();
+		}
+	}
+
+	fn close_offer(&mut self, offer: &OfferKey) {
+		if self.pending_offers.remove(offer) {
+			self.metrics.pending_offers.with_label_values(&[&offer.2.to_string()]).dec();
+		}
+	}
+
+	fn open_dispute(&mut self, confirmation: ConfirmationKey) {
+		if self.open_disputes.insert(confirmation) {
+			self.metrics.open_disputes.inc();
+		}
+	}
+
+	fn close_dispute(&mut self, confirmation: &ConfirmationKey) {
+		if self.open_disputes.remove(confirmation) {
+			self.metrics.open_disputes.dec();
+		}
+	}
+
+	/// Update the metrics with an `event` of a `block`, whose offers are read with `offer`.
+	fn on_event(
+		&mut self,
+		event: pallet_fedecom_ps_demo::Event<Runtime>,
+		block: BlockKind,
+		offer: impl Fn(&OfferKey) -> Option<FlexibilitySellingData>,
+	) {
+		let finalized = block == BlockKind::Finalized;
+
+		match event {
+			pallet_fedecom_ps_demo::Event::SuccessfullySoldFlexibility {
+				seller,
+				buyer,
+				flexibility_market_identifier: market,
+				flexibility_market_timestamp: timestamp,
+				asset_identifier: asset,
+				..
+			} => {
+				self.metrics.offers.with_label_values(&[&market.to_string(), block.label()]).inc();
+				if finalized {
+					self.open_offer((seller, buyer, market, timestamp, asset));
+				}
+			},
+			pallet_fedecom_ps_demo::Event::FlexibilitySellingConfirmed {
+				seller,
+				buyer,
+				flexibility_market_identifier: market,
+				flexibility_market_timestamp: timestamp,
+				asset_identifier: asset,
+			} => {
+				let key = (seller, buyer, market, timestamp, asset);
+				let labels = [&market.to_string()[..], block.label()];
+
+				self.metrics.confirmations.with_label_values(&labels).inc();
+				// The event does not carry the traded power, read it from the ledger
+				match offer(&key) {
+					Some(data) => {
+						let power = data.sold_power as u64;
+						self.metrics.traded_power.with_label_values(&labels).inc_by(power);
+						self.metrics
+							.traded_value
+							.with_label_values(&labels)
+							.inc_by(power * data.change_fct_w as u64);
+					},
+					None => log::warn!(target: LOG_TARGET, "Confirmed offer {:?} not found", key),
+				}
+				if finalized {
+					self.close_offer(&key);
+				}
+			},
+			pallet_fedecom_ps_demo::Event::FlexibilitySellingRejected {
+				seller,
+				buyer,
+				flexibility_market_identifier: market,
+				flexibility_market_timestamp: timestamp,
+				asset_identifier: asset,
+			} => {
+				self.metrics
+					.rejections
+					.with_label_values(&[&market.to_string(), block.label()])
+					.inc();
+				if finalized {
+					self.close_offer(&(seller, buyer, market, timestamp, asset));
+				}
+			},
+			pallet_fedecom_ps_demo::Event::AddedToConfirmations {
+				key_sender,
+				key_receiver,
+				ts,
+				status,
+				..
+			} if finalized && is_dispute(status) =>
+				self.open_dispute((key_sender, key_receiver, ts)),
+			pallet_fedecom_ps_demo::Event::RemovedFromConfirmations {
+				key_sender,
+				key_receiver,
+				ts,
+			} if finalized => self.close_dispute(&(key_sender, key_receiver, ts)),
+			// Close the pending offers of the period which are no longer stored
+			pallet_fedecom_ps_demo::Event::MarketPeriodClosed {
+				flexibility_market_identifier: market,
+				flexibility_market_timestamp: timestamp,
+				..
+			} if finalized => {
+				let closed: Vec<OfferKey> = self
+					.pending_offers
+					.iter()
+					.filter(|key| (key.2, key.3) == (market, timestamp) && offer(key).is_none())
+					.cloned()
+					.collect();
+				for key in closed {
+					self.close_offer(&key);
+				}
+			},
+			_ => {},
+		}
+	}
+}
+
+struct Worker<C, BE> {
+	client: Arc<C>,
+	tracker: Tracker,
+	_backend: PhantomData<BE>,
+}
+
+impl<C, BE> Worker<C, BE>
+where
+	BE: Backend<Block>,
+	C: StorageProvider<Block, BE>,
+{
+	/// Initialize the gauges from the state of the finalized block `hash`.
+	fn load(&mut self, hash: Hash) {
+		let prefix = StorageKey(storage::prefix(storage::OFFERS));
+		match self.client.storage_pairs(hash, Some(&prefix), None) {
+			Ok(pairs) =>
+				for (key, data) in pairs {
+					let offer = offer_key(&mut &key.0[prefix.0.len()..]);
+					let data = FlexibilitySellingData::decode(&mut &data.0[..]);
+					match (offer, data) {
+						(Some(offer), Ok(data)) =>
+							if data.state == FLEXIBILITY_SELLING_STATE_NOT_DECIDED {
+								self.tracker.open_offer(offer);
+							},
+						_ => log::warn!(target: LOG_TARGET, "Undecodable offer {:?}", key),
+					}
+				},
+			Err(e) => log::warn!(target: LOG_TARGET, "Failed to read the offers: {}", e),
+		}
+
+		let prefix = StorageKey(storage::prefix(b"Confirmations"));
+		match self.client.storage_pairs(hash, Some(&prefix), None) {
+			Ok(pairs) =>
+				for (key, data) in pairs {
+					let confirmation = confirmation_key(&mut &key.0[prefix.0.len()..]);
+					let status = u32::decode(&mut &data.0[..]);
+					match (confirmation, status) {
+						(Some(confirmation), Ok(status)) =>
+							if is_dispute(status) {
+								self.tracker.open_dispute(confirmation);
+							},
+						_ => log::warn!(target: LOG_TARGET, "Undecodable confirmation {:?}", key),
+					}
+				},
+			Err(e) => log::warn!(target: LOG_TARGET, "Failed to read the confirmations: {}", e),
+		}
+	}
+
+	/// Events of the block `hash`.
+	fn events(&self, hash: Hash) -> Vec<EventRecord> {
+		block_events(&*self.client, hash).unwrap_or_else(|e| {
+			log::warn!(target: LOG_TARGET, "Failed to read the events at {}: {}", hash, e);
+			Vec::new()
+		})
+	}
+
+	/// Update the metrics with the events of the block `hash`.
+	fn on_block(&mut self, hash: Hash, block: BlockKind) {
+		for record in self.events(hash) {
+			let RuntimeEvent::FedecomPSDemo(event) = record.event else { continue };
+			let client = &self.client;
+			self.tracker.on_event(event, block, |key| offer::<C, BE>(client, hash, key));
+		}
+	}
+}
+
+/// Whether a payment confirmation of `status` disputes the payment.
+fn is_dispute(status: u32) -> bool {
+	matches!(status, CONFIRMATION_NOK_OVERESTIMATION | CONFIRMATION_NOK_UNDERESTIMATION)
+}
+
+/// Offer `key` as stored at the block `hash`.
+fn offer<C, BE>(client: &C, hash: Hash, key: &OfferKey) -> Option<FlexibilitySellingData>
+where
+	BE: Backend<Block>,
+	C: StorageProvider<Block, BE>,
+{
+	let data = client.storage(hash, &StorageKey(storage::offer(key))).ok()??;
+	FlexibilitySellingData::decode(&mut &data.0[..]).ok()
+}
+
+/// Follow the imported and finalized blocks of `client` and update `metrics` with the market
+/// activity.
+pub async fn run<C, BE>(client: Arc<C>, metrics: Metrics)
+where
+	BE: Backend<Block> + 'static,
+	C: BlockchainEvents<Block> + StorageProvider<Block, BE> + HeaderBackend<Block> + 'static,
+{
+	// Subscribe before reading the state, so that no finalized block is missed
+	let imported = client.import_notification_stream().map(Notification::Imported);
+	let finalized = client.finality_notification_stream().map(Notification::Finalized);
+	let mut notifications = stream::select(imported, finalized);
+
+	let info = client.info();
+	let mut last_finalized = info.finalized_number;
+	let mut worker = Worker { client, tracker: Tracker::new(metrics), _backend: PhantomData };
+	worker.load(info.finalized_hash);
+
+	while let Some(notification) = notifications.next().await {
+		match notification {
+			Notification::Imported(notification) =>
+				if notification.is_new_best {
+					worker.on_block(notification.hash, BlockKind::Best);
+				},
+			Notification::Finalized(notification) => {
+				// The tree route holds the blocks finalized along with `hash`, oldest first
+				let number = *notification.header.number();
+				let first = number - notification.tree_route.len() as u32;
+
+				for (index, hash) in
+					notification.tree_route.iter().chain(iter::once(&notification.hash)).enumerate()
+				{
+					// Skip the blocks whose state was already loaded
+					if first + index as u32 > last_finalized {
+						worker.on_block(*hash, BlockKind::Finalized);
+					}
+				}
+				last_finalized = last_finalized.max(number);
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use node_template_runtime::{
+		pallet_fedecom_ps_demo::{CONFIRMATION_OK, FLEXIBILITY_SELLING_STATE_CONFIRMED},
+		AccountId,
+	};
+	use pallet_fedecom_ps_demo::Event;
+	use sp_keyring::AccountKeyring::{Alice, Bob};
+
+	fn tracker() -> Tracker {
+		Tracker::new(Metrics::register(&Registry::new()).unwrap())
+	}
+
+	fn accounts() -> (AccountId, AccountId) {
+		(Alice.to_account_id(), Bob.to_account_id())
+	}
+
+	fn sold(asset: u32) -> Event<Runtime> {
+		let (seller, buyer) = accounts();
+		Event::SuccessfullySoldFlexibility {
+			seller,
+			buyer,
+			flexibility_market_identifier: 1,
+			flexibility_market_timestamp: 900,
+			asset_identifier: asset,
+			sold_power: 10,
+			change_fct_w: 3,
+		}
+	}
+
+	fn confirmed(asset: u32) -> Event<Runtime> {
+		let (seller, buyer) = accounts();
+		Event::FlexibilitySellingConfirmed {
+			seller,
+			buyer,
+			flexibility_market_identifier: 1,
+			flexibility_market_timestamp: 900,
+			asset_identifier: asset,
+		}
+	}
+
+	fn offer_data(_: &OfferKey) -> Option<FlexibilitySellingData> {
+		Some(FlexibilitySellingData {
+			sold_power: 10,
+			change_fct_w: 3,
+			state: FLEXIBILITY_SELLING_STATE_CONFIRMED,
+		})
+	}
+
+	fn no_offer(_: &OfferKey) -> Option<FlexibilitySellingData> {
+		None
+	}
+
+	#[test]
+	fn counters_are_labelled_by_market_and_block() {
+		let mut tracker = tracker();
+		tracker.on_event(sold(7), BlockKind::Best, no_offer);
+		tracker.on_event(sold(7), BlockKind::Finalized, no_offer);
+		tracker.on_event(confirmed(7), BlockKind::Finalized, offer_data);
+
+		let metrics = &tracker.metrics;
+		assert_eq!(metrics.offers.with_label_values(&["1", "best"]).get(), 1);
+		assert_eq!(metrics.offers.with_label_values(&["1", "finalized"]).get(), 1);
+		assert_eq!(metrics.confirmations.with_label_values(&["1", "best"]).get(), 0);
+		assert_eq!(metrics.confirmations.with_label_values(&["1", "finalized"]).get(), 1);
+		assert_eq!(metrics.traded_power.with_label_values(&["1", "finalized"]).get(), 10);
+		assert_eq!(metrics.traded_value.with_label_values(&["1", "finalized"]).get(), 30);
+	}
+
+	#[test]
+	fn pending_offers_follow_the_finalized_blocks() {
+		let mut tracker = tracker();
+		let pending =
+			|tracker: &Tracker| tracker.metrics.pending_offers.with_label_values(&["1"]).get();
+
+		tracker.on_event(sold(7), BlockKind::Best, no_offer);
+		assert_eq!(pending(&tracker), 0);
+
+		tracker.on_event(sold(7), BlockKind::Finalized, no_offer);
+		tracker.on_event(sold(8), BlockKind::Finalized, no_offer);
+		tracker.on_event(sold(9), BlockKind::Finalized, no_offer);
+		assert_eq!(pending(&tracker), 3);
+
+		tracker.on_event(confirmed(7), BlockKind::Finalized, offer_data);
+		let (seller, buyer) = accounts();
+		tracker.on_event(
+			Event::FlexibilitySellingRejected {
+				seller,
+				buyer,
+				flexibility_market_identifier: 1,
+				flexibility_market_timestamp: 900,
+				asset_identifier: 8,
+			},
+			BlockKind::Finalized,
+			no_offer,
+		);
+		assert_eq!(pending(&tracker), 1);
+
+		// The offers still stored after the closing of their period stay pending
+		let closed = Event::MarketPeriodClosed {
+			flexibility_market_identifier: 1,
+			flexibility_market_timestamp: 900,
+			removed: 0,
+			settled: 0,
+			remaining: 1,
+		};
+		tracker.on_event(closed.clone(), BlockKind::Finalized, offer_data);
+		assert_eq!(pending(&tracker), 1);
+		tracker.on_event(closed, BlockKind::Finalized, no_offer);
+		assert_eq!(pending(&tracker), 0);
+	}
+
+	#[test]
+	fn disputes_are_open_until_their_confirmation_is_removed() {
+		let mut tracker = tracker();
+		let (sender, receiver) = accounts();
+		let added = |status| Event::AddedToConfirmations {
+			key_confirmer: receiver.clone(),
+			key_sender: sender.clone(),
+			key_receiver: receiver.clone(),
+			ts: 900,
+			status,
+		};
+
+		tracker.on_event(added(CONFIRMATION_NOK_OVERESTIMATION), BlockKind::Best, no_offer);
+		tracker.on_event(added(CONFIRMATION_OK), BlockKind::Finalized, no_offer);
+		assert_eq!(tracker.metrics.open_disputes.get(), 0);
+
+		// Unknown statuses are not disputes
+		tracker.on_event(added(4), BlockKind::Finalized, no_offer);
+		assert_eq!(tracker.metrics.open_disputes.get(), 0);
+
+		tracker.on_event(added(CONFIRMATION_NOK_OVERESTIMATION), BlockKind::Finalized, no_offer);
+		assert_eq!(tracker.metrics.open_disputes.get(), 1);
+
+		let removed = Event::RemovedFromConfirmations {
+			key_sender: sender.clone(),
+			key_receiver: receiver.clone(),
+			ts: 900,
+		};
+		tracker.on_event(removed, BlockKind::Finalized, no_offer);
+		assert_eq!(tracker.metrics.open_disputes.get(), 0);
+	}
+}
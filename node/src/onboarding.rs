@@ -0,0 +1,560 @@
+//! `build-fedecom-spec` subcommand, generating the chain spec of a pilot from CSV or JSON files of
+//! its accounts, assets, flexibility markets and validators.
+//!
+//! The files are validated as a whole, and every invalid row is reported with its line (CSV) or
+//! index (JSON) before any chain spec is written. The chain spec is then built like `build-spec`
+//! does, with the same `--raw` and bootnode options.
+
+use crate::chain_spec;
+use node_template_runtime::{
+	pallet_fedecom_ps_demo::{
+		PARTICIPANT_ROLE_AGGREGATOR, PARTICIPANT_ROLE_DSO, PARTICIPANT_ROLE_MARKET_OPERATOR,
+		PARTICIPANT_ROLE_PROSUMER,
+	},
+	AccountId, Balance, SS58Prefix, EXISTENTIAL_DEPOSIT,
+};
+use sc_cli::{BuildSpecCmd, CliConfiguration, NodeKeyParams, SharedParams};
+use sc_network::PeerId;
+use sc_service::{config::NetworkConfiguration, ChainType};
+use serde::{de::DeserializeOwned, Deserialize};
+use sp_consensus_aura::sr25519::AuthorityId as AuraId;
+use sp_consensus_grandpa::AuthorityId as GrandpaId;
+use sp_core::{crypto::Ss58Codec, ed25519, sr25519, OpaquePeerId};
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fs,
+	path::{Path, PathBuf},
+};
+
+/// Account of the accounts file: SS58 address, role, initial balance and council membership.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccountRow {
+	account: String,
+	#[serde(default)]
+	role: String,
+	#[serde(default)]
+	balance: Option<Balance>,
+	#[serde(default)]
+	council: Option<bool>,
+}
+
+/// Asset of the assets file: identifier and SS58 address of its owner.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AssetRow {
+	asset: u32,
+	owner: String,
+}
+
+/// Flexibility market of the markets file: identifier and SS58 address of its operator.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MarketRow {
+	market: u32,
+	operator: String,
+}
+
+/// Validator of the authorities file: SS58 address of its account, SS58 public keys of its Aura
+/// (sr25519) and GRANDPA (ed25519) session keys, and peer id of its node.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AuthorityRow {
+	account: String,
+	aura: String,
+	grandpa: String,
+	node: String,
+}
+
+/// Validators, well-known nodes and council of a chain.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Authorities {
+	/// Validators with their Aura and GRANDPA session keys
+	pub validators: Vec<(AccountId, AuraId, GrandpaId)>,
+	/// Well-known nodes with their owner
+	pub nodes: Vec<(OpaquePeerId, AccountId)>,
+	/// Council members
+	pub council: Vec<AccountId>,
+}
+
+/// Accounts, assets and flexibility markets of a pilot, validated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pilot {
+	/// Market participants with their role
+	pub participants: Vec<(AccountId, u32)>,
+	/// Flexibility markets with their operator
+	pub markets: Vec<(u32, AccountId)>,
+	/// Assets/flexibilities with their owner
+	pub assets: Vec<(u32, AccountId)>,
+	/// Initial balances of the accounts
+	pub balances: Vec<(AccountId, Balance)>,
+}
+
+/// Role of a participant, by name or number.
+fn role(name: &str) -> Option<u32> {
+	match name.to_ascii_lowercase().replace('_', "-").as_str() {
+		"dso" | "1" => Some(PARTICIPANT_ROLE_DSO),
+		"aggregator" | "2" => Some(PARTICIPANT_ROLE_AGGREGATOR),
+		"prosumer" | "3" => Some(PARTICIPANT_ROLE_PROSUMER),
+		"market-operator" | "4" => Some(PARTICIPANT_ROLE_MARKET_OPERATOR),
+		_ => None,
+	}
+}
+
+/// Account of an SS58 address in the format of the chain.
+fn account(address: &str) -> Result<AccountId, String> {
+	let (account, format) = AccountId::from_ss58check_with_version(address.trim())
+		.map_err(|e| format!("invalid SS58 address `{}`: {:?}", address, e))?;
+	if u16::from(format) != u16::from(SS58Prefix::get()) {
+		return Err(format!(
+			"SS58 address `{}` of format {} instead of {}",
+			address,
+			u16::from(format),
+			SS58Prefix::get()
+		))
+	}
+	Ok(account)
+}
+
+/// Public key given as an SS58 address, of any format.
+fn public_key<T: Ss58Codec>(address: &str, what: &str) -> Result<T, String> {
+	T::from_ss58check_with_version(address.trim())
+		.map(|(public, _)| public)
+		.map_err(|e| format!("invalid {} key `{}`: {:?}", what, address, e))
+}
+
+/// Rows of a CSV file with a header, or of a JSON array of objects, with their location.
+fn rows<T: DeserializeOwned>(path: &Path) -> Result<Vec<(String, T)>, String> {
+	let content =
+		fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+	let name = path.display();
+
+	if path.extension().map_or(false, |extension| extension == "json") {
+		let rows: Vec<T> = serde_json::from_slice(&content)
+			.map_err(|e| format!("Invalid JSON in {}: {}", name, e))?;
+		Ok(rows.into_iter().enumerate().map(|(i, row)| (format!("{}[{}]", name, i), row)).collect())
+	} else {
+		let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(&content[..]);
+		let headers =
+			reader.headers().map_err(|e| format!("Invalid CSV in {}: {}", name, e))?.clone();
+		reader
+			.records()
+			.map(|record| {
+				let record = record.map_err(|e| format!("Invalid CSV in {}: {}", name, e))?;
+				let location = format!("{}:{}", name, record.position().map_or(0, |p| p.line()));
+				match record.deserialize(Some(&headers)) {
+					Ok(row) => Ok((location, row)),
+					Err(e) => Err(format!("Invalid CSV at {}: {}", location, e)),
+				}
+			})
+			.collect()
+	}
+}
+
+impl Pilot {
+	/// Read and validate the pilot files.
+	pub fn load(
+		accounts: &Path,
+		assets: &Path,
+		markets: Option<&Path>,
+		authorities: &Path,
+	) -> Result<(Self, Authorities), String> {
+		let markets = match markets {
+			Some(path) => rows(path)?,
+			None => Vec::new(),
+		};
+		Self::validate(rows(accounts)?, rows(assets)?, markets, rows(authorities)?)
+	}
+
+	/// Validate the rows of the pilot files, reporting every invalid row.
+	fn validate(
+		accounts: Vec<(String, AccountRow)>,
+		assets: Vec<(String, AssetRow)>,
+		markets: Vec<(String, MarketRow)>,
+		validators: Vec<(String, AuthorityRow)>,
+	) -> Result<(Self, Authorities), String> {
+		let mut errors = Vec::new();
+		let mut pilot = Self::default();
+		let mut authorities = Authorities::default();
+		let mut roles = BTreeMap::new();
+
+		for (location, row) in accounts {
+			let account = match account(&row.account) {
+				Ok(account) => account,
+				Err(e) => {
+					errors.push(format!("{}: {}", location, e));
+					continue
+				},
+			};
+			if roles.contains_key(&account) {
+				errors.push(format!("{}: duplicate account {}", location, row.account));
+				continue
+			}
+			let role = match row.role.trim() {
+				"" => None,
+				name => match role(name) {
+					Some(role) => Some(role),
+					None => {
+						errors.push(format!("{}: unknown role `{}`", location, name));
+						continue
+					},
+				},
+			};
+			match row.balance {
+				None | Some(0) => {},
+				Some(balance) if balance < EXISTENTIAL_DEPOSIT => errors.push(format!(
+					"{}: balance {} below the existential deposit of {}",
+					location, balance, EXISTENTIAL_DEPOSIT
+				)),
+				Some(balance) => pilot.balances.push((account.clone(), balance)),
+			}
+			if let Some(role) = role {
+				pilot.participants.push((account.clone(), role));
+			}
+			if row.council == Some(true) {
+				authorities.council.push(account.clone());
+			}
+			roles.insert(account, role);
+		}
+
+		let mut asset_ids = BTreeSet::new();
+		for (location, row) in assets {
+			if !asset_ids.insert(row.asset) {
+				errors.push(format!("{}: duplicate asset {}", location, row.asset));
+				continue
+			}
+			match account(&row.owner) {
+				Ok(owner) if matches!(roles.get(&owner), Some(Some(_))) =>
+					pilot.assets.push((row.asset, owner)),
+				Ok(_) => errors.push(format!(
+					"{}: owner {} of asset {} is not a market participant",
+					location, row.owner, row.asset
+				)),
+				Err(e) => errors.push(format!("{}: {}", location, e)),
+			}
+		}
+
+		let mut market_ids = BTreeSet::new();
+		for (location, row) in markets {
+			if !market_ids.insert(row.market) {
+				errors.push(format!("{}: duplicate market {}", location, row.market));
+				continue
+			}
+			match account(&row.operator) {
+				Ok(operator)
+					if roles.get(&operator) == Some(&Some(PARTICIPANT_ROLE_MARKET_OPERATOR)) =>
+					pilot.markets.push((row.market, operator)),
+				Ok(_) => errors.push(format!(
+					"{}: operator {} of market {} is not a market operator",
+					location, row.operator, row.market
+				)),
+				Err(e) => errors.push(format!("{}: {}", location, e)),
+			}
+		}
+
+		let mut nodes = BTreeSet::new();
+		for (location, row) in validators {
+			let keys = account(&row.account).and_then(|account| {
+				let aura = public_key::<sr25519::Public>(&row.aura, "Aura")?;
+				let grandpa = public_key::<ed25519::Public>(&row.grandpa, "GRANDPA")?;
+				Ok((account, AuraId::from(aura), GrandpaId::from(grandpa)))
+			});
+			let node = row
+				.node
+				.trim()
+				.parse::<PeerId>()
+				.map_err(|e| format!("invalid peer id `{}`: {}", row.node, e));
+			let (keys, node) = match (keys, node) {
+				(Ok(keys), Ok(node)) => (keys, node.to_bytes()),
+				(Err(e), _) | (_, Err(e)) => {
+					errors.push(format!("{}: {}", location, e));
+					continue
+				},
+			};
+			if authorities.validators.iter().any(|(account, ..)| *account == keys.0) {
+				errors.push(format!("{}: duplicate validator {}", location, row.account));
+			} else if !nodes.insert(node.clone()) {
+				errors.push(format!("{}: duplicate node {}", location, row.node));
+			} else {
+				authorities.nodes.push((OpaquePeerId(node), keys.0.clone()));
+				authorities.validators.push(keys);
+			}
+		}
+		if authorities.validators.is_empty() {
+			errors.push("the authorities file lists no validator".into());
+		}
+		if authorities.council.is_empty() {
+			errors.push("the accounts file marks no council member".into());
+		}
+
+		if errors.is_empty() {
+			Ok((pilot, authorities))
+		} else {
+			Err(errors.join("\n"))
+		}
+	}
+}
+
+/// Build the chain spec of a pilot from files of its accounts, assets and flexibility markets.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct BuildFedecomSpecCmd {
+	/// CSV or JSON file of the accounts, with the `account`, `role`, `balance` and `council`
+	/// columns.
+	///
+	/// The role is `dso`, `aggregator`, `prosumer` or `market-operator`, or empty for accounts
+	/// which are only funded. The balance is in the smallest unit, 10^-12 FCT. The council members
+	/// are marked `true`.
+	#[arg(long, value_name = "PATH")]
+	pub accounts: PathBuf,
+
+	/// CSV or JSON file of the assets, with the `asset` and `owner` columns.
+	#[arg(long, value_name = "PATH")]
+	pub assets: PathBuf,
+
+	/// CSV or JSON file of the flexibility markets, with the `market` and `operator` columns.
+	#[arg(long, value_name = "PATH")]
+	pub markets: Option<PathBuf>,
+
+	/// CSV or JSON file of the validators, with the `account`, `aura`, `grandpa` and `node`
+	/// columns.
+	///
+	/// The Aura (sr25519) and GRANDPA (ed25519) session keys are SS58 public keys, and the node is
+	/// the peer id of the node of the validator, allowed to join the network.
+	#[arg(long, value_name = "PATH")]
+	pub authorities: PathBuf,
+
+	/// Name of the chain.
+	#[arg(long, default_value = "FEDECOM Pilot")]
+	pub name: String,
+
+	/// Identifier of the chain.
+	#[arg(long, default_value = "fedecom_pilot")]
+	pub id: String,
+
+	#[clap(flatten)]
+	pub build_spec: BuildSpecCmd,
+}
+
+impl BuildFedecomSpecCmd {
+	/// Validate the files and print the chain spec, as `build-spec` does.
+	pub fn run(&self, network_config: NetworkConfiguration) -> sc_cli::Result<()> {
+		let (pilot, authorities) = Pilot::load(
+			&self.accounts,
+			&self.assets,
+			self.markets.as_deref(),
+			&self.authorities,
+		)?;
+		eprintln!(
+			"{} participants, {} funded accounts, {} assets, {} markets, {} validators and {} \
+			council members",
+			pilot.participants.len(),
+			pilot.balances.len(),
+			pilot.assets.len(),
+			pilot.markets.len(),
+			authorities.validators.len(),
+			authorities.council.len()
+		);
+		let spec = chain_spec::fedecom_pilot_config(
+			ChainType::Live,
+			&self.name,
+			&self.id,
+			authorities,
+			pilot,
+		)?;
+		self.build_spec.run(Box::new(spec), network_config)
+	}
+}
+
+impl CliConfiguration for BuildFedecomSpecCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.build_spec.shared_params
+	}
+
+	fn node_key_params(&self) -> Option<&NodeKeyParams> {
+		Some(&self.build_spec.node_key_params)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::Pair;
+	use sp_keyring::{
+		Ed25519Keyring,
+		Sr25519Keyring::{self, Alice, Bob, Charlie, Dave},
+	};
+
+	fn address(keyring: Sr25519Keyring) -> String {
+		keyring.to_account_id().to_ss58check()
+	}
+
+	fn account_row(account: String, role: &str, balance: Option<Balance>) -> AccountRow {
+		AccountRow { account, role: role.into(), balance, council: None }
+	}
+
+	fn authority_row(keyring: Sr25519Keyring, node: &PeerId) -> AuthorityRow {
+		AuthorityRow {
+			account: address(keyring),
+			aura: keyring.public().to_ss58check(),
+			grandpa: ed25519::Pair::from_string(&keyring.to_seed(), None)
+				.unwrap()
+				.public()
+				.to_ss58check(),
+			node: node.to_base58(),
+		}
+	}
+
+	fn located<T>(rows: Vec<T>) -> Vec<(String, T)> {
+		rows.into_iter().enumerate().map(|(i, row)| (format!("row {}", i + 1), row)).collect()
+	}
+
+	fn accounts() -> Vec<(String, AccountRow)> {
+		located(vec![
+			AccountRow {
+				council: Some(true),
+				..account_row(address(Alice), "dso", Some(1_000_000))
+			},
+			account_row(address(Bob), "Market_Operator", None),
+			account_row(address(Charlie), "prosumer", Some(0)),
+			account_row(address(Dave), "", Some(EXISTENTIAL_DEPOSIT)),
+		])
+	}
+
+	#[test]
+	fn valid_files_are_imported() {
+		let node = PeerId::random();
+		let (pilot, authorities) = Pilot::validate(
+			accounts(),
+			located(vec![AssetRow { asset: 7, owner: address(Charlie) }]),
+			located(vec![MarketRow { market: 1, operator: address(Bob) }]),
+			located(vec![authority_row(Dave, &node)]),
+		)
+		.unwrap();
+
+		assert_eq!(
+			pilot,
+			Pilot {
+				participants: vec![
+					(Alice.to_account_id(), PARTICIPANT_ROLE_DSO),
+					(Bob.to_account_id(), PARTICIPANT_ROLE_MARKET_OPERATOR),
+					(Charlie.to_account_id(), PARTICIPANT_ROLE_PROSUMER),
+				],
+				markets: vec![(1, Bob.to_account_id())],
+				assets: vec![(7, Charlie.to_account_id())],
+				balances: vec![
+					(Alice.to_account_id(), 1_000_000),
+					(Dave.to_account_id(), EXISTENTIAL_DEPOSIT),
+				],
+			}
+		);
+		assert_eq!(
+			authorities,
+			Authorities {
+				validators: vec![(
+					Dave.to_account_id(),
+					Dave.public().into(),
+					Ed25519Keyring::Dave.public().into()
+				)],
+				nodes: vec![(OpaquePeerId(node.to_bytes()), Dave.to_account_id())],
+				council: vec![Alice.to_account_id()],
+			}
+		);
+	}
+
+	#[test]
+	fn every_invalid_row_is_reported() {
+		let mut accounts = accounts();
+		accounts.extend(located(vec![
+			account_row(address(Alice), "prosumer", None),
+			account_row("5Grwva".into(), "prosumer", None),
+			account_row(Bob.to_account_id().to_ss58check_with_version(0u16.into()), "", None),
+			account_row(address(Charlie), "trader", None),
+			account_row(address(Dave), "", Some(1)),
+		]));
+		let node = PeerId::random();
+		let error = Pilot::validate(
+			accounts,
+			located(vec![
+				AssetRow { asset: 8, owner: address(Dave) },
+				AssetRow { asset: 8, owner: address(Charlie) },
+			]),
+			located(vec![MarketRow { market: 1, operator: address(Alice) }]),
+			located(vec![
+				authority_row(Alice, &node),
+				authority_row(Alice, &PeerId::random()),
+				authority_row(Bob, &node),
+				AuthorityRow { aura: "5Grwva".into(), ..authority_row(Charlie, &PeerId::random()) },
+				AuthorityRow { node: "12D3".into(), ..authority_row(Dave, &PeerId::random()) },
+			]),
+		)
+		.unwrap_err();
+
+		let errors: Vec<_> = error.lines().collect();
+		assert_eq!(errors.len(), 12, "{}", error);
+		assert!(errors[0].starts_with("row 1: duplicate account"));
+		assert!(errors[1].starts_with("row 2: invalid SS58 address `5Grwva`"));
+		assert!(errors[2].contains("of format 0 instead of 42"));
+		assert!(errors[3].starts_with("row 4: duplicate account"));
+		assert!(errors[4].starts_with("row 5: duplicate account"));
+		assert!(errors[5].contains("asset 8 is not a market participant"));
+		assert!(errors[6].contains("duplicate asset 8"));
+		assert!(errors[7].contains("market 1 is not a market operator"));
+		assert!(errors[8].starts_with("row 2: duplicate validator"));
+		assert!(errors[9].starts_with("row 3: duplicate node"));
+		assert!(errors[10].starts_with("row 4: invalid Aura key `5Grwva`"));
+		assert!(errors[11].starts_with("row 5: invalid peer id `12D3`"));
+	}
+
+	#[test]
+	fn validators_and_council_are_required() {
+		let accounts = located(vec![account_row(address(Alice), "dso", None)]);
+		let error = Pilot::validate(accounts, Vec::new(), Vec::new(), Vec::new()).unwrap_err();
+
+		assert_eq!(
+			error.lines().collect::<Vec<_>>(),
+			[
+				"the authorities file lists no validator",
+				"the accounts file marks no council member"
+			]
+		);
+	}
+
+	#[test]
+	fn csv_and_json_files_are_read() {
+		let directory = tempfile::tempdir().unwrap();
+		let csv = directory.path().join("accounts.csv");
+		fs::write(
+			&csv,
+			format!(
+				"account,role,balance,council\n{}, dso ,1000,true\n{},,,\n",
+				address(Alice),
+				address(Bob)
+			),
+		)
+		.unwrap();
+		let json = directory.path().join("assets.json");
+		fs::write(&json, format!(r#"[{{ "asset": 3, "owner": "{}" }}]"#, address(Alice)))
+			.unwrap();
+		let validators = directory.path().join("authorities.csv");
+		let row = authority_row(Bob, &PeerId::random());
+		fs::write(
+			&validators,
+			format!(
+				"account,aura,grandpa,node\n{},{},{},{}\n",
+				row.account, row.aura, row.grandpa, row.node
+			),
+		)
+		.unwrap();
+
+		let (pilot, authorities) = Pilot::load(&csv, &json, None, &validators).unwrap();
+		assert_eq!(pilot.participants, [(Alice.to_account_id(), PARTICIPANT_ROLE_DSO)]);
+		assert_eq!(pilot.balances, [(Alice.to_account_id(), 1000)]);
+		assert_eq!(pilot.assets, [(3, Alice.to_account_id())]);
+		assert_eq!(authorities.validators[0].0, Bob.to_account_id());
+		assert_eq!(authorities.council, [Alice.to_account_id()]);
+
+		fs::write(&csv, format!("account,role,balance\n{},dso,lots\n", address(Alice))).unwrap();
+		let error = Pilot::load(&csv, &json, None, &validators).unwrap_err();
+		assert!(error.starts_with(&format!("Invalid CSV at {}:2", csv.display())), "{}", error);
+	}
+}
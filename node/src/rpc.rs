@@ -5,14 +5,34 @@
 
 #![warn(missing_docs)]
 
-use std::sync::Arc;
+use std::{
+	collections::HashSet,
+	iter,
+	marker::PhantomData,
+	sync::{Arc, Mutex},
+};
 
-use jsonrpsee::RpcModule;
-use node_template_runtime::{opaque::Block, AccountId, Balance, Nonce};
+use futures::{
+	channel::mpsc, future, stream, stream::BoxStream, FutureExt, Stream, StreamExt, TryStreamExt,
+};
+use jsonrpsee::{
+	proc_macros::rpc,
+	types::{error::ErrorObject, SubscriptionResult},
+	RpcModule, SubscriptionSink,
+};
+use node_template_runtime::{
+	opaque::Block, pallet_fedecom_ps_demo, AccountId, Balance, Hash, Nonce, Runtime, RuntimeEvent,
+};
+use sc_client_api::{Backend, BlockchainEvents, StorageProvider};
+use sc_consensus_manual_seal::EngineCommand;
+use sc_rpc::SubscriptionTaskExecutor;
 use sc_transaction_pool_api::TransactionPool;
+use serde::{Deserialize, Serialize};
 use sp_api::ProvideRuntimeApi;
 use sp_block_builder::BlockBuilder;
 use sp_blockchain::{Error as BlockChainError, HeaderBackend, HeaderMetadata};
+use sp_core::traits::SpawnNamed;
+use sp_runtime::traits::Header;
 
 pub use sc_rpc_api::DenyUnsafe;
 
@@ -24,29 +44,71 @@ pub struct FullDeps<C, P> {
 	pub pool: Arc<P>,
 	/// Whether to deny unsafe calls
 	pub deny_unsafe: DenyUnsafe,
+	/// Executor of the subscription tasks
+	pub subscription_executor: SubscriptionTaskExecutor,
+	/// Manual seal command sink, when blocks are sealed on demand
+	pub command_sink: Option<mpsc::Sender<EngineCommand<Hash>>>,
+	/// Index of the fedecom events and extrinsics, when enabled
+	pub index: Option<Arc<crate::index::Index>>,
+	/// EIC codes of the settlement documents, when their export is enabled
+	pub eic_codes: Option<Arc<crate::cim::eic::EicCodes>>,
 }
 
 /// Instantiate all full RPC extensions.
-pub fn create_full<C, P>(
+pub fn create_full<C, P, BE>(
 	deps: FullDeps<C, P>,
 ) -> Result<RpcModule<()>, Box<dyn std::error::Error + Send + Sync>>
 where
+	BE: Backend<Block> + 'static,
 	C: ProvideRuntimeApi<Block>,
 	C: HeaderBackend<Block> + HeaderMetadata<Block, Error = BlockChainError> + 'static,
+	C: BlockchainEvents<Block> + StorageProvider<Block, BE>,
 	C: Send + Sync + 'static,
 	C::Api: substrate_frame_rpc_system::AccountNonceApi<Block, AccountId, Nonce>,
 	C::Api: pallet_transaction_payment_rpc::TransactionPaymentRuntimeApi<Block, Balance>,
+	C::Api: pallet_fedecom_ps_demo_rpc::FedecomRuntimeApi<Block>,
 	C::Api: BlockBuilder<Block>,
 	P: TransactionPool + 'static,
 {
+	use crate::{
+		cim::rpc::{FedecomSettlement, FedecomSettlementApiServer},
+		index::rpc::{FedecomIndex, FedecomIndexApiServer},
+	};
+	use pallet_fedecom_ps_demo_rpc::{Fedecom, FedecomApiServer};
 	use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApiServer};
+	use sc_consensus_manual_seal::rpc::{ManualSeal, ManualSealApiServer};
 	use substrate_frame_rpc_system::{System, SystemApiServer};
 
 	let mut module = RpcModule::new(());
-	let FullDeps { client, pool, deny_unsafe } = deps;
+	let FullDeps {
+		client,
+		pool,
+		deny_unsafe,
+		subscription_executor,
+		command_sink,
+		index,
+		eic_codes,
+	} = deps;
 
 	module.merge(System::new(client.clone(), pool, deny_unsafe).into_rpc())?;
-	module.merge(TransactionPayment::new(client).into_rpc())?;
+	module.merge(TransactionPayment::new(client.clone()).into_rpc())?;
+	module.merge(Fedecom::new(client.clone()).into_rpc())?;
+	module.merge(FedecomEvents::new(client.clone(), subscription_executor).into_rpc())?;
+
+	if let Some(command_sink) = command_sink {
+		// `engine_createBlock` and `engine_finalizeBlock`
+		module.merge(ManualSeal::new(command_sink).into_rpc())?;
+	}
+
+	if let Some(index) = index {
+		// `fedecom_index_*`
+		module.merge(FedecomIndex::new(index).into_rpc())?;
+	}
+
+	if let Some(codes) = eic_codes {
+		// `fedecom_settlementDocuments`
+		module.merge(FedecomSettlement::<_, BE>::new(client, codes, deny_unsafe).into_rpc())?;
+	}
 
 	// Extend this RPC with a custom API by using the following syntax.
 	// `YourRpcStruct` should have a reference to a client, which is needed
@@ -55,3 +117,665 @@ where
 
 	Ok(module)
 }
+
+/// Delivery options of the fedecom event subscriptions.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SubscriptionOptions {
+	/// Only deliver the events of finalized blocks, otherwise the events of every new best block
+	/// are delivered, even if the block is later retracted.
+	pub finalized_only: bool,
+	/// Deliver the events of the blocks since this number first, e.g. to resume after the last
+	/// notification received before a reconnection.
+	///
+	/// At most [`MAX_REPLAYED_BLOCKS`] blocks below the best block are replayed.
+	pub from_block: Option<u32>,
+}
+
+/// Maximum number of blocks replayed by a subscription before its live blocks, a day of blocks.
+pub const MAX_REPLAYED_BLOCKS: u32 = node_template_runtime::DAYS;
+
+/// Error code of the subscriptions replaying too many blocks.
+const REPLAY_ERROR: i32 = 1;
+
+/// Kind of change of a payment or a confirmation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Change {
+	/// Added by the sender (payment) or the receiver (confirmation)
+	Added,
+	/// Value modified by the sender
+	Modified,
+	/// Removed
+	Removed,
+}
+
+/// Event of the flexibility market delivered to the subscribers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum MarketEvent {
+	/// Flexibility offered by a seller to a buyer
+	#[serde(rename_all = "camelCase")]
+	Offer {
+		/// Seller of the flexibility
+		seller: AccountId,
+		/// Buyer of the flexibility
+		buyer: AccountId,
+		/// Flexibility market identifier
+		market: u32,
+		/// Flexibility market timestamp
+		market_timestamp: u32,
+		/// Asset/flexibility identifier
+		asset: u32,
+		/// Sold power, in W
+		sold_power: u32,
+		/// Change, in FCT/W
+		change_fct_w: u32,
+	},
+	/// Decision of the buyer on an offer
+	#[serde(rename_all = "camelCase")]
+	Decision {
+		/// Seller of the flexibility
+		seller: AccountId,
+		/// Buyer of the flexibility
+		buyer: AccountId,
+		/// Flexibility market identifier
+		market: u32,
+		/// Flexibility market timestamp
+		market_timestamp: u32,
+		/// Asset/flexibility identifier
+		asset: u32,
+		/// Whether the offer is confirmed, otherwise it is rejected
+		confirmed: bool,
+	},
+	/// Payment between a sender and a receiver
+	#[serde(rename_all = "camelCase")]
+	Payment {
+		/// Kind of change of the payment
+		change: Change,
+		/// Sender of the payment
+		sender: AccountId,
+		/// Receiver of the payment
+		receiver: AccountId,
+		/// Payment timestamp
+		ts: u32,
+		/// Paid value, unknown for modified and removed payments
+		value: Option<u32>,
+	},
+	/// Confirmation of a payment by its receiver
+	#[serde(rename_all = "camelCase")]
+	Confirmation {
+		/// Kind of change of the confirmation
+		change: Change,
+		/// Sender of the payment
+		sender: AccountId,
+		/// Receiver of the payment, confirming it
+		receiver: AccountId,
+		/// Payment timestamp
+		ts: u32,
+		/// Confirmation status, unknown for removed confirmations
+		status: Option<u32>,
+	},
+}
+
+impl MarketEvent {
+	/// Market event of a fedecom pallet event, if any.
+	fn from_event(event: pallet_fedecom_ps_demo::Event<Runtime>) -> Option<Self> {
+		use pallet_fedecom_ps_demo::Event::*;
+
+		Some(match event {
+			SuccessfullySoldFlexibility {
+				seller,
+				buyer,
+				flexibility_market_identifier: market,
+				flexibility_market_timestamp: market_timestamp,
+				asset_identifier: asset,
+				sold_power,
+				change_fct_w,
+			} => Self::Offer {
+				seller,
+				buyer,
+				market,
+				market_timestamp,
+				asset,
+				sold_power,
+				change_fct_w,
+			},
+			FlexibilitySellingConfirmed {
+				seller,
+				buyer,
+				flexibility_market_identifier: market,
+				flexibility_market_timestamp: market_timestamp,
+				asset_identifier: asset,
+			} => Self::Decision { seller, buyer, market, market_timestamp, asset, confirmed: true },
+			FlexibilitySellingRejected {
+				seller,
+				buyer,
+				flexibility_market_identifier: market,
+				flexibility_market_timestamp: market_timestamp,
+				asset_identifier: asset,
+			} =>
+				Self::Decision { seller, buyer, market, market_timestamp, asset, confirmed: false },
+			AddedToPayments { key_sender, key_receiver, ts, value } => Self::Payment {
+				change: Change::Added,
+				sender: key_sender,
+				receiver: key_receiver,
+				ts,
+				value: Some(value),
+			},
+			SetInPayments { key_sender, key_receiver, ts } => Self::Payment {
+				change: Change::Modified,
+				sender: key_sender,
+				receiver: key_receiver,
+				ts,
+				value: None,
+			},
+			RemovedFromPayments { key_sender, key_receiver, ts } => Self::Payment {
+				change: Change::Removed,
+				sender: key_sender,
+				receiver: key_receiver,
+				ts,
+				value: None,
+			},
+			AddedToConfirmations { key_sender, key_receiver, ts, status, .. } =>
+				Self::Confirmation {
+					change: Change::Added,
+					sender: key_sender,
+					receiver: key_receiver,
+					ts,
+					status: Some(status),
+				},
+			RemovedFromConfirmations { key_sender, key_receiver, ts } => Self::Confirmation {
+				change: Change::Removed,
+				sender: key_sender,
+				receiver: key_receiver,
+				ts,
+				status: None,
+			},
+			_ => return None,
+		})
+	}
+
+	/// Type of the event, as serialized in the `type` field.
+	pub fn kind(&self) -> &'static str {
+		match self {
+			Self::Offer { .. } => "offer",
+			Self::Decision { .. } => "decision",
+			Self::Payment { .. } => "payment",
+			Self::Confirmation { .. } => "confirmation",
+		}
+	}
+
+	/// Flexibility market of the event, if any.
+	pub fn market(&self) -> Option<u32> {
+		match self {
+			Self::Offer { market, .. } | Self::Decision { market, .. } => Some(*market),
+			Self::Payment { .. } | Self::Confirmation { .. } => None,
+		}
+	}
+
+	/// Accounts involved in the event: seller and buyer, or sender and receiver.
+	pub fn accounts(&self) -> [&AccountId; 2] {
+		match self {
+			Self::Offer { seller, buyer, .. } | Self::Decision { seller, buyer, .. } =>
+				[seller, buyer],
+			Self::Payment { sender, receiver, .. } | Self::Confirmation { sender, receiver, .. } =>
+				[sender, receiver],
+		}
+	}
+}
+
+/// Notification of a fedecom event to the subscribers.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventNotification {
+	/// Number of the block of the event, to resume the subscription from.
+	pub block_number: u32,
+	/// Hash of the block of the event.
+	pub block_hash: Hash,
+	/// Whether the block is finalized, otherwise it may be retracted.
+	pub finalized: bool,
+	/// Index of the event in the block.
+	pub event_index: u32,
+	/// Index of the extrinsic which deposited the event, if any.
+	pub extrinsic_index: Option<u32>,
+	/// The event.
+	#[serde(flatten)]
+	pub event: MarketEvent,
+}
+
+impl EventNotification {
+	/// Notifications of the market events of the block `hash`.
+	///
+	/// Fails if the events cannot be read, e.g. when the state of the block is pruned.
+	pub fn from_block<C, BE>(
+		client: &C,
+		hash: Hash,
+		number: u32,
+		finalized: bool,
+	) -> sp_blockchain::Result<Vec<Self>>
+	where
+		BE: Backend<Block>,
+		C: StorageProvider<Block, BE>,
+	{
+		let events = crate::events::block_events(client, hash)?;
+		Ok(Self::from_events(events, hash, number, finalized))
+	}
+
+	/// Notifications of the market events among the `events` of the block `hash`.
+	fn from_events(
+		events: Vec<crate::events::EventRecord>,
+		hash: Hash,
+		number: u32,
+		finalized: bool,
+	) -> Vec<Self> {
+		events
+			.into_iter()
+			.enumerate()
+			.filter_map(|(index, record)| {
+				let RuntimeEvent::FedecomPSDemo(event) = record.event else { return None };
+				Some(EventNotification {
+					block_number: number,
+					block_hash: hash,
+					finalized,
+					event_index: index as u32,
+					extrinsic_index: match record.phase {
+						frame_system::Phase::ApplyExtrinsic(index) => Some(index),
+						_ => None,
+					},
+					event: MarketEvent::from_event(event)?,
+				})
+			})
+			.collect()
+	}
+}
+
+/// Block whose events are delivered: its hash, its number and whether it is finalized.
+pub type EventBlock = (Hash, u32, bool);
+
+/// Blocks whose events are delivered according to `options`.
+///
+/// The blocks since `options.from_block` are replayed first, and the stream fails if one of them
+/// is no longer known.
+pub fn event_blocks<C, BE>(
+	client: Arc<C>,
+	options: &SubscriptionOptions,
+) -> BoxStream<'static, sp_blockchain::Result<EventBlock>>
+where
+	BE: Backend<Block> + 'static,
+	C: BlockchainEvents<Block> + HeaderBackend<Block> + StorageProvider<Block, BE>,
+	C: Send + Sync + 'static,
+{
+	let finalized_only = options.finalized_only;
+
+	// Subscribe before reading the head, so that no block is missed
+	let live = if finalized_only {
+		client
+			.finality_notification_stream()
+			.flat_map(|notification| {
+				// The tree route holds the blocks finalized along with `hash`, oldest first
+				let number = *notification.header.number();
+				let first = number - notification.tree_route.len() as u32;
+				let hashes: Vec<_> = notification
+					.tree_route
+					.iter()
+					.chain(iter::once(&notification.hash))
+					.enumerate()
+					.map(|(index, hash)| (*hash, first + index as u32, true))
+					.collect();
+				stream::iter(hashes)
+			})
+			.boxed()
+	} else {
+		client
+			.import_notification_stream()
+			.filter(|notification| future::ready(notification.is_new_best))
+			.flat_map(|notification| {
+				// Blocks of another fork becoming the best chain are delivered too
+				let mut blocks: Vec<_> = notification
+					.tree_route
+					.iter()
+					.flat_map(|tree_route| tree_route.enacted().to_vec())
+					.map(|block| (block.hash, block.number, false))
+					.collect();
+				blocks.push((notification.hash, *notification.header.number(), false));
+				stream::iter(blocks)
+			})
+			.boxed()
+	};
+
+	let info = client.info();
+	let finalized_number = info.finalized_number;
+	let head = if finalized_only { finalized_number } else { info.best_number };
+
+	match options.from_block {
+		Some(from) => {
+			let replayed = stream::iter(from..=head).map(move |number| {
+				let hash = client.hash(number)?.ok_or_else(|| {
+					BlockChainError::UnknownBlock(format!("No block #{} to replay", number))
+				})?;
+				Ok((hash, number, number <= finalized_number))
+			});
+			join_replayed(replayed, live, head, finalized_only)
+		},
+		None => live.map(Ok).boxed(),
+	}
+}
+
+/// The `replayed` blocks up to `head`, then the `live` blocks which were not replayed.
+///
+/// All the finalized blocks up to `head` are replayed, but a reorganisation can re-enact best
+/// blocks up to `head` which were not, so the replayed best blocks are told apart by their hash.
+fn join_replayed<R, L>(
+	replayed: R,
+	live: L,
+	head: u32,
+	finalized_only: bool,
+) -> BoxStream<'static, sp_blockchain::Result<EventBlock>>
+where
+	R: Stream<Item = sp_blockchain::Result<EventBlock>> + Send + 'static,
+	L: Stream<Item = EventBlock> + Send + 'static,
+{
+	let replayed_hashes = Arc::new(Mutex::new(HashSet::new()));
+
+	let hashes = replayed_hashes.clone();
+	let replayed = replayed.inspect_ok(move |(hash, _, _)| {
+		if !finalized_only {
+			hashes.lock().expect("not poisoned; qed").insert(*hash);
+		}
+	});
+	let live = live.filter(move |(hash, number, _)| {
+		let replayed = *number <= head &&
+			(finalized_only || replayed_hashes.lock().expect("not poisoned; qed").contains(hash));
+		future::ready(!replayed)
+	});
+
+	replayed.chain(live.map(Ok)).boxed()
+}
+
+/// Subscriptions to the events of the flexibility market.
+#[rpc(server)]
+pub trait FedecomEventsApi {
+	/// Subscribe to the offers, decisions, payments and confirmations.
+	///
+	/// The subscription is closed with an error if the events of a block cannot be read, e.g.
+	/// when its state is pruned.
+	#[subscription(
+		name = "fedecom_subscribeEvents" => "fedecom_event",
+		unsubscribe = "fedecom_unsubscribeEvents",
+		item = EventNotification
+	)]
+	fn subscribe_events(&self, options: Option<SubscriptionOptions>);
+
+	/// Subscribe to the offers and decisions of the flexibility market `market`.
+	#[subscription(
+		name = "fedecom_subscribeMarket" => "fedecom_marketEvent",
+		unsubscribe = "fedecom_unsubscribeMarket",
+		item = EventNotification
+	)]
+	fn subscribe_market(&self, market: u32, options: Option<SubscriptionOptions>);
+}
+
+/// Implementation of [`FedecomEventsApiServer`] following the blocks of the client.
+pub struct FedecomEvents<C, BE> {
+	client: Arc<C>,
+	executor: SubscriptionTaskExecutor,
+	_backend: PhantomData<BE>,
+}
+
+impl<C, BE> FedecomEvents<C, BE> {
+	/// Create a new instance of the fedecom event subscriptions.
+	pub fn new(client: Arc<C>, executor: SubscriptionTaskExecutor) -> Self {
+		Self { client, executor, _backend: PhantomData }
+	}
+}
+
+impl<C, BE> FedecomEvents<C, BE>
+where
+	BE: Backend<Block> + 'static,
+	C: BlockchainEvents<Block> + HeaderBackend<Block> + StorageProvider<Block, BE>,
+	C: Send + Sync + 'static,
+{
+	/// Pipe the notifications of the events of `market`, or of all events, to `sink`.
+	fn subscribe(
+		&self,
+		mut sink: SubscriptionSink,
+		market: Option<u32>,
+		options: SubscriptionOptions,
+	) {
+		if let Some(from) = options.from_block {
+			let best_number = self.client.info().best_number;
+			if best_number.saturating_sub(from) > MAX_REPLAYED_BLOCKS {
+				let _ = sink.reject(ErrorObject::owned(
+					REPLAY_ERROR,
+					"Too many blocks to replay.",
+					Some(format!(
+						"At most {} blocks below the best block #{} are replayed",
+						MAX_REPLAYED_BLOCKS, best_number
+					)),
+				));
+				return
+			}
+		}
+
+		let client = self.client.clone();
+		let notifications = event_blocks(self.client.clone(), &options)
+			.and_then(move |(hash, number, finalized)| {
+				future::ready(EventNotification::from_block(&*client, hash, number, finalized))
+			})
+			.map_ok(move |mut notifications| {
+				if market.is_some() {
+					notifications.retain(|notification| notification.event.market() == market);
+				}
+				stream::iter(notifications.into_iter().map(Ok))
+			})
+			.try_flatten()
+			.boxed();
+
+		let future = async move {
+			// Tell the subscriber why the stream ended, e.g. the error reading a block
+			let closed = sink.pipe_from_try_stream(notifications).await;
+			sink.close(closed);
+		};
+		self.executor.spawn("fedecom-rpc-subscription", Some("rpc"), future.boxed());
+	}
+}
+
+impl<C, BE> FedecomEventsApiServer for FedecomEvents<C, BE>
+where
+	BE: Backend<Block> + 'static,
+	C: BlockchainEvents<Block> + HeaderBackend<Block> + StorageProvider<Block, BE>,
+	C: Send + Sync + 'static,
+{
+	fn subscribe_events(
+		&self,
+		sink: SubscriptionSink,
+		options: Option<SubscriptionOptions>,
+	) -> SubscriptionResult {
+		self.subscribe(sink, None, options.unwrap_or_default());
+		Ok(())
+	}
+
+	fn subscribe_market(
+		&self,
+		sink: SubscriptionSink,
+		market: u32,
+		options: Option<SubscriptionOptions>,
+	) -> SubscriptionResult {
+		self.subscribe(sink, Some(market), options.unwrap_or_default());
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::block_on;
+	use pallet_fedecom_ps_demo::Event;
+	use sp_keyring::AccountKeyring::{Alice, Bob};
+
+	fn sold() -> Event<Runtime> {
+		Event::SuccessfullySoldFlexibility {
+			seller: Alice.to_account_id(),
+			buyer: Bob.to_account_id(),
+			flexibility_market_identifier: 1,
+			flexibility_market_timestamp: 900,
+			asset_identifier: 7,
+			sold_power: 10,
+			change_fct_w: 3,
+		}
+	}
+
+	fn record(phase: frame_system::Phase, event: RuntimeEvent) -> crate::events::EventRecord {
+		frame_system::EventRecord { phase, event, topics: Vec::new() }
+	}
+
+	fn block(byte: u8, number: u32, finalized: bool) -> EventBlock {
+		(Hash::repeat_byte(byte), number, finalized)
+	}
+
+	fn joined(
+		replayed: Vec<sp_blockchain::Result<EventBlock>>,
+		live: Vec<EventBlock>,
+		head: u32,
+		finalized_only: bool,
+	) -> Vec<EventBlock> {
+		let blocks =
+			join_replayed(stream::iter(replayed), stream::iter(live), head, finalized_only);
+		block_on(blocks.map(|block| block.unwrap()).collect())
+	}
+
+	#[test]
+	fn offers_and_decisions_are_events_of_their_market() {
+		let offer = MarketEvent::from_event(sold()).unwrap();
+		assert_eq!(offer.kind(), "offer");
+		assert_eq!(offer.market(), Some(1));
+		assert_eq!(offer.accounts(), [&Alice.to_account_id(), &Bob.to_account_id()]);
+
+		let rejected = MarketEvent::from_event(Event::FlexibilitySellingRejected {
+			seller: Alice.to_account_id(),
+			buyer: Bob.to_account_id(),
+			flexibility_market_identifier: 1,
+			flexibility_market_timestamp: 900,
+			asset_identifier: 7,
+		})
+		.unwrap();
+		assert_eq!(
+			rejected,
+			MarketEvent::Decision {
+				seller: Alice.to_account_id(),
+				buyer: Bob.to_account_id(),
+				market: 1,
+				market_timestamp: 900,
+				asset: 7,
+				confirmed: false,
+			}
+		);
+	}
+
+	#[test]
+	fn payments_and_confirmations_are_events_of_no_market() {
+		let removed = MarketEvent::from_event(Event::RemovedFromPayments {
+			key_sender: Bob.to_account_id(),
+			key_receiver: Alice.to_account_id(),
+			ts: 900,
+		})
+		.unwrap();
+		assert_eq!(removed.kind(), "payment");
+		assert_eq!(removed.market(), None);
+		assert_eq!(
+			removed,
+			MarketEvent::Payment {
+				change: Change::Removed,
+				sender: Bob.to_account_id(),
+				receiver: Alice.to_account_id(),
+				ts: 900,
+				value: None,
+			}
+		);
+
+		let confirmation = MarketEvent::from_event(Event::AddedToConfirmations {
+			key_confirmer: Alice.to_account_id(),
+			key_sender: Bob.to_account_id(),
+			key_receiver: Alice.to_account_id(),
+			ts: 900,
+			status: 1,
+		})
+		.unwrap();
+		assert_eq!(confirmation.kind(), "confirmation");
+		assert_eq!(confirmation.accounts(), [&Bob.to_account_id(), &Alice.to_account_id()]);
+		assert_eq!(MarketEvent::from_event(Event::ConfirmationOK()), None);
+	}
+
+	#[test]
+	fn notifications_keep_the_indices_of_the_market_events() {
+		let events = vec![
+			record(
+				frame_system::Phase::Initialization,
+				RuntimeEvent::System(frame_system::Event::ExtrinsicSuccess {
+					dispatch_info: Default::default(),
+				}),
+			),
+			record(frame_system::Phase::ApplyExtrinsic(2), RuntimeEvent::FedecomPSDemo(sold())),
+			record(
+				frame_system::Phase::ApplyExtrinsic(2),
+				RuntimeEvent::FedecomPSDemo(Event::ConfirmationOK()),
+			),
+		];
+		let hash = Hash::repeat_byte(1);
+
+		let notifications = EventNotification::from_events(events, hash, 12, false);
+		assert_eq!(notifications.len(), 1);
+		assert_eq!(notifications[0].event_index, 1);
+		assert_eq!(notifications[0].extrinsic_index, Some(2));
+
+		let json = serde_json::to_value(&notifications[0]).unwrap();
+		assert_eq!(json["type"], "offer");
+		assert_eq!(json["blockNumber"], 12);
+		assert_eq!(json["finalized"], false);
+		assert_eq!(json["marketTimestamp"], 900);
+		assert_eq!(json["changeFctW"], 3);
+	}
+
+	#[test]
+	fn replay_joins_the_finalized_blocks_without_duplicates() {
+		let replayed = vec![Ok(block(3, 3, true)), Ok(block(4, 4, true)), Ok(block(5, 5, true))];
+		let live = vec![block(4, 4, true), block(5, 5, true), block(6, 6, true)];
+
+		let numbers: Vec<_> =
+			joined(replayed, live, 5, true).into_iter().map(|(_, number, _)| number).collect();
+		assert_eq!(numbers, vec![3, 4, 5, 6]);
+	}
+
+	#[test]
+	fn best_blocks_re_enacted_by_a_reorganisation_are_delivered() {
+		let replayed =
+			vec![Ok(block(3, 3, true)), Ok(block(4, 4, false)), Ok(block(5, 5, false))];
+		// Block #5 is imported again, then a fork from #3 becomes the best chain
+		let live = vec![block(5, 5, false), block(14, 4, false), block(15, 5, false)];
+
+		assert_eq!(
+			joined(replayed, live, 5, false),
+			vec![
+				block(3, 3, true),
+				block(4, 4, false),
+				block(5, 5, false),
+				block(14, 4, false),
+				block(15, 5, false),
+			]
+		);
+	}
+
+	#[test]
+	fn unknown_replayed_blocks_are_errors() {
+		let replayed = vec![
+			Ok(block(3, 3, true)),
+			Err(BlockChainError::UnknownBlock("No block #4 to replay".into())),
+		];
+		let blocks = join_replayed(stream::iter(replayed), stream::empty(), 4, true);
+
+		let blocks: Vec<_> = block_on(blocks.collect());
+		assert!(blocks[0].is_ok());
+		assert!(matches!(blocks[1], Err(BlockChainError::UnknownBlock(_))));
+	}
+}
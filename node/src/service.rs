@@ -1,16 +1,30 @@
 //! Service and ServiceFactory implementation. Specialized wrapper over substrate service.
 
-use futures::FutureExt;
-use node_template_runtime::{self, opaque::Block, RuntimeApi};
+use futures::{channel::mpsc, FutureExt, StreamExt};
+use node_template_runtime::{self, opaque::Block, Hash, RuntimeApi, SLOT_DURATION};
 use sc_client_api::{Backend, BlockBackend};
 use sc_consensus_aura::{ImportQueueParams, SlotProportion, StartAuraParams};
 use sc_consensus_grandpa::SharedVoterState;
+use sc_consensus_manual_seal::{
+	consensus::aura::AuraConsensusDataProvider, EngineCommand, InstantSealParams,
+	ManualSealParams,
+};
 pub use sc_executor::NativeElseWasmExecutor;
-use sc_service::{error::Error as ServiceError, Configuration, TaskManager, WarpSyncParams};
+use sc_service::{
+	error::Error as ServiceError, BlocksPruning, Configuration, PruningMode, TaskManager,
+	WarpSyncParams,
+};
 use sc_telemetry::{Telemetry, TelemetryWorker};
 use sc_transaction_pool_api::OffchainTransactionPoolFactory;
 use sp_consensus_aura::sr25519::AuthorityPair as AuraPair;
-use std::{sync::Arc, time::Duration};
+use sp_inherents::{InherentData, InherentDataProvider, InherentIdentifier};
+use substrate_prometheus_endpoint::Registry;
+use std::{
+	path::{Path, PathBuf},
+	str::FromStr,
+	sync::Arc,
+	time::Duration,
+};
 
 // Our native executor instance.
 pub struct ExecutorDispatch;
@@ -34,7 +48,7 @@ impl sc_executor::NativeExecutionDispatch for ExecutorDispatch {
 
 pub(crate) type FullClient =
 	sc_service::TFullClient<Block, RuntimeApi, NativeElseWasmExecutor<ExecutorDispatch>>;
-type FullBackend = sc_service::TFullBackend<Block>;
+pub(crate) type FullBackend = sc_service::TFullBackend<Block>;
 type FullSelectChain = sc_consensus::LongestChain<FullBackend, Block>;
 
 #[allow(clippy::type_complexity)]
@@ -139,8 +153,263 @@ pub fn new_partial(
 	})
 }
 
+/// Block sealing method used instead of Aura and GRANDPA, for development and testing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sealing {
+	/// Seal a block as soon as a transaction enters the pool, and finalize it.
+	Instant,
+	/// Seal and finalize blocks on demand through the `engine_*` RPC methods.
+	Manual,
+	/// Seal and finalize a block every given number of milliseconds.
+	Interval(u64),
+}
+
+impl FromStr for Sealing {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_lowercase().as_str() {
+			"instant" => Ok(Self::Instant),
+			"manual" => Ok(Self::Manual),
+			other => match other.strip_prefix("interval=") {
+				Some(millis) => match millis.parse::<u64>() {
+					Ok(millis) if millis > 0 => Ok(Self::Interval(millis)),
+					_ => Err(format!("invalid sealing interval `{}`", millis)),
+				},
+				None => Err(format!(
+					"invalid sealing method `{}`, expected `instant`, `manual` or `interval=<ms>`",
+					s
+				)),
+			},
+		}
+	}
+}
+
+/// Timestamp inherent data provider for manually sealed blocks.
+///
+/// Aura requires the slot of every block to be strictly greater than the one of its parent, while
+/// manual sealing can author many blocks within a single slot. Every provided timestamp is
+/// therefore at least one slot after the one of the parent block, moving ahead of the wall clock if
+/// needed.
+struct ManualSealTimestampProvider {
+	/// Timestamp of the parent block, in milliseconds.
+	parent: u64,
+}
+
+#[async_trait::async_trait]
+impl InherentDataProvider for ManualSealTimestampProvider {
+	async fn provide_inherent_data(
+		&self,
+		inherent_data: &mut InherentData,
+	) -> Result<(), sp_inherents::Error> {
+		let now = sp_timestamp::Timestamp::current().as_millis();
+		let timestamp = now.max(self.parent + SLOT_DURATION);
+
+		inherent_data.put_data(sp_timestamp::INHERENT_IDENTIFIER, &timestamp)
+	}
+
+	async fn try_handle_error(
+		&self,
+		_: &InherentIdentifier,
+		_: &[u8],
+	) -> Option<Result<(), sp_inherents::Error>> {
+		None
+	}
+}
+
+/// Partial components of a node authoring blocks with manual seal.
+#[allow(clippy::type_complexity)]
+fn new_manual_seal_partial(
+	config: &Configuration,
+) -> Result<
+	sc_service::PartialComponents<
+		FullClient,
+		FullBackend,
+		FullSelectChain,
+		sc_consensus::DefaultImportQueue<Block, FullClient>,
+		sc_transaction_pool::FullPool<Block, FullClient>,
+		Option<Telemetry>,
+	>,
+	ServiceError,
+> {
+	let telemetry = config
+		.telemetry_endpoints
+		.clone()
+		.filter(|x| !x.is_empty())
+		.map(|endpoints| -> Result<_, sc_telemetry::Error> {
+			let worker = TelemetryWorker::new(16)?;
+			let telemetry = worker.handle().new_telemetry(endpoints);
+			Ok((worker, telemetry))
+		})
+		.transpose()?;
+
+	let executor = sc_service::new_native_or_wasm_executor(config);
+	let (client, backend, keystore_container, task_manager) =
+		sc_service::new_full_parts::<Block, RuntimeApi, _>(
+			config,
+			telemetry.as_ref().map(|(_, telemetry)| telemetry.handle()),
+			executor,
+		)?;
+	let client = Arc::new(client);
+
+	let telemetry = telemetry.map(|(worker, telemetry)| {
+		task_manager.spawn_handle().spawn("telemetry", None, worker.run());
+		telemetry
+	});
+
+	let select_chain = sc_consensus::LongestChain::new(backend.clone());
+
+	let transaction_pool = sc_transaction_pool::BasicPool::new_full(
+		config.transaction_pool.clone(),
+		config.role.is_authority().into(),
+		config.prometheus_registry(),
+		task_manager.spawn_essential_handle(),
+		client.clone(),
+	);
+
+	let import_queue = sc_consensus_manual_seal::import_queue(
+		Box::new(client.clone()),
+		&task_manager.spawn_essential_handle(),
+		config.prometheus_registry(),
+	);
+
+	Ok(sc_service::PartialComponents {
+		client,
+		backend,
+		task_manager,
+		import_queue,
+		keystore_container,
+		select_chain,
+		transaction_pool,
+		other: telemetry,
+	})
+}
+
+/// Spawn the task exporting the Prometheus metrics of the flexibility market to `registry`.
+fn spawn_market_metrics(
+	task_manager: &TaskManager,
+	client: Arc<FullClient>,
+	registry: &Registry,
+) -> Result<(), ServiceError> {
+	let metrics = crate::metrics::Metrics::register(registry)?;
+	task_manager.spawn_handle().spawn(
+		"fedecom-metrics",
+		None,
+		crate::metrics::run::<_, FullBackend>(client, metrics),
+	);
+	Ok(())
+}
+
+/// Ensure that the node keeps the state and body of the finalized blocks, which the fedecom index
+/// reads to catch up with the chain.
+fn ensure_archive(config: &Configuration) -> Result<(), ServiceError> {
+	let archive_state = matches!(
+		config.state_pruning,
+		Some(PruningMode::ArchiveAll) | Some(PruningMode::ArchiveCanonical)
+	);
+	let archive_blocks = !matches!(config.blocks_pruning, BlocksPruning::Some(_));
+	if archive_state && archive_blocks {
+		Ok(())
+	} else {
+		Err(ServiceError::Other(
+			"The fedecom index reads the state and body of every finalized block: start the node \
+			with `--state-pruning archive-canonical` and without `--blocks-pruning <number>`"
+				.into(),
+		))
+	}
+}
+
+/// Open the fedecom index at `path` and spawn the task writing the blocks of `client` in it.
+fn spawn_index(
+	task_manager: &TaskManager,
+	client: Arc<FullClient>,
+	path: &Path,
+) -> Result<Arc<crate::index::Index>, ServiceError> {
+	let index = crate::index::Index::open(path).map(Arc::new).map_err(|e| {
+		ServiceError::Other(format!("Failed to open the fedecom index {}: {}", path.display(), e))
+	})?;
+	// The indexer writes in the SQLite database synchronously
+	task_manager.spawn_handle().spawn_blocking(
+		"fedecom-index",
+		None,
+		crate::index::run::<_, FullBackend>(client, index.clone()),
+	);
+	Ok(index)
+}
+
+/// Load the webhooks configuration at `path` and spawn the tasks delivering the finalized events
+/// of `client`, with their cursors and dead-letter logs in the directory `store`.
+fn spawn_webhooks(
+	task_manager: &TaskManager,
+	client: Arc<FullClient>,
+	path: &Path,
+	store: &Path,
+) -> Result<(), ServiceError> {
+	let config = crate::webhook::Config::load(path).map_err(ServiceError::Other)?;
+	let store = crate::webhook::Store::open(store).map_err(|e| {
+		ServiceError::Other(format!("Failed to open the fedecom webhooks store: {}", e))
+	})?;
+	let tasks = crate::webhook::endpoint_tasks::<_, FullBackend>(client, config, store)
+		.map_err(ServiceError::Other)?;
+	for task in tasks {
+		task_manager.spawn_handle().spawn("fedecom-webhook", None, task);
+	}
+	Ok(())
+}
+
+/// Optional fedecom services of the node.
+#[derive(Clone, Debug, Default)]
+pub struct FedecomOptions {
+	/// Path of the SQLite index of the fedecom events and extrinsics.
+	pub index: Option<PathBuf>,
+	/// Path of the TOML configuration of the webhooks of the finalized fedecom events.
+	pub webhooks: Option<PathBuf>,
+	/// Path of the TOML table of the EIC codes of the settlement documents.
+	pub eic_codes: Option<PathBuf>,
+}
+
+impl FedecomOptions {
+	/// Spawn the index and webhook tasks enabled by the options, returning the index.
+	fn spawn(
+		&self,
+		config: &Configuration,
+		task_manager: &TaskManager,
+		client: &Arc<FullClient>,
+	) -> Result<Option<Arc<crate::index::Index>>, ServiceError> {
+		if let Some(path) = &self.webhooks {
+			let store = config.data_path.join("fedecom-webhooks");
+			spawn_webhooks(task_manager, client.clone(), path, &store)?;
+		}
+		if self.index.is_some() {
+			ensure_archive(config)?;
+		}
+		self.index.as_ref().map(|path| spawn_index(task_manager, client.clone(), path)).transpose()
+	}
+
+	/// EIC codes of the settlement documents, enabling their RPC method when given.
+	fn eic_codes(&self) -> Result<Option<Arc<crate::cim::eic::EicCodes>>, ServiceError> {
+		self.eic_codes
+			.as_ref()
+			.map(|path| crate::cim::eic::EicCodes::load(path).map(Arc::new))
+			.transpose()
+			.map_err(ServiceError::Other)
+	}
+}
+
 /// Builds a new service for a full client.
-pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
+///
+/// Blocks are authored with Aura and finalized with GRANDPA, unless a `sealing` method is given,
+/// in which case both are replaced by manual seal. The optional `fedecom` services are spawned
+/// alongside.
+pub fn new_full(
+	config: Configuration,
+	sealing: Option<Sealing>,
+	fedecom: FedecomOptions,
+) -> Result<TaskManager, ServiceError> {
+	if let Some(sealing) = sealing {
+		return new_full_manual_seal(config, sealing, fedecom)
+	}
+
 	let sc_service::PartialComponents {
 		client,
 		backend,
@@ -180,6 +449,10 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 			warp_sync_params: Some(WarpSyncParams::WithProvider(warp_sync)),
 		})?;
 
+	// Peers are filtered by the offchain worker of `pallet_node_authorization`, which updates the
+	// reserved peers of the network from the authorized nodes after every imported block. Without
+	// offchain workers, as on the nodes that are not authorities by default, the node follows the
+	// authorized nodes itself.
 	if config.offchain_worker.enabled {
 		task_manager.spawn_handle().spawn(
 			"offchain-workers-runner",
@@ -199,6 +472,12 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 			.run(client.clone(), task_manager.spawn_handle())
 			.boxed(),
 		);
+	} else {
+		task_manager.spawn_handle().spawn(
+			"authorized-nodes",
+			None,
+			crate::authorization::follow(client.clone(), network.clone()),
+		);
 	}
 
 	let role = config.role.clone();
@@ -207,15 +486,24 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 	let name = config.network.node_name.clone();
 	let enable_grandpa = !config.disable_grandpa;
 	let prometheus_registry = config.prometheus_registry().cloned();
+	let index = fedecom.spawn(&config, &task_manager, &client)?;
+	let eic_codes = fedecom.eic_codes()?;
 
 	let rpc_extensions_builder = {
 		let client = client.clone();
 		let pool = transaction_pool.clone();
 
-		Box::new(move |deny_unsafe, _| {
-			let deps =
-				crate::rpc::FullDeps { client: client.clone(), pool: pool.clone(), deny_unsafe };
-			crate::rpc::create_full(deps).map_err(Into::into)
+		Box::new(move |deny_unsafe, subscription_executor| {
+			let deps = crate::rpc::FullDeps {
+				client: client.clone(),
+				pool: pool.clone(),
+				deny_unsafe,
+				subscription_executor,
+				command_sink: None,
+				index: index.clone(),
+				eic_codes: eic_codes.clone(),
+			};
+			crate::rpc::create_full::<_, _, FullBackend>(deps).map_err(Into::into)
 		})
 	};
 
@@ -234,6 +522,10 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 		telemetry: telemetry.as_mut(),
 	})?;
 
+	if let Some(registry) = prometheus_registry.as_ref() {
+		spawn_market_metrics(&task_manager, client.clone(), registry)?;
+	}
+
 	if role.is_authority() {
 		let proposer_factory = sc_basic_authorship::ProposerFactory::new(
 			task_manager.spawn_handle(),
@@ -329,3 +621,192 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 	network_starter.start_network();
 	Ok(task_manager)
 }
+
+/// Builds a new service for a full client sealing blocks with `sealing` instead of Aura and
+/// GRANDPA.
+fn new_full_manual_seal(
+	config: Configuration,
+	sealing: Sealing,
+	fedecom: FedecomOptions,
+) -> Result<TaskManager, ServiceError> {
+	let sc_service::PartialComponents {
+		client,
+		backend,
+		mut task_manager,
+		import_queue,
+		keystore_container,
+		select_chain,
+		transaction_pool,
+		other: mut telemetry,
+	} = new_manual_seal_partial(&config)?;
+
+	let net_config = sc_network::config::FullNetworkConfiguration::new(&config.network);
+
+	let (network, system_rpc_tx, tx_handler_controller, network_starter, sync_service) =
+		sc_service::build_network(sc_service::BuildNetworkParams {
+			config: &config,
+			net_config,
+			client: client.clone(),
+			transaction_pool: transaction_pool.clone(),
+			spawn_handle: task_manager.spawn_handle(),
+			import_queue,
+			block_announce_validator_builder: None,
+			warp_sync_params: None,
+		})?;
+
+	if config.offchain_worker.enabled {
+		task_manager.spawn_handle().spawn(
+			"offchain-workers-runner",
+			"offchain-worker",
+			sc_offchain::OffchainWorkers::new(sc_offchain::OffchainWorkerOptions {
+				runtime_api_provider: client.clone(),
+				is_validator: config.role.is_authority(),
+				keystore: Some(keystore_container.keystore()),
+				offchain_db: backend.offchain_storage(),
+				transaction_pool: Some(OffchainTransactionPoolFactory::new(
+					transaction_pool.clone(),
+				)),
+				network_provider: network.clone(),
+				enable_http_requests: true,
+				custom_extensions: |_| vec![],
+			})
+			.run(client.clone(), task_manager.spawn_handle())
+			.boxed(),
+		);
+	}
+
+	let prometheus_registry = config.prometheus_registry().cloned();
+	let index = fedecom.spawn(&config, &task_manager, &client)?;
+	let eic_codes = fedecom.eic_codes()?;
+
+	// Only the manual mode is driven through RPC, the other modes create their own commands.
+	let (command_sink, commands_stream) = match sealing {
+		Sealing::Manual => {
+			let (sink, stream) = mpsc::channel::<EngineCommand<Hash>>(1024);
+			(Some(sink), Some(stream))
+		},
+		_ => (None, None),
+	};
+
+	let rpc_extensions_builder = {
+		let client = client.clone();
+		let pool = transaction_pool.clone();
+
+		Box::new(move |deny_unsafe, subscription_executor| {
+			let deps = crate::rpc::FullDeps {
+				client: client.clone(),
+				pool: pool.clone(),
+				deny_unsafe,
+				subscription_executor,
+				command_sink: command_sink.clone(),
+				index: index.clone(),
+				eic_codes: eic_codes.clone(),
+			};
+			crate::rpc::create_full::<_, _, FullBackend>(deps).map_err(Into::into)
+		})
+	};
+
+	let _rpc_handlers = sc_service::spawn_tasks(sc_service::SpawnTasksParams {
+		network,
+		client: client.clone(),
+		keystore: keystore_container.keystore(),
+		task_manager: &mut task_manager,
+		transaction_pool: transaction_pool.clone(),
+		rpc_builder: rpc_extensions_builder,
+		backend,
+		system_rpc_tx,
+		tx_handler_controller,
+		sync_service,
+		config,
+		telemetry: telemetry.as_mut(),
+	})?;
+
+	if let Some(registry) = prometheus_registry.as_ref() {
+		spawn_market_metrics(&task_manager, client.clone(), registry)?;
+	}
+
+	let proposer_factory = sc_basic_authorship::ProposerFactory::new(
+		task_manager.spawn_handle(),
+		client.clone(),
+		transaction_pool.clone(),
+		prometheus_registry.as_ref(),
+		telemetry.as_ref().map(|x| x.handle()),
+	);
+
+	// The blocks are sealed on the best block or on the parent given to `engine_createBlock`, whose
+	// timestamp may be ahead of the wall clock
+	let create_inherent_data_providers = {
+		let client = client.clone();
+		move |parent, ()| {
+			let client = client.clone();
+			async move {
+				let parent = crate::events::block_timestamp(&*client, parent)?;
+				Ok::<_, Box<dyn std::error::Error + Send + Sync>>(ManualSealTimestampProvider {
+					parent,
+				})
+			}
+		}
+	};
+
+	match sealing {
+		Sealing::Instant => {
+			let authorship_future =
+				sc_consensus_manual_seal::run_instant_seal_and_finalize(InstantSealParams {
+					block_import: client.clone(),
+					env: proposer_factory,
+					client: client.clone(),
+					pool: transaction_pool,
+					select_chain,
+					consensus_data_provider: Some(Box::new(AuraConsensusDataProvider::new(
+						client.clone(),
+					))),
+					create_inherent_data_providers,
+				});
+
+			task_manager.spawn_essential_handle().spawn_blocking(
+				"instant-seal",
+				Some("block-authoring"),
+				authorship_future,
+			);
+		},
+		Sealing::Manual | Sealing::Interval(_) => {
+			let commands_stream = match (sealing, commands_stream) {
+				(Sealing::Interval(millis), _) => futures::stream::unfold((), move |()| async move {
+					futures_timer::Delay::new(Duration::from_millis(millis)).await;
+					let command = EngineCommand::SealNewBlock {
+						create_empty: true,
+						finalize: true,
+						parent_hash: None,
+						sender: None,
+					};
+					Some((command, ()))
+				})
+				.boxed(),
+				(_, commands_stream) =>
+					commands_stream.expect("the manual mode creates a command stream; qed").boxed(),
+			};
+
+			let authorship_future = sc_consensus_manual_seal::run_manual_seal(ManualSealParams {
+				block_import: client.clone(),
+				env: proposer_factory,
+				client: client.clone(),
+				pool: transaction_pool,
+				commands_stream,
+				select_chain,
+				consensus_data_provider: Some(Box::new(AuraConsensusDataProvider::new(
+					client.clone(),
+				))),
+				create_inherent_data_providers,
+			});
+
+			task_manager.spawn_essential_handle().spawn_blocking(
+				"manual-seal",
+				Some("block-authoring"),
+				authorship_future,
+			);
+		},
+	}
+
+	network_starter.start_network();
+	Ok(task_manager)
+}
@@ -0,0 +1,343 @@
+//! `simulate` subcommand, a deterministic load generator for the flexibility market.
+//!
+//! A scenario (see [`scenario`]) describes the simulated prosumers, aggregators and DSOs, the
+//! distribution of their offers and the deviations of their deliveries. `simulate spec` writes the
+//! chain spec registering the simulated accounts, and `simulate run` drives a node started with it
+//! over RPC: every market interval, the prosumers offer flexibility, the buyers decide and the
+//! sellers confirm the payments of the delivered flexibility, each step waiting for the
+//! transaction pool to drain. With a node started with `--sealing manual`, `--seal` makes the
+//! simulator create the blocks itself, so that their contents only depend on the scenario.
+//! The run ends with a report of the throughput, block fullness, pool rejections and ledger (see
+//! [`report`]).
+
+pub mod report;
+pub mod scenario;
+
+use crate::{
+	chain_spec,
+	events::{storage_value_key, EventRecord},
+	fedecom::{
+		offline,
+		remote::{Node, RuntimeVersion},
+		signer::Signer,
+	},
+};
+use codec::{Decode, Encode};
+use frame_support::{
+	dispatch::{DispatchClass, PerDispatchClass},
+	weights::Weight,
+};
+use futures::{stream, StreamExt};
+use jsonrpsee::rpc_params;
+use node_template_runtime::{opaque, BlockWeights, Hash, RuntimeEvent, UncheckedExtrinsic};
+use report::{BlockStats, LedgerStats, Report};
+use sc_cli::Result;
+use sc_service::ChainSpec as _;
+use scenario::{Phase, Population, Scenario};
+use sp_core::{crypto::Ss58Codec, storage::StorageKey, Bytes};
+use sp_runtime::{generic::SignedBlock, traits::Header as _};
+use std::{
+	path::PathBuf,
+	time::{Duration, Instant},
+};
+
+/// Delay between two polls of the transaction pool.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Simulate flexibility market participants trading against a node.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct SimulateCmd {
+	#[command(subcommand)]
+	pub action: SimulateAction,
+}
+
+/// Step of a simulation.
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum SimulateAction {
+	/// Print the chain spec registering the simulated participants, market and assets.
+	Spec {
+		/// TOML scenario of the simulation.
+		#[arg(long, value_name = "PATH")]
+		scenario: PathBuf,
+
+		/// Chain whose authorities, well-known nodes and council are kept: `fedecom-demo` or
+		/// `fedecom-local`.
+		#[arg(long, default_value = "fedecom-demo")]
+		template: String,
+
+		/// Print the chain spec as raw storage.
+		#[arg(long)]
+		raw: bool,
+	},
+
+	/// Run the scenario against a node started with the chain spec of `spec`.
+	Run {
+		/// TOML scenario of the simulation.
+		#[arg(long, value_name = "PATH")]
+		scenario: PathBuf,
+
+		/// WebSocket URL of the RPC endpoint of the node.
+		#[arg(long, value_name = "URL", default_value = "ws://127.0.0.1:9944")]
+		url: String,
+
+		/// Create the blocks through `engine_createBlock`, for a node started with
+		/// `--sealing manual`.
+		#[arg(long)]
+		seal: bool,
+
+		/// Seconds a step waits for the transaction pool to drain.
+		#[arg(long, value_name = "SECONDS", default_value_t = 120)]
+		step_timeout: u64,
+
+		/// Number of extrinsics submitted concurrently.
+		#[arg(long, default_value_t = 64)]
+		concurrency: usize,
+
+		/// Print the report as JSON.
+		#[arg(long)]
+		json: bool,
+	},
+}
+
+/// Number of the best block of `node`.
+async fn best_number(node: &Node) -> Result<u32> {
+	let header: opaque::Header = node.request("chain_getHeader", rpc_params![]).await?;
+	Ok(*header.number())
+}
+
+/// Value stored at `key` in the state of the block `hash`, decoded.
+async fn storage<T: Decode>(node: &Node, key: StorageKey, hash: Hash) -> Result<Option<T>> {
+	let data: Option<Bytes> = node.request("state_getStorage", rpc_params![key, hash]).await?;
+	data.map(|data| T::decode(&mut &data[..]))
+		.transpose()
+		.map_err(|e| format!("Undecodable storage of block {:?}: {}", hash, e).into())
+}
+
+/// Statistics of the block `number` of `node`.
+async fn block_stats(node: &Node, number: u32) -> Result<BlockStats> {
+	let hash: Hash = node.request("chain_getBlockHash", rpc_params![number]).await?;
+	let block: SignedBlock<opaque::Block> =
+		node.request("chain_getBlock", rpc_params![hash]).await?;
+	let extrinsics = block
+		.block
+		.extrinsics
+		.iter()
+		.filter(|extrinsic| {
+			UncheckedExtrinsic::decode(&mut &extrinsic.encode()[..])
+				.map_or(false, |extrinsic| extrinsic.signature.is_some())
+		})
+		.count();
+
+	let events: Vec<EventRecord> =
+		storage(node, storage_value_key(b"System", b"Events"), hash).await?.unwrap_or_default();
+	let failed = events
+		.iter()
+		.filter(|record| {
+			matches!(
+				record.event,
+				RuntimeEvent::System(frame_system::Event::ExtrinsicFailed { .. })
+			)
+		})
+		.count();
+
+	let weight: PerDispatchClass<Weight> =
+		storage(node, storage_value_key(b"System", b"BlockWeight"), hash)
+			.await?
+			.unwrap_or_default();
+	let limits = BlockWeights::get();
+	let max = limits.get(DispatchClass::Normal).max_total.unwrap_or(limits.max_block);
+	let fullness = weight.get(DispatchClass::Normal).ref_time() as f64 / max.ref_time() as f64;
+
+	Ok(BlockStats { extrinsics, failed, fullness })
+}
+
+/// Runner of a simulation against a node.
+struct Simulation<'a> {
+	node: Node,
+	population: &'a Population,
+	genesis: Hash,
+	version: RuntimeVersion,
+	/// Hash of the metadata of the runtime of the node
+	metadata_hash: Hash,
+	/// Next nonce of every simulated account, fetched from the node when unknown
+	nonces: Vec<Option<u32>>,
+	seal: bool,
+	step_timeout: Duration,
+	concurrency: usize,
+}
+
+impl Simulation<'_> {
+	async fn nonce(&mut self, signer: usize) -> Result<u32> {
+		let nonce = match self.nonces[signer] {
+			Some(nonce) => nonce,
+			None => {
+				let account = self.population.account(signer).to_ss58check();
+				self.node.request("system_accountNextIndex", rpc_params![account]).await?
+			},
+		};
+		self.nonces[signer] = Some(nonce + 1);
+		Ok(nonce)
+	}
+
+	/// Sign and submit the calls of `phase`, then wait for the transaction pool to drain.
+	async fn run_phase(&mut self, phase: &Phase, report: &mut Report) -> Result<()> {
+		let best: opaque::Header = self.node.request("chain_getHeader", rpc_params![]).await?;
+		let best = (*best.number(), best.hash());
+
+		let mut extrinsics = Vec::with_capacity(phase.calls.len());
+		for (signer, call) in &phase.calls {
+			let transaction = offline::online_transaction(
+				call.clone(),
+				self.nonce(*signer).await?,
+				self.genesis,
+				best,
+				(self.version.spec_version, self.version.transaction_version),
+				self.metadata_hash,
+			);
+			let key = Signer::Pair(self.population.pairs[*signer].clone());
+			extrinsics.push((*signer, Bytes(transaction.sign(&key)?.encode())));
+		}
+
+		let node = &self.node;
+		let results: Vec<_> = stream::iter(extrinsics)
+			.map(|(signer, extrinsic)| async move {
+				let result: Result<Hash> =
+					node.request("author_submitExtrinsic", rpc_params![extrinsic]).await;
+				(signer, result)
+			})
+			.buffered(self.concurrency)
+			.collect()
+			.await;
+
+		let mut rejected = 0;
+		for (signer, result) in results {
+			match result {
+				Ok(_) => report.submitted += 1,
+				Err(e) => {
+					*report.rejected.entry(e.to_string()).or_default() += 1;
+					// The nonces following the rejected one are not in use
+					self.nonces[signer] = None;
+					rejected += 1;
+				},
+			}
+		}
+
+		let stalled = self.drain().await?;
+		report.stalled += stalled;
+		eprintln!(
+			"{}: {} submitted, {} rejected, {} stalled",
+			phase.name,
+			phase.calls.len() - rejected,
+			rejected,
+			stalled
+		);
+		Ok(())
+	}
+
+	/// Wait for the transaction pool to drain, sealing blocks with `--seal`, and return the number
+	/// of extrinsics left in the pool at the timeout.
+	async fn drain(&self) -> Result<usize> {
+		let deadline = Instant::now() + self.step_timeout;
+		loop {
+			let pending: Vec<Bytes> =
+				self.node.request("author_pendingExtrinsics", rpc_params![]).await?;
+			if pending.is_empty() || Instant::now() >= deadline {
+				return Ok(pending.len())
+			}
+			// Sealing fails when no extrinsic is ready, e.g. when all of them wait for a nonce
+			let sealed = self.seal &&
+				self.node
+					.request::<serde_json::Value>(
+						"engine_createBlock",
+						rpc_params![false, true, Option::<Hash>::None],
+					)
+					.await
+					.is_ok();
+			if !sealed {
+				futures_timer::Delay::new(POLL_INTERVAL).await;
+			}
+		}
+	}
+}
+
+impl SimulateCmd {
+	/// Run the step of the simulation.
+	pub async fn run(&self) -> Result<()> {
+		match &self.action {
+			SimulateAction::Spec { scenario, template, raw } => {
+				let scenario = Scenario::load(scenario)?;
+				let pilot = scenario.pilot(&scenario.population());
+				let (chain_type, authorities) = chain_spec::template_authorities(template)?;
+				let spec = chain_spec::fedecom_pilot_config(
+					chain_type,
+					"FEDECOM Simulation",
+					"fedecom_simulation",
+					authorities,
+					pilot,
+				)?;
+				println!("{}", spec.as_json(*raw)?);
+				Ok(())
+			},
+			SimulateAction::Run { scenario, url, seal, step_timeout, concurrency, json } => {
+				let scenario = Scenario::load(scenario)?;
+				let step_timeout = Duration::from_secs(*step_timeout);
+				let report = simulate(&scenario, url, *seal, step_timeout, *concurrency).await?;
+				if *json {
+					let json = serde_json::to_string_pretty(&report)
+						.map_err(|e| format!("Failed to serialize the report: {}", e))?;
+					println!("{}", json);
+				} else {
+					println!("{}", report);
+				}
+				Ok(())
+			},
+		}
+	}
+}
+
+/// Run `scenario` against the node at `url`.
+async fn simulate(
+	scenario: &Scenario,
+	url: &str,
+	seal: bool,
+	step_timeout: Duration,
+	concurrency: usize,
+) -> Result<Report> {
+	let population = scenario.population();
+	let phases = scenario.phases(&population);
+	let node = Node::connect(url).await?;
+	let genesis = node.request("chain_getBlockHash", rpc_params![0u32]).await?;
+	let version = node.request("state_getRuntimeVersion", rpc_params![]).await?;
+	let metadata: Bytes = node.request("state_getMetadata", rpc_params![]).await?;
+	let first = best_number(&node).await?;
+
+	let mut simulation = Simulation {
+		node,
+		population: &population,
+		genesis,
+		version,
+		metadata_hash: offline::metadata_hash(&metadata),
+		nonces: vec![None; population.pairs.len()],
+		seal,
+		step_timeout,
+		concurrency: concurrency.max(1),
+	};
+	let mut report = Report { seed: scenario.seed, ..Default::default() };
+	let started = Instant::now();
+	for phase in &phases {
+		simulation.run_phase(phase, &mut report).await?;
+	}
+	let duration = started.elapsed();
+
+	let node = simulation.node;
+	let last = best_number(&node).await?;
+	let mut blocks = Vec::new();
+	for number in first + 1..=last {
+		blocks.push(block_stats(&node, number).await?);
+	}
+	report.add_blocks(&blocks, duration);
+
+	// The state is read at the best block of a new connection
+	report.ledger = LedgerStats::read(&Node::connect(url).await?, scenario.market).await?;
+	Ok(report)
+}
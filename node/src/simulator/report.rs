@@ -0,0 +1,176 @@
+//! Report of a market simulation: submissions, blocks and end-of-run ledger.
+
+use crate::{
+	fedecom::query::{self, confirmation_status, OfferFilter, OfferState, State},
+	storage,
+};
+use codec::Decode;
+use sc_cli::Result;
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+/// Block produced during a simulation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockStats {
+	/// Signed extrinsics of the block
+	pub extrinsics: usize,
+	/// Extrinsics of the block whose dispatch failed
+	pub failed: usize,
+	/// Weight of the normal extrinsics, as a fraction of their maximum weight
+	pub fullness: f64,
+}
+
+/// Flexibility market ledger at the end of a simulation.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LedgerStats {
+	/// Offers of the simulated market, by state
+	pub offers: BTreeMap<&'static str, usize>,
+	/// Payments of the ledger
+	pub payments: usize,
+	/// Total value of the payments, in FCT
+	pub paid_fct: u64,
+	/// Payment confirmations of the ledger, by status
+	pub confirmations: BTreeMap<&'static str, usize>,
+}
+
+impl LedgerStats {
+	/// Statistics of the ledger in `state`, counting the offers of `market`.
+	pub async fn read(state: &impl State, market: u32) -> Result<Self> {
+		let mut stats = Self::default();
+
+		let filter = OfferFilter { market: Some(market), ..Default::default() };
+		for offer in query::offers(state, &filter).await? {
+			*stats.offers.entry(OfferState::name(offer.state)).or_default() += 1;
+		}
+		for (key, value) in state.pairs(storage::prefix(b"Payments")).await? {
+			let value = u32::decode(&mut &value[..])
+				.map_err(|e| format!("Undecodable payment 0x{}: {}", hex::encode(&key), e))?;
+			stats.payments += 1;
+			stats.paid_fct += u64::from(value);
+		}
+		for (key, status) in state.pairs(storage::prefix(b"Confirmations")).await? {
+			let status = u32::decode(&mut &status[..]).map_err(|e| {
+				format!("Undecodable confirmation 0x{}: {}", hex::encode(&key), e)
+			})?;
+			*stats.confirmations.entry(confirmation_status(status)).or_default() += 1;
+		}
+		Ok(stats)
+	}
+}
+
+/// Outcome of a simulation.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+	/// Seed of the scenario
+	pub seed: u64,
+	/// Extrinsics accepted by the transaction pool
+	pub submitted: usize,
+	/// Extrinsics rejected by the transaction pool, by reason
+	pub rejected: BTreeMap<String, usize>,
+	/// Extrinsics still in the transaction pool at the end of a step
+	pub stalled: usize,
+	/// Blocks produced during the simulation
+	pub blocks: usize,
+	/// Signed extrinsics included in the blocks
+	pub included: usize,
+	/// Included extrinsics whose dispatch failed
+	pub failed: usize,
+	/// Duration of the simulation, in seconds
+	pub duration_secs: f64,
+	/// Included extrinsics per second
+	pub throughput: f64,
+	/// Mean fullness of the blocks, in percent of the maximum normal weight
+	pub mean_fullness: f64,
+	/// Fullness of the fullest block, in percent of the maximum normal weight
+	pub max_fullness: f64,
+	/// Flexibility market ledger at the end of the simulation
+	pub ledger: LedgerStats,
+}
+
+impl Report {
+	/// Account for the `blocks` produced in `duration`.
+	pub fn add_blocks(&mut self, blocks: &[BlockStats], duration: Duration) {
+		self.blocks = blocks.len();
+		self.included = blocks.iter().map(|block| block.extrinsics).sum();
+		self.failed = blocks.iter().map(|block| block.failed).sum();
+		self.duration_secs = duration.as_secs_f64();
+		if self.duration_secs > 0.0 {
+			self.throughput = self.included as f64 / self.duration_secs;
+		}
+		if !blocks.is_empty() {
+			let fullness = blocks.iter().map(|block| block.fullness * 100.0);
+			self.mean_fullness = fullness.clone().sum::<f64>() / blocks.len() as f64;
+			self.max_fullness = fullness.fold(0.0, f64::max);
+		}
+	}
+}
+
+fn counts<K: fmt::Display>(counts: &BTreeMap<K, usize>) -> String {
+	if counts.is_empty() {
+		return "none".into()
+	}
+	let counts: Vec<_> = counts.iter().map(|(key, count)| format!("{} {}", count, key)).collect();
+	counts.join(", ")
+}
+
+impl fmt::Display for Report {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "Seed {}, {:.1} s", self.seed, self.duration_secs)?;
+		writeln!(
+			f,
+			"Submitted: {}, rejected by the pool: {}, stalled in the pool: {}",
+			self.submitted,
+			self.rejected.values().sum::<usize>(),
+			self.stalled
+		)?;
+		for (reason, count) in &self.rejected {
+			writeln!(f, "  {} x {}", count, reason)?;
+		}
+		writeln!(
+			f,
+			"Included: {} in {} blocks ({:.1} extrinsics/s), failed: {}",
+			self.included, self.blocks, self.throughput, self.failed
+		)?;
+		writeln!(
+			f,
+			"Block fullness: {:.1}% mean, {:.1}% max",
+			self.mean_fullness, self.max_fullness
+		)?;
+		writeln!(f, "Offers: {}", counts(&self.ledger.offers))?;
+		writeln!(f, "Payments: {}, {} FCT", self.ledger.payments, self.ledger.paid_fct)?;
+		write!(f, "Confirmations: {}", counts(&self.ledger.confirmations))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn blocks_are_aggregated() {
+		let mut report = Report { seed: 7, submitted: 30, ..Default::default() };
+		report.rejected.insert("Invalid Transaction".into(), 2);
+		report.ledger.offers.insert("confirmed", 8);
+		report.ledger.offers.insert("rejected", 2);
+
+		let blocks = [
+			BlockStats { extrinsics: 10, failed: 0, fullness: 0.1 },
+			BlockStats { extrinsics: 20, failed: 1, fullness: 0.3 },
+			BlockStats { extrinsics: 0, failed: 0, fullness: 0.0 },
+		];
+		report.add_blocks(&blocks, Duration::from_secs(10));
+
+		assert_eq!((report.blocks, report.included, report.failed), (3, 30, 1));
+		assert_eq!(report.throughput, 3.0);
+		assert!((report.mean_fullness - 40.0 / 3.0).abs() < 1e-9);
+		assert!((report.max_fullness - 30.0).abs() < 1e-9);
+
+		let text = report.to_string();
+		assert!(text.contains("Submitted: 30, rejected by the pool: 2, stalled in the pool: 0"));
+		assert!(text.contains("  2 x Invalid Transaction"));
+		assert!(text.contains("Offers: 8 confirmed, 2 rejected"));
+		assert!(text.ends_with("Confirmations: none"));
+	}
+}
@@ -0,0 +1,444 @@
+//! Scenario of a market simulation, read from a TOML file, and the operations it generates.
+//!
+//! Every random draw comes from a ChaCha generator seeded by the scenario, in a fixed order, so
+//! that a scenario always generates the same accounts and operations.
+
+use crate::onboarding::Pilot;
+use node_template_runtime::{
+	pallet_fedecom_ps_demo::{
+		Call as FedecomPSDemoCall, CONFIRMATION_NOK_OVERESTIMATION,
+		CONFIRMATION_NOK_UNDERESTIMATION, CONFIRMATION_OK, FLEXIBILITY_SELLING_STATE_CONFIRMED,
+		FLEXIBILITY_SELLING_STATE_REJECTED, PARTICIPANT_ROLE_AGGREGATOR, PARTICIPANT_ROLE_DSO,
+		PARTICIPANT_ROLE_MARKET_OPERATOR, PARTICIPANT_ROLE_PROSUMER,
+	},
+	AccountId, Balance, Runtime, RuntimeCall, EXISTENTIAL_DEPOSIT,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Deserialize;
+use sp_core::{crypto::DeriveJunction, sr25519, Pair};
+use std::{fs, path::Path};
+
+/// Root of the derivation paths of the simulated accounts.
+const ROOT_SURI: &str = "//FedecomSimulator";
+
+/// Default duration between two market timestamps, in seconds.
+const DEFAULT_INTERVAL: u32 = 900;
+
+/// Default initial balance of the simulated accounts, 1000 FCT.
+const DEFAULT_BALANCE: Balance = 1_000_000_000_000_000;
+
+/// Number of simulated market participants of every role.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Participants {
+	/// Prosumers, each owning one asset and selling its flexibility
+	pub prosumers: u32,
+	/// Aggregators, buying flexibility
+	#[serde(default)]
+	pub aggregators: u32,
+	/// DSOs, buying flexibility
+	#[serde(default)]
+	pub dsos: u32,
+}
+
+/// Distribution of the flexibility offers of the prosumers.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Offers {
+	/// Probability of a prosumer offering flexibility in a market interval
+	pub probability: f64,
+	/// Range of the sold power, in W, drawn uniformly
+	pub power_w: [u32; 2],
+	/// Range of the change, in FCT/W, drawn uniformly
+	pub change_fct_w: [u32; 2],
+	/// Probability of the buyer confirming an offer rather than rejecting it
+	pub acceptance: f64,
+}
+
+/// Relative frequencies of the confirmation statuses of the delivered flexibility.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Deliveries {
+	/// Delivery matching its payment
+	pub ok: f64,
+	/// Delivery below its payment
+	#[serde(default)]
+	pub overestimation: f64,
+	/// Delivery above its payment
+	#[serde(default)]
+	pub underestimation: f64,
+}
+
+/// Scenario of a market simulation.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+	/// Seed of the random draws
+	pub seed: u64,
+	/// Flexibility market of the offers, operated by a simulated market operator
+	pub market: u32,
+	/// Market timestamp of the first interval, in Unix seconds
+	pub start: u32,
+	/// Number of simulated market intervals
+	pub intervals: u32,
+	/// Duration between two market timestamps, in seconds
+	#[serde(default = "default_interval")]
+	pub interval: u32,
+	/// Initial balance of the simulated accounts, paying their fees, in the smallest unit
+	#[serde(default = "default_balance")]
+	pub balance: Balance,
+	pub participants: Participants,
+	pub offers: Offers,
+	pub deliveries: Deliveries,
+}
+
+fn default_interval() -> u32 {
+	DEFAULT_INTERVAL
+}
+
+fn default_balance() -> Balance {
+	DEFAULT_BALANCE
+}
+
+fn check_probability(probability: f64, what: &str) -> Result<(), String> {
+	if (0.0..=1.0).contains(&probability) {
+		Ok(())
+	} else {
+		Err(format!("`{}` must be a probability from 0 to 1", what))
+	}
+}
+
+fn check_range([min, max]: [u32; 2], what: &str) -> Result<(), String> {
+	if min <= max {
+		Ok(())
+	} else {
+		Err(format!("`{}` must be a range [min, max]", what))
+	}
+}
+
+/// Simulated accounts, identified by their index: the market operator, then the DSOs, the
+/// aggregators and the prosumers.
+pub struct Population {
+	/// Key pairs of the accounts
+	pub pairs: Vec<sr25519::Pair>,
+	/// Role of the accounts
+	pub roles: Vec<u32>,
+	/// Index of the first prosumer
+	first_prosumer: usize,
+}
+
+impl Population {
+	/// Account of the simulated account `index`.
+	pub fn account(&self, index: usize) -> AccountId {
+		self.pairs[index].public().into()
+	}
+
+	/// Indices of the accounts of `role`.
+	pub fn with_role(&self, role: u32) -> Vec<usize> {
+		(0..self.roles.len()).filter(|&index| self.roles[index] == role).collect()
+	}
+
+	/// Asset owned by the prosumer `index`, numbered from 1.
+	fn asset(&self, index: usize) -> u32 {
+		(index - self.first_prosumer) as u32 + 1
+	}
+}
+
+/// Operations of a step of the simulation, signed by the simulated accounts.
+pub struct Phase {
+	/// Description of the step
+	pub name: String,
+	/// Calls with the index of their signer, in submission order
+	pub calls: Vec<(usize, RuntimeCall)>,
+}
+
+/// Flexibility offer drawn for a prosumer.
+struct Draw {
+	seller: usize,
+	buyer: usize,
+	power: u32,
+	change: u32,
+	confirmed: bool,
+	status: u32,
+}
+
+impl Scenario {
+	/// Read the scenario from the TOML file at `path`.
+	pub fn load(path: &Path) -> Result<Self, String> {
+		let content = fs::read_to_string(path)
+			.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+		Self::parse(&content).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+	}
+
+	/// Parse and validate a TOML scenario.
+	pub fn parse(content: &str) -> Result<Self, String> {
+		let scenario: Self = toml::from_str(content).map_err(|e| e.to_string())?;
+
+		if scenario.participants.prosumers == 0 {
+			return Err("`participants.prosumers` must be at least 1".into())
+		}
+		if scenario.participants.aggregators + scenario.participants.dsos == 0 {
+			return Err("at least one aggregator or DSO must buy the flexibility".into())
+		}
+		if scenario.interval == 0 {
+			return Err("`interval` must be at least 1 second".into())
+		}
+		let last = u64::from(scenario.start) +
+			u64::from(scenario.intervals.saturating_sub(1)) * u64::from(scenario.interval);
+		if last > u64::from(u32::MAX) {
+			return Err("the last market timestamp is out of range".into())
+		}
+		if scenario.balance < EXISTENTIAL_DEPOSIT {
+			return Err(format!("`balance` must be at least {}", EXISTENTIAL_DEPOSIT))
+		}
+
+		let offers = &scenario.offers;
+		check_probability(offers.probability, "offers.probability")?;
+		check_probability(offers.acceptance, "offers.acceptance")?;
+		check_range(offers.power_w, "offers.power_w")?;
+		check_range(offers.change_fct_w, "offers.change_fct_w")?;
+		if u64::from(offers.power_w[1]) * u64::from(offers.change_fct_w[1]) > u64::from(u32::MAX) {
+			return Err("the payment of the largest offer is out of range".into())
+		}
+
+		let deliveries = &scenario.deliveries;
+		let weights = [deliveries.ok, deliveries.overestimation, deliveries.underestimation];
+		if weights.iter().any(|weight| *weight < 0.0) || weights.iter().sum::<f64>() <= 0.0 {
+			return Err("`deliveries` must be non-negative frequencies, not all zero".into())
+		}
+
+		Ok(scenario)
+	}
+
+	/// Accounts of the simulation, derived from a fixed root.
+	pub fn population(&self) -> Population {
+		let root =
+			sr25519::Pair::from_string(ROOT_SURI, None).expect("static values are valid; qed");
+		let participants = &self.participants;
+		let mut pairs = Vec::new();
+		let mut roles = Vec::new();
+
+		for (name, role, count) in [
+			("operator", PARTICIPANT_ROLE_MARKET_OPERATOR, 1),
+			("dso", PARTICIPANT_ROLE_DSO, participants.dsos),
+			("aggregator", PARTICIPANT_ROLE_AGGREGATOR, participants.aggregators),
+			("prosumer", PARTICIPANT_ROLE_PROSUMER, participants.prosumers),
+		] {
+			for index in 0..count {
+				let path = [DeriveJunction::hard(name), DeriveJunction::hard(index)];
+				let (pair, _) =
+					root.derive(path.into_iter(), None).expect("hard derivation succeeds; qed");
+				pairs.push(pair);
+				roles.push(role);
+			}
+		}
+		let first_prosumer = pairs.len() - participants.prosumers as usize;
+		Population { pairs, roles, first_prosumer }
+	}
+
+	/// Participants, market, assets and balances of the chain spec of the simulation.
+	pub fn pilot(&self, population: &Population) -> Pilot {
+		let accounts = (0..population.pairs.len()).map(|index| population.account(index));
+		let prosumers = population.with_role(PARTICIPANT_ROLE_PROSUMER);
+
+		Pilot {
+			participants: accounts.clone().zip(population.roles.iter().copied()).collect(),
+			markets: vec![(self.market, population.account(0))],
+			assets: prosumers
+				.into_iter()
+				.map(|index| (population.asset(index), population.account(index)))
+				.collect(),
+			balances: accounts.map(|account| (account, self.balance)).collect(),
+		}
+	}
+
+	fn draws(&self, rng: &mut ChaCha8Rng, population: &Population) -> Vec<Draw> {
+		let buyers = [
+			population.with_role(PARTICIPANT_ROLE_DSO),
+			population.with_role(PARTICIPANT_ROLE_AGGREGATOR),
+		]
+		.concat();
+		let deliveries = &self.deliveries;
+		let total = deliveries.ok + deliveries.overestimation + deliveries.underestimation;
+		let [min_power, max_power] = self.offers.power_w;
+		let [min_change, max_change] = self.offers.change_fct_w;
+
+		let mut draws = Vec::new();
+		for seller in population.with_role(PARTICIPANT_ROLE_PROSUMER) {
+			if !rng.gen_bool(self.offers.probability) {
+				continue
+			}
+			let buyer = buyers[rng.gen_range(0..buyers.len())];
+			let power = rng.gen_range(min_power..=max_power);
+			let change = rng.gen_range(min_change..=max_change);
+			let confirmed = rng.gen_bool(self.offers.acceptance);
+			let delivery = rng.gen::<f64>() * total;
+			let status = if delivery < deliveries.ok {
+				CONFIRMATION_OK
+			} else if delivery < deliveries.ok + deliveries.overestimation {
+				CONFIRMATION_NOK_OVERESTIMATION
+			} else {
+				CONFIRMATION_NOK_UNDERESTIMATION
+			};
+			draws.push(Draw { seller, buyer, power, change, confirmed, status });
+		}
+		draws
+	}
+
+	/// Operations of the simulation: for every market interval, the offers of the prosumers, the
+	/// decisions of their buyers and the delivery confirmations of the confirmed offers.
+	pub fn phases(&self, population: &Population) -> Vec<Phase> {
+		let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+		let mut phases = Vec::new();
+
+		for interval in 0..self.intervals {
+			let timestamp = self.start + interval * self.interval;
+			let draws = self.draws(&mut rng, population);
+			let call = |call: FedecomPSDemoCall<Runtime>| RuntimeCall::from(call);
+
+			let offers = draws
+				.iter()
+				.map(|draw| {
+					let offer = FedecomPSDemoCall::flexibility_selling {
+						buyer: population.account(draw.buyer),
+						flexibility_market_identifier: self.market,
+						flexibility_market_timestamp: timestamp,
+						asset_identifier: population.asset(draw.seller),
+						sold_power: draw.power,
+						change_fct_w: draw.change,
+					};
+					(draw.seller, call(offer))
+				})
+				.collect();
+			let decisions = draws
+				.iter()
+				.map(|draw| {
+					let decision = FedecomPSDemoCall::flexibility_purchase_decision {
+						seller: population.account(draw.seller),
+						flexibility_market_identifier: self.market,
+						flexibility_market_timestamp: timestamp,
+						asset_identifier: population.asset(draw.seller),
+						new_state: if draw.confirmed {
+							FLEXIBILITY_SELLING_STATE_CONFIRMED
+						} else {
+							FLEXIBILITY_SELLING_STATE_REJECTED
+						},
+					};
+					(draw.buyer, call(decision))
+				})
+				.collect();
+			// The seller confirms, as receiver, the payment of the buyer
+			let deliveries = draws
+				.iter()
+				.filter(|draw| draw.confirmed)
+				.map(|draw| {
+					let confirmation = FedecomPSDemoCall::add_confirmation {
+						key_sender: population.account(draw.buyer),
+						ts: timestamp,
+						status: draw.status,
+					};
+					(draw.seller, call(confirmation))
+				})
+				.collect();
+
+			for (name, calls) in
+				[("offers", offers), ("decisions", decisions), ("deliveries", deliveries)]
+			{
+				phases.push(Phase { name: format!("{} at {}", name, timestamp), calls });
+			}
+		}
+		phases
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Encode;
+
+	const SCENARIO: &str = r#"
+		seed = 7
+		market = 1
+		start = 1699920000
+		intervals = 2
+
+		[participants]
+		prosumers = 20
+		aggregators = 2
+		dsos = 1
+
+		[offers]
+		probability = 0.5
+		power_w = [500, 5000]
+		change_fct_w = [1, 3]
+		acceptance = 0.8
+
+		[deliveries]
+		ok = 0.9
+		overestimation = 0.05
+		underestimation = 0.05
+	"#;
+
+	fn encoded(phases: &[Phase]) -> Vec<Vec<(usize, Vec<u8>)>> {
+		phases
+			.iter()
+			.map(|phase| {
+				phase.calls.iter().map(|(signer, call)| (*signer, call.encode())).collect()
+			})
+			.collect()
+	}
+
+	#[test]
+	fn population_and_pilot_follow_the_scenario() {
+		let scenario = Scenario::parse(SCENARIO).unwrap();
+		let population = scenario.population();
+		let pilot = scenario.pilot(&population);
+
+		assert_eq!(population.pairs.len(), 24);
+		assert_eq!(population.roles[0], PARTICIPANT_ROLE_MARKET_OPERATOR);
+		assert_eq!(population.with_role(PARTICIPANT_ROLE_PROSUMER), (4..24).collect::<Vec<_>>());
+		assert_eq!(pilot.markets, [(1, population.account(0))]);
+		assert_eq!(pilot.assets.first(), Some(&(1, population.account(4))));
+		assert_eq!(pilot.assets.len(), 20);
+		assert_eq!(pilot.balances.len(), 24);
+		// The accounts are the same for every run
+		assert_eq!(scenario.population().account(23), population.account(23));
+	}
+
+	#[test]
+	fn phases_are_reproducible() {
+		let scenario = Scenario::parse(SCENARIO).unwrap();
+		let population = scenario.population();
+		let phases = scenario.phases(&population);
+
+		assert_eq!(phases.len(), 6);
+		assert_eq!(phases[0].name, "offers at 1699920000");
+		assert_eq!(phases[5].name, "deliveries at 1699920900");
+		assert_eq!(phases[0].calls.len(), phases[1].calls.len());
+		assert!(phases[2].calls.len() <= phases[1].calls.len());
+		assert!(!phases[0].calls.is_empty() && phases[0].calls.len() < 20);
+		assert_eq!(encoded(&phases), encoded(&scenario.phases(&population)));
+
+		let reseeded = Scenario::parse(&SCENARIO.replace("seed = 7", "seed = 8")).unwrap();
+		assert_ne!(encoded(&phases), encoded(&reseeded.phases(&population)));
+	}
+
+	#[test]
+	fn invalid_scenarios_are_rejected() {
+		let probability = SCENARIO.replace("probability = 0.5", "probability = 1.5");
+		assert_eq!(
+			Scenario::parse(&probability).unwrap_err(),
+			"`offers.probability` must be a probability from 0 to 1"
+		);
+
+		let range = SCENARIO.replace("[500, 5000]", "[5000, 500]");
+		assert!(Scenario::parse(&range).is_err());
+
+		let buyers = SCENARIO.replace("aggregators = 2", "aggregators = 0").replace("dsos = 1", "");
+		assert!(Scenario::parse(&buyers).is_err());
+
+		let overflow = SCENARIO.replace("[1, 3]", "[1, 1000000]");
+		assert!(Scenario::parse(&overflow).is_err());
+	}
+}
@@ -0,0 +1,227 @@
+//! Storage keys of the fedecom pallet, to read its state without the runtime.
+
+use codec::{Decode, Encode};
+use node_template_runtime::AccountId;
+use sp_core::hashing::{blake2_128, twox_128, twox_64};
+
+/// Name of the fedecom pallet in `construct_runtime!`, which prefixes its storage keys.
+///
+/// This is the default instance of the pallet: the node tooling does not read the storage of the
+/// second instance, `FedecomPSDemo2`.
+const PALLET: &[u8] = b"FedecomPSDemo";
+
+/// Storage item of the offers of the flexibility market ledger.
+pub const OFFERS: &[u8] = b"FlexibilityOffers";
+
+/// Storage item of the confirmed offers kept from the closed market periods, keyed like the offers.
+pub const SETTLED_OFFERS: &[u8] = b"SettledOffers";
+
+/// Storage item of the number of offers of the market periods.
+pub const PERIOD_OFFERS: &[u8] = b"PeriodOffers";
+
+/// Storage item of the number of offers of the sellers in the market periods.
+pub const SELLER_OFFERS: &[u8] = b"SellerOffers";
+
+/// Storage item of the closed market periods, keyed like the number of offers of the periods.
+pub const CLOSED_PERIODS: &[u8] = b"ClosedPeriods";
+
+/// Key of an offer in the flexibility market ledger: seller, buyer, market, timestamp and asset.
+///
+/// The offers are stored by market, timestamp, seller, buyer and asset.
+pub type OfferKey = (AccountId, AccountId, u32, u32, u32);
+
+/// Key of a payment or of its confirmation: sender, receiver and timestamp.
+pub type ConfirmationKey = (AccountId, AccountId, u32);
+
+/// Storage key prefix of the `storage` item of the fedecom pallet.
+pub fn prefix(storage: &[u8]) -> Vec<u8> {
+	[twox_128(PALLET), twox_128(storage)].concat()
+}
+
+fn blake2_128_concat(data: impl Encode) -> Vec<u8> {
+	let data = data.encode();
+	[&blake2_128(&data)[..], &data].concat()
+}
+
+fn twox_64_concat(data: impl Encode) -> Vec<u8> {
+	let data = data.encode();
+	[&twox_64(&data)[..], &data].concat()
+}
+
+/// Decode the next `Blake2_128Concat` or `Twox64Concat` part of a storage map key, whose hash is
+/// `hash_len` bytes long.
+fn key_part<T: Decode>(key: &mut &[u8], hash_len: usize) -> Option<T> {
+	*key = key.get(hash_len..)?;
+	T::decode(key).ok()
+}
+
+/// Prefix of the keys of the `storage` item of `market`, in the period starting at `timestamp` if
+/// given.
+fn market_prefix(storage: &[u8], market: u32, timestamp: Option<u32>) -> Vec<u8> {
+	let mut key = [prefix(storage), twox_64_concat(market)].concat();
+	if let Some(timestamp) = timestamp {
+		key.extend(twox_64_concat(timestamp));
+	}
+	key
+}
+
+/// Prefix of the keys of the offers of `market`, in the period starting at `timestamp` if given.
+pub fn offers_prefix(market: u32, timestamp: Option<u32>) -> Vec<u8> {
+	market_prefix(OFFERS, market, timestamp)
+}
+
+fn offer_storage_key(storage: &[u8], key: &OfferKey) -> Vec<u8> {
+	let (seller, buyer, market, timestamp, asset) = key;
+	[
+		market_prefix(storage, *market, Some(*timestamp)),
+		blake2_128_concat(seller),
+		blake2_128_concat(buyer),
+		twox_64_concat(asset),
+	]
+	.concat()
+}
+
+/// Storage key of an offer.
+pub fn offer(key: &OfferKey) -> Vec<u8> {
+	offer_storage_key(OFFERS, key)
+}
+
+/// Storage key of a confirmed offer kept from a closed period.
+pub fn settled_offer(key: &OfferKey) -> Vec<u8> {
+	offer_storage_key(SETTLED_OFFERS, key)
+}
+
+/// Decode the key of an offer, without the storage prefix.
+pub fn offer_key(key: &mut &[u8]) -> Option<OfferKey> {
+	let market = key_part(key, 8)?;
+	let timestamp = key_part(key, 8)?;
+	Some((key_part(key, 16)?, key_part(key, 16)?, market, timestamp, key_part(key, 8)?))
+}
+
+/// Storage key of the number of offers of the period of `market` starting at `timestamp`.
+pub fn period_offers(market: u32, timestamp: u32) -> Vec<u8> {
+	[prefix(PERIOD_OFFERS), twox_64_concat(market), twox_64_concat(timestamp)].concat()
+}
+
+/// Decode the market and timestamp of the key of a period, without the storage prefix.
+pub fn period_key(key: &mut &[u8]) -> Option<(u32, u32)> {
+	Some((key_part(key, 8)?, key_part(key, 8)?))
+}
+
+/// Storage key of the closed period of `market` starting at `timestamp`.
+pub fn closed_period(market: u32, timestamp: u32) -> Vec<u8> {
+	[prefix(CLOSED_PERIODS), twox_64_concat(market), twox_64_concat(timestamp)].concat()
+}
+
+/// Storage key of the number of offers of `seller` in the period of `market` starting at
+/// `timestamp`.
+pub fn seller_offers(market: u32, timestamp: u32, seller: &AccountId) -> Vec<u8> {
+	[market_prefix(SELLER_OFFERS, market, Some(timestamp)), blake2_128_concat(seller)].concat()
+}
+
+/// Decode the market, timestamp and seller of the key of a seller's offers in a period, without
+/// the storage prefix.
+pub fn seller_offers_key(key: &mut &[u8]) -> Option<(u32, u32, AccountId)> {
+	Some((key_part(key, 8)?, key_part(key, 8)?, key_part(key, 16)?))
+}
+
+fn payment_storage_key(storage: &[u8], key: &ConfirmationKey) -> Vec<u8> {
+	let (sender, receiver, ts) = key;
+	[prefix(storage), blake2_128_concat(sender), blake2_128_concat(receiver), twox_64_concat(ts)]
+		.concat()
+}
+
+/// Storage key of a payment.
+pub fn payment(key: &ConfirmationKey) -> Vec<u8> {
+	payment_storage_key(b"Payments", key)
+}
+
+/// Storage key of a payment confirmation.
+pub fn confirmation(key: &ConfirmationKey) -> Vec<u8> {
+	payment_storage_key(b"Confirmations", key)
+}
+
+/// Decode the key of a payment confirmation, without the storage prefix.
+pub fn confirmation_key(key: &mut &[u8]) -> Option<ConfirmationKey> {
+	Some((key_part(key, 16)?, key_part(key, 16)?, key_part(key, 8)?))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use node_template_runtime::{
+		pallet_fedecom_ps_demo::{
+			ClosedPeriods, Confirmations, FlexibilityOffers, Payments, PeriodOffers, SellerOffers,
+			SettledOffers,
+		},
+		Runtime,
+	};
+	use sp_keyring::AccountKeyring::{Alice, Bob};
+
+	/// `key` without the storage prefix of `storage`.
+	fn suffix<'a>(storage: &[u8], key: &'a [u8]) -> &'a [u8] {
+		key.strip_prefix(&prefix(storage)[..]).expect("key of the storage item")
+	}
+
+	#[test]
+	fn offer_keys_round_trip() {
+		let key = (Alice.to_account_id(), Bob.to_account_id(), 1, 900, 7);
+		let pallet_key = (1u32, 900u32, key.0.clone(), key.1.clone(), 7u32);
+
+		let offer_key_of = FlexibilityOffers::<Runtime>::hashed_key_for(pallet_key.clone());
+		assert_eq!(offer(&key), offer_key_of);
+		assert_eq!(offer_key(&mut suffix(OFFERS, &offer_key_of)), Some(key.clone()));
+		assert!(offer_key_of.starts_with(&offers_prefix(1, Some(900))));
+		assert!(offer_key_of.starts_with(&offers_prefix(1, None)));
+
+		let settled_key_of = SettledOffers::<Runtime>::hashed_key_for(pallet_key);
+		assert_eq!(settled_offer(&key), settled_key_of);
+		assert_eq!(offer_key(&mut suffix(SETTLED_OFFERS, &settled_key_of)), Some(key));
+	}
+
+	#[test]
+	fn period_keys_round_trip() {
+		let period_key_of = PeriodOffers::<Runtime>::hashed_key_for(1u32, 900u32);
+		assert_eq!(period_offers(1, 900), period_key_of);
+		assert_eq!(period_key(&mut suffix(PERIOD_OFFERS, &period_key_of)), Some((1, 900)));
+
+		let closed_key_of = ClosedPeriods::<Runtime>::hashed_key_for(1u32, 900u32);
+		assert_eq!(closed_period(1, 900), closed_key_of);
+		assert_eq!(period_key(&mut suffix(CLOSED_PERIODS, &closed_key_of)), Some((1, 900)));
+
+		let seller = Alice.to_account_id();
+		let seller_key_of = SellerOffers::<Runtime>::hashed_key_for((1u32, 900u32, seller.clone()));
+		assert_eq!(seller_offers(1, 900, &seller), seller_key_of);
+		assert_eq!(
+			seller_offers_key(&mut suffix(SELLER_OFFERS, &seller_key_of)),
+			Some((1, 900, seller))
+		);
+	}
+
+	#[test]
+	fn payment_and_confirmation_keys_round_trip() {
+		let key = (Bob.to_account_id(), Alice.to_account_id(), 900);
+		let pallet_key = (key.0.clone(), key.1.clone(), 900u32);
+
+		let payment_key_of = Payments::<Runtime>::hashed_key_for(pallet_key.clone());
+		assert_eq!(payment(&key), payment_key_of);
+		assert_eq!(confirmation_key(&mut suffix(b"Payments", &payment_key_of)), Some(key.clone()));
+
+		let confirmation_key_of = Confirmations::<Runtime>::hashed_key_for(pallet_key);
+		assert_eq!(confirmation(&key), confirmation_key_of);
+		assert_eq!(
+			confirmation_key(&mut suffix(b"Confirmations", &confirmation_key_of)),
+			Some(key)
+		);
+	}
+
+	#[test]
+	fn truncated_keys_are_not_decoded() {
+		let key = (Alice.to_account_id(), Bob.to_account_id(), 1, 900, 7);
+		let offer_key_of = offer(&key);
+		let suffix = suffix(OFFERS, &offer_key_of);
+
+		assert_eq!(offer_key(&mut &suffix[..suffix.len() - 1]), None);
+		assert_eq!(period_key(&mut &suffix[..8]), None);
+	}
+}
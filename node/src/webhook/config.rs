@@ -0,0 +1,129 @@
+//! Configuration of the webhook notifier, read from a TOML file.
+
+use crate::rpc::MarketEvent;
+use node_template_runtime::AccountId;
+use serde::Deserialize;
+use std::{collections::HashSet, fs, path::Path, time::Duration};
+
+/// Types of the market events that can be delivered.
+const EVENT_KINDS: &[&str] = &["offer", "decision", "payment", "confirmation"];
+
+/// Webhook notifier configuration.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+	/// Retry policy of the deliveries.
+	#[serde(default)]
+	pub retry: RetryConfig,
+	/// Endpoints the events are delivered to.
+	#[serde(rename = "endpoint", default)]
+	pub endpoints: Vec<EndpointConfig>,
+}
+
+/// Retry policy of the deliveries.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+	/// Number of attempts before a delivery goes to the dead-letter log.
+	pub max_attempts: u32,
+	/// Delay before the first retry, doubled after every failed attempt, in milliseconds.
+	pub initial_backoff_ms: u64,
+	/// Maximum delay between two attempts, in milliseconds.
+	pub max_backoff_ms: u64,
+	/// Timeout of an attempt, in milliseconds.
+	pub timeout_ms: u64,
+}
+
+impl Default for RetryConfig {
+	fn default() -> Self {
+		Self {
+			max_attempts: 8,
+			initial_backoff_ms: 1_000,
+			max_backoff_ms: 300_000,
+			timeout_ms: 10_000,
+		}
+	}
+}
+
+impl RetryConfig {
+	/// Delay before the attempt following the failed attempt number `attempt`, starting at 1.
+	pub fn backoff(&self, attempt: u32) -> Duration {
+		let factor = 1u64.checked_shl(attempt.saturating_sub(1)).unwrap_or(u64::MAX);
+		let millis = self.initial_backoff_ms.saturating_mul(factor).min(self.max_backoff_ms);
+		Duration::from_millis(millis)
+	}
+}
+
+/// HTTP endpoint the events are delivered to.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointConfig {
+	/// Name of the endpoint, used for its delivery cursor and dead-letter log.
+	pub name: String,
+	/// URL the events are POSTed to.
+	pub url: String,
+	/// Secret key of the HMAC-SHA256 signature of the payloads.
+	pub secret: String,
+	/// Types of the delivered events (`offer`, `decision`, `payment`, `confirmation`), all of
+	/// them when empty.
+	#[serde(default)]
+	pub events: Vec<String>,
+	/// Flexibility markets of the delivered offers and decisions, all of them when empty.
+	#[serde(default)]
+	pub markets: Vec<u32>,
+	/// Accounts involved in the delivered events, all of them when empty.
+	#[serde(default)]
+	pub accounts: Vec<AccountId>,
+}
+
+impl EndpointConfig {
+	/// Whether `event` passes the filters of the endpoint.
+	pub fn matches(&self, event: &MarketEvent) -> bool {
+		(self.events.is_empty() || self.events.iter().any(|kind| kind == event.kind())) &&
+			(self.markets.is_empty() ||
+				event.market().map_or(false, |market| self.markets.contains(&market))) &&
+			(self.accounts.is_empty() ||
+				event.accounts().iter().any(|account| self.accounts.contains(account)))
+	}
+}
+
+impl Config {
+	/// Read the configuration from the TOML file at `path`.
+	pub fn load(path: &Path) -> Result<Self, String> {
+		let content = fs::read_to_string(path)
+			.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+		Self::parse(&content).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+	}
+
+	/// Parse and validate a TOML configuration.
+	pub fn parse(content: &str) -> Result<Self, String> {
+		let config: Self = toml::from_str(content).map_err(|e| e.to_string())?;
+
+		let mut names = HashSet::new();
+		for endpoint in &config.endpoints {
+			let valid_name = !endpoint.name.is_empty() &&
+				endpoint
+					.name
+					.chars()
+					.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+			if !valid_name {
+				return Err(format!(
+					"endpoint name `{}` must only contain letters, digits, `-` and `_`",
+					endpoint.name
+				))
+			}
+			if !names.insert(&endpoint.name) {
+				return Err(format!("duplicate endpoint `{}`", endpoint.name))
+			}
+			let unknown = endpoint.events.iter().find(|kind| !EVENT_KINDS.contains(&kind.as_str()));
+			if let Some(kind) = unknown {
+				return Err(format!("unknown event type `{}` of endpoint `{}`", kind, endpoint.name))
+			}
+		}
+		if config.retry.max_attempts == 0 {
+			return Err("`retry.max_attempts` must be at least 1".into())
+		}
+
+		Ok(config)
+	}
+}
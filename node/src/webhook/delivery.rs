@@ -0,0 +1,103 @@
+//! Signed HTTP deliveries with retries.
+
+use super::config::{EndpointConfig, RetryConfig};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::Duration;
+
+/// Header holding the identifier of the delivery, identical across its attempts.
+pub const DELIVERY_HEADER: &str = "X-Fedecom-Delivery";
+
+/// Header holding the `sha256=<hex>` HMAC signature of the body.
+pub const SIGNATURE_HEADER: &str = "X-Fedecom-Signature";
+
+/// HMAC-SHA256 signature of `body` with `secret`, as sent in [`SIGNATURE_HEADER`].
+pub fn signature(secret: &str, body: &[u8]) -> String {
+	let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+		.expect("HMAC accepts keys of any length; qed");
+	mac.update(body);
+	format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Failed delivery attempt.
+#[derive(Debug)]
+struct AttemptError {
+	/// Whether another attempt may succeed
+	retryable: bool,
+	message: String,
+}
+
+/// HTTP endpoint the events are delivered to.
+pub struct Endpoint {
+	/// Configuration of the endpoint
+	pub config: EndpointConfig,
+	retry: RetryConfig,
+	http: reqwest::Client,
+}
+
+impl Endpoint {
+	/// Create the endpoint of `config`, with the retry policy `retry`.
+	pub fn new(config: EndpointConfig, retry: RetryConfig) -> Result<Self, String> {
+		let http = reqwest::Client::builder()
+			.timeout(Duration::from_millis(retry.timeout_ms))
+			.build()
+			.map_err(|e| format!("Failed to create the HTTP client of `{}`: {}", config.name, e))?;
+		Ok(Self { config, retry, http })
+	}
+
+	/// Retry policy of the deliveries to the endpoint.
+	pub fn retry(&self) -> &RetryConfig {
+		&self.retry
+	}
+
+	async fn attempt(&self, id: &str, body: &[u8], signature: &str) -> Result<(), AttemptError> {
+		let response = self
+			.http
+			.post(&self.config.url)
+			.header(reqwest::header::CONTENT_TYPE, "application/json")
+			.header(DELIVERY_HEADER, id)
+			.header(SIGNATURE_HEADER, signature)
+			.body(body.to_vec())
+			.send()
+			.await
+			.map_err(|e| AttemptError { retryable: true, message: e.to_string() })?;
+
+		let status = response.status();
+		if status.is_success() {
+			Ok(())
+		} else {
+			// Other client errors would fail the same way again
+			let retryable = status.is_server_error() ||
+				status == reqwest::StatusCode::REQUEST_TIMEOUT ||
+				status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+			Err(AttemptError { retryable, message: format!("HTTP status {}", status) })
+		}
+	}
+
+	/// POST `body` to the endpoint, retrying with an exponential backoff. Returns the error of the
+	/// last attempt when the delivery is abandoned.
+	pub async fn deliver(&self, id: &str, body: &[u8]) -> Result<(), String> {
+		let signature = signature(&self.config.secret, body);
+
+		let mut attempt = 1;
+		loop {
+			match self.attempt(id, body, &signature).await {
+				Ok(()) => return Ok(()),
+				Err(e) if e.retryable && attempt < self.retry.max_attempts => {
+					let backoff = self.retry.backoff(attempt);
+					log::debug!(
+						target: super::LOG_TARGET,
+						"Delivery {} to `{}` failed ({}), retrying in {:?}",
+						id,
+						self.config.name,
+						e.message,
+						backoff
+					);
+					futures_timer::Delay::new(backoff).await;
+					attempt += 1;
+				},
+				Err(e) => return Err(format!("{} after {} attempt(s)", e.message, attempt)),
+			}
+		}
+	}
+}
@@ -0,0 +1,217 @@
+//! Webhook notifier of the finalized market events.
+//!
+//! Enabled with `--fedecom-webhooks <config.toml>`, the notifier POSTs the market events of the
+//! finalized blocks (the notifications of `fedecom_subscribeEvents`) to HTTP endpoints:
+//!
+//! ```toml
+//! [retry]
+//! max_attempts = 8
+//! initial_backoff_ms = 1000
+//! max_backoff_ms = 300000
+//! timeout_ms = 10000
+//!
+//! [[endpoint]]
+//! name = "billing"
+//! url = "https://billing.example.com/fedecom"
+//! secret = "shared secret"
+//! events = ["decision", "payment"]
+//! markets = [1]
+//! accounts = ["5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"]
+//! ```
+//!
+//! Every payload carries a `deliveryId`, also sent in the `X-Fedecom-Delivery` header, and is
+//! signed with the HMAC-SHA256 of the body keyed with the endpoint secret, sent in the
+//! `X-Fedecom-Signature` header as `sha256=<hex>`. Failed attempts are retried with an exponential
+//! backoff, and abandoned deliveries are appended to a dead-letter log. Blocks whose events cannot
+//! be read, e.g. because their state is pruned, are retried the same way before being dead-lettered
+//! as a whole, so that no block is skipped silently.
+//!
+//! Every endpoint is served by its own task, in block order. Its delivery cursor is stored in the
+//! `fedecom-webhooks` directory of the chain data, so that the notifier resumes after the last
+//! delivered block when the node restarts. A new endpoint starts with the blocks finalized after
+//! the node is started.
+
+pub mod config;
+pub mod delivery;
+pub mod store;
+#[cfg(test)]
+mod tests;
+
+pub use config::Config;
+pub use delivery::{signature, Endpoint, DELIVERY_HEADER, SIGNATURE_HEADER};
+pub use store::Store;
+
+use crate::rpc::{event_blocks, EventNotification, SubscriptionOptions};
+use futures::StreamExt;
+use node_template_runtime::opaque::Block;
+use sc_client_api::{Backend, BlockchainEvents, StorageProvider};
+use serde::Serialize;
+use sp_blockchain::HeaderBackend;
+use std::sync::Arc;
+
+const LOG_TARGET: &str = "fedecom-webhook";
+
+/// Body POSTed to the endpoints.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Payload<'a> {
+	/// Unique identifier of the delivery: `<block hash>-<event index>`.
+	pub delivery_id: String,
+	/// The delivered notification.
+	#[serde(flatten)]
+	pub notification: &'a EventNotification,
+}
+
+impl<'a> Payload<'a> {
+	fn new(notification: &'a EventNotification) -> Self {
+		Self {
+			delivery_id: format!("{:?}-{}", notification.block_hash, notification.event_index),
+			notification,
+		}
+	}
+}
+
+/// Deliver the `notifications` of the block `number` matching the filters of `endpoint`, then
+/// move its cursor past the block.
+async fn deliver_block(
+	endpoint: &Endpoint,
+	store: &Store,
+	number: u32,
+	notifications: &[EventNotification],
+) {
+	let name = &endpoint.config.name;
+
+	for notification in notifications.iter().filter(|n| endpoint.config.matches(&n.event)) {
+		let payload = Payload::new(notification);
+		let body = serde_json::to_string(&payload).expect("notifications are serializable; qed");
+
+		if let Err(error) = endpoint.deliver(&payload.delivery_id, body.as_bytes()).await {
+			log::warn!(
+				target: LOG_TARGET,
+				"Delivery {} to `{}` abandoned: {}",
+				payload.delivery_id,
+				name,
+				error
+			);
+			if let Err(e) = store.dead_letter(name, &body, &error) {
+				let id = &payload.delivery_id;
+				log::error!(target: LOG_TARGET, "Failed to dead-letter {}: {}", id, e);
+			}
+		}
+	}
+
+	if let Err(e) = store.set_cursor(name, number) {
+		log::error!(target: LOG_TARGET, "Failed to store the cursor of `{}`: {}", name, e);
+	}
+}
+
+/// Handle the error reading the block `number` at the read `attempt`, starting at 1: wait before
+/// the next attempt, or dead-letter the block and move the cursor past it after the last attempt.
+/// Returns whether the block was dead-lettered.
+async fn block_unreadable(
+	endpoint: &Endpoint,
+	store: &Store,
+	number: u32,
+	attempt: u32,
+	error: &str,
+) -> bool {
+	let name = &endpoint.config.name;
+	let retry = endpoint.retry();
+
+	if attempt < retry.max_attempts {
+		let backoff = retry.backoff(attempt);
+		log::warn!(
+			target: LOG_TARGET,
+			"Failed to read block #{} for `{}` ({}), retrying in {:?}",
+			number,
+			name,
+			error,
+			backoff
+		);
+		futures_timer::Delay::new(backoff).await;
+		return false
+	}
+
+	log::error!(target: LOG_TARGET, "Block #{} for `{}` abandoned: {}", number, name, error);
+	let error = format!("{} after {} attempt(s)", error, attempt);
+	let payload = serde_json::json!({ "blockNumber": number }).to_string();
+	if let Err(e) = store.dead_letter(name, &payload, &error) {
+		log::error!(target: LOG_TARGET, "Failed to dead-letter block #{}: {}", number, e);
+	}
+	if let Err(e) = store.set_cursor(name, number) {
+		log::error!(target: LOG_TARGET, "Failed to store the cursor of `{}`: {}", name, e);
+	}
+	true
+}
+
+/// Deliver the events of the finalized blocks of `client` to `endpoint`.
+async fn run_endpoint<C, BE>(client: Arc<C>, endpoint: Endpoint, store: Arc<Store>)
+where
+	BE: Backend<Block> + 'static,
+	C: BlockchainEvents<Block> + HeaderBackend<Block> + StorageProvider<Block, BE>,
+	C: Send + Sync + 'static,
+{
+	let name = &endpoint.config.name;
+	let mut next = match store.cursor(name) {
+		Ok(Some(cursor)) => cursor + 1,
+		Ok(None) => client.info().finalized_number + 1,
+		Err(e) => {
+			log::error!(target: LOG_TARGET, "Failed to read the cursor of `{}`: {}", name, e);
+			client.info().finalized_number + 1
+		},
+	};
+	log::info!(target: LOG_TARGET, "Delivering to `{}` from block #{}", name, next);
+
+	let mut attempt = 1;
+	loop {
+		// (Re)start after the last delivered block, whose cursor is stored
+		let options = SubscriptionOptions { finalized_only: true, from_block: Some(next) };
+		let mut blocks = event_blocks(client.clone(), &options);
+
+		let error = loop {
+			let Some(block) = blocks.next().await else { return };
+			let notifications = block.and_then(|(hash, number, _)| {
+				Ok((number, EventNotification::from_block(&*client, hash, number, true)?))
+			});
+			match notifications {
+				Ok((number, notifications)) => {
+					deliver_block(&endpoint, &store, number, &notifications).await;
+					next = number + 1;
+					attempt = 1;
+				},
+				Err(e) => break e,
+			}
+		};
+
+		if block_unreadable(&endpoint, &store, next, attempt, &error.to_string()).await {
+			next += 1;
+			attempt = 1;
+		} else {
+			attempt += 1;
+		}
+	}
+}
+
+/// Futures delivering the finalized events of `client` to the endpoints of `config`, one per
+/// endpoint, with their cursors and dead-letter logs in `store`.
+pub fn endpoint_tasks<C, BE>(
+	client: Arc<C>,
+	config: Config,
+	store: Store,
+) -> Result<Vec<impl std::future::Future<Output = ()>>, String>
+where
+	BE: Backend<Block> + 'static,
+	C: BlockchainEvents<Block> + HeaderBackend<Block> + StorageProvider<Block, BE>,
+	C: Send + Sync + 'static,
+{
+	let store = Arc::new(store);
+
+	config
+		.endpoints
+		.into_iter()
+		.map(|endpoint| {
+			let endpoint = Endpoint::new(endpoint, config.retry.clone())?;
+			Ok(run_endpoint(client.clone(), endpoint, store.clone()))
+		})
+		.collect()
+}
@@ -0,0 +1,78 @@
+//! Delivery cursors and dead-letter logs of the webhook endpoints.
+//!
+//! Every endpoint has two files in the store directory:
+//! - `<name>.cursor`, the number of the last finalized block whose events were all delivered or
+//!   dead-lettered,
+//! - `<name>.dead-letter.jsonl`, one JSON line per delivery abandoned after its last attempt, or
+//!   per block whose events could not be read, with the `{"blockNumber": <number>}` payload.
+
+use serde::Serialize;
+use std::{
+	fs::{self, OpenOptions},
+	io::{self, Write},
+	path::{Path, PathBuf},
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Entry of a dead-letter log.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeadLetter<'a> {
+	/// Time the delivery was abandoned, in milliseconds since the Unix epoch
+	time: u64,
+	/// Error of the last attempt
+	error: &'a str,
+	/// Undelivered payload
+	payload: &'a serde_json::value::RawValue,
+}
+
+/// Store of the webhook endpoints.
+pub struct Store {
+	dir: PathBuf,
+}
+
+impl Store {
+	/// Open the store in `dir`, creating the directory if needed.
+	pub fn open(dir: &Path) -> io::Result<Self> {
+		fs::create_dir_all(dir)?;
+		Ok(Self { dir: dir.to_path_buf() })
+	}
+
+	fn path(&self, endpoint: &str, extension: &str) -> PathBuf {
+		self.dir.join(format!("{}.{}", endpoint, extension))
+	}
+
+	/// Number of the last block delivered to `endpoint`, if any.
+	pub fn cursor(&self, endpoint: &str) -> io::Result<Option<u32>> {
+		match fs::read_to_string(self.path(endpoint, "cursor")) {
+			Ok(content) => content
+				.trim()
+				.parse()
+				.map(Some)
+				.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+			Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+			Err(e) => Err(e),
+		}
+	}
+
+	/// Record that the events up to the block `number` were delivered to `endpoint`.
+	pub fn set_cursor(&self, endpoint: &str, number: u32) -> io::Result<()> {
+		// Written aside then renamed, so that a crash never leaves a truncated cursor
+		let temporary = self.path(endpoint, "cursor.tmp");
+		fs::write(&temporary, number.to_string())?;
+		fs::rename(temporary, self.path(endpoint, "cursor"))
+	}
+
+	/// Append the abandoned `payload` to the dead-letter log of `endpoint`.
+	pub fn dead_letter(&self, endpoint: &str, payload: &str, error: &str) -> io::Result<()> {
+		let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+		let payload = serde_json::value::RawValue::from_string(payload.to_owned())?;
+		let entry = DeadLetter { time: time as u64, error, payload: &payload };
+
+		let mut log = OpenOptions::new()
+			.create(true)
+			.append(true)
+			.open(self.path(endpoint, "dead-letter.jsonl"))?;
+		writeln!(log, "{}", serde_json::to_string(&entry)?)
+	}
+}
@@ -0,0 +1,330 @@
+use super::{
+	block_unreadable,
+	config::{Config, EndpointConfig, RetryConfig},
+	deliver_block,
+	delivery::Endpoint,
+	signature, Store, DELIVERY_HEADER, SIGNATURE_HEADER,
+};
+use crate::rpc::{Change, EventNotification, MarketEvent};
+use hyper::{
+	service::{make_service_fn, service_fn},
+	Body, HeaderMap, Request, Response, Server, StatusCode,
+};
+use node_template_runtime::{AccountId, Hash};
+use sp_keyring::AccountKeyring::{Alice, Bob, Charlie};
+use std::{
+	collections::VecDeque,
+	convert::Infallible,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+/// Request received by the stand-in endpoint.
+struct Received {
+	headers: HeaderMap,
+	body: Vec<u8>,
+}
+
+#[derive(Default)]
+struct StandInState {
+	/// Statuses of the next responses, `200 OK` once exhausted
+	statuses: Mutex<VecDeque<u16>>,
+	received: Mutex<Vec<Received>>,
+}
+
+/// Local HTTP server standing in for a webhook endpoint.
+struct StandIn {
+	url: String,
+	state: Arc<StandInState>,
+}
+
+impl StandIn {
+	/// Start a stand-in answering the first requests with `statuses`.
+	fn start(statuses: &[u16]) -> Self {
+		let state = Arc::new(StandInState::default());
+		state.statuses.lock().unwrap().extend(statuses);
+
+		let service_state = state.clone();
+		let make_service = make_service_fn(move |_| {
+			let state = service_state.clone();
+			async move {
+				Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+					let state = state.clone();
+					async move {
+						let headers = request.headers().clone();
+						let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+						let body = body.to_vec();
+						state.received.lock().unwrap().push(Received { headers, body });
+
+						let status = state.statuses.lock().unwrap().pop_front().unwrap_or(200);
+						let mut response = Response::new(Body::empty());
+						*response.status_mut() = StatusCode::from_u16(status).unwrap();
+						Ok::<_, Infallible>(response)
+					}
+				}))
+			}
+		});
+
+		let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+		let url = format!("http://{}/hook", server.local_addr());
+		tokio::spawn(server);
+
+		Self { url, state }
+	}
+
+	fn received(&self) -> std::sync::MutexGuard<Vec<Received>> {
+		self.state.received.lock().unwrap()
+	}
+}
+
+fn retry() -> RetryConfig {
+	RetryConfig { max_attempts: 3, initial_backoff_ms: 1, max_backoff_ms: 10, timeout_ms: 5_000 }
+}
+
+fn endpoint(url: &str) -> EndpointConfig {
+	EndpointConfig {
+		name: "billing".into(),
+		url: url.into(),
+		secret: "secret".into(),
+		events: vec![],
+		markets: vec![],
+		accounts: vec![],
+	}
+}
+
+fn offer(seller: AccountId, buyer: AccountId, market: u32) -> MarketEvent {
+	MarketEvent::Offer {
+		seller,
+		buyer,
+		market,
+		market_timestamp: 10,
+		asset: 1,
+		sold_power: 500,
+		change_fct_w: 2,
+	}
+}
+
+fn payment(sender: AccountId, receiver: AccountId) -> MarketEvent {
+	MarketEvent::Payment { change: Change::Added, sender, receiver, ts: 20, value: Some(1_000) }
+}
+
+fn notification(event_index: u32, event: MarketEvent) -> EventNotification {
+	EventNotification {
+		block_number: 7,
+		block_hash: Hash::repeat_byte(7),
+		finalized: true,
+		event_index,
+		extrinsic_index: Some(1),
+		event,
+	}
+}
+
+#[test]
+fn signature_is_hmac_sha256() {
+	assert_eq!(
+		signature("key", b"The quick brown fox jumps over the lazy dog"),
+		"sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+	);
+}
+
+#[test]
+fn backoff_doubles_up_to_the_maximum() {
+	let retry = RetryConfig { initial_backoff_ms: 100, max_backoff_ms: 1_000, ..retry() };
+
+	assert_eq!(retry.backoff(1), Duration::from_millis(100));
+	assert_eq!(retry.backoff(2), Duration::from_millis(200));
+	assert_eq!(retry.backoff(4), Duration::from_millis(800));
+	assert_eq!(retry.backoff(5), Duration::from_millis(1_000));
+	assert_eq!(retry.backoff(100), Duration::from_millis(1_000));
+}
+
+#[test]
+fn parses_config() {
+	let config = Config::parse(
+		r#"
+		[retry]
+		max_attempts = 5
+
+		[[endpoint]]
+		name = "billing"
+		url = "http://localhost:8080/hook"
+		secret = "secret"
+		events = ["decision", "payment"]
+		markets = [1, 2]
+		accounts = ["5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"]
+
+		[[endpoint]]
+		name = "audit"
+		url = "http://localhost:8081/hook"
+		secret = "other"
+		"#,
+	)
+	.unwrap();
+
+	assert_eq!(config.retry.max_attempts, 5);
+	assert_eq!(config.retry.timeout_ms, RetryConfig::default().timeout_ms);
+	assert_eq!(config.endpoints.len(), 2);
+	assert_eq!(config.endpoints[0].markets, vec![1, 2]);
+	assert_eq!(config.endpoints[0].accounts, vec![Alice.to_account_id()]);
+	assert!(config.endpoints[1].events.is_empty());
+}
+
+#[test]
+fn rejects_invalid_config() {
+	let endpoint = |name: &str, extra: &str| {
+		format!(
+			"[[endpoint]]\nname = \"{}\"\nurl = \"http://localhost\"\nsecret = \"s\"\n{}\n",
+			name, extra
+		)
+	};
+
+	assert!(Config::parse(&endpoint("../escape", "")).unwrap_err().contains("endpoint name"));
+	assert!(Config::parse(&endpoint("", "")).is_err());
+	assert!(Config::parse(&(endpoint("a", "") + &endpoint("a", "")))
+		.unwrap_err()
+		.contains("duplicate"));
+	assert!(Config::parse(&endpoint("a", "events = [\"transfer\"]"))
+		.unwrap_err()
+		.contains("unknown event type"));
+	assert!(Config::parse(&endpoint("a", "colour = \"blue\"")).is_err());
+	assert!(Config::parse("[retry]\nmax_attempts = 0").is_err());
+}
+
+#[test]
+fn filters_events() {
+	let mut config = endpoint("http://localhost");
+	assert!(config.matches(&offer(Alice.into(), Bob.into(), 1)));
+
+	config.events = vec!["payment".into()];
+	assert!(!config.matches(&offer(Alice.into(), Bob.into(), 1)));
+	assert!(config.matches(&payment(Alice.into(), Bob.into())));
+
+	config.events = vec![];
+	config.markets = vec![2];
+	assert!(!config.matches(&offer(Alice.into(), Bob.into(), 1)));
+	assert!(config.matches(&offer(Alice.into(), Bob.into(), 2)));
+	// Payments are not bound to a market
+	assert!(!config.matches(&payment(Alice.into(), Bob.into())));
+
+	config.markets = vec![];
+	config.accounts = vec![Bob.into()];
+	assert!(config.matches(&offer(Alice.into(), Bob.into(), 1)));
+	assert!(config.matches(&payment(Bob.into(), Alice.into())));
+	assert!(!config.matches(&payment(Alice.into(), Charlie.into())));
+}
+
+#[tokio::test]
+async fn delivers_signed_payloads_and_moves_the_cursor() {
+	let stand_in = StandIn::start(&[]);
+	let dir = tempfile::tempdir().unwrap();
+	let store = Store::open(dir.path()).unwrap();
+	let mut config = endpoint(&stand_in.url);
+	config.events = vec!["payment".into()];
+	let endpoint = Endpoint::new(config, retry()).unwrap();
+
+	let notifications = [
+		notification(0, offer(Alice.into(), Bob.into(), 1)),
+		notification(3, payment(Alice.into(), Bob.into())),
+	];
+	deliver_block(&endpoint, &store, 7, &notifications).await;
+
+	let received = stand_in.received();
+	assert_eq!(received.len(), 1);
+	let request = &received[0];
+	let delivery_id = format!("{:?}-3", Hash::repeat_byte(7));
+	assert_eq!(request.headers[DELIVERY_HEADER], delivery_id.as_str());
+	assert_eq!(request.headers["content-type"], "application/json");
+	assert_eq!(request.headers[SIGNATURE_HEADER], signature("secret", &request.body).as_str());
+
+	let payload: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+	assert_eq!(payload["deliveryId"], delivery_id.as_str());
+	assert_eq!(payload["type"], "payment");
+	assert_eq!(payload["blockNumber"], 7);
+	assert_eq!(payload["eventIndex"], 3);
+	assert_eq!(payload["value"], 1_000);
+
+	assert_eq!(store.cursor("billing").unwrap(), Some(7));
+	assert!(!dir.path().join("billing.dead-letter.jsonl").exists());
+}
+
+#[tokio::test]
+async fn retries_failed_deliveries() {
+	let stand_in = StandIn::start(&[500, 503]);
+	let dir = tempfile::tempdir().unwrap();
+	let store = Store::open(dir.path()).unwrap();
+	let endpoint = Endpoint::new(endpoint(&stand_in.url), retry()).unwrap();
+
+	deliver_block(&endpoint, &store, 7, &[notification(0, payment(Alice.into(), Bob.into()))])
+		.await;
+
+	let received = stand_in.received();
+	assert_eq!(received.len(), 3);
+	// Every attempt is the same delivery
+	assert!(received.iter().all(|request| request.body == received[0].body &&
+		request.headers[DELIVERY_HEADER] == received[0].headers[DELIVERY_HEADER]));
+	assert!(!dir.path().join("billing.dead-letter.jsonl").exists());
+}
+
+#[tokio::test]
+async fn dead_letters_abandoned_deliveries() {
+	let stand_in = StandIn::start(&[500, 500, 500, 400]);
+	let dir = tempfile::tempdir().unwrap();
+	let store = Store::open(dir.path()).unwrap();
+	let endpoint = Endpoint::new(endpoint(&stand_in.url), retry()).unwrap();
+
+	let notifications = [
+		notification(0, payment(Alice.into(), Bob.into())),
+		notification(1, payment(Bob.into(), Alice.into())),
+	];
+	deliver_block(&endpoint, &store, 7, &notifications).await;
+
+	// All the attempts of the first delivery, a single one for the rejected second delivery
+	assert_eq!(stand_in.received().len(), 4);
+	assert_eq!(store.cursor("billing").unwrap(), Some(7));
+
+	let log = std::fs::read_to_string(dir.path().join("billing.dead-letter.jsonl")).unwrap();
+	let entries: Vec<serde_json::Value> =
+		log.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+	assert_eq!(entries.len(), 2);
+	assert_eq!(entries[0]["error"], "HTTP status 500 Internal Server Error after 3 attempt(s)");
+	assert_eq!(entries[0]["payload"]["eventIndex"], 0);
+	assert_eq!(entries[1]["error"], "HTTP status 400 Bad Request after 1 attempt(s)");
+	assert_eq!(entries[1]["payload"]["eventIndex"], 1);
+}
+
+#[tokio::test]
+async fn dead_letters_unreadable_blocks_after_the_last_attempt() {
+	let dir = tempfile::tempdir().unwrap();
+	let store = Store::open(dir.path()).unwrap();
+	let endpoint = Endpoint::new(endpoint("http://localhost"), retry()).unwrap();
+	store.set_cursor("billing", 6).unwrap();
+
+	// The cursor stays before the block until its last attempt
+	assert!(!block_unreadable(&endpoint, &store, 7, 1, "State pruned").await);
+	assert!(!block_unreadable(&endpoint, &store, 7, 2, "State pruned").await);
+	assert_eq!(store.cursor("billing").unwrap(), Some(6));
+	assert!(!dir.path().join("billing.dead-letter.jsonl").exists());
+
+	assert!(block_unreadable(&endpoint, &store, 7, 3, "State pruned").await);
+	assert_eq!(store.cursor("billing").unwrap(), Some(7));
+
+	let log = std::fs::read_to_string(dir.path().join("billing.dead-letter.jsonl")).unwrap();
+	let entry: serde_json::Value = serde_json::from_str(log.trim()).unwrap();
+	assert_eq!(entry["error"], "State pruned after 3 attempt(s)");
+	assert_eq!(entry["payload"], serde_json::json!({ "blockNumber": 7 }));
+}
+
+#[test]
+fn persists_cursors() {
+	let dir = tempfile::tempdir().unwrap();
+
+	let store = Store::open(dir.path()).unwrap();
+	assert_eq!(store.cursor("billing").unwrap(), None);
+	store.set_cursor("billing", 41).unwrap();
+	store.set_cursor("billing", 42).unwrap();
+	store.set_cursor("audit", 3).unwrap();
+
+	let store = Store::open(dir.path()).unwrap();
+	assert_eq!(store.cursor("billing").unwrap(), Some(42));
+	assert_eq!(store.cursor("audit").unwrap(), Some(3));
+}
@@ -0,0 +1,129 @@
+//! RPC methods of the FEDECOM PS demo pallet.
+
+use std::sync::Arc;
+
+use jsonrpsee::{
+	core::RpcResult,
+	proc_macros::rpc,
+	types::error::{CallError, ErrorObject},
+};
+use serde::{Deserialize, Serialize};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::traits::Block as BlockT;
+
+pub use pallet_fedecom_ps_demo_runtime_api::{
+	CircuitBreakerStatus, FedecomApi as FedecomRuntimeApi,
+};
+
+/// Status of the circuit breaker of the flexibility market.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CircuitBreaker {
+	/// Whether all the market calls are paused.
+	pub pallet_paused: bool,
+	/// Names of the paused calls.
+	pub paused_calls: Vec<String>,
+	/// Paused flexibility market identifiers.
+	pub paused_markets: Vec<u32>,
+}
+
+impl From<CircuitBreakerStatus> for CircuitBreaker {
+	fn from(status: CircuitBreakerStatus) -> Self {
+		CircuitBreaker {
+			pallet_paused: status.pallet_paused,
+			paused_calls: status
+				.paused_calls
+				.into_iter()
+				.map(|name| String::from_utf8_lossy(&name).into_owned())
+				.collect(),
+			paused_markets: status.paused_markets,
+		}
+	}
+}
+
+/// RPC methods of the flexibility market.
+#[rpc(client, server)]
+pub trait FedecomApi<BlockHash> {
+	/// Status of the circuit breaker at the given block, or at the best block, of the named
+	/// instance of the pallet, or of the default instance.
+	#[method(name = "fedecom_circuitBreaker")]
+	fn circuit_breaker(
+		&self,
+		at: Option<BlockHash>,
+		instance: Option<String>,
+	) -> RpcResult<CircuitBreaker>;
+
+	/// Names of the instances of the pallet at the given block, or at the best block, the default
+	/// instance first.
+	#[method(name = "fedecom_instances")]
+	fn instances(&self, at: Option<BlockHash>) -> RpcResult<Vec<String>>;
+}
+
+/// Error code of the runtime API call failures.
+const RUNTIME_ERROR: i32 = 1;
+/// Error code of the queries of an unknown instance of the pallet.
+const UNKNOWN_INSTANCE: i32 = 2;
+
+fn runtime_error(message: &str, e: impl ToString) -> jsonrpsee::core::Error {
+	CallError::Custom(ErrorObject::owned(RUNTIME_ERROR, message, Some(e.to_string()))).into()
+}
+
+/// Implementation of [`FedecomApiServer`] on top of the runtime API.
+pub struct Fedecom<C, Block> {
+	client: Arc<C>,
+	_marker: std::marker::PhantomData<Block>,
+}
+
+impl<C, Block> Fedecom<C, Block> {
+	/// Create a new instance of the fedecom RPC methods.
+	pub fn new(client: Arc<C>) -> Self {
+		Self { client, _marker: Default::default() }
+	}
+}
+
+impl<C, Block> FedecomApiServer<<Block as BlockT>::Hash> for Fedecom<C, Block>
+where
+	Block: BlockT,
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+	C::Api: FedecomRuntimeApi<Block>,
+{
+	fn circuit_breaker(
+		&self,
+		at: Option<Block::Hash>,
+		instance: Option<String>,
+	) -> RpcResult<CircuitBreaker> {
+		let at = at.unwrap_or_else(|| self.client.info().best_hash);
+		let api = self.client.runtime_api();
+		let message = "Unable to query the circuit breaker status.";
+
+		let status = match instance {
+			None => api.circuit_breaker_status(at).map_err(|e| runtime_error(message, e))?,
+			Some(instance) => api
+				.instance_circuit_breaker_status(at, instance.clone().into_bytes())
+				.map_err(|e| runtime_error(message, e))?
+				.ok_or_else(|| {
+					CallError::Custom(ErrorObject::owned(
+						UNKNOWN_INSTANCE,
+						"Unknown flexibility market instance.",
+						Some(instance),
+					))
+				})?,
+		};
+		Ok(status.into())
+	}
+
+	fn instances(&self, at: Option<Block::Hash>) -> RpcResult<Vec<String>> {
+		let at = at.unwrap_or_else(|| self.client.info().best_hash);
+
+		let instances = self
+			.client
+			.runtime_api()
+			.instances(at)
+			.map_err(|e| runtime_error("Unable to query the instances.", e))?;
+		Ok(instances
+			.into_iter()
+			.map(|name| String::from_utf8_lossy(&name).into_owned())
+			.collect())
+	}
+}
@@ -0,0 +1,26 @@
+//! Runtime API of the FEDECOM PS demo pallet.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use pallet_fedecom_ps_demo::CircuitBreakerStatus;
+use sp_std::vec::Vec;
+
+sp_api::decl_runtime_apis! {
+	/// Queries of the flexibility market state.
+	///
+	/// The instances of the pallet are identified by their name in the runtime, e.g.
+	/// `FedecomPSDemo` for the default instance.
+	#[api_version(2)]
+	pub trait FedecomApi {
+		/// Status of the circuit breaker of the default instance: paused pallet, calls and markets.
+		fn circuit_breaker_status() -> CircuitBreakerStatus;
+
+		/// Names of the instances of the pallet, the default instance first.
+		#[api_version(2)]
+		fn instances() -> Vec<Vec<u8>>;
+
+		/// Status of the circuit breaker of the named instance, `None` if there is no such instance.
+		#[api_version(2)]
+		fn instance_circuit_breaker_status(instance: Vec<u8>) -> Option<CircuitBreakerStatus>;
+	}
+}
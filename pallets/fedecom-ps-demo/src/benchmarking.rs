@@ -1,35 +1,172 @@
-//! Benchmarking setup for pallet-template
+//! Benchmarking setup for pallet-fedecom-ps-demo
+//!
+//! The ledger benchmarks compare the market-scoped layout of the offers with the layout before
+//! version 1: closing a period only visits the offers of the period, while clearing a period of
+//! the former ledger goes through the offers of every period.
 #![cfg(feature = "runtime-benchmarks")]
 use super::*;
 
-#[allow(unused)]
-use crate::Pallet as Template;
+use crate::{
+	migrations::v1::{migrate_offers, FlexibilityMarketLedger, MigrateToMarketScopedLedger},
+	pallet::{
+		Assets, FlexibilityOffers, Markets, MigratingLedger, Participants, Payments, PeriodOffers,
+		SellerOffers, SettledOffers,
+	},
+	Pallet as FedecomPSDemo,
+};
 use frame_benchmarking::v2::*;
+use frame_support::traits::{EnsureOrigin, Get, OnRuntimeUpgrade, StorageVersion};
 use frame_system::RawOrigin;
 
-#[benchmarks]
+const MARKET: u32 = 1;
+const PERIOD: u32 = 1_000;
+
+/// Register the participants of an offer of the `asset` of `seller`, and the benchmarked market.
+fn register_offer<T: Config<I>, I: 'static>(
+	seller: &T::AccountId,
+	buyer: &T::AccountId,
+	asset: u32,
+) {
+	let operator: T::AccountId = account("operator", 0, 0);
+	Participants::<T, I>::insert(seller, PARTICIPANT_ROLE_PROSUMER);
+	Participants::<T, I>::insert(buyer, PARTICIPANT_ROLE_DSO);
+	Participants::<T, I>::insert(&operator, PARTICIPANT_ROLE_MARKET_OPERATOR);
+	Markets::<T, I>::insert(MARKET, operator);
+	Assets::<T, I>::insert(asset, seller);
+}
+
+/// Store `n` offers with the given `state` in the `period` of the benchmarked market.
+fn fill_period<T: Config<I>, I: 'static>(period: u32, n: u32, state: u32) {
+	let seller: T::AccountId = account("seller", 0, 0);
+	let buyer: T::AccountId = account("buyer", 0, 0);
+	for asset in 0..n {
+		FlexibilityOffers::<T, I>::insert(
+			(MARKET, period, &seller, &buyer, asset),
+			FlexibilitySellingData { sold_power: 10, change_fct_w: 2, state },
+		);
+	}
+	PeriodOffers::<T, I>::insert(MARKET, period, n);
+	SellerOffers::<T, I>::insert((MARKET, period, &seller), n);
+}
+
+/// Store `n` offers in the `period` of the benchmarked market of the ledger before version 1.
+fn fill_legacy_period<T: Config<I>, I: 'static>(period: u32, n: u32) {
+	let seller: T::AccountId = account("seller", 0, 0);
+	let buyer: T::AccountId = account("buyer", 0, 0);
+	for asset in 0..n {
+		FlexibilityMarketLedger::<T, I>::insert(
+			(&seller, &buyer, MARKET, period, asset),
+			FlexibilitySellingData::default(),
+		);
+	}
+}
+
+#[instance_benchmarks]
 mod benchmarks {
 	use super::*;
 
-	// #[benchmark]
-	// fn do_something() {
-	// 	let value = 100u32.into();
-	// 	let caller: T::AccountId = whitelisted_caller();
-	// 	#[extrinsic_call]
-	// 	do_something(RawOrigin::Signed(caller), value);
-	//
-	// 	assert_eq!(Something::<T>::get(), Some(value));
-	// }
-	//
-	// #[benchmark]
-	// fn cause_error() {
-	// 	Something::<T>::put(100u32);
-	// 	let caller: T::AccountId = whitelisted_caller();
-	// 	#[extrinsic_call]
-	// 	cause_error(RawOrigin::Signed(caller));
-	//
-	// 	assert_eq!(Something::<T>::get(), Some(101u32));
-	// }
-	//
-	// impl_benchmark_test_suite!(Template, crate::mock::new_test_ext(), crate::mock::Test);
+	#[benchmark]
+	fn flexibility_selling() {
+		let seller: T::AccountId = whitelisted_caller();
+		let buyer: T::AccountId = account("buyer", 0, 0);
+		// The last offer the period can take
+		let max = T::MaxOffersPerPeriod::get();
+		fill_period::<T, I>(PERIOD, max.saturating_sub(1), FLEXIBILITY_SELLING_STATE_NOT_DECIDED);
+		register_offer::<T, I>(&seller, &buyer, max);
+
+		#[extrinsic_call]
+		_(RawOrigin::Signed(seller), buyer, MARKET, PERIOD, max, 10, 2);
+
+		assert_eq!(PeriodOffers::<T, I>::get(MARKET, PERIOD), max);
+	}
+
+	#[benchmark]
+	fn flexibility_purchase() -> Result<(), BenchmarkError> {
+		let seller: T::AccountId = account("seller", 0, 0);
+		let buyer: T::AccountId = whitelisted_caller();
+		register_offer::<T, I>(&seller, &buyer, 0);
+		FedecomPSDemo::<T, I>::flexibility_selling(
+			RawOrigin::Signed(seller.clone()).into(),
+			buyer.clone(),
+			MARKET,
+			PERIOD,
+			0,
+			10,
+			2,
+		)?;
+
+		#[extrinsic_call]
+		flexibility_purchase_decision(
+			RawOrigin::Signed(buyer.clone()),
+			seller.clone(),
+			MARKET,
+			PERIOD,
+			0,
+			FLEXIBILITY_SELLING_STATE_CONFIRMED,
+		);
+
+		assert_eq!(Payments::<T, I>::get((&buyer, &seller, PERIOD)), 20);
+		Ok(())
+	}
+
+	#[benchmark]
+	fn close_market_period(
+		n: Linear<0, { T::MaxOffersPerPeriod::get() }>,
+	) -> Result<(), BenchmarkError> {
+		// Full periods around the closed one, which must not be visited. The offers of the closed
+		// period are confirmed, so that all of them are moved to the settled offers
+		let max = T::MaxOffersPerPeriod::get();
+		let state = FLEXIBILITY_SELLING_STATE_CONFIRMED;
+		fill_period::<T, I>(PERIOD - 1, max, state);
+		fill_period::<T, I>(PERIOD, n, state);
+		fill_period::<T, I>(PERIOD + 1, max, state);
+		let origin =
+			T::GovernanceOrigin::try_successful_origin().map_err(|_| BenchmarkError::Weightless)?;
+
+		#[extrinsic_call]
+		_(origin as T::RuntimeOrigin, MARKET, PERIOD);
+
+		assert_eq!(PeriodOffers::<T, I>::get(MARKET, PERIOD), 0);
+		assert_eq!(SettledOffers::<T, I>::iter_prefix((MARKET, PERIOD)).count() as u32, n);
+		assert_eq!(PeriodOffers::<T, I>::get(MARKET, PERIOD + 1), max);
+		Ok(())
+	}
+
+	/// Clearing the `n` offers of a period of the ledger before version 1, which also holds `m`
+	/// offers of another period.
+	#[benchmark]
+	fn clear_legacy_period(n: Linear<0, 1_000>, m: Linear<0, 1_000>) {
+		fill_legacy_period::<T, I>(PERIOD, n);
+		fill_legacy_period::<T, I>(PERIOD + 1, m);
+
+		#[block]
+		{
+			let offers: Vec<_> = FlexibilityMarketLedger::<T, I>::iter_keys()
+				.filter(|(_, _, market, period, _)| *market == MARKET && *period == PERIOD)
+				.collect();
+			for offer in offers {
+				FlexibilityMarketLedger::<T, I>::remove(offer);
+			}
+		}
+
+		assert_eq!(FlexibilityMarketLedger::<T, I>::iter_keys().count() as u32, m);
+	}
+
+	/// Moving one offer in the `on_initialize` of a block following the upgrade.
+	#[benchmark]
+	fn migrate_offer() {
+		fill_legacy_period::<T, I>(PERIOD, 1);
+		StorageVersion::new(0).put::<FedecomPSDemo<T, I>>();
+		MigrateToMarketScopedLedger::<T, I>::on_runtime_upgrade();
+
+		#[block]
+		{
+			migrate_offers::<T, I>(1);
+		}
+
+		assert_eq!(PeriodOffers::<T, I>::get(MARKET, PERIOD), 1);
+		assert!(!MigratingLedger::<T, I>::get());
+	}
+
+	impl_benchmark_test_suite!(FedecomPSDemo, crate::mock::new_test_ext(), crate::mock::Test);
 }
@@ -4,8 +4,14 @@
 /// <https://docs.substrate.io/reference/frame-pallets/>
 pub use pallet::*;
 
-use frame_support::{dispatch::DispatchResult};
+use frame_support::{
+	dispatch::{DispatchResult, DispatchResultWithPostInfo, GetCallName},
+	sp_std::prelude::*,
+	weights::Weight,
+};
 use frame_system::ensure_signed;
+use codec::{Decode, Encode};
+use scale_info::TypeInfo;
 
 
 #[cfg(test)]
@@ -14,17 +20,53 @@ mod mock;
 #[cfg(test)]
 mod tests;
 
+#[cfg(test)]
+mod model_tests;
+
+#[cfg(test)]
+mod scenario_tests;
+
 #[cfg(feature = "runtime-benchmarks")]
 mod benchmarking;
+pub mod migrations;
+pub mod traits;
+pub use traits::*;
 pub mod weights;
 pub use weights::*;
 
 pub type KeyLedger = str;
 pub type ValueLedger = u32;
 
-const CONFIRMATION_OK: u32 = 1;
-const CONFIRMATION_NOK_OVERESTIMATION: u32 = 2;
-const CONFIRMATION_NOK_UNDERESTIMATION: u32 = 3;
+/// Calls of the market participants, which can be paused by the circuit breaker.
+///
+/// The calls of the privileged origins (market registry and circuit breaker) are never paused.
+pub const PAUSABLE_CALLS: &[&str] = &[
+	"check_payment_call",
+	"get_payment_call",
+	"modify_payment",
+	"add_payment",
+	"remove_payment",
+	"add_confirmation",
+	"remove_confirmation",
+	"flexibility_selling",
+	"flexibility_purchase_decision",
+];
+
+/// Status of the circuit breaker, as returned by the runtime API.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Default, TypeInfo)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct CircuitBreakerStatus {
+	/// Whether all the pausable calls are paused
+	pub pallet_paused: bool,
+	/// Names of the paused calls
+	pub paused_calls: Vec<Vec<u8>>,
+	/// Paused flexibility market identifiers
+	pub paused_markets: Vec<u32>,
+}
+
+pub const CONFIRMATION_OK: u32 = 1;
+pub const CONFIRMATION_NOK_OVERESTIMATION: u32 = 2;
+pub const CONFIRMATION_NOK_UNDERESTIMATION: u32 = 3;
 
 #[frame_support::pallet]
 pub mod pallet {
@@ -36,24 +78,54 @@ pub mod pallet {
 	pub  const FLEXIBILITY_SELLING_STATE_CONFIRMED: u32 = 2;
 	pub const FLEXIBILITY_SELLING_STATE_REJECTED: u32 = 3;
 
+	pub const PARTICIPANT_ROLE_DSO: u32 = 1;
+	pub const PARTICIPANT_ROLE_AGGREGATOR: u32 = 2;
+	pub const PARTICIPANT_ROLE_PROSUMER: u32 = 3;
+	pub const PARTICIPANT_ROLE_MARKET_OPERATOR: u32 = 4;
+
+	/// Version 1 keys the offers by market and period, see [`crate::migrations::v1`].
+	pub const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
+
+	/// Flexibility market pallet, instantiable so that several markets are governed independently.
+	///
+	/// Every instance has its own registry, ledger, governance origins and circuit breaker. The
+	/// default instance `()` holds the storage of the former single-instance pallet.
 	#[pallet::pallet]
-	pub struct Pallet<T>(_);
+	#[pallet::storage_version(STORAGE_VERSION)]
+	pub struct Pallet<T, I = ()>(PhantomData<(T, I)>);
 
 	/// Configure the pallet by specifying the parameters and types on which it depends.
 	#[pallet::config]
-	pub trait Config: frame_system::Config {
+	pub trait Config<I: 'static = ()>: frame_system::Config {
 		/// Because this pallet emits events, it depends on the runtime's definition of an event.
-		type RuntimeEvent: From<Event<Self>> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
+		type RuntimeEvent: From<Event<Self, I>> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
 		/// Type representing the weight of this pallet
 		type WeightInfo: WeightInfo;
+		/// Origin allowed to register market participants, flexibility markets and assets
+		type GovernanceOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+		/// Origin allowed to pause and unpause the market through the circuit breaker
+		type PauseOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+		/// Handler of the flexibility sales confirmed by their buyer
+		type OnFlexibilityConfirmed: OnFlexibilityConfirmed<Self::AccountId>;
+		/// Handler of the payments whose delivery is confirmed by their receiver
+		type OnPaymentConfirmed: OnPaymentConfirmed<Self::AccountId>;
+		/// Handler of the deliveries disputed as over- or underestimated by the receiver
+		type OnDeliveryDisputed: OnDeliveryDisputed<Self::AccountId>;
 
 		#[pallet::constant]
 		type MaxLength: Get<u32>;
+		/// Maximum number of offers of a market period, which bounds the cost of closing it
+		#[pallet::constant]
+		type MaxOffersPerPeriod: Get<u32>;
+		/// Maximum number of offers of a seller in a market period, so that a single seller cannot
+		/// fill a period and lock the other sellers out of it
+		#[pallet::constant]
+		type MaxOffersPerSeller: Get<u32>;
 	}
 
 	#[pallet::storage]
 	#[pallet::getter(fn payments)]
-	pub(super) type Payments<T: Config> = StorageNMap<
+	pub type Payments<T: Config<I>, I: 'static = ()> = StorageNMap<
 		_,
 		(
 			NMapKey<Blake2_128Concat, T::AccountId>,
@@ -66,7 +138,7 @@ pub mod pallet {
 
 	#[pallet::storage]
 	#[pallet::getter(fn confirmations)]
-	pub(super) type Confirmations<T: Config> = StorageNMap<
+	pub type Confirmations<T: Config<I>, I: 'static = ()> = StorageNMap<
 		_,
 		(
 			NMapKey<Blake2_128Concat, T::AccountId>,
@@ -88,28 +160,153 @@ pub mod pallet {
 	}
 
 	#[pallet::storage]
-	#[pallet::getter(fn flexibility_market_ledger)]
-	pub(super) type FlexibilityMarketLedger<T: Config> = StorageNMap<
+	#[pallet::getter(fn flexibility_offers)]
+	// Offers of the flexibility market ledger, prefixed by market and period so that the offers of a
+	// period are read or removed without going through the other periods
+	pub type FlexibilityOffers<T: Config<I>, I: 'static = ()> = StorageNMap<
 		_,
 		(
+			// Flexibility market identifier
+			NMapKey<Twox64Concat, u32>,
+			// Flexibility market timestamp, i.e. the market period
+			NMapKey<Twox64Concat, u32>,
 			// Seller
 			NMapKey<Blake2_128Concat, T::AccountId>,
 			// Buyer
 			NMapKey<Blake2_128Concat, T::AccountId>,
+			// Asset/flexibility identifier
+			NMapKey<Twox64Concat, u32>,
+		),
+		FlexibilitySellingData,
+		ValueQuery,
+	>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn period_offer_count)]
+	// Number of offers of a market period, at most `MaxOffersPerPeriod`
+	pub type PeriodOffers<T: Config<I>, I: 'static = ()> = StorageDoubleMap<_, Twox64Concat, u32, Twox64Concat, u32, u32, ValueQuery>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn seller_offer_count)]
+	// Number of offers of a seller in a market period, at most `MaxOffersPerSeller`
+	pub type SellerOffers<T: Config<I>, I: 'static = ()> = StorageNMap<
+		_,
+		(
 			// Flexibility market identifier
 			NMapKey<Twox64Concat, u32>,
-			// Flexibility market timestamp
+			// Flexibility market timestamp, i.e. the market period
 			NMapKey<Twox64Concat, u32>,
-			// Asset/flexibility identifier
+			// Seller
+			NMapKey<Blake2_128Concat, T::AccountId>,
+		),
+		u32,
+		ValueQuery,
+	>;
+
+	#[pallet::storage]
+	// Closed market periods, which take no more offers nor purchase decisions
+	pub type ClosedPeriods<T: Config<I>, I: 'static = ()> = StorageDoubleMap<_, Twox64Concat, u32, Twox64Concat, u32, (), OptionQuery>;
+
+	#[pallet::storage]
+	// Offers confirmed in the closed market periods, kept for their settlement with the same key as
+	// in `FlexibilityOffers`
+	pub type SettledOffers<T: Config<I>, I: 'static = ()> = StorageNMap<
+		_,
+		(
+			NMapKey<Twox64Concat, u32>,
+			NMapKey<Twox64Concat, u32>,
+			NMapKey<Blake2_128Concat, T::AccountId>,
+			NMapKey<Blake2_128Concat, T::AccountId>,
 			NMapKey<Twox64Concat, u32>,
 		),
 		FlexibilitySellingData,
-	    ValueQuery,
+		OptionQuery,
 	>;
 
+	#[pallet::storage]
+	#[pallet::getter(fn migrating_ledger)]
+	// Whether offers of the ledger before version 1 are still moved by `on_initialize`, see
+	// `crate::migrations::v1`
+	pub(super) type MigratingLedger<T: Config<I>, I: 'static = ()> = StorageValue<_, bool, ValueQuery>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn participants)]
+	// Role of the registered market participants
+	pub(super) type Participants<T: Config<I>, I: 'static = ()> = StorageMap<_, Blake2_128Concat, T::AccountId, u32, OptionQuery>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn markets)]
+	// Operator of the registered flexibility markets
+	pub(super) type Markets<T: Config<I>, I: 'static = ()> = StorageMap<_, Twox64Concat, u32, T::AccountId, OptionQuery>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn assets)]
+	// Owner of the registered assets/flexibilities
+	pub(super) type Assets<T: Config<I>, I: 'static = ()> = StorageMap<_, Twox64Concat, u32, T::AccountId, OptionQuery>;
+
+	/// Name of a pallet call, as used by the circuit breaker
+	pub type CallName = BoundedVec<u8, ConstU32<64>>;
+
+	#[pallet::storage]
+	#[pallet::getter(fn pallet_paused)]
+	// Whether all the pausable calls are paused by the circuit breaker
+	pub(super) type PalletPaused<T: Config<I>, I: 'static = ()> = StorageValue<_, bool, ValueQuery>;
+
+	#[pallet::storage]
+	// Calls paused by the circuit breaker
+	pub(super) type PausedCalls<T: Config<I>, I: 'static = ()> = StorageMap<_, Blake2_128Concat, CallName, (), OptionQuery>;
+
+	#[pallet::storage]
+	// Flexibility markets paused by the circuit breaker
+	pub(super) type PausedMarkets<T: Config<I>, I: 'static = ()> = StorageMap<_, Twox64Concat, u32, (), OptionQuery>;
+
+	#[pallet::genesis_config]
+	#[derive(frame_support::DefaultNoBound)]
+	pub struct GenesisConfig<T: Config<I>, I: 'static = ()> {
+		#[serde(skip)]
+		pub phantom: PhantomData<I>,
+		/// Market participants with their role
+		pub participants: Vec<(T::AccountId, u32)>,
+		/// Flexibility market identifiers with their operator
+		pub markets: Vec<(u32, T::AccountId)>,
+		/// Asset/flexibility identifiers with their owner
+		pub assets: Vec<(u32, T::AccountId)>,
+	}
+
+	#[pallet::genesis_build]
+	impl<T: Config<I>, I: 'static> BuildGenesisConfig for GenesisConfig<T, I> {
+		fn build(&self) {
+			// The registries are checked like the market calls check them, so that a chain cannot
+			// start with offers of unknown participants or markets run by other participants
+			for (participant, role) in &self.participants {
+				assert!(Pallet::<T, I>::is_known_role(*role), "Unknown role {} of a genesis participant", role);
+				assert!(!Participants::<T, I>::contains_key(participant), "Duplicate genesis participant");
+				Participants::<T, I>::insert(participant, role);
+			}
+			for (market, operator) in &self.markets {
+				assert!(!Markets::<T, I>::contains_key(market), "Duplicate genesis market {}", market);
+				assert!(
+					Participants::<T, I>::get(operator) == Some(PARTICIPANT_ROLE_MARKET_OPERATOR),
+					"Operator of the genesis market {} is not a registered market operator",
+					market
+				);
+				Markets::<T, I>::insert(market, operator);
+			}
+			for (asset, owner) in &self.assets {
+				assert!(!Assets::<T, I>::contains_key(asset), "Duplicate genesis asset {}", asset);
+				assert!(
+					Participants::<T, I>::contains_key(owner),
+					"Owner of the genesis asset {} is not a registered participant",
+					asset
+				);
+				Assets::<T, I>::insert(asset, owner);
+			}
+		}
+	}
+
 	#[pallet::event]
 	#[pallet::generate_deposit(pub (super) fn deposit_event)]
-	pub enum Event<T: Config> {
+	pub enum Event<T: Config<I>, I: 'static = ()> {
 		// Events related to Payments StorageNMap
 		GotFromPayments {
 			key_sender: T::AccountId,
@@ -189,12 +386,64 @@ pub mod pallet {
 			flexibility_market_identifier: u32,
 			flexibility_market_timestamp: u32,
 			asset_identifier: u32,
-		}
+		},
+
+		// Events related to the market registry
+		ParticipantRegistered {
+			participant: T::AccountId,
+			role: u32,
+		},
+		ParticipantRemoved {
+			participant: T::AccountId,
+		},
+		MarketRegistered {
+			flexibility_market_identifier: u32,
+			operator: T::AccountId,
+		},
+		MarketRemoved {
+			flexibility_market_identifier: u32,
+		},
+		AssetRegistered {
+			asset_identifier: u32,
+			owner: T::AccountId,
+		},
+
+		// Events related to the circuit breaker
+		PalletPaused(),
+		PalletUnpaused(),
+		CallPaused {
+			call_name: Vec<u8>,
+		},
+		CallUnpaused {
+			call_name: Vec<u8>,
+		},
+		MarketPaused {
+			flexibility_market_identifier: u32,
+		},
+		MarketUnpaused {
+			flexibility_market_identifier: u32,
+		},
+
+		// Events related to the market periods
+		MarketPeriodClosed {
+			flexibility_market_identifier: u32,
+			flexibility_market_timestamp: u32,
+			removed: u32,
+			settled: u32,
+			remaining: u32,
+		},
+
+		// Events related to the migration of the ledger before version 1
+		MigratedPeriodAboveBound {
+			flexibility_market_identifier: u32,
+			flexibility_market_timestamp: u32,
+		},
+		LedgerMigrated(),
 	}
 
 	// Errors inform users that something went wrong.
 	#[pallet::error]
-	pub enum Error<T> {
+	pub enum Error<T, I = ()> {
 		NoneValue,
 		StorageOverflow,
 		PaymentAlreadyExists,
@@ -205,18 +454,41 @@ pub mod pallet {
 		FlexibilitySellingAlreadyDecided,
 		FlexibilitySellingRejected,
 		FlexibilitySellingUnknownState,
+		ParticipantUnknownRole,
+		ParticipantNotExists,
+		MarketNotExists,
+		CallNotPausable,
+		AlreadyPaused,
+		NotPaused,
+		FlexibilityPeriodFull,
+		NotMarketOperator,
+		AssetNotExists,
+		NotAssetOwner,
+		FlexibilityPeriodClosed,
+		SellerQuotaFull,
+		LedgerMigrating,
+	}
+
+	#[pallet::hooks]
+	impl<T: Config<I>, I: 'static> Hooks<BlockNumberFor<T>> for Pallet<T, I> {
+		fn on_initialize(_n: BlockNumberFor<T>) -> Weight {
+			match MigratingLedger::<T, I>::get() {
+				true => crate::migrations::v1::migrate_offers::<T, I>(crate::migrations::v1::MIGRATED_OFFERS_PER_BLOCK),
+				false => T::DbWeight::get().reads(1),
+			}
+		}
 	}
 
 	// Calls
 	#[pallet::call]
-	impl<T: Config> Pallet<T> {
+	impl<T: Config<I>, I: 'static> Pallet<T, I> {
 		// Calls with null weights
 		#[pallet::call_index(1)]
 		#[pallet::weight(T::WeightInfo::check_payment_call())]
 		pub fn check_payment_call(origin: OriginFor<T>, key_sender: T::AccountId, key_receiver: T::AccountId, ts: u32) -> DispatchResult {
 			let _ = ensure_signed(origin)?;
 
-			if Payments::<T>::contains_key((key_sender, key_receiver, ts)) == true {
+			if Payments::<T, I>::contains_key((key_sender, key_receiver, ts)) == true {
 				Self::deposit_event(Event::InPayments());
 			}
 			else {
@@ -231,7 +503,7 @@ pub mod pallet {
 		pub fn get_payment_call(origin: OriginFor<T>, key_sender: T::AccountId, key_receiver: T::AccountId, ts: u32) -> DispatchResult {
 			let _ = ensure_signed(origin)?;
 
-			let value = Payments::<T>::get((key_sender.clone(), key_receiver.clone(), ts));
+			let value = Payments::<T, I>::get((key_sender.clone(), key_receiver.clone(), ts));
 
 			Self::deposit_event(Event::GotFromPayments { key_sender, key_receiver, ts, value });
 
@@ -243,15 +515,15 @@ pub mod pallet {
 		pub fn modify_payment(origin: OriginFor<T>, key_receiver: T::AccountId, ts: u32, value: u32) -> DispatchResult {
 			let source = ensure_signed(origin.clone())?;
 
-			match <Payments<T>>::contains_key((source.clone(), key_receiver.clone(), ts)) {
-				false => return Err(Error::<T>::PaymentNotExists.into()),
+			match <Payments<T, I>>::contains_key((source.clone(), key_receiver.clone(), ts)) {
+				false => return Err(Error::<T, I>::PaymentNotExists.into()),
 				true => {
 					// Check if a confirmation with the triple (sender, receiver, timestamp) has already been stored
-					match <Confirmations<T>>::contains_key((source.clone(), key_receiver.clone(), ts)) {
-						true => return Err(Error::<T>::ConfirmationAlreadyExists.into()),
+					match <Confirmations<T, I>>::contains_key((source.clone(), key_receiver.clone(), ts)) {
+						true => return Err(Error::<T, I>::ConfirmationAlreadyExists.into()),
 						false => {
 							// Modify the payment
-							Payments::<T>::set((source.clone(), key_receiver.clone(), ts), value);
+							Payments::<T, I>::set((source.clone(), key_receiver.clone(), ts), value);
 							Self::deposit_event(Event::SetInPayments { key_sender: source, key_receiver, ts});
 							Ok(())
 						}
@@ -265,11 +537,11 @@ pub mod pallet {
 		pub fn add_payment(origin: OriginFor<T>, key_receiver: T::AccountId, ts: u32, value: u32) -> DispatchResult {
 			let source = ensure_signed(origin.clone())?;
 
-			match <Payments<T>>::contains_key((source.clone(), key_receiver.clone(), ts)) {
-				true => return Err(Error::<T>::PaymentAlreadyExists.into()),
+			match <Payments<T, I>>::contains_key((source.clone(), key_receiver.clone(), ts)) {
+				true => return Err(Error::<T, I>::PaymentAlreadyExists.into()),
 				false => {
 					// Insert the new payment
-					Payments::<T>::insert((source.clone(), key_receiver.clone(), ts), value);
+					Payments::<T, I>::insert((source.clone(), key_receiver.clone(), ts), value);
 					Self::deposit_event(Event::AddedToPayments { key_sender: source, key_receiver, ts, value });
 					Ok(())
 				},
@@ -281,15 +553,15 @@ pub mod pallet {
 		pub fn remove_payment(origin: OriginFor<T>, key_receiver: T::AccountId, ts: u32) -> DispatchResult {
 			let source = ensure_signed(origin.clone())?;
 
-			match <Payments<T>>::contains_key((source.clone(), key_receiver.clone(), ts)) {
-				false => return Err(Error::<T>::PaymentNotExists.into()),
+			match <Payments<T, I>>::contains_key((source.clone(), key_receiver.clone(), ts)) {
+				false => return Err(Error::<T, I>::PaymentNotExists.into()),
 				true => {
 					// Check if a confirmation with the triple (sender, receiver, timestamp) has already been stored
-					match <Confirmations<T>>::contains_key((source.clone(), key_receiver.clone(), ts)) {
-						true => return Err(Error::<T>::ConfirmationAlreadyExists.into()),
+					match <Confirmations<T, I>>::contains_key((source.clone(), key_receiver.clone(), ts)) {
+						true => return Err(Error::<T, I>::ConfirmationAlreadyExists.into()),
 						false => {
 							// Remove the payment
-							Payments::<T>::remove((source.clone(), key_receiver.clone(), ts));
+							Payments::<T, I>::remove((source.clone(), key_receiver.clone(), ts));
 							Self::deposit_event(Event::RemovedFromPayments { key_sender: source, key_receiver, ts});
 							Ok(())
 						}
@@ -299,34 +571,41 @@ pub mod pallet {
 		}
 
 		#[pallet::call_index(6)]
-		#[pallet::weight(T::WeightInfo::add_confirmation())]
-		pub fn add_confirmation(origin: OriginFor<T>, key_sender: T::AccountId, ts: u32, status: u32) -> DispatchResult {
+		#[pallet::weight(T::WeightInfo::add_confirmation()
+			.saturating_add(T::OnPaymentConfirmed::weight().max(T::OnDeliveryDisputed::weight())))]
+		pub fn add_confirmation(origin: OriginFor<T>, key_sender: T::AccountId, ts: u32, status: u32) -> DispatchResultWithPostInfo {
 			let source = ensure_signed(origin.clone())?;
 
 			// Check if a payment with the triple (sender, receiver, timestamp) has already been stored
-			match <Payments<T>>::contains_key((key_sender.clone(), source.clone(), ts)) {
-				false => return Err(Error::<T>::PaymentNotExists.into()),
+			match <Payments<T, I>>::contains_key((key_sender.clone(), source.clone(), ts)) {
+				false => return Err(Error::<T, I>::PaymentNotExists.into()),
 				true => {
 					// Check if a confirmation with the triple (sender, receiver, timestamp) has already been stored
-					match <Confirmations<T>>::contains_key((key_sender.clone(), source.clone(), ts)) {
-						true => return Err(Error::<T>::ConfirmationAlreadyExists.into()),
+					match <Confirmations<T, I>>::contains_key((key_sender.clone(), source.clone(), ts)) {
+						true => return Err(Error::<T, I>::ConfirmationAlreadyExists.into()),
 						false => {
 							// Insert the new confirmation
-							Confirmations::<T>::insert((key_sender.clone(), source.clone(), ts), status);
+							Confirmations::<T, I>::insert((key_sender.clone(), source.clone(), ts), status);
 
-							Self::deposit_event(Event::AddedToConfirmations { key_confirmer: source.clone(), key_sender: key_sender, key_receiver: source.clone(), ts, status });
+							Self::deposit_event(Event::AddedToConfirmations { key_confirmer: source.clone(), key_sender: key_sender.clone(), key_receiver: source.clone(), ts, status });
 
+							let mut hook_weight = Weight::zero();
 							if status == CONFIRMATION_OK {
 								Self::deposit_event(Event::ConfirmationOK ());
+								let value = Payments::<T, I>::get((&key_sender, &source, ts));
+								hook_weight = T::OnPaymentConfirmed::on_payment_confirmed(&key_sender, &source, ts, value);
 							}
 							else if status == CONFIRMATION_NOK_OVERESTIMATION {
 								Self::deposit_event(Event::ConfirmationOverEstimation ());
+								hook_weight = T::OnDeliveryDisputed::on_delivery_disputed(&key_sender, &source, ts, DeliveryDeviation::OverEstimation);
 							}
 							else if status == CONFIRMATION_NOK_UNDERESTIMATION {
 								Self::deposit_event(Event::ConfirmationUnderEstimation ());
+								hook_weight = T::OnDeliveryDisputed::on_delivery_disputed(&key_sender, &source, ts, DeliveryDeviation::UnderEstimation);
 							}
 
-							Ok(())
+							// Refund the unused weight of the settlement handlers
+							Ok(Some(T::WeightInfo::add_confirmation().saturating_add(hook_weight)).into())
 						}
 					}
 				},
@@ -339,11 +618,11 @@ pub mod pallet {
 			let source = ensure_signed(origin.clone())?;
 
 			// Check if a confirmation with the triple (sender, receiver, timestamp) has already been stored
-			match <Confirmations<T>>::contains_key((key_sender.clone(), source.clone(), ts)) {
-				false => return Err(Error::<T>::ConfirmationNotExists.into()),
+			match <Confirmations<T, I>>::contains_key((key_sender.clone(), source.clone(), ts)) {
+				false => return Err(Error::<T, I>::ConfirmationNotExists.into()),
 				true => {
 					// Remove the confirmation
-					Confirmations::<T>::remove((key_sender.clone(), source.clone(), ts));
+					Confirmations::<T, I>::remove((key_sender.clone(), source.clone(), ts));
 					Self::deposit_event(Event::RemovedFromConfirmations { key_sender: key_sender.clone(), key_receiver: source.clone(), ts});
 					Ok(())
 				}
@@ -361,11 +640,12 @@ pub mod pallet {
 								   change_fct_w: u32,
 								   ) -> DispatchResult {
 			let seller = ensure_signed(origin.clone())?;
-			let flexibility_data = FlexibilitySellingData { sold_power, change_fct_w, state: FLEXIBILITY_SELLING_STATE_NOT_DECIDED};
-			FlexibilityMarketLedger::<T>::insert((seller.clone(), buyer.clone(), flexibility_market_identifier, flexibility_market_timestamp, asset_identifier),
-												 flexibility_data);
+			let owner = Self::ensure_registered_offer(&seller, &buyer, flexibility_market_identifier, asset_identifier)?;
+			ensure!(owner == seller, Error::<T, I>::NotAssetOwner);
+			Self::ensure_open_period(flexibility_market_identifier, flexibility_market_timestamp)?;
 
-			if FlexibilityMarketLedger::<T>::contains_key((seller.clone(), buyer.clone(), flexibility_market_identifier, flexibility_market_timestamp, asset_identifier)) == true {
+			// An existing offer must not be overwritten, otherwise an already decided selling could be reset
+			if FlexibilityOffers::<T, I>::contains_key((flexibility_market_identifier, flexibility_market_timestamp, seller.clone(), buyer.clone(), asset_identifier)) == true {
 				Self::deposit_event(Event::AlreadySoldFlexibility {
 					seller,
 					buyer,
@@ -375,6 +655,17 @@ pub mod pallet {
 				});
 			}
 			else {
+				let offers = PeriodOffers::<T, I>::get(flexibility_market_identifier, flexibility_market_timestamp);
+				ensure!(offers < T::MaxOffersPerPeriod::get(), Error::<T, I>::FlexibilityPeriodFull);
+				let seller_offers = SellerOffers::<T, I>::get((flexibility_market_identifier, flexibility_market_timestamp, &seller));
+				ensure!(seller_offers < T::MaxOffersPerSeller::get(), Error::<T, I>::SellerQuotaFull);
+
+				let flexibility_data = FlexibilitySellingData { sold_power, change_fct_w, state: FLEXIBILITY_SELLING_STATE_NOT_DECIDED};
+				FlexibilityOffers::<T, I>::insert((flexibility_market_identifier, flexibility_market_timestamp, seller.clone(), buyer.clone(), asset_identifier),
+												  flexibility_data);
+				PeriodOffers::<T, I>::insert(flexibility_market_identifier, flexibility_market_timestamp, offers + 1);
+				SellerOffers::<T, I>::insert((flexibility_market_identifier, flexibility_market_timestamp, &seller), seller_offers + 1);
+
 				Self::deposit_event(Event::SuccessfullySoldFlexibility {
 					seller,
 					buyer,
@@ -390,17 +681,19 @@ pub mod pallet {
 		}
 
 		#[pallet::call_index(9)]
-		#[pallet::weight(T::WeightInfo::flexibility_purchase())]
+		#[pallet::weight(T::WeightInfo::flexibility_purchase().saturating_add(T::OnFlexibilityConfirmed::weight()))]
 		pub fn flexibility_purchase_decision(origin: OriginFor<T>,
 											seller: T::AccountId,
 											flexibility_market_identifier: u32,
 											flexibility_market_timestamp: u32,
 											asset_identifier: u32,
 											new_state: u32
-		) -> DispatchResult {
+		) -> DispatchResultWithPostInfo {
 			let buyer = ensure_signed(origin.clone())?;
+			Self::ensure_registered_offer(&seller, &buyer, flexibility_market_identifier, asset_identifier)?;
+			Self::ensure_open_period(flexibility_market_identifier, flexibility_market_timestamp)?;
 
-			match <FlexibilityMarketLedger<T>>::contains_key((seller.clone(), buyer.clone(), flexibility_market_identifier, flexibility_market_timestamp, asset_identifier)) {
+			match <FlexibilityOffers<T, I>>::contains_key((flexibility_market_identifier, flexibility_market_timestamp, seller.clone(), buyer.clone(), asset_identifier)) {
 				false => {
 					// Not existing entry
 					Self::deposit_event(Event::FlexibilitySellingNotExisting {
@@ -410,39 +703,52 @@ pub mod pallet {
 						flexibility_market_timestamp,
 						asset_identifier
 					});
-					return Err(Error::<T>::FlexibilitySellingNotExisting.into())
+					return Err(Error::<T, I>::FlexibilitySellingNotExisting.into())
 				},
 				true => {
-					let mut flexibility_data = FlexibilityMarketLedger::<T>::get((&seller, &buyer, &flexibility_market_identifier, &flexibility_market_timestamp, &asset_identifier));
+					let mut flexibility_data = FlexibilityOffers::<T, I>::get((&flexibility_market_identifier, &flexibility_market_timestamp, &seller, &buyer, &asset_identifier));
 					match flexibility_data.state {
 						FLEXIBILITY_SELLING_STATE_NOT_DECIDED => {
 							match new_state {
 								// The selling is confirmed by the buyer
 								FLEXIBILITY_SELLING_STATE_CONFIRMED => {
+									let tkns_to_pay = flexibility_data.sold_power.checked_mul(flexibility_data.change_fct_w)
+										.ok_or(Error::<T, I>::StorageOverflow)?;
+									// The payment of the period adds up the sales between the same participants, on
+									// every market, and must not be changed once its delivery is confirmed
+									ensure!(!Confirmations::<T, I>::contains_key((&buyer, &seller, flexibility_market_timestamp)),
+											Error::<T, I>::ConfirmationAlreadyExists);
+									let payment = Payments::<T, I>::get((&buyer, &seller, flexibility_market_timestamp))
+										.checked_add(tkns_to_pay)
+										.ok_or(Error::<T, I>::StorageOverflow)?;
+
 									// Market state confirmation
 									flexibility_data.state = new_state;
-									FlexibilityMarketLedger::<T>::set((seller.clone(), buyer.clone(), flexibility_market_identifier, flexibility_market_timestamp, asset_identifier),
-																	  flexibility_data.clone());
+									FlexibilityOffers::<T, I>::set((flexibility_market_identifier, flexibility_market_timestamp, seller.clone(), buyer.clone(), asset_identifier),
+																   flexibility_data.clone());
 
 									// Perform the payment
-									let tkns_to_pay = flexibility_data.sold_power * flexibility_data.change_fct_w;
-									Payments::<T>::insert((buyer.clone(), seller.clone(), flexibility_market_timestamp), tkns_to_pay);
+									Payments::<T, I>::insert((buyer.clone(), seller.clone(), flexibility_market_timestamp), payment);
 
 									Self::deposit_event(Event::FlexibilitySellingConfirmed {
-										seller,
-										buyer,
+										seller: seller.clone(),
+										buyer: buyer.clone(),
 										flexibility_market_identifier,
 										flexibility_market_timestamp,
 										asset_identifier
 									});
-									Ok(())
+
+									let offer = OfferKey { seller, buyer, flexibility_market_identifier, flexibility_market_timestamp, asset_identifier };
+									let hook_weight = T::OnFlexibilityConfirmed::on_flexibility_confirmed(&offer, &flexibility_data, tkns_to_pay);
+									// Refund the unused weight of the settlement handlers
+									Ok(Some(T::WeightInfo::flexibility_purchase().saturating_add(hook_weight)).into())
 								}
 								// The selling is rejected by the buyer
 								FLEXIBILITY_SELLING_STATE_REJECTED => {
 									// Market state rejection
 									flexibility_data.state = new_state;
-									FlexibilityMarketLedger::<T>::set((seller.clone(), buyer.clone(), flexibility_market_identifier, flexibility_market_timestamp, asset_identifier),
-																	  flexibility_data.clone());
+									FlexibilityOffers::<T, I>::set((flexibility_market_identifier, flexibility_market_timestamp, seller.clone(), buyer.clone(), asset_identifier),
+																   flexibility_data.clone());
 
 									Self::deposit_event(Event::FlexibilitySellingRejected {
 										seller,
@@ -451,43 +757,357 @@ pub mod pallet {
 										flexibility_market_timestamp,
 										asset_identifier
 									});
-									Ok(())
+									Ok(Some(T::WeightInfo::flexibility_purchase()).into())
 								}
-								_ => { return Err(Error::<T>::FlexibilitySellingUnknownState.into()) }
+								_ => { return Err(Error::<T, I>::FlexibilitySellingUnknownState.into()) }
 							}
 						}
 						_ => {
-							return Err(Error::<T>::ConfirmationAlreadyExists.into())
+							return Err(Error::<T, I>::ConfirmationAlreadyExists.into())
 						}
 					}
 				}
 			}
 		}
+
+		// Market registry, managed by governance
+		#[pallet::call_index(10)]
+		#[pallet::weight(T::WeightInfo::register_participant())]
+		pub fn register_participant(origin: OriginFor<T>, participant: T::AccountId, role: u32) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+
+			ensure!(Self::is_known_role(role), Error::<T, I>::ParticipantUnknownRole);
+			// A registered participant gets its role replaced
+			Participants::<T, I>::insert(&participant, role);
+			Self::deposit_event(Event::ParticipantRegistered { participant, role });
+			Ok(())
+		}
+
+		#[pallet::call_index(11)]
+		#[pallet::weight(T::WeightInfo::remove_participant())]
+		pub fn remove_participant(origin: OriginFor<T>, participant: T::AccountId) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+
+			match Participants::<T, I>::take(&participant) {
+				None => Err(Error::<T, I>::ParticipantNotExists.into()),
+				Some(_) => {
+					Self::deposit_event(Event::ParticipantRemoved { participant });
+					Ok(())
+				}
+			}
+		}
+
+		#[pallet::call_index(12)]
+		#[pallet::weight(T::WeightInfo::register_market())]
+		pub fn register_market(origin: OriginFor<T>, flexibility_market_identifier: u32, operator: T::AccountId) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+
+			// A registered market gets its operator replaced
+			Markets::<T, I>::insert(flexibility_market_identifier, &operator);
+			Self::deposit_event(Event::MarketRegistered { flexibility_market_identifier, operator });
+			Ok(())
+		}
+
+		#[pallet::call_index(13)]
+		#[pallet::weight(T::WeightInfo::remove_market())]
+		pub fn remove_market(origin: OriginFor<T>, flexibility_market_identifier: u32) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+
+			match Markets::<T, I>::take(flexibility_market_identifier) {
+				None => Err(Error::<T, I>::MarketNotExists.into()),
+				Some(_) => {
+					Self::deposit_event(Event::MarketRemoved { flexibility_market_identifier });
+					Ok(())
+				}
+			}
+		}
+
+		#[pallet::call_index(14)]
+		#[pallet::weight(T::WeightInfo::register_asset())]
+		pub fn register_asset(origin: OriginFor<T>, asset_identifier: u32, owner: T::AccountId) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+
+			// A registered asset gets its owner replaced
+			Assets::<T, I>::insert(asset_identifier, &owner);
+			Self::deposit_event(Event::AssetRegistered { asset_identifier, owner });
+			Ok(())
+		}
+
+		// Circuit breaker, the paused calls are filtered out by the runtime's `BaseCallFilter`
+		#[pallet::call_index(15)]
+		#[pallet::weight(T::WeightInfo::pause_pallet())]
+		pub fn pause_pallet(origin: OriginFor<T>) -> DispatchResult {
+			T::PauseOrigin::ensure_origin(origin)?;
+
+			ensure!(!PalletPaused::<T, I>::get(), Error::<T, I>::AlreadyPaused);
+			PalletPaused::<T, I>::put(true);
+			Self::deposit_event(Event::PalletPaused());
+			Ok(())
+		}
+
+		#[pallet::call_index(16)]
+		#[pallet::weight(T::WeightInfo::unpause_pallet())]
+		pub fn unpause_pallet(origin: OriginFor<T>) -> DispatchResult {
+			T::PauseOrigin::ensure_origin(origin)?;
+
+			ensure!(PalletPaused::<T, I>::get(), Error::<T, I>::NotPaused);
+			PalletPaused::<T, I>::kill();
+			Self::deposit_event(Event::PalletUnpaused());
+			Ok(())
+		}
+
+		#[pallet::call_index(17)]
+		#[pallet::weight(T::WeightInfo::pause_call())]
+		pub fn pause_call(origin: OriginFor<T>, call_name: Vec<u8>) -> DispatchResult {
+			T::PauseOrigin::ensure_origin(origin)?;
+
+			let name = Self::pausable_call_name(&call_name)?;
+			ensure!(!PausedCalls::<T, I>::contains_key(&name), Error::<T, I>::AlreadyPaused);
+			PausedCalls::<T, I>::insert(name, ());
+			Self::deposit_event(Event::CallPaused { call_name });
+			Ok(())
+		}
+
+		#[pallet::call_index(18)]
+		#[pallet::weight(T::WeightInfo::unpause_call())]
+		pub fn unpause_call(origin: OriginFor<T>, call_name: Vec<u8>) -> DispatchResult {
+			T::PauseOrigin::ensure_origin(origin)?;
+
+			let name = Self::pausable_call_name(&call_name)?;
+			ensure!(PausedCalls::<T, I>::take(&name).is_some(), Error::<T, I>::NotPaused);
+			Self::deposit_event(Event::CallUnpaused { call_name });
+			Ok(())
+		}
+
+		#[pallet::call_index(19)]
+		#[pallet::weight(T::WeightInfo::pause_market())]
+		pub fn pause_market(origin: OriginFor<T>, flexibility_market_identifier: u32) -> DispatchResult {
+			T::PauseOrigin::ensure_origin(origin)?;
+
+			ensure!(!PausedMarkets::<T, I>::contains_key(flexibility_market_identifier), Error::<T, I>::AlreadyPaused);
+			PausedMarkets::<T, I>::insert(flexibility_market_identifier, ());
+			Self::deposit_event(Event::MarketPaused { flexibility_market_identifier });
+			Ok(())
+		}
+
+		#[pallet::call_index(20)]
+		#[pallet::weight(T::WeightInfo::unpause_market())]
+		pub fn unpause_market(origin: OriginFor<T>, flexibility_market_identifier: u32) -> DispatchResult {
+			T::PauseOrigin::ensure_origin(origin)?;
+
+			ensure!(PausedMarkets::<T, I>::take(flexibility_market_identifier).is_some(), Error::<T, I>::NotPaused);
+			Self::deposit_event(Event::MarketUnpaused { flexibility_market_identifier });
+			Ok(())
+		}
+
+		// Market periods, closed by governance or by the operator of the market
+		#[pallet::call_index(21)]
+		#[pallet::weight(T::WeightInfo::close_market_period(T::MaxOffersPerPeriod::get()))]
+		pub fn close_market_period(origin: OriginFor<T>, flexibility_market_identifier: u32, flexibility_market_timestamp: u32) -> DispatchResultWithPostInfo {
+			Self::ensure_market_operator(origin, flexibility_market_identifier)?;
+			ensure!(!MigratingLedger::<T, I>::get(), Error::<T, I>::LedgerMigrating);
+
+			// Only the offers of the period are visited, at most `MaxOffersPerPeriod` per call. More
+			// offers are only left by the migration of an older ledger, and removed by the next calls
+			let offers = PeriodOffers::<T, I>::get(flexibility_market_identifier, flexibility_market_timestamp);
+			ensure!(
+				offers > 0 || !Self::is_period_closed(flexibility_market_identifier, flexibility_market_timestamp),
+				Error::<T, I>::FlexibilityPeriodClosed
+			);
+			ClosedPeriods::<T, I>::insert(flexibility_market_identifier, flexibility_market_timestamp, ());
+
+			// The confirmed offers are kept for their settlement, the others are removed
+			let (mut removed, mut settled) = (0u32, 0u32);
+			for ((seller, buyer, asset_identifier), data) in FlexibilityOffers::<T, I>::drain_prefix((flexibility_market_identifier, flexibility_market_timestamp))
+				.take(T::MaxOffersPerPeriod::get() as usize)
+			{
+				SellerOffers::<T, I>::mutate_exists((flexibility_market_identifier, flexibility_market_timestamp, &seller), |count| {
+					*count = count.and_then(|count| count.checked_sub(1)).filter(|count| *count > 0)
+				});
+				match data.state {
+					FLEXIBILITY_SELLING_STATE_CONFIRMED => {
+						SettledOffers::<T, I>::insert((flexibility_market_identifier, flexibility_market_timestamp, seller, buyer, asset_identifier), data);
+						settled += 1;
+					}
+					_ => removed += 1,
+				}
+			}
+			let remaining = offers.saturating_sub(removed + settled);
+			match remaining {
+				0 => PeriodOffers::<T, I>::remove(flexibility_market_identifier, flexibility_market_timestamp),
+				_ => PeriodOffers::<T, I>::insert(flexibility_market_identifier, flexibility_market_timestamp, remaining),
+			}
+
+			Self::deposit_event(Event::MarketPeriodClosed { flexibility_market_identifier, flexibility_market_timestamp, removed, settled, remaining });
+			Ok(Some(T::WeightInfo::close_market_period(removed + settled)).into())
+		}
+	}
+
+	// Market registry
+	impl<T: Config<I>, I: 'static> Pallet<T, I> {
+		fn is_known_role(role: u32) -> bool {
+			matches!(role, PARTICIPANT_ROLE_DSO | PARTICIPANT_ROLE_AGGREGATOR | PARTICIPANT_ROLE_PROSUMER | PARTICIPANT_ROLE_MARKET_OPERATOR)
+		}
+
+		/// Ensure that the seller and the buyer of an offer are registered participants and that its
+		/// market and asset are registered, returning the owner of the asset
+		fn ensure_registered_offer(seller: &T::AccountId, buyer: &T::AccountId, flexibility_market_identifier: u32, asset_identifier: u32) -> Result<T::AccountId, DispatchError> {
+			ensure!(Participants::<T, I>::contains_key(seller), Error::<T, I>::ParticipantNotExists);
+			ensure!(Participants::<T, I>::contains_key(buyer), Error::<T, I>::ParticipantNotExists);
+			ensure!(Markets::<T, I>::contains_key(flexibility_market_identifier), Error::<T, I>::MarketNotExists);
+			Assets::<T, I>::get(asset_identifier).ok_or_else(|| Error::<T, I>::AssetNotExists.into())
+		}
+	}
+
+	// Market periods
+	impl<T: Config<I>, I: 'static> Pallet<T, I> {
+		/// Whether the market period is closed, i.e. only its settled offers are kept
+		pub fn is_period_closed(flexibility_market_identifier: u32, flexibility_market_timestamp: u32) -> bool {
+			ClosedPeriods::<T, I>::contains_key(flexibility_market_identifier, flexibility_market_timestamp)
+		}
+
+		/// Ensure that the market period still takes offers and purchase decisions
+		fn ensure_open_period(flexibility_market_identifier: u32, flexibility_market_timestamp: u32) -> DispatchResult {
+			// The offers of the ledger before version 1 are not all in `FlexibilityOffers` yet
+			ensure!(!MigratingLedger::<T, I>::get(), Error::<T, I>::LedgerMigrating);
+			ensure!(!Self::is_period_closed(flexibility_market_identifier, flexibility_market_timestamp), Error::<T, I>::FlexibilityPeriodClosed);
+			Ok(())
+		}
+
+		/// Ensure that `origin` is the governance origin or the operator of the market
+		fn ensure_market_operator(origin: OriginFor<T>, flexibility_market_identifier: u32) -> DispatchResult {
+			let origin = match T::GovernanceOrigin::try_origin(origin) {
+				Ok(_) => return Ok(()),
+				Err(origin) => origin,
+			};
+			let who = ensure_signed(origin)?;
+			match Markets::<T, I>::get(flexibility_market_identifier) {
+				None => Err(Error::<T, I>::MarketNotExists.into()),
+				Some(operator) if operator == who => Ok(()),
+				Some(_) => Err(Error::<T, I>::NotMarketOperator.into()),
+			}
+		}
+
+		/// Offers of a market period, with their seller, buyer and asset
+		pub fn market_period_offers(flexibility_market_identifier: u32, flexibility_market_timestamp: u32) -> Vec<(T::AccountId, T::AccountId, u32, FlexibilitySellingData)> {
+			FlexibilityOffers::<T, I>::iter_prefix((flexibility_market_identifier, flexibility_market_timestamp))
+				.map(|((seller, buyer, asset_identifier), data)| (seller, buyer, asset_identifier, data))
+				.collect()
+		}
+
+		/// Confirmed offers kept from a closed market period, with their seller, buyer and asset
+		pub fn settled_period_offers(flexibility_market_identifier: u32, flexibility_market_timestamp: u32) -> Vec<(T::AccountId, T::AccountId, u32, FlexibilitySellingData)> {
+			SettledOffers::<T, I>::iter_prefix((flexibility_market_identifier, flexibility_market_timestamp))
+				.map(|((seller, buyer, asset_identifier), data)| (seller, buyer, asset_identifier, data))
+				.collect()
+		}
+	}
+
+	// Circuit breaker
+	impl<T: Config<I>, I: 'static> Pallet<T, I> {
+		fn pausable_call_name(call_name: &[u8]) -> Result<CallName, DispatchError> {
+			match PAUSABLE_CALLS.iter().any(|name| name.as_bytes() == call_name) {
+				true => Ok(CallName::truncate_from(call_name.to_vec())),
+				false => Err(Error::<T, I>::CallNotPausable.into()),
+			}
+		}
+
+		/// Whether `call` is paused by the circuit breaker, as a whole pallet, by name or by market
+		pub fn is_paused(call: &Call<T, I>) -> bool {
+			let call_name = call.get_call_name();
+			if !PAUSABLE_CALLS.contains(&call_name) {
+				return false
+			}
+			if PalletPaused::<T, I>::get() {
+				return true
+			}
+			if PausedCalls::<T, I>::contains_key(CallName::truncate_from(call_name.as_bytes().to_vec())) {
+				return true
+			}
+
+			match call {
+				Call::flexibility_selling { flexibility_market_identifier, .. } |
+				Call::flexibility_purchase_decision { flexibility_market_identifier, .. } =>
+					PausedMarkets::<T, I>::contains_key(flexibility_market_identifier),
+				_ => false,
+			}
+		}
+
+		pub fn circuit_breaker_status() -> CircuitBreakerStatus {
+			CircuitBreakerStatus {
+				pallet_paused: PalletPaused::<T, I>::get(),
+				paused_calls: PausedCalls::<T, I>::iter_keys().map(|name| name.into_inner()).collect(),
+				paused_markets: PausedMarkets::<T, I>::iter_keys().collect(),
+			}
+		}
 	}
 
 	// Queries
-	impl<T: Config> Pallet<T> {
+	impl<T: Config<I>, I: 'static> Pallet<T, I> {
 		pub fn get_payment(key_sender: T::AccountId, key_receiver: T::AccountId, timestamp: u32) -> u32 {
-			return Payments::<T>::get((&key_sender, &key_receiver, &timestamp))
+			return Payments::<T, I>::get((&key_sender, &key_receiver, &timestamp))
 		}
 
 		pub fn check_payment(key_sender: T::AccountId, key_receiver: T::AccountId, timestamp: u32) -> bool {
-			return Payments::<T>::contains_key((&key_sender, &key_receiver, &timestamp))
+			return Payments::<T, I>::contains_key((&key_sender, &key_receiver, &timestamp))
 		}
 
 		pub fn get_confirmation(key_sender: T::AccountId, key_receiver: T::AccountId, timestamp: u32) -> u32 {
-			return Payments::<T>::get((&key_sender, &key_receiver, &timestamp))
+			return Confirmations::<T, I>::get((&key_sender, &key_receiver, &timestamp))
 		}
 
 		pub fn check_confirmation(key_sender: T::AccountId, key_receiver: T::AccountId, timestamp: u32) -> bool {
-			return Confirmations::<T>::contains_key((&key_sender, &key_receiver, &timestamp))
+			return Confirmations::<T, I>::contains_key((&key_sender, &key_receiver, &timestamp))
 		}
 		pub fn get_flexibility_selling(seller: T::AccountId,
 									   buyer: T::AccountId,
 									   flexibility_market_identifier: u32,
 									   flexibility_market_timestamp: u32,
 									   asset_identifier: u32) -> FlexibilitySellingData {
-			return FlexibilityMarketLedger::<T>::get((&seller, &buyer, &flexibility_market_identifier, &flexibility_market_timestamp, &asset_identifier))
+			let key = (&flexibility_market_identifier, &flexibility_market_timestamp, &seller, &buyer, &asset_identifier);
+			// The offers confirmed in a closed period are only found among the settled offers
+			return SettledOffers::<T, I>::get(key).unwrap_or_else(|| FlexibilityOffers::<T, I>::get(key))
+		}
+	}
+
+	impl<T: Config<I>, I: 'static> FlexibilityLedger<T::AccountId> for Pallet<T, I> {
+		fn offer(key: &OfferKey<T::AccountId>) -> Option<FlexibilitySellingData> {
+			let key = (key.flexibility_market_identifier, key.flexibility_market_timestamp, &key.seller, &key.buyer, key.asset_identifier);
+			FlexibilityOffers::<T, I>::contains_key(key)
+				.then(|| FlexibilityOffers::<T, I>::get(key))
+				.or_else(|| SettledOffers::<T, I>::get(key))
+		}
+
+		fn offers_in_period(flexibility_market_identifier: u32, flexibility_market_timestamp: u32) -> Vec<(OfferKey<T::AccountId>, FlexibilitySellingData)> {
+			let mut offers = Self::market_period_offers(flexibility_market_identifier, flexibility_market_timestamp);
+			offers.extend(Self::settled_period_offers(flexibility_market_identifier, flexibility_market_timestamp));
+			offers
+				.into_iter()
+				.map(|(seller, buyer, asset_identifier, data)| {
+					let offer = OfferKey { seller, buyer, flexibility_market_identifier, flexibility_market_timestamp, asset_identifier };
+					(offer, data)
+				})
+				.collect()
+		}
+
+		fn payment(sender: &T::AccountId, receiver: &T::AccountId, ts: u32) -> Option<u32> {
+			Payments::<T, I>::contains_key((sender, receiver, ts)).then(|| Payments::<T, I>::get((sender, receiver, ts)))
+		}
+
+		fn confirmation(sender: &T::AccountId, receiver: &T::AccountId, ts: u32) -> Option<u32> {
+			Confirmations::<T, I>::contains_key((sender, receiver, ts)).then(|| Confirmations::<T, I>::get((sender, receiver, ts)))
+		}
+
+		fn participant_role(participant: &T::AccountId) -> Option<u32> {
+			Participants::<T, I>::get(participant)
+		}
+
+		fn market_operator(flexibility_market_identifier: u32) -> Option<T::AccountId> {
+			Markets::<T, I>::get(flexibility_market_identifier)
+		}
+
+		fn asset_owner(asset_identifier: u32) -> Option<T::AccountId> {
+			Assets::<T, I>::get(asset_identifier)
 		}
 	}
 }
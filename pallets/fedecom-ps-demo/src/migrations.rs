@@ -0,0 +1,126 @@
+//! Storage migrations of the pallet.
+
+/// Version 1: the offers of the flexibility market ledger are keyed by market and period.
+///
+/// Before version 1, `FlexibilityMarketLedger` keyed the offers by seller, buyer, market, market
+/// timestamp and asset, so that the offers of a market period were only found by going through
+/// the whole ledger. Version 1 moves them to `FlexibilityOffers`, keyed by market, market
+/// timestamp, seller, buyer and asset, and counts the offers of every period in `PeriodOffers`
+/// and of every seller in a period in `SellerOffers`.
+pub mod v1 {
+	use crate::{
+		pallet::{FlexibilityOffers, MigratingLedger, PeriodOffers, SellerOffers},
+		Config, Event, FlexibilitySellingData, Pallet, WeightInfo,
+	};
+	use frame_support::{
+		pallet_prelude::*,
+		sp_std::{marker::PhantomData, prelude::*},
+		traits::OnRuntimeUpgrade,
+	};
+	#[cfg(feature = "try-runtime")]
+	use frame_support::sp_runtime::TryRuntimeError;
+
+	/// Ledger of the offers before version 1.
+	#[frame_support::storage_alias]
+	pub type FlexibilityMarketLedger<T: Config<I>, I: 'static> = StorageNMap<
+		Pallet<T, I>,
+		(
+			// Seller
+			NMapKey<Blake2_128Concat, <T as frame_system::Config>::AccountId>,
+			// Buyer
+			NMapKey<Blake2_128Concat, <T as frame_system::Config>::AccountId>,
+			// Flexibility market identifier
+			NMapKey<Twox64Concat, u32>,
+			// Flexibility market timestamp
+			NMapKey<Twox64Concat, u32>,
+			// Asset/flexibility identifier
+			NMapKey<Twox64Concat, u32>,
+		),
+		FlexibilitySellingData,
+		OptionQuery,
+	>;
+
+	/// Number of offers moved by `on_initialize` in every block while the ledger is migrated.
+	pub const MIGRATED_OFFERS_PER_BLOCK: u32 = 100;
+
+	/// Start moving the offers of the ledger to the market-scoped ledger of version 1.
+	///
+	/// The upgrade block only sets the storage version and flags the migration: the offers are
+	/// then moved by `on_initialize`, [`MIGRATED_OFFERS_PER_BLOCK`] per block, so that the weight
+	/// of a block stays bounded whatever the size of the ledger. Until the ledger is migrated, the
+	/// market calls are rejected with `LedgerMigrating`.
+	pub struct MigrateToMarketScopedLedger<T, I = ()>(PhantomData<(T, I)>);
+
+	impl<T: Config<I>, I: 'static> OnRuntimeUpgrade for MigrateToMarketScopedLedger<T, I> {
+		fn on_runtime_upgrade() -> Weight {
+			if Pallet::<T, I>::on_chain_storage_version() >= 1 {
+				return T::DbWeight::get().reads(1)
+			}
+
+			if FlexibilityMarketLedger::<T, I>::iter_keys().next().is_some() {
+				MigratingLedger::<T, I>::put(true);
+			}
+			StorageVersion::new(1).put::<Pallet<T, I>>();
+
+			T::DbWeight::get().reads_writes(2, 2)
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn pre_upgrade() -> Result<Vec<u8>, TryRuntimeError> {
+			let offers = FlexibilityMarketLedger::<T, I>::iter_keys().count() as u32;
+			Ok(offers.encode())
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade(state: Vec<u8>) -> Result<(), TryRuntimeError> {
+			let offers = u32::decode(&mut &state[..]).map_err(|_| "Undecodable offer count")?;
+			// No offer is moved in the upgrade block
+			ensure!(
+				FlexibilityMarketLedger::<T, I>::iter_keys().count() as u32 == offers,
+				"Offers moved in the upgrade block"
+			);
+			ensure!(MigratingLedger::<T, I>::get() == (offers > 0), "Migration not flagged");
+			ensure!(Pallet::<T, I>::on_chain_storage_version() == 1, "Storage version not set");
+			Ok(())
+		}
+	}
+
+	/// Move at most `limit` offers of the ledger before version 1, returning the consumed weight.
+	///
+	/// The periods holding more than `MaxOffersPerPeriod` offers once migrated keep all of them:
+	/// a `MigratedPeriodAboveBound` event reports each of them, they take no more offers and take
+	/// several `close_market_period` calls to be closed. `LedgerMigrated` is deposited once the
+	/// whole ledger is moved.
+	pub fn migrate_offers<T: Config<I>, I: 'static>(limit: u32) -> Weight {
+		let mut offers = 0u64;
+		for ((seller, buyer, market, timestamp, asset), offer) in
+			FlexibilityMarketLedger::<T, I>::drain().take(limit as usize)
+		{
+			let count = PeriodOffers::<T, I>::mutate(market, timestamp, |count| {
+				*count = count.saturating_add(1);
+				*count
+			});
+			if count == T::MaxOffersPerPeriod::get().saturating_add(1) {
+				Pallet::<T, I>::deposit_event(Event::MigratedPeriodAboveBound {
+					flexibility_market_identifier: market,
+					flexibility_market_timestamp: timestamp,
+				});
+			}
+			SellerOffers::<T, I>::mutate((market, timestamp, &seller), |count| {
+				*count = count.saturating_add(1)
+			});
+			FlexibilityOffers::<T, I>::insert((market, timestamp, seller, buyer, asset), offer);
+			offers += 1;
+		}
+
+		// A last read finds out whether the ledger is empty
+		if FlexibilityMarketLedger::<T, I>::iter_keys().next().is_none() {
+			MigratingLedger::<T, I>::kill();
+			Pallet::<T, I>::deposit_event(Event::LedgerMigrated());
+		}
+
+		T::WeightInfo::migrate_offer()
+			.saturating_mul(offers)
+			.saturating_add(T::DbWeight::get().reads_writes(2, 1))
+	}
+}
@@ -1,7 +1,17 @@
 // use frame_support::pallet_prelude::Get;
 use crate as pallet_fedecom_ps_demo;
 // use crate as pallet_template;
-use frame_support::traits::{ConstU16, ConstU64};
+use crate::{
+	DeliveryDeviation, FlexibilitySellingData, OfferKey, OnDeliveryDisputed,
+	OnFlexibilityConfirmed, OnPaymentConfirmed, PARTICIPANT_ROLE_DSO,
+	PARTICIPANT_ROLE_MARKET_OPERATOR, PARTICIPANT_ROLE_PROSUMER,
+};
+use frame_support::{
+	ord_parameter_types, parameter_types,
+	traits::{ConstU16, ConstU32, ConstU64, Contains, OnFinalize, OnInitialize},
+	weights::Weight,
+};
+use frame_system::{EnsureRoot, EnsureSignedBy};
 use sp_core::H256;
 use sp_runtime::{
 	traits::{BlakeTwo256, IdentityLookup},
@@ -16,12 +26,31 @@ frame_support::construct_runtime!(
 	pub enum Test
 	{
 		System: frame_system,
-		FedecomPSDemo: pallet_fedecom_ps_demo
+		Timestamp: pallet_timestamp,
+		Balances: pallet_balances,
+		FedecomPSDemo: pallet_fedecom_ps_demo,
+		FedecomPSDemo2: pallet_fedecom_ps_demo::<Instance2>
 	}
 );
 
+/// Milliseconds between two blocks of the mock runtime.
+pub const MILLISECS_PER_BLOCK: u64 = 6000;
+
+/// Filter the calls paused by the circuit breaker, as the runtime does.
+pub struct MockCallFilter;
+
+impl Contains<RuntimeCall> for MockCallFilter {
+	fn contains(call: &RuntimeCall) -> bool {
+		match call {
+			RuntimeCall::FedecomPSDemo(call) => !FedecomPSDemo::is_paused(call),
+			RuntimeCall::FedecomPSDemo2(call) => !FedecomPSDemo2::is_paused(call),
+			_ => true,
+		}
+	}
+}
+
 impl frame_system::Config for Test {
-	type BaseCallFilter = frame_support::traits::Everything;
+	type BaseCallFilter = MockCallFilter;
 	type BlockWeights = ();
 	type BlockLength = ();
 	type DbWeight = ();
@@ -37,22 +66,245 @@ impl frame_system::Config for Test {
 	type BlockHashCount = ConstU64<250>;
 	type Version = ();
 	type PalletInfo = PalletInfo;
-	type AccountData = ();
+	type AccountData = pallet_balances::AccountData<u64>;
 	type OnNewAccount = ();
 	type OnKilledAccount = ();
 	type SystemWeightInfo = ();
 	type SS58Prefix = ConstU16<42>;
 	type OnSetCode = ();
-	type MaxConsumers = frame_support::traits::ConstU32<16>;
+	type MaxConsumers = ConstU32<16>;
+}
+
+impl pallet_timestamp::Config for Test {
+	type Moment = u64;
+	type OnTimestampSet = ();
+	type MinimumPeriod = ConstU64<{ MILLISECS_PER_BLOCK / 2 }>;
+	type WeightInfo = ();
+}
+
+impl pallet_balances::Config for Test {
+	type MaxLocks = ConstU32<50>;
+	type MaxReserves = ();
+	type ReserveIdentifier = [u8; 8];
+	type Balance = u64;
+	type RuntimeEvent = RuntimeEvent;
+	type DustRemoval = ();
+	type ExistentialDeposit = ConstU64<1>;
+	type AccountStore = System;
+	type WeightInfo = ();
+	type FreezeIdentifier = ();
+	type MaxFreezes = ();
+	type RuntimeHoldReason = ();
+	type MaxHolds = ();
+}
+
+/// Settlement hook called by the default instance of the pallet.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SettlementHook {
+	FlexibilityConfirmed(OfferKey<u64>, u32),
+	PaymentConfirmed(u64, u64, u32, u32),
+	DeliveryDisputed(u64, u64, u32, DeliveryDeviation),
+}
+
+parameter_types! {
+	/// Settlement hooks called so far, in order
+	pub static SettlementHooks: Vec<SettlementHook> = vec![];
+	/// Few offers per market period, so that the tests fill periods
+	pub const MaxOffersPerPeriod: u32 = 4;
+	/// Half a period per seller, so that a period is only filled by several sellers
+	pub const MaxOffersPerSeller: u32 = 2;
+}
+
+/// Weight consumed by every call of [`RecordSettlement`].
+pub const SETTLEMENT_HOOK_WEIGHT: Weight = Weight::from_parts(1_000, 0);
+
+/// Settlement handler recording its calls in [`SettlementHooks`].
+pub struct RecordSettlement;
+
+impl OnFlexibilityConfirmed<u64> for RecordSettlement {
+	fn on_flexibility_confirmed(
+		offer: &OfferKey<u64>,
+		_sale: &FlexibilitySellingData,
+		payment: u32,
+	) -> Weight {
+		SettlementHooks::mutate(|hooks| {
+			hooks.push(SettlementHook::FlexibilityConfirmed(offer.clone(), payment))
+		});
+		SETTLEMENT_HOOK_WEIGHT
+	}
+
+	fn weight() -> Weight {
+		SETTLEMENT_HOOK_WEIGHT
+	}
+}
+
+impl OnPaymentConfirmed<u64> for RecordSettlement {
+	fn on_payment_confirmed(sender: &u64, receiver: &u64, ts: u32, value: u32) -> Weight {
+		SettlementHooks::mutate(|hooks| {
+			hooks.push(SettlementHook::PaymentConfirmed(*sender, *receiver, ts, value))
+		});
+		SETTLEMENT_HOOK_WEIGHT
+	}
+
+	fn weight() -> Weight {
+		SETTLEMENT_HOOK_WEIGHT
+	}
+}
+
+impl OnDeliveryDisputed<u64> for RecordSettlement {
+	fn on_delivery_disputed(
+		sender: &u64,
+		receiver: &u64,
+		ts: u32,
+		deviation: DeliveryDeviation,
+	) -> Weight {
+		SettlementHooks::mutate(|hooks| {
+			hooks.push(SettlementHook::DeliveryDisputed(*sender, *receiver, ts, deviation))
+		});
+		SETTLEMENT_HOOK_WEIGHT
+	}
+
+	fn weight() -> Weight {
+		SETTLEMENT_HOOK_WEIGHT
+	}
 }
 
 impl pallet_fedecom_ps_demo::Config for Test {
 	type RuntimeEvent = RuntimeEvent;
 	type WeightInfo = ();
+	type GovernanceOrigin = EnsureRoot<u64>;
+	type PauseOrigin = EnsureRoot<u64>;
+	type OnFlexibilityConfirmed = RecordSettlement;
+	type OnPaymentConfirmed = RecordSettlement;
+	type OnDeliveryDisputed = RecordSettlement;
 	type MaxLength = ();
+	type MaxOffersPerPeriod = MaxOffersPerPeriod;
+	type MaxOffersPerSeller = MaxOffersPerSeller;
+}
+
+ord_parameter_types! {
+	/// Administrator of the second instance of the pallet
+	pub const MarketAdmin: u64 = 100;
+}
+
+/// Second flexibility market, governed by its own administrator.
+impl pallet_fedecom_ps_demo::Config<pallet_fedecom_ps_demo::Instance2> for Test {
+	type RuntimeEvent = RuntimeEvent;
+	type WeightInfo = ();
+	type GovernanceOrigin = EnsureSignedBy<MarketAdmin, u64>;
+	type PauseOrigin = EnsureSignedBy<MarketAdmin, u64>;
+	type OnFlexibilityConfirmed = ();
+	type OnPaymentConfirmed = ();
+	type OnDeliveryDisputed = ();
+	type MaxLength = ConstU32<64>;
+	type MaxOffersPerPeriod = MaxOffersPerPeriod;
+	type MaxOffersPerSeller = MaxOffersPerSeller;
 }
 
 // Build genesis storage according to the mock runtime.
 pub fn new_test_ext() -> sp_io::TestExternalities {
 	frame_system::GenesisConfig::<Test>::default().build_storage().unwrap().into()
 }
+
+/// Externalities of the ledger tests, whose offers need registered participants, markets and
+/// assets.
+///
+/// The prosumers 1, 4 and 5 sell the flexibility of their assets (200 to 203, 210 to 212 and 220)
+/// to the DSO 2, on the markets 100 and 101 operated by 3.
+pub fn market_test_ext() -> sp_io::TestExternalities {
+	ExtBuilder::default()
+		.participants(vec![
+			(1, PARTICIPANT_ROLE_PROSUMER),
+			(2, PARTICIPANT_ROLE_DSO),
+			(3, PARTICIPANT_ROLE_MARKET_OPERATOR),
+			(4, PARTICIPANT_ROLE_PROSUMER),
+			(5, PARTICIPANT_ROLE_PROSUMER),
+		])
+		.markets(vec![(100, 3), (101, 3)])
+		.assets(vec![
+			(200, 1),
+			(201, 1),
+			(202, 1),
+			(203, 1),
+			(210, 4),
+			(211, 4),
+			(212, 4),
+			(220, 5),
+		])
+		.build()
+}
+
+/// Builder of the externalities of a test, with endowed accounts, a registered market and a clock.
+#[derive(Default)]
+pub struct ExtBuilder {
+	balances: Vec<(u64, u64)>,
+	participants: Vec<(u64, u32)>,
+	markets: Vec<(u32, u64)>,
+	assets: Vec<(u32, u64)>,
+	timestamp: u64,
+}
+
+impl ExtBuilder {
+	/// Endow the accounts with the given free balances.
+	pub fn balances(mut self, balances: Vec<(u64, u64)>) -> Self {
+		self.balances = balances;
+		self
+	}
+
+	/// Register the market participants with their role.
+	pub fn participants(mut self, participants: Vec<(u64, u32)>) -> Self {
+		self.participants = participants;
+		self
+	}
+
+	/// Register the flexibility markets with their operator.
+	pub fn markets(mut self, markets: Vec<(u32, u64)>) -> Self {
+		self.markets = markets;
+		self
+	}
+
+	/// Register the assets/flexibilities with their owner.
+	pub fn assets(mut self, assets: Vec<(u32, u64)>) -> Self {
+		self.assets = assets;
+		self
+	}
+
+	/// Set the time of the first block, in milliseconds since the unix epoch.
+	pub fn timestamp(mut self, timestamp: u64) -> Self {
+		self.timestamp = timestamp;
+		self
+	}
+
+	/// Externalities at block 1, whose events are recorded.
+	pub fn build(self) -> sp_io::TestExternalities {
+		let storage = RuntimeGenesisConfig {
+			system: Default::default(),
+			balances: pallet_balances::GenesisConfig { balances: self.balances },
+			fedecom_ps_demo: pallet_fedecom_ps_demo::GenesisConfig {
+				participants: self.participants,
+				markets: self.markets,
+				assets: self.assets,
+				..Default::default()
+			},
+			fedecom_ps_demo2: Default::default(),
+		}
+		.build_storage()
+		.unwrap();
+
+		let mut ext = sp_io::TestExternalities::new(storage);
+		ext.execute_with(|| {
+			System::set_block_number(1);
+			Timestamp::set_timestamp(self.timestamp);
+		});
+		ext
+	}
+}
+
+/// Finalize the current block and initialize the next one, `MILLISECS_PER_BLOCK` later.
+pub fn next_block() {
+	Timestamp::on_finalize(System::block_number());
+	System::on_finalize(System::block_number());
+	System::set_block_number(System::block_number() + 1);
+	System::on_initialize(System::block_number());
+	Timestamp::set_timestamp(Timestamp::now() + MILLISECS_PER_BLOCK);
+}
@@ -0,0 +1,563 @@
+//! Model-based tests for pallet-fedecom-ps-demo.
+//!
+//! Random sequences of all the pallet calls of the market participants, and of the closing of
+//! market periods by governance, are generated with `proptest`, applied both to the mock runtime
+//! and to a plain in-memory reference model, and after every step the dispatch results, deposited
+//! events, storage contents and query functions are compared. Failing sequences are shrunk by
+//! `proptest` to a minimal reproduction.
+use crate::{
+	mock::*,
+	pallet::{
+		ClosedPeriods, Confirmations, FlexibilityOffers, Payments, PeriodOffers, SellerOffers,
+		SettledOffers,
+	},
+	Error, Event, FlexibilitySellingData, CONFIRMATION_NOK_OVERESTIMATION, CONFIRMATION_NOK_UNDERESTIMATION,
+	CONFIRMATION_OK, FLEXIBILITY_SELLING_STATE_CONFIRMED, FLEXIBILITY_SELLING_STATE_NOT_DECIDED,
+	FLEXIBILITY_SELLING_STATE_REJECTED, PARTICIPANT_ROLE_MARKET_OPERATOR,
+	PARTICIPANT_ROLE_PROSUMER,
+};
+use frame_support::dispatch::DispatchResult;
+use proptest::prelude::*;
+use sp_runtime::DispatchError;
+use std::collections::{BTreeMap, BTreeSet};
+
+type AccountId = u64;
+type PaymentKey = (AccountId, AccountId, u32);
+type LedgerKey = (AccountId, AccountId, u32, u32, u32);
+
+/// Registered participants: the account 4 is not registered.
+const PARTICIPANTS: [(AccountId, u32); 3] = [
+	(1, PARTICIPANT_ROLE_PROSUMER),
+	(2, PARTICIPANT_ROLE_PROSUMER),
+	(3, PARTICIPANT_ROLE_MARKET_OPERATOR),
+];
+/// Registered markets with their operator: the market 2 is not registered.
+const MARKETS: [(u32, AccountId); 2] = [(0, 3), (1, 3)];
+/// Registered assets with their owner: the asset 2 is not registered.
+const ASSETS: [(u32, AccountId); 2] = [(0, 1), (1, 2)];
+
+/// A single pallet call, signed by `origin`.
+#[derive(Clone, Debug)]
+enum Action {
+	CheckPaymentCall { origin: AccountId, key_sender: AccountId, key_receiver: AccountId, ts: u32 },
+	GetPaymentCall { origin: AccountId, key_sender: AccountId, key_receiver: AccountId, ts: u32 },
+	ModifyPayment { origin: AccountId, key_receiver: AccountId, ts: u32, value: u32 },
+	AddPayment { origin: AccountId, key_receiver: AccountId, ts: u32, value: u32 },
+	RemovePayment { origin: AccountId, key_receiver: AccountId, ts: u32 },
+	AddConfirmation { origin: AccountId, key_sender: AccountId, ts: u32, status: u32 },
+	RemoveConfirmation { origin: AccountId, key_sender: AccountId, ts: u32 },
+	FlexibilitySelling {
+		origin: AccountId,
+		buyer: AccountId,
+		market: u32,
+		market_ts: u32,
+		asset: u32,
+		sold_power: u32,
+		change_fct_w: u32,
+	},
+	FlexibilityPurchaseDecision {
+		origin: AccountId,
+		seller: AccountId,
+		market: u32,
+		market_ts: u32,
+		asset: u32,
+		new_state: u32,
+	},
+	/// Signed by root, the governance origin of the mock runtime
+	CloseMarketPeriod { market: u32, market_ts: u32 },
+}
+
+/// Reference model of the pallet storage.
+#[derive(Default, Debug)]
+struct Model {
+	payments: BTreeMap<PaymentKey, u32>,
+	confirmations: BTreeMap<PaymentKey, u32>,
+	/// Offers of the open periods, and confirmed offers of the closed periods
+	ledger: BTreeMap<LedgerKey, FlexibilitySellingData>,
+	closed: BTreeSet<(u32, u32)>,
+}
+
+impl Model {
+	/// Number of offers of an open market period.
+	fn period_offers(&self, market: u32, market_ts: u32) -> u32 {
+		if self.closed.contains(&(market, market_ts)) {
+			return 0
+		}
+		let in_period = |&&(_, _, m, ts, _): &&LedgerKey| (m, ts) == (market, market_ts);
+		self.ledger.keys().filter(in_period).count() as u32
+	}
+
+	/// Number of offers of a seller in an open market period.
+	fn seller_offers(&self, market: u32, market_ts: u32, seller: AccountId) -> u32 {
+		if self.closed.contains(&(market, market_ts)) {
+			return 0
+		}
+		let of_seller =
+			|&&(s, _, m, ts, _): &&LedgerKey| (s, m, ts) == (seller, market, market_ts);
+		self.ledger.keys().filter(of_seller).count() as u32
+	}
+
+	/// Owner of the asset of an offer whose participants, market and asset are registered.
+	fn registered_offer(
+		seller: AccountId,
+		buyer: AccountId,
+		market: u32,
+		asset: u32,
+	) -> Result<AccountId, Error<Test>> {
+		let registered = |who: AccountId| PARTICIPANTS.iter().any(|&(account, _)| account == who);
+		if !registered(seller) || !registered(buyer) {
+			return Err(Error::ParticipantNotExists)
+		}
+		if !MARKETS.iter().any(|&(registered, _)| registered == market) {
+			return Err(Error::MarketNotExists)
+		}
+		ASSETS
+			.iter()
+			.find(|&&(registered, _)| registered == asset)
+			.map(|&(_, owner)| owner)
+			.ok_or(Error::AssetNotExists)
+	}
+
+	/// Apply `action` to the model, returning the expected result and events.
+	fn apply(&mut self, action: &Action) -> (Result<(), Error<Test>>, Vec<Event<Test>>) {
+		match *action {
+			Action::CheckPaymentCall { key_sender, key_receiver, ts, .. } => {
+				let event = if self.payments.contains_key(&(key_sender, key_receiver, ts)) {
+					Event::InPayments()
+				} else {
+					Event::NotInPayments()
+				};
+				(Ok(()), vec![event])
+			},
+			Action::GetPaymentCall { key_sender, key_receiver, ts, .. } => {
+				let value =
+					self.payments.get(&(key_sender, key_receiver, ts)).copied().unwrap_or_default();
+				(Ok(()), vec![Event::GotFromPayments { key_sender, key_receiver, ts, value }])
+			},
+			Action::ModifyPayment { origin, key_receiver, ts, value } => {
+				let key = (origin, key_receiver, ts);
+				if !self.payments.contains_key(&key) {
+					return (Err(Error::PaymentNotExists), vec![])
+				}
+				if self.confirmations.contains_key(&key) {
+					return (Err(Error::ConfirmationAlreadyExists), vec![])
+				}
+				self.payments.insert(key, value);
+				(Ok(()), vec![Event::SetInPayments { key_sender: origin, key_receiver, ts }])
+			},
+			Action::AddPayment { origin, key_receiver, ts, value } => {
+				let key = (origin, key_receiver, ts);
+				if self.payments.contains_key(&key) {
+					return (Err(Error::PaymentAlreadyExists), vec![])
+				}
+				self.payments.insert(key, value);
+				(Ok(()), vec![Event::AddedToPayments { key_sender: origin, key_receiver, ts, value }])
+			},
+			Action::RemovePayment { origin, key_receiver, ts } => {
+				let key = (origin, key_receiver, ts);
+				if !self.payments.contains_key(&key) {
+					return (Err(Error::PaymentNotExists), vec![])
+				}
+				if self.confirmations.contains_key(&key) {
+					return (Err(Error::ConfirmationAlreadyExists), vec![])
+				}
+				self.payments.remove(&key);
+				(Ok(()), vec![Event::RemovedFromPayments { key_sender: origin, key_receiver, ts }])
+			},
+			Action::AddConfirmation { origin, key_sender, ts, status } => {
+				let key = (key_sender, origin, ts);
+				if !self.payments.contains_key(&key) {
+					return (Err(Error::PaymentNotExists), vec![])
+				}
+				if self.confirmations.contains_key(&key) {
+					return (Err(Error::ConfirmationAlreadyExists), vec![])
+				}
+				self.confirmations.insert(key, status);
+				let mut events = vec![Event::AddedToConfirmations {
+					key_confirmer: origin,
+					key_sender,
+					key_receiver: origin,
+					ts,
+					status,
+				}];
+				match status {
+					CONFIRMATION_OK => events.push(Event::ConfirmationOK()),
+					CONFIRMATION_NOK_OVERESTIMATION => events.push(Event::ConfirmationOverEstimation()),
+					CONFIRMATION_NOK_UNDERESTIMATION =>
+						events.push(Event::ConfirmationUnderEstimation()),
+					_ => {},
+				}
+				(Ok(()), events)
+			},
+			Action::RemoveConfirmation { origin, key_sender, ts } => {
+				let key = (key_sender, origin, ts);
+				if self.confirmations.remove(&key).is_none() {
+					return (Err(Error::ConfirmationNotExists), vec![])
+				}
+				(
+					Ok(()),
+					vec![Event::RemovedFromConfirmations { key_sender, key_receiver: origin, ts }],
+				)
+			},
+			Action::FlexibilitySelling {
+				origin,
+				buyer,
+				market,
+				market_ts,
+				asset,
+				sold_power,
+				change_fct_w,
+			} => {
+				match Self::registered_offer(origin, buyer, market, asset) {
+					Err(error) => return (Err(error), vec![]),
+					Ok(owner) if owner != origin => return (Err(Error::NotAssetOwner), vec![]),
+					Ok(_) => {},
+				}
+				if self.closed.contains(&(market, market_ts)) {
+					return (Err(Error::FlexibilityPeriodClosed), vec![])
+				}
+				let key = (origin, buyer, market, market_ts, asset);
+				if self.ledger.contains_key(&key) {
+					return (
+						Ok(()),
+						vec![Event::AlreadySoldFlexibility {
+							seller: origin,
+							buyer,
+							flexibility_market_identifier: market,
+							flexibility_market_timestamp: market_ts,
+							asset_identifier: asset,
+						}],
+					)
+				}
+				if self.period_offers(market, market_ts) >= MaxOffersPerPeriod::get() {
+					return (Err(Error::FlexibilityPeriodFull), vec![])
+				}
+				if self.seller_offers(market, market_ts, origin) >= MaxOffersPerSeller::get() {
+					return (Err(Error::SellerQuotaFull), vec![])
+				}
+				self.ledger.insert(
+					key,
+					FlexibilitySellingData {
+						sold_power,
+						change_fct_w,
+						state: FLEXIBILITY_SELLING_STATE_NOT_DECIDED,
+					},
+				);
+				(
+					Ok(()),
+					vec![Event::SuccessfullySoldFlexibility {
+						seller: origin,
+						buyer,
+						flexibility_market_identifier: market,
+						flexibility_market_timestamp: market_ts,
+						asset_identifier: asset,
+						sold_power,
+						change_fct_w,
+					}],
+				)
+			},
+			Action::FlexibilityPurchaseDecision {
+				origin,
+				seller,
+				market,
+				market_ts,
+				asset,
+				new_state,
+			} => {
+				if let Err(error) = Self::registered_offer(seller, origin, market, asset) {
+					return (Err(error), vec![])
+				}
+				if self.closed.contains(&(market, market_ts)) {
+					return (Err(Error::FlexibilityPeriodClosed), vec![])
+				}
+				let key = (seller, origin, market, market_ts, asset);
+				let Some(data) = self.ledger.get(&key).cloned() else {
+					// The event deposited before failing is rolled back together with the storage
+					return (Err(Error::FlexibilitySellingNotExisting), vec![])
+				};
+				if data.state != FLEXIBILITY_SELLING_STATE_NOT_DECIDED {
+					return (Err(Error::ConfirmationAlreadyExists), vec![])
+				}
+				match new_state {
+					FLEXIBILITY_SELLING_STATE_CONFIRMED => {
+						let Some(tkns_to_pay) = data.sold_power.checked_mul(data.change_fct_w)
+						else {
+							return (Err(Error::StorageOverflow), vec![])
+						};
+						let payment_key = (origin, seller, market_ts);
+						if self.confirmations.contains_key(&payment_key) {
+							return (Err(Error::ConfirmationAlreadyExists), vec![])
+						}
+						let payment = self.payments.get(&payment_key).copied().unwrap_or_default();
+						let Some(payment) = payment.checked_add(tkns_to_pay) else {
+							return (Err(Error::StorageOverflow), vec![])
+						};
+						self.ledger.insert(key, FlexibilitySellingData { state: new_state, ..data });
+						self.payments.insert(payment_key, payment);
+						(
+							Ok(()),
+							vec![Event::FlexibilitySellingConfirmed {
+								seller,
+								buyer: origin,
+								flexibility_market_identifier: market,
+								flexibility_market_timestamp: market_ts,
+								asset_identifier: asset,
+							}],
+						)
+					},
+					FLEXIBILITY_SELLING_STATE_REJECTED => {
+						self.ledger.insert(key, FlexibilitySellingData { state: new_state, ..data });
+						(
+							Ok(()),
+							vec![Event::FlexibilitySellingRejected {
+								seller,
+								buyer: origin,
+								flexibility_market_identifier: market,
+								flexibility_market_timestamp: market_ts,
+								asset_identifier: asset,
+							}],
+						)
+					},
+					_ => (Err(Error::FlexibilitySellingUnknownState), vec![]),
+				}
+			},
+			Action::CloseMarketPeriod { market, market_ts } => {
+				if !self.closed.insert((market, market_ts)) {
+					return (Err(Error::FlexibilityPeriodClosed), vec![])
+				}
+				// The confirmed offers are kept as settled offers
+				let offers = self.ledger.len();
+				self.ledger.retain(|&(_, _, m, ts, _), data| {
+					(m, ts) != (market, market_ts) || data.state == FLEXIBILITY_SELLING_STATE_CONFIRMED
+				});
+				let removed = (offers - self.ledger.len()) as u32;
+				let in_period = |&&(_, _, m, ts, _): &&LedgerKey| (m, ts) == (market, market_ts);
+				let settled = self.ledger.keys().filter(in_period).count() as u32;
+				(
+					Ok(()),
+					vec![Event::MarketPeriodClosed {
+						flexibility_market_identifier: market,
+						flexibility_market_timestamp: market_ts,
+						removed,
+						settled,
+						remaining: 0,
+					}],
+				)
+			},
+		}
+	}
+}
+
+/// Dispatch `action` against the mock runtime.
+fn dispatch(action: &Action) -> DispatchResult {
+	match action.clone() {
+		Action::CheckPaymentCall { origin, key_sender, key_receiver, ts } =>
+			FedecomPSDemo::check_payment_call(RuntimeOrigin::signed(origin), key_sender, key_receiver, ts),
+		Action::GetPaymentCall { origin, key_sender, key_receiver, ts } =>
+			FedecomPSDemo::get_payment_call(RuntimeOrigin::signed(origin), key_sender, key_receiver, ts),
+		Action::ModifyPayment { origin, key_receiver, ts, value } =>
+			FedecomPSDemo::modify_payment(RuntimeOrigin::signed(origin), key_receiver, ts, value),
+		Action::AddPayment { origin, key_receiver, ts, value } =>
+			FedecomPSDemo::add_payment(RuntimeOrigin::signed(origin), key_receiver, ts, value),
+		Action::RemovePayment { origin, key_receiver, ts } =>
+			FedecomPSDemo::remove_payment(RuntimeOrigin::signed(origin), key_receiver, ts),
+		Action::AddConfirmation { origin, key_sender, ts, status } =>
+			FedecomPSDemo::add_confirmation(RuntimeOrigin::signed(origin), key_sender, ts, status)
+				.map(|_| ())
+				.map_err(|e| e.error),
+		Action::RemoveConfirmation { origin, key_sender, ts } =>
+			FedecomPSDemo::remove_confirmation(RuntimeOrigin::signed(origin), key_sender, ts),
+		Action::FlexibilitySelling {
+			origin,
+			buyer,
+			market,
+			market_ts,
+			asset,
+			sold_power,
+			change_fct_w,
+		} => FedecomPSDemo::flexibility_selling(
+			RuntimeOrigin::signed(origin),
+			buyer,
+			market,
+			market_ts,
+			asset,
+			sold_power,
+			change_fct_w,
+		),
+		Action::FlexibilityPurchaseDecision { origin, seller, market, market_ts, asset, new_state } =>
+			FedecomPSDemo::flexibility_purchase_decision(
+				RuntimeOrigin::signed(origin),
+				seller,
+				market,
+				market_ts,
+				asset,
+				new_state,
+			)
+			.map(|_| ())
+			.map_err(|e| e.error),
+		Action::CloseMarketPeriod { market, market_ts } =>
+			FedecomPSDemo::close_market_period(RuntimeOrigin::root(), market, market_ts)
+				.map(|_| ())
+				.map_err(|e| e.error),
+	}
+}
+
+/// Pallet events deposited since the last reset.
+fn pallet_events() -> Vec<Event<Test>> {
+	System::events()
+		.into_iter()
+		.filter_map(|record| match record.event {
+			RuntimeEvent::FedecomPSDemo(event) => Some(event),
+			_ => None,
+		})
+		.collect()
+}
+
+/// Compare the whole pallet storage and the query functions with the model.
+fn check_storage(model: &Model) -> Result<(), TestCaseError> {
+	let payments: BTreeMap<_, _> = Payments::<Test>::iter().collect();
+	prop_assert_eq!(&payments, &model.payments);
+	let confirmations: BTreeMap<_, _> = Confirmations::<Test>::iter().collect();
+	prop_assert_eq!(&confirmations, &model.confirmations);
+	let ledger: BTreeMap<_, _> = FlexibilityOffers::<Test>::iter()
+		.chain(SettledOffers::<Test>::iter())
+		.map(|((market, market_ts, seller, buyer, asset), data)| {
+			((seller, buyer, market, market_ts, asset), data)
+		})
+		.collect();
+	prop_assert_eq!(&ledger, &model.ledger);
+	let closed: BTreeSet<_> = ClosedPeriods::<Test>::iter_keys().collect();
+	prop_assert_eq!(&closed, &model.closed);
+	for (market, market_ts, offers) in PeriodOffers::<Test>::iter() {
+		prop_assert_eq!(offers, model.period_offers(market, market_ts));
+	}
+	for ((market, market_ts, seller), offers) in SellerOffers::<Test>::iter() {
+		prop_assert_eq!(offers, model.seller_offers(market, market_ts, seller));
+	}
+	let offers: u32 = PeriodOffers::<Test>::iter_values().sum();
+	prop_assert_eq!(offers as usize, FlexibilityOffers::<Test>::iter_keys().count());
+	let seller_offers: u32 = SellerOffers::<Test>::iter_values().sum();
+	prop_assert_eq!(seller_offers, offers);
+
+	for &(sender, receiver, ts) in model.payments.keys().chain(model.confirmations.keys()) {
+		prop_assert_eq!(
+			FedecomPSDemo::check_payment(sender, receiver, ts),
+			model.payments.contains_key(&(sender, receiver, ts))
+		);
+		prop_assert_eq!(
+			FedecomPSDemo::get_payment(sender, receiver, ts),
+			model.payments.get(&(sender, receiver, ts)).copied().unwrap_or_default()
+		);
+		prop_assert_eq!(
+			FedecomPSDemo::check_confirmation(sender, receiver, ts),
+			model.confirmations.contains_key(&(sender, receiver, ts))
+		);
+		prop_assert_eq!(
+			FedecomPSDemo::get_confirmation(sender, receiver, ts),
+			model.confirmations.get(&(sender, receiver, ts)).copied().unwrap_or_default()
+		);
+	}
+	for (&(seller, buyer, market, market_ts, asset), data) in model.ledger.iter() {
+		prop_assert_eq!(
+			&FedecomPSDemo::get_flexibility_selling(seller, buyer, market, market_ts, asset),
+			data
+		);
+	}
+	Ok(())
+}
+
+// A handful of accounts and timestamps keep key collisions, and thus interesting paths, frequent.
+fn account() -> impl Strategy<Value = AccountId> {
+	1..=4u64
+}
+
+fn small() -> impl Strategy<Value = u32> {
+	0..3u32
+}
+
+fn amount() -> impl Strategy<Value = u32> {
+	prop_oneof![4 => 0..1_000u32, 1 => any::<u32>()]
+}
+
+fn state() -> impl Strategy<Value = u32> {
+	0..=4u32
+}
+
+fn action() -> impl Strategy<Value = Action> {
+	prop_oneof![
+		(account(), account(), account(), small()).prop_map(|(origin, key_sender, key_receiver, ts)| {
+			Action::CheckPaymentCall { origin, key_sender, key_receiver, ts }
+		}),
+		(account(), account(), account(), small()).prop_map(|(origin, key_sender, key_receiver, ts)| {
+			Action::GetPaymentCall { origin, key_sender, key_receiver, ts }
+		}),
+		(account(), account(), small(), amount()).prop_map(|(origin, key_receiver, ts, value)| {
+			Action::ModifyPayment { origin, key_receiver, ts, value }
+		}),
+		(account(), account(), small(), amount()).prop_map(|(origin, key_receiver, ts, value)| {
+			Action::AddPayment { origin, key_receiver, ts, value }
+		}),
+		(account(), account(), small())
+			.prop_map(|(origin, key_receiver, ts)| Action::RemovePayment { origin, key_receiver, ts }),
+		(account(), account(), small(), state()).prop_map(|(origin, key_sender, ts, status)| {
+			Action::AddConfirmation { origin, key_sender, ts, status }
+		}),
+		(account(), account(), small())
+			.prop_map(|(origin, key_sender, ts)| Action::RemoveConfirmation { origin, key_sender, ts }),
+		(account(), account(), small(), small(), small(), amount(), amount()).prop_map(
+			|(origin, buyer, market, market_ts, asset, sold_power, change_fct_w)| {
+				Action::FlexibilitySelling {
+					origin,
+					buyer,
+					market,
+					market_ts,
+					asset,
+					sold_power,
+					change_fct_w,
+				}
+			}
+		),
+		(account(), account(), small(), small(), small(), state()).prop_map(
+			|(origin, seller, market, market_ts, asset, new_state)| {
+				Action::FlexibilityPurchaseDecision {
+					origin,
+					seller,
+					market,
+					market_ts,
+					asset,
+					new_state,
+				}
+			}
+		),
+		(small(), small())
+			.prop_map(|(market, market_ts)| Action::CloseMarketPeriod { market, market_ts }),
+	]
+}
+
+proptest! {
+	#![proptest_config(ProptestConfig::with_cases(256))]
+
+	#[test]
+	fn runtime_matches_model(actions in prop::collection::vec(action(), 1..64)) {
+		let ext = ExtBuilder::default()
+			.participants(PARTICIPANTS.to_vec())
+			.markets(MARKETS.to_vec())
+			.assets(ASSETS.to_vec())
+			.build();
+		ext.execute_with(|| -> Result<(), TestCaseError> {
+			let mut model = Model::default();
+
+			for action in actions.iter() {
+				System::reset_events();
+
+				let (expected_result, expected_events) = model.apply(action);
+				let result = dispatch(action);
+
+				prop_assert_eq!(result, expected_result.map_err(DispatchError::from), "{:?}", action);
+				prop_assert_eq!(pallet_events(), expected_events, "{:?}", action);
+				check_storage(&model)?;
+			}
+			Ok(())
+		})?;
+	}
+}
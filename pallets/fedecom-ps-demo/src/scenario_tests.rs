@@ -0,0 +1,515 @@
+//! Scenario tests for pallet-fedecom-ps-demo.
+//!
+//! A scenario is a RON file of the `scenarios` directory describing, block by block, the calls
+//! dispatched on the mock runtime and the expected outcome: the result of every call, the events
+//! deposited by the pallet in the block and the contents of the storage at its end. Market
+//! regression tests are written as scenarios, without Rust; every scenario of the directory is
+//! run by `scenarios_pass`, which reports all the unmet expectations of all the scenarios.
+//!
+//! The calls are those of the pallet, with the same parameter names, and the roles, offer states
+//! and confirmation statuses are written by name (e.g. `Prosumer`, `Confirmed`, `OverEstimation`)
+//! or as `Code(n)` for any other value. Events are written as their `Debug` format, e.g.
+//! `"ParticipantRemoved { participant: 2 }"`, and errors by name, e.g. `"PaymentNotExists"`,
+//! `"CallFiltered"` or `"BadOrigin"`.
+use crate::{
+	mock::*,
+	pallet::{
+		Confirmations, FlexibilityOffers, Participants, Payments, PeriodOffers, SettledOffers,
+	},
+	FlexibilitySellingData, CONFIRMATION_NOK_OVERESTIMATION, CONFIRMATION_NOK_UNDERESTIMATION,
+	CONFIRMATION_OK, FLEXIBILITY_SELLING_STATE_CONFIRMED, FLEXIBILITY_SELLING_STATE_NOT_DECIDED,
+	FLEXIBILITY_SELLING_STATE_REJECTED, PARTICIPANT_ROLE_AGGREGATOR, PARTICIPANT_ROLE_DSO,
+	PARTICIPANT_ROLE_MARKET_OPERATOR, PARTICIPANT_ROLE_PROSUMER,
+};
+use serde::Deserialize;
+use sp_runtime::{traits::Dispatchable, DispatchError, ModuleError};
+use std::{fs, path::Path};
+
+type AccountId = u64;
+
+/// Role of a market participant.
+#[derive(Debug, Clone, Copy, Deserialize)]
+enum Role {
+	Dso,
+	Aggregator,
+	Prosumer,
+	MarketOperator,
+	Code(u32),
+}
+
+impl Role {
+	fn code(self) -> u32 {
+		match self {
+			Role::Dso => PARTICIPANT_ROLE_DSO,
+			Role::Aggregator => PARTICIPANT_ROLE_AGGREGATOR,
+			Role::Prosumer => PARTICIPANT_ROLE_PROSUMER,
+			Role::MarketOperator => PARTICIPANT_ROLE_MARKET_OPERATOR,
+			Role::Code(code) => code,
+		}
+	}
+}
+
+/// State of a flexibility offer.
+#[derive(Debug, Clone, Copy, Deserialize)]
+enum OfferState {
+	NotDecided,
+	Confirmed,
+	Rejected,
+	Code(u32),
+}
+
+impl OfferState {
+	fn code(self) -> u32 {
+		match self {
+			OfferState::NotDecided => FLEXIBILITY_SELLING_STATE_NOT_DECIDED,
+			OfferState::Confirmed => FLEXIBILITY_SELLING_STATE_CONFIRMED,
+			OfferState::Rejected => FLEXIBILITY_SELLING_STATE_REJECTED,
+			OfferState::Code(code) => code,
+		}
+	}
+}
+
+/// Status of a payment confirmation.
+#[derive(Debug, Clone, Copy, Deserialize)]
+enum Status {
+	Ok,
+	OverEstimation,
+	UnderEstimation,
+	Code(u32),
+}
+
+impl Status {
+	fn code(self) -> u32 {
+		match self {
+			Status::Ok => CONFIRMATION_OK,
+			Status::OverEstimation => CONFIRMATION_NOK_OVERESTIMATION,
+			Status::UnderEstimation => CONFIRMATION_NOK_UNDERESTIMATION,
+			Status::Code(code) => code,
+		}
+	}
+}
+
+/// State of the mock runtime before the first block.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Genesis {
+	balances: Vec<(AccountId, u64)>,
+	participants: Vec<(AccountId, Role)>,
+	markets: Vec<(u32, AccountId)>,
+	assets: Vec<(u32, AccountId)>,
+	/// Time of the first block, in milliseconds since the unix epoch
+	timestamp: u64,
+}
+
+/// Origin of a call.
+#[derive(Debug, Clone, Copy, Deserialize)]
+enum Origin {
+	Signed(AccountId),
+	Root,
+}
+
+/// Call of the pallet, with the names of its parameters.
+#[derive(Debug, Clone, Deserialize)]
+enum Call {
+	CheckPaymentCall { key_sender: AccountId, key_receiver: AccountId, ts: u32 },
+	GetPaymentCall { key_sender: AccountId, key_receiver: AccountId, ts: u32 },
+	ModifyPayment { key_receiver: AccountId, ts: u32, value: u32 },
+	AddPayment { key_receiver: AccountId, ts: u32, value: u32 },
+	RemovePayment { key_receiver: AccountId, ts: u32 },
+	AddConfirmation { key_sender: AccountId, ts: u32, status: Status },
+	RemoveConfirmation { key_sender: AccountId, ts: u32 },
+	FlexibilitySelling {
+		buyer: AccountId,
+		flexibility_market_identifier: u32,
+		flexibility_market_timestamp: u32,
+		asset_identifier: u32,
+		sold_power: u32,
+		change_fct_w: u32,
+	},
+	FlexibilityPurchaseDecision {
+		seller: AccountId,
+		flexibility_market_identifier: u32,
+		flexibility_market_timestamp: u32,
+		asset_identifier: u32,
+		new_state: OfferState,
+	},
+	RegisterParticipant { participant: AccountId, role: Role },
+	RemoveParticipant { participant: AccountId },
+	RegisterMarket { flexibility_market_identifier: u32, operator: AccountId },
+	RemoveMarket { flexibility_market_identifier: u32 },
+	RegisterAsset { asset_identifier: u32, owner: AccountId },
+	PausePallet,
+	UnpausePallet,
+	PauseCall { call_name: String },
+	UnpauseCall { call_name: String },
+	PauseMarket { flexibility_market_identifier: u32 },
+	UnpauseMarket { flexibility_market_identifier: u32 },
+	CloseMarketPeriod { flexibility_market_identifier: u32, flexibility_market_timestamp: u32 },
+}
+
+impl Call {
+	fn runtime_call(self) -> RuntimeCall {
+		use crate::Call as C;
+		let call = match self {
+			Call::CheckPaymentCall { key_sender, key_receiver, ts } =>
+				C::check_payment_call { key_sender, key_receiver, ts },
+			Call::GetPaymentCall { key_sender, key_receiver, ts } =>
+				C::get_payment_call { key_sender, key_receiver, ts },
+			Call::ModifyPayment { key_receiver, ts, value } =>
+				C::modify_payment { key_receiver, ts, value },
+			Call::AddPayment { key_receiver, ts, value } =>
+				C::add_payment { key_receiver, ts, value },
+			Call::RemovePayment { key_receiver, ts } => C::remove_payment { key_receiver, ts },
+			Call::AddConfirmation { key_sender, ts, status } =>
+				C::add_confirmation { key_sender, ts, status: status.code() },
+			Call::RemoveConfirmation { key_sender, ts } =>
+				C::remove_confirmation { key_sender, ts },
+			Call::FlexibilitySelling {
+				buyer,
+				flexibility_market_identifier,
+				flexibility_market_timestamp,
+				asset_identifier,
+				sold_power,
+				change_fct_w,
+			} => C::flexibility_selling {
+				buyer,
+				flexibility_market_identifier,
+				flexibility_market_timestamp,
+				asset_identifier,
+				sold_power,
+				change_fct_w,
+			},
+			Call::FlexibilityPurchaseDecision {
+				seller,
+				flexibility_market_identifier,
+				flexibility_market_timestamp,
+				asset_identifier,
+				new_state,
+			} => C::flexibility_purchase_decision {
+				seller,
+				flexibility_market_identifier,
+				flexibility_market_timestamp,
+				asset_identifier,
+				new_state: new_state.code(),
+			},
+			Call::RegisterParticipant { participant, role } =>
+				C::register_participant { participant, role: role.code() },
+			Call::RemoveParticipant { participant } => C::remove_participant { participant },
+			Call::RegisterMarket { flexibility_market_identifier, operator } =>
+				C::register_market { flexibility_market_identifier, operator },
+			Call::RemoveMarket { flexibility_market_identifier } =>
+				C::remove_market { flexibility_market_identifier },
+			Call::RegisterAsset { asset_identifier, owner } =>
+				C::register_asset { asset_identifier, owner },
+			Call::PausePallet => C::pause_pallet {},
+			Call::UnpausePallet => C::unpause_pallet {},
+			Call::PauseCall { call_name } => C::pause_call { call_name: call_name.into_bytes() },
+			Call::UnpauseCall { call_name } =>
+				C::unpause_call { call_name: call_name.into_bytes() },
+			Call::PauseMarket { flexibility_market_identifier } =>
+				C::pause_market { flexibility_market_identifier },
+			Call::UnpauseMarket { flexibility_market_identifier } =>
+				C::unpause_market { flexibility_market_identifier },
+			Call::CloseMarketPeriod {
+				flexibility_market_identifier,
+				flexibility_market_timestamp,
+			} => C::close_market_period {
+				flexibility_market_identifier,
+				flexibility_market_timestamp,
+			},
+		};
+		RuntimeCall::FedecomPSDemo(call)
+	}
+}
+
+/// Expected result of a call.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+enum Outcome {
+	#[default]
+	Ok,
+	/// The call fails with the named error
+	Err(String),
+}
+
+/// Call dispatched in a block.
+#[derive(Debug, Deserialize)]
+struct Action {
+	origin: Origin,
+	call: Call,
+	#[serde(default)]
+	expect: Outcome,
+}
+
+/// Offer of the flexibility market ledger, or settled offer of a closed period.
+#[derive(Debug, Deserialize)]
+struct Offer {
+	sold_power: u32,
+	change_fct_w: u32,
+	state: OfferState,
+}
+
+/// Expected storage entry at the end of a block, `None` when the entry must be absent.
+#[derive(Debug, Deserialize)]
+enum Entry {
+	Payment { key_sender: AccountId, key_receiver: AccountId, ts: u32, value: Option<u32> },
+	Confirmation {
+		key_sender: AccountId,
+		key_receiver: AccountId,
+		ts: u32,
+		status: Option<Status>,
+	},
+	Offer {
+		seller: AccountId,
+		buyer: AccountId,
+		flexibility_market_identifier: u32,
+		flexibility_market_timestamp: u32,
+		asset_identifier: u32,
+		offer: Option<Offer>,
+	},
+	/// Number of offers of a market period
+	PeriodOffers {
+		flexibility_market_identifier: u32,
+		flexibility_market_timestamp: u32,
+		offers: u32,
+	},
+	Participant { participant: AccountId, role: Option<Role> },
+	Balance { account: AccountId, free: u64 },
+}
+
+impl Entry {
+	/// Description of the mismatch between the entry and the storage, if any.
+	fn check(&self) -> Option<String> {
+		let (expected, actual) = match self {
+			Entry::Payment { key_sender, key_receiver, ts, value } => {
+				let key = (key_sender, key_receiver, ts);
+				let actual =
+					Payments::<Test>::contains_key(key).then(|| Payments::<Test>::get(key));
+				(format!("{:?}", value), format!("{:?}", actual))
+			},
+			Entry::Confirmation { key_sender, key_receiver, ts, status } => {
+				let key = (key_sender, key_receiver, ts);
+				let actual = Confirmations::<Test>::contains_key(key)
+					.then(|| Confirmations::<Test>::get(key));
+				(format!("{:?}", status.map(Status::code)), format!("{:?}", actual))
+			},
+			Entry::Offer {
+				seller,
+				buyer,
+				flexibility_market_identifier,
+				flexibility_market_timestamp,
+				asset_identifier,
+				offer,
+			} => {
+				let key = (
+					flexibility_market_identifier,
+					flexibility_market_timestamp,
+					seller,
+					buyer,
+					asset_identifier,
+				);
+				let offer = offer.as_ref().map(|offer| FlexibilitySellingData {
+					sold_power: offer.sold_power,
+					change_fct_w: offer.change_fct_w,
+					state: offer.state.code(),
+				});
+				// The offers confirmed in a closed period are kept as settled offers
+				let actual = FlexibilityOffers::<Test>::contains_key(key)
+					.then(|| FlexibilityOffers::<Test>::get(key))
+					.or_else(|| SettledOffers::<Test>::get(key));
+				(format!("{:?}", offer), format!("{:?}", actual))
+			},
+			Entry::PeriodOffers {
+				flexibility_market_identifier,
+				flexibility_market_timestamp,
+				offers,
+			} => {
+				let actual = PeriodOffers::<Test>::get(
+					flexibility_market_identifier,
+					flexibility_market_timestamp,
+				);
+				(offers.to_string(), actual.to_string())
+			},
+			Entry::Participant { participant, role } => (
+				format!("{:?}", role.map(Role::code)),
+				format!("{:?}", Participants::<Test>::get(participant)),
+			),
+			Entry::Balance { account, free } =>
+				(free.to_string(), Balances::free_balance(account).to_string()),
+		};
+		(expected != actual)
+			.then(|| format!("{:?}: expected {}, found {}", self, expected, actual))
+	}
+}
+
+/// Block of a scenario.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Block {
+	/// Time of the block, by default `MILLISECS_PER_BLOCK` after the previous one
+	timestamp: Option<u64>,
+	actions: Vec<Action>,
+	/// Events deposited by the pallet in the block, in order, unchecked when omitted
+	events: Option<Vec<String>>,
+	storage: Vec<Entry>,
+}
+
+/// Market scenario, run on the mock runtime.
+#[derive(Debug, Deserialize)]
+struct Scenario {
+	description: String,
+	#[serde(default)]
+	genesis: Genesis,
+	blocks: Vec<Block>,
+}
+
+/// Name of a dispatch error: the name of the pallet error, or the kind of the other errors.
+fn error_name(error: DispatchError) -> String {
+	match error {
+		DispatchError::Module(ModuleError { message: Some(message), .. }) => message.into(),
+		error => format!("{:?}", error),
+	}
+}
+
+/// Events deposited by the pallet since the beginning of the block.
+fn pallet_events() -> Vec<String> {
+	System::events()
+		.into_iter()
+		.filter_map(|record| match record.event {
+			RuntimeEvent::FedecomPSDemo(event) => Some(format!("{:?}", event)),
+			_ => None,
+		})
+		.collect()
+}
+
+impl Scenario {
+	fn parse(text: &str) -> Result<Self, String> {
+		ron::from_str(text).map_err(|e| e.to_string())
+	}
+
+	/// Run the scenario and return its unmet expectations.
+	fn run(self) -> Vec<String> {
+		let genesis = self.genesis;
+		let participants = genesis.participants.iter().map(|(a, role)| (*a, role.code())).collect();
+		let mut ext = ExtBuilder::default()
+			.balances(genesis.balances)
+			.participants(participants)
+			.markets(genesis.markets)
+			.assets(genesis.assets)
+			.timestamp(genesis.timestamp)
+			.build();
+
+		let mut failures = Vec::new();
+		ext.execute_with(|| {
+			for (position, block) in self.blocks.into_iter().enumerate() {
+				if position > 0 {
+					next_block();
+				}
+				if let Some(timestamp) = block.timestamp {
+					Timestamp::set_timestamp(timestamp);
+				}
+				let number = System::block_number();
+
+				for (index, action) in block.actions.into_iter().enumerate() {
+					let origin = match action.origin {
+						Origin::Signed(account) => RuntimeOrigin::signed(account),
+						Origin::Root => RuntimeOrigin::root(),
+					};
+					let result = action.call.clone().runtime_call().dispatch(origin);
+					let outcome = match result {
+						Ok(_) => Outcome::Ok,
+						Err(e) => Outcome::Err(error_name(e.error)),
+					};
+					if outcome != action.expect {
+						failures.push(format!(
+							"block {}, action {} {:?}: expected {:?}, found {:?}",
+							number, index, action.call, action.expect, outcome
+						));
+					}
+				}
+
+				if let Some(expected) = block.events {
+					let events = pallet_events();
+					if events != expected {
+						failures.push(format!(
+							"block {}: expected events {:#?}, found {:#?}",
+							number, expected, events
+						));
+					}
+				}
+				for entry in &block.storage {
+					if let Some(failure) = entry.check() {
+						failures.push(format!("block {}: {}", number, failure));
+					}
+				}
+			}
+		});
+		failures
+	}
+}
+
+#[test]
+fn scenarios_pass() {
+	let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios");
+	let mut paths: Vec<_> = fs::read_dir(&directory)
+		.unwrap()
+		.map(|entry| entry.unwrap().path())
+		.filter(|path| path.extension().map_or(false, |extension| extension == "ron"))
+		.collect();
+	paths.sort();
+	assert!(!paths.is_empty(), "No scenario in {}", directory.display());
+
+	let mut failures = Vec::new();
+	for path in paths {
+		let name = path.file_name().unwrap().to_string_lossy().into_owned();
+		match Scenario::parse(&fs::read_to_string(&path).unwrap()) {
+			Ok(scenario) => {
+				let description = scenario.description.clone();
+				for failure in scenario.run() {
+					failures.push(format!("{} ({}): {}", name, description, failure));
+				}
+			},
+			Err(e) => failures.push(format!("{}: {}", name, e)),
+		}
+	}
+	assert!(failures.is_empty(), "Failed scenarios:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn unmet_expectations_are_reported() {
+	let scenario = Scenario::parse(
+		r#"Scenario(
+			description: "Wrong expectations",
+			genesis: (balances: [(1, 100)]),
+			blocks: [
+				(
+					actions: [
+						(origin: Signed(1), call: AddPayment(key_receiver: 2, ts: 7, value: 10)),
+						(
+							origin: Signed(1),
+							call: AddPayment(key_receiver: 2, ts: 7, value: 10),
+							expect: Err("PaymentNotExists"),
+						),
+						(origin: Signed(1), call: PausePallet, expect: Err("BadOrigin")),
+					],
+					events: Some([]),
+				),
+				(
+					storage: [
+						Payment(key_sender: 1, key_receiver: 2, ts: 7, value: Some(11)),
+						Balance(account: 1, free: 100),
+					],
+				),
+			],
+		)"#,
+	)
+	.unwrap();
+
+	let failures = scenario.run();
+	assert_eq!(failures.len(), 3, "{:#?}", failures);
+	assert!(failures[0].starts_with("block 1, action 1 AddPayment"));
+	assert!(failures[0]
+		.ends_with(r#"expected Err("PaymentNotExists"), found Err("PaymentAlreadyExists")"#));
+	assert!(failures[1].starts_with("block 1: expected events"));
+	assert!(failures[2].starts_with("block 2: Payment"));
+	assert!(failures[2].ends_with("expected Some(11), found Some(10)"));
+}
@@ -1,5 +1,11 @@
-use crate::{FlexibilitySellingData, FLEXIBILITY_SELLING_STATE_NOT_DECIDED, FLEXIBILITY_SELLING_STATE_CONFIRMED, FLEXIBILITY_SELLING_STATE_REJECTED, mock::*};
-use frame_support::{assert_ok};
+use crate::{Error, FlexibilitySellingData, FLEXIBILITY_SELLING_STATE_NOT_DECIDED, FLEXIBILITY_SELLING_STATE_CONFIRMED, FLEXIBILITY_SELLING_STATE_REJECTED, PARTICIPANT_ROLE_DSO, PARTICIPANT_ROLE_MARKET_OPERATOR, PARTICIPANT_ROLE_PROSUMER, mock::*};
+use crate::{CONFIRMATION_OK, CONFIRMATION_NOK_OVERESTIMATION, CONFIRMATION_NOK_UNDERESTIMATION, DeliveryDeviation, FlexibilityLedger, OfferKey, OnPaymentConfirmed, WeightInfo};
+use crate::migrations::v1::{migrate_offers, FlexibilityMarketLedger, MigrateToMarketScopedLedger};
+use frame_support::{assert_noop, assert_ok, storage::StoragePrefixedMap, traits::{GetStorageVersion, Hooks, OnRuntimeUpgrade, StorageVersion}, weights::Weight};
+use sp_runtime::{BuildStorage, DispatchError};
+
+// Events of the default instance
+type Event = crate::Event<Test>;
 
 #[test]
 fn check_payment() {
@@ -193,7 +199,20 @@ fn add_confirmation() {
             1
         ));
 
-        assert_eq!(FedecomPSDemo::get_confirmation(sender, receiver, timestamp), value_to_insert);
+        assert_eq!(FedecomPSDemo::get_confirmation(sender, receiver, timestamp), 1);
+    });
+}
+
+#[test]
+fn get_confirmation_returns_the_status_and_not_the_payment() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(FedecomPSDemo::add_payment(RuntimeOrigin::signed(1), 2, 1000, 100));
+        // No status until the receiver confirms the payment
+        assert_eq!(FedecomPSDemo::get_confirmation(1, 2, 1000), 0);
+
+        assert_ok!(FedecomPSDemo::add_confirmation(RuntimeOrigin::signed(2), 1, 1000, CONFIRMATION_NOK_UNDERESTIMATION));
+        assert_eq!(FedecomPSDemo::get_confirmation(1, 2, 1000), CONFIRMATION_NOK_UNDERESTIMATION);
+        assert_eq!(FedecomPSDemo::get_payment(1, 2, 1000), 100);
     });
 }
 
@@ -318,7 +337,7 @@ fn try_to_modify_confirmed_payment() {
 }
 #[test]
 fn try_to_sell_flexibility_confirmed() {
-    new_test_ext().execute_with(|| {
+    market_test_ext().execute_with(|| {
         let seller = 1;
         let buyer = 2;
         let flexibility_market_identifier = 100;
@@ -394,7 +413,7 @@ fn try_to_sell_flexibility_confirmed() {
 }
 #[test]
 fn try_to_sell_flexibility_rejected() {
-    new_test_ext().execute_with(|| {
+    market_test_ext().execute_with(|| {
         let seller = 1;
         let buyer = 2;
         let flexibility_market_identifier = 100;
@@ -440,3 +459,689 @@ fn try_to_sell_flexibility_rejected() {
 }
 
 
+fn genesis_storage(participants: Vec<(u64, u32)>, markets: Vec<(u32, u64)>, assets: Vec<(u32, u64)>) -> sp_runtime::Storage {
+    RuntimeGenesisConfig {
+        system: Default::default(),
+        balances: Default::default(),
+        fedecom_ps_demo: crate::GenesisConfig { participants, markets, assets, ..Default::default() },
+        fedecom_ps_demo2: Default::default(),
+    }
+    .build_storage()
+    .unwrap()
+}
+
+#[test]
+fn genesis_registers_participants_markets_and_assets() {
+    let storage = genesis_storage(
+        vec![(1, PARTICIPANT_ROLE_DSO), (2, PARTICIPANT_ROLE_PROSUMER), (3, PARTICIPANT_ROLE_MARKET_OPERATOR)],
+        vec![(100, 3)],
+        vec![(200, 2)],
+    );
+
+    sp_io::TestExternalities::from(storage).execute_with(|| {
+        assert_eq!(FedecomPSDemo::participants(1), Some(PARTICIPANT_ROLE_DSO));
+        assert_eq!(FedecomPSDemo::participants(2), Some(PARTICIPANT_ROLE_PROSUMER));
+        assert_eq!(FedecomPSDemo::participants(4), None);
+        assert_eq!(FedecomPSDemo::markets(100), Some(3));
+        assert_eq!(FedecomPSDemo::assets(200), Some(2));
+        assert_eq!(FedecomPSDemo::assets(201), None);
+    });
+}
+
+#[test]
+#[should_panic(expected = "Unknown role 5 of a genesis participant")]
+fn genesis_rejects_unknown_roles() {
+    genesis_storage(vec![(1, 5)], vec![], vec![]);
+}
+
+#[test]
+#[should_panic(expected = "Duplicate genesis participant")]
+fn genesis_rejects_duplicate_participants() {
+    genesis_storage(vec![(1, PARTICIPANT_ROLE_DSO), (1, PARTICIPANT_ROLE_PROSUMER)], vec![], vec![]);
+}
+
+#[test]
+#[should_panic(expected = "Operator of the genesis market 100 is not a registered market operator")]
+fn genesis_rejects_markets_of_other_participants() {
+    genesis_storage(vec![(3, PARTICIPANT_ROLE_DSO)], vec![(100, 3)], vec![]);
+}
+
+#[test]
+#[should_panic(expected = "Duplicate genesis asset 200")]
+fn genesis_rejects_duplicate_assets() {
+    genesis_storage(vec![(1, PARTICIPANT_ROLE_PROSUMER), (2, PARTICIPANT_ROLE_PROSUMER)], vec![], vec![(200, 1), (200, 2)]);
+}
+
+#[test]
+#[should_panic(expected = "Owner of the genesis asset 200 is not a registered participant")]
+fn genesis_rejects_assets_of_unknown_owners() {
+    genesis_storage(vec![], vec![], vec![(200, 2)]);
+}
+
+#[test]
+fn offers_need_registered_participants_markets_and_assets() {
+    market_test_ext().execute_with(|| {
+        assert_noop!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(5), 2, 100, 1000, 200, 10, 2), Error::<Test>::ParticipantNotExists);
+        assert_noop!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 5, 100, 1000, 200, 10, 2), Error::<Test>::ParticipantNotExists);
+        assert_noop!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 102, 1000, 200, 10, 2), Error::<Test>::MarketNotExists);
+        assert_noop!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1000, 204, 10, 2), Error::<Test>::AssetNotExists);
+        // Only the owner of an asset sells its flexibility
+        assert_noop!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(4), 2, 100, 1000, 200, 10, 2), Error::<Test>::NotAssetOwner);
+        assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1000, 200, 10, 2));
+
+        // The offers of removed participants and markets can no longer be decided
+        assert_ok!(FedecomPSDemo::remove_participant(RuntimeOrigin::root(), 2));
+        assert_noop!(
+            FedecomPSDemo::flexibility_purchase_decision(RuntimeOrigin::signed(2), 1, 100, 1000, 200, FLEXIBILITY_SELLING_STATE_CONFIRMED),
+            Error::<Test>::ParticipantNotExists
+        );
+        assert_ok!(FedecomPSDemo::register_participant(RuntimeOrigin::root(), 2, PARTICIPANT_ROLE_DSO));
+        assert_ok!(FedecomPSDemo::remove_market(RuntimeOrigin::root(), 100));
+        assert_noop!(
+            FedecomPSDemo::flexibility_purchase_decision(RuntimeOrigin::signed(2), 1, 100, 1000, 200, FLEXIBILITY_SELLING_STATE_CONFIRMED),
+            Error::<Test>::MarketNotExists
+        );
+        assert_ok!(FedecomPSDemo::register_market(RuntimeOrigin::root(), 100, 3));
+        assert_ok!(FedecomPSDemo::flexibility_purchase_decision(RuntimeOrigin::signed(2), 1, 100, 1000, 200, FLEXIBILITY_SELLING_STATE_CONFIRMED));
+    });
+}
+
+#[test]
+fn governance_registers_and_removes_participants() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+
+        assert_ok!(FedecomPSDemo::register_participant(RuntimeOrigin::root(), 1, PARTICIPANT_ROLE_DSO));
+        System::assert_last_event(Event::ParticipantRegistered { participant: 1, role: PARTICIPANT_ROLE_DSO }.into());
+        assert_eq!(FedecomPSDemo::participants(1), Some(PARTICIPANT_ROLE_DSO));
+
+        // The role of a registered participant is replaced
+        assert_ok!(FedecomPSDemo::register_participant(RuntimeOrigin::root(), 1, PARTICIPANT_ROLE_PROSUMER));
+        assert_eq!(FedecomPSDemo::participants(1), Some(PARTICIPANT_ROLE_PROSUMER));
+
+        assert_ok!(FedecomPSDemo::remove_participant(RuntimeOrigin::root(), 1));
+        System::assert_last_event(Event::ParticipantRemoved { participant: 1 }.into());
+        assert_eq!(FedecomPSDemo::participants(1), None);
+        assert_noop!(FedecomPSDemo::remove_participant(RuntimeOrigin::root(), 1), Error::<Test>::ParticipantNotExists);
+    });
+}
+
+#[test]
+fn unknown_participant_role_is_rejected() {
+    new_test_ext().execute_with(|| {
+        assert_noop!(FedecomPSDemo::register_participant(RuntimeOrigin::root(), 1, 0), Error::<Test>::ParticipantUnknownRole);
+        assert_noop!(FedecomPSDemo::register_participant(RuntimeOrigin::root(), 1, 5), Error::<Test>::ParticipantUnknownRole);
+    });
+}
+
+#[test]
+fn governance_registers_markets_and_assets() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+
+        assert_ok!(FedecomPSDemo::register_market(RuntimeOrigin::root(), 100, 3));
+        System::assert_last_event(Event::MarketRegistered { flexibility_market_identifier: 100, operator: 3 }.into());
+        assert_eq!(FedecomPSDemo::markets(100), Some(3));
+
+        assert_ok!(FedecomPSDemo::register_asset(RuntimeOrigin::root(), 200, 2));
+        System::assert_last_event(Event::AssetRegistered { asset_identifier: 200, owner: 2 }.into());
+        assert_eq!(FedecomPSDemo::assets(200), Some(2));
+
+        assert_ok!(FedecomPSDemo::remove_market(RuntimeOrigin::root(), 100));
+        System::assert_last_event(Event::MarketRemoved { flexibility_market_identifier: 100 }.into());
+        assert_eq!(FedecomPSDemo::markets(100), None);
+        assert_noop!(FedecomPSDemo::remove_market(RuntimeOrigin::root(), 100), Error::<Test>::MarketNotExists);
+    });
+}
+
+#[test]
+fn registry_requires_governance_origin() {
+    new_test_ext().execute_with(|| {
+        assert_noop!(FedecomPSDemo::register_participant(RuntimeOrigin::signed(1), 1, PARTICIPANT_ROLE_DSO), DispatchError::BadOrigin);
+        assert_noop!(FedecomPSDemo::remove_participant(RuntimeOrigin::signed(1), 1), DispatchError::BadOrigin);
+        assert_noop!(FedecomPSDemo::register_market(RuntimeOrigin::signed(1), 100, 1), DispatchError::BadOrigin);
+        assert_noop!(FedecomPSDemo::remove_market(RuntimeOrigin::signed(1), 100), DispatchError::BadOrigin);
+        assert_noop!(FedecomPSDemo::register_asset(RuntimeOrigin::signed(1), 200, 1), DispatchError::BadOrigin);
+    });
+}
+
+fn add_payment_call() -> crate::Call<Test> {
+    crate::Call::add_payment { key_receiver: 2, ts: 10, value: 100 }
+}
+
+fn flexibility_selling_call(flexibility_market_identifier: u32) -> crate::Call<Test> {
+    crate::Call::flexibility_selling {
+        buyer: 2,
+        flexibility_market_identifier,
+        flexibility_market_timestamp: 1000,
+        asset_identifier: 7,
+        sold_power: 10,
+        change_fct_w: 2,
+    }
+}
+
+#[test]
+fn pausing_the_pallet_pauses_every_market_call() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+
+        assert_ok!(FedecomPSDemo::pause_pallet(RuntimeOrigin::root()));
+        System::assert_last_event(Event::PalletPaused().into());
+        assert!(FedecomPSDemo::is_paused(&add_payment_call()));
+        assert!(FedecomPSDemo::is_paused(&flexibility_selling_call(1)));
+        // The privileged calls stay available
+        assert!(!FedecomPSDemo::is_paused(&crate::Call::unpause_pallet {}));
+        assert!(!FedecomPSDemo::is_paused(&crate::Call::register_market { flexibility_market_identifier: 1, operator: 3 }));
+        assert_noop!(FedecomPSDemo::pause_pallet(RuntimeOrigin::root()), Error::<Test>::AlreadyPaused);
+
+        assert_ok!(FedecomPSDemo::unpause_pallet(RuntimeOrigin::root()));
+        System::assert_last_event(Event::PalletUnpaused().into());
+        assert!(!FedecomPSDemo::is_paused(&add_payment_call()));
+        assert_noop!(FedecomPSDemo::unpause_pallet(RuntimeOrigin::root()), Error::<Test>::NotPaused);
+    });
+}
+
+#[test]
+fn pausing_a_call_only_pauses_that_call() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+
+        assert_ok!(FedecomPSDemo::pause_call(RuntimeOrigin::root(), b"add_payment".to_vec()));
+        System::assert_last_event(Event::CallPaused { call_name: b"add_payment".to_vec() }.into());
+        assert!(FedecomPSDemo::is_paused(&add_payment_call()));
+        assert!(!FedecomPSDemo::is_paused(&flexibility_selling_call(1)));
+
+        assert_ok!(FedecomPSDemo::unpause_call(RuntimeOrigin::root(), b"add_payment".to_vec()));
+        System::assert_last_event(Event::CallUnpaused { call_name: b"add_payment".to_vec() }.into());
+        assert!(!FedecomPSDemo::is_paused(&add_payment_call()));
+    });
+}
+
+#[test]
+fn only_market_calls_can_be_paused() {
+    new_test_ext().execute_with(|| {
+        assert_noop!(FedecomPSDemo::pause_call(RuntimeOrigin::root(), b"unpause_call".to_vec()), Error::<Test>::CallNotPausable);
+        assert_noop!(FedecomPSDemo::pause_call(RuntimeOrigin::root(), b"not_a_call".to_vec()), Error::<Test>::CallNotPausable);
+        assert_noop!(FedecomPSDemo::unpause_call(RuntimeOrigin::root(), b"add_payment".to_vec()), Error::<Test>::NotPaused);
+    });
+}
+
+#[test]
+fn pausing_a_market_only_pauses_its_flexibility_calls() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+
+        assert_ok!(FedecomPSDemo::pause_market(RuntimeOrigin::root(), 1));
+        System::assert_last_event(Event::MarketPaused { flexibility_market_identifier: 1 }.into());
+        assert!(FedecomPSDemo::is_paused(&flexibility_selling_call(1)));
+        assert!(!FedecomPSDemo::is_paused(&flexibility_selling_call(2)));
+        assert!(!FedecomPSDemo::is_paused(&add_payment_call()));
+        assert_eq!(
+            FedecomPSDemo::circuit_breaker_status(),
+            crate::CircuitBreakerStatus { pallet_paused: false, paused_calls: vec![], paused_markets: vec![1] }
+        );
+
+        assert_ok!(FedecomPSDemo::unpause_market(RuntimeOrigin::root(), 1));
+        System::assert_last_event(Event::MarketUnpaused { flexibility_market_identifier: 1 }.into());
+        assert!(!FedecomPSDemo::is_paused(&flexibility_selling_call(1)));
+        assert_noop!(FedecomPSDemo::unpause_market(RuntimeOrigin::root(), 1), Error::<Test>::NotPaused);
+    });
+}
+
+#[test]
+fn circuit_breaker_requires_pause_origin() {
+    new_test_ext().execute_with(|| {
+        assert_noop!(FedecomPSDemo::pause_pallet(RuntimeOrigin::signed(1)), DispatchError::BadOrigin);
+        assert_noop!(FedecomPSDemo::pause_call(RuntimeOrigin::signed(1), b"add_payment".to_vec()), DispatchError::BadOrigin);
+        assert_noop!(FedecomPSDemo::pause_market(RuntimeOrigin::signed(1), 1), DispatchError::BadOrigin);
+    });
+}
+
+#[test]
+fn instances_are_governed_independently() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+
+        // Every instance only accepts its own governance origin
+        assert_noop!(FedecomPSDemo2::register_market(RuntimeOrigin::root(), 1, 3), DispatchError::BadOrigin);
+        assert_noop!(
+            FedecomPSDemo::register_market(RuntimeOrigin::signed(MarketAdmin::get()), 1, 3),
+            DispatchError::BadOrigin
+        );
+        assert_ok!(FedecomPSDemo2::register_market(RuntimeOrigin::signed(MarketAdmin::get()), 1, 3));
+        System::assert_last_event(RuntimeEvent::FedecomPSDemo2(crate::Event::MarketRegistered {
+            flexibility_market_identifier: 1,
+            operator: 3,
+        }));
+        assert_eq!(FedecomPSDemo2::markets(1), Some(3));
+        assert_eq!(FedecomPSDemo::markets(1), None);
+
+        // Ledgers and circuit breakers are separate
+        assert_ok!(FedecomPSDemo2::add_payment(RuntimeOrigin::signed(1), 2, 10, 100));
+        assert!(FedecomPSDemo2::check_payment(1, 2, 10));
+        assert!(!FedecomPSDemo::check_payment(1, 2, 10));
+        assert_ok!(FedecomPSDemo::pause_pallet(RuntimeOrigin::root()));
+        assert!(FedecomPSDemo::is_paused(&add_payment_call()));
+        assert!(!FedecomPSDemo2::is_paused(&crate::Call::add_payment { key_receiver: 2, ts: 10, value: 100 }));
+    });
+}
+
+#[test]
+fn default_instance_keeps_the_single_instance_storage() {
+    let mut prefix = sp_io::hashing::twox_128(b"FedecomPSDemo").to_vec();
+    prefix.extend(sp_io::hashing::twox_128(b"Payments"));
+    assert_eq!(crate::pallet::Payments::<Test>::final_prefix().to_vec(), prefix);
+
+    let mut prefix = sp_io::hashing::twox_128(b"FedecomPSDemo2").to_vec();
+    prefix.extend(sp_io::hashing::twox_128(b"Payments"));
+    assert_eq!(crate::pallet::Payments::<Test, crate::Instance2>::final_prefix().to_vec(), prefix);
+}
+
+#[test]
+fn confirmed_sale_calls_the_settlement_hook() {
+    market_test_ext().execute_with(|| {
+        SettlementHooks::take();
+        assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1000, 200, 10, 2));
+        assert!(SettlementHooks::get().is_empty());
+
+        let post_info = FedecomPSDemo::flexibility_purchase_decision(
+            RuntimeOrigin::signed(2),
+            1,
+            100,
+            1000,
+            200,
+            FLEXIBILITY_SELLING_STATE_CONFIRMED,
+        ).unwrap();
+        let offer = OfferKey {
+            seller: 1,
+            buyer: 2,
+            flexibility_market_identifier: 100,
+            flexibility_market_timestamp: 1000,
+            asset_identifier: 200,
+        };
+        assert_eq!(SettlementHooks::get(), vec![SettlementHook::FlexibilityConfirmed(offer, 20)]);
+        assert_eq!(post_info.actual_weight, Some(<() as WeightInfo>::flexibility_purchase() + SETTLEMENT_HOOK_WEIGHT));
+    });
+}
+
+#[test]
+fn rejected_sale_refunds_the_settlement_hook() {
+    market_test_ext().execute_with(|| {
+        SettlementHooks::take();
+        assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1000, 200, 10, 2));
+
+        let post_info = FedecomPSDemo::flexibility_purchase_decision(
+            RuntimeOrigin::signed(2),
+            1,
+            100,
+            1000,
+            200,
+            FLEXIBILITY_SELLING_STATE_REJECTED,
+        ).unwrap();
+        assert!(SettlementHooks::get().is_empty());
+        assert_eq!(post_info.actual_weight, Some(<() as WeightInfo>::flexibility_purchase()));
+    });
+}
+
+#[test]
+fn confirmations_call_the_settlement_hooks() {
+    new_test_ext().execute_with(|| {
+        SettlementHooks::take();
+        for ts in 1..=4 {
+            assert_ok!(FedecomPSDemo::add_payment(RuntimeOrigin::signed(1), 2, ts, 100));
+        }
+
+        let hooked_weight = Some(<() as WeightInfo>::add_confirmation() + SETTLEMENT_HOOK_WEIGHT);
+        let post_info = FedecomPSDemo::add_confirmation(RuntimeOrigin::signed(2), 1, 1, CONFIRMATION_OK).unwrap();
+        assert_eq!(post_info.actual_weight, hooked_weight);
+        let post_info = FedecomPSDemo::add_confirmation(RuntimeOrigin::signed(2), 1, 2, CONFIRMATION_NOK_OVERESTIMATION).unwrap();
+        assert_eq!(post_info.actual_weight, hooked_weight);
+        let post_info = FedecomPSDemo::add_confirmation(RuntimeOrigin::signed(2), 1, 3, CONFIRMATION_NOK_UNDERESTIMATION).unwrap();
+        assert_eq!(post_info.actual_weight, hooked_weight);
+        // An unknown status is stored without calling any hook
+        let post_info = FedecomPSDemo::add_confirmation(RuntimeOrigin::signed(2), 1, 4, 0).unwrap();
+        assert_eq!(post_info.actual_weight, Some(<() as WeightInfo>::add_confirmation()));
+
+        assert_eq!(SettlementHooks::get(), vec![
+            SettlementHook::PaymentConfirmed(1, 2, 1, 100),
+            SettlementHook::DeliveryDisputed(1, 2, 2, DeliveryDeviation::OverEstimation),
+            SettlementHook::DeliveryDisputed(1, 2, 3, DeliveryDeviation::UnderEstimation),
+        ]);
+    });
+}
+
+#[test]
+fn tuples_of_settlement_hooks_call_every_hook() {
+    new_test_ext().execute_with(|| {
+        SettlementHooks::take();
+        type Hooks = (RecordSettlement, (), RecordSettlement);
+
+        assert_eq!(<Hooks as OnPaymentConfirmed<u64>>::weight(), SETTLEMENT_HOOK_WEIGHT.saturating_mul(2));
+        assert_eq!(<Hooks as OnPaymentConfirmed<u64>>::on_payment_confirmed(&1, &2, 10, 100), SETTLEMENT_HOOK_WEIGHT.saturating_mul(2));
+        assert_eq!(<() as OnPaymentConfirmed<u64>>::weight(), Weight::zero());
+        assert_eq!(SettlementHooks::get(), vec![SettlementHook::PaymentConfirmed(1, 2, 10, 100); 2]);
+    });
+}
+
+#[test]
+fn ledger_is_readable_through_the_trait() {
+    new_test_ext().execute_with(|| {
+        type Ledger = FedecomPSDemo;
+        let offer = OfferKey {
+            seller: 1,
+            buyer: 2,
+            flexibility_market_identifier: 100,
+            flexibility_market_timestamp: 1000,
+            asset_identifier: 200,
+        };
+        assert_eq!(<Ledger as FlexibilityLedger<u64>>::offer(&offer), None);
+        assert_eq!(<Ledger as FlexibilityLedger<u64>>::payment(&2, &1, 1000), None);
+
+        assert_ok!(FedecomPSDemo::register_participant(RuntimeOrigin::root(), 1, PARTICIPANT_ROLE_PROSUMER));
+        assert_ok!(FedecomPSDemo::register_participant(RuntimeOrigin::root(), 2, PARTICIPANT_ROLE_DSO));
+        assert_ok!(FedecomPSDemo::register_market(RuntimeOrigin::root(), 100, 3));
+        assert_ok!(FedecomPSDemo::register_asset(RuntimeOrigin::root(), 200, 1));
+        assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1000, 200, 10, 2));
+        assert_ok!(FedecomPSDemo::flexibility_purchase_decision(
+            RuntimeOrigin::signed(2),
+            1,
+            100,
+            1000,
+            200,
+            FLEXIBILITY_SELLING_STATE_CONFIRMED,
+        ));
+        assert_ok!(FedecomPSDemo::add_confirmation(RuntimeOrigin::signed(1), 2, 1000, CONFIRMATION_OK));
+
+        let sale = FlexibilitySellingData { sold_power: 10, change_fct_w: 2, state: FLEXIBILITY_SELLING_STATE_CONFIRMED };
+        assert_eq!(<Ledger as FlexibilityLedger<u64>>::offer(&offer), Some(sale.clone()));
+        assert_eq!(<Ledger as FlexibilityLedger<u64>>::payment(&2, &1, 1000), Some(20));
+        assert_eq!(<Ledger as FlexibilityLedger<u64>>::confirmation(&2, &1, 1000), Some(CONFIRMATION_OK));
+        assert_eq!(<Ledger as FlexibilityLedger<u64>>::participant_role(&1), Some(PARTICIPANT_ROLE_PROSUMER));
+        assert_eq!(<Ledger as FlexibilityLedger<u64>>::market_operator(100), Some(3));
+        assert_eq!(<Ledger as FlexibilityLedger<u64>>::asset_owner(200), Some(1));
+        assert_eq!(<Ledger as FlexibilityLedger<u64>>::asset_owner(201), None);
+        assert_eq!(<Ledger as FlexibilityLedger<u64>>::offers_in_period(100, 1000), vec![(offer, sale)]);
+        assert_eq!(<Ledger as FlexibilityLedger<u64>>::offers_in_period(100, 1001), vec![]);
+    });
+}
+
+#[test]
+fn selling_a_sold_offer_again_keeps_it() {
+    market_test_ext().execute_with(|| {
+        assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1000, 200, 10, 2));
+        System::assert_last_event(Event::SuccessfullySoldFlexibility {
+            seller: 1,
+            buyer: 2,
+            flexibility_market_identifier: 100,
+            flexibility_market_timestamp: 1000,
+            asset_identifier: 200,
+            sold_power: 10,
+            change_fct_w: 2,
+        }.into());
+        assert_ok!(FedecomPSDemo::flexibility_purchase_decision(RuntimeOrigin::signed(2), 1, 100, 1000, 200, FLEXIBILITY_SELLING_STATE_CONFIRMED));
+
+        // Selling it again neither resets the decision nor changes the power and price of the offer
+        assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1000, 200, 30, 5));
+        System::assert_last_event(Event::AlreadySoldFlexibility {
+            seller: 1,
+            buyer: 2,
+            flexibility_market_identifier: 100,
+            flexibility_market_timestamp: 1000,
+            asset_identifier: 200,
+        }.into());
+        let confirmed = FlexibilitySellingData { sold_power: 10, change_fct_w: 2, state: FLEXIBILITY_SELLING_STATE_CONFIRMED };
+        assert_eq!(FedecomPSDemo::get_flexibility_selling(1, 2, 100, 1000, 200), confirmed);
+        assert_eq!(FedecomPSDemo::period_offer_count(100, 1000), 1);
+        assert_eq!(FedecomPSDemo::get_payment(2, 1, 1000), 20);
+    });
+}
+
+#[test]
+fn confirming_an_offer_whose_payment_overflows_fails() {
+    market_test_ext().execute_with(|| {
+        assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1000, 200, u32::MAX, 2));
+        assert_noop!(FedecomPSDemo::flexibility_purchase_decision(RuntimeOrigin::signed(2), 1, 100, 1000, 200, FLEXIBILITY_SELLING_STATE_CONFIRMED), Error::<Test>::StorageOverflow);
+
+        // The offer can still be rejected
+        assert_ok!(FedecomPSDemo::flexibility_purchase_decision(RuntimeOrigin::signed(2), 1, 100, 1000, 200, FLEXIBILITY_SELLING_STATE_REJECTED));
+        assert_eq!(FedecomPSDemo::get_payment(2, 1, 1000), 0);
+    });
+}
+
+#[test]
+fn market_periods_take_a_bounded_number_of_offers() {
+    market_test_ext().execute_with(|| {
+        for (seller, asset_identifier) in [(1, 200), (1, 201), (4, 210), (4, 211)] {
+            assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(seller), 2, 100, 1000, asset_identifier, 10, 2));
+        }
+        assert_eq!(FedecomPSDemo::period_offer_count(100, 1000), MaxOffersPerPeriod::get());
+
+        assert_noop!(
+            FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(5), 2, 100, 1000, 220, 10, 2),
+            Error::<Test>::FlexibilityPeriodFull
+        );
+        // An offer already sold is still reported, and the other periods are not full
+        assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1000, 200, 10, 2));
+        assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(5), 2, 100, 1001, 220, 10, 2));
+        assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(5), 2, 101, 1000, 220, 10, 2));
+        assert_eq!(FedecomPSDemo::period_offer_count(100, 1000), MaxOffersPerPeriod::get());
+        assert_eq!(FedecomPSDemo::period_offer_count(100, 1001), 1);
+    });
+}
+
+#[test]
+fn sellers_take_a_bounded_share_of_a_period() {
+    market_test_ext().execute_with(|| {
+        for asset_identifier in 200..200 + MaxOffersPerSeller::get() {
+            assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1000, asset_identifier, 10, 2));
+        }
+        assert_eq!(FedecomPSDemo::seller_offer_count((100u32, 1000u32, 1u64)), MaxOffersPerSeller::get());
+
+        assert_noop!(
+            FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1000, 203, 10, 2),
+            Error::<Test>::SellerQuotaFull
+        );
+        // The other sellers and the other periods are still open to the seller
+        assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(4), 2, 100, 1000, 210, 10, 2));
+        assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1001, 203, 10, 2));
+        assert_eq!(FedecomPSDemo::seller_offer_count((100u32, 1000u32, 4u64)), 1);
+        assert_eq!(FedecomPSDemo::seller_offer_count((100u32, 1001u32, 1u64)), 1);
+    });
+}
+
+#[test]
+fn closing_a_market_period_only_removes_its_offers() {
+    market_test_ext().execute_with(|| {
+        for (market, period) in [(100, 1000), (100, 1000), (100, 1001), (101, 1000)] {
+            let asset_identifier = 200 + FedecomPSDemo::period_offer_count(market, period);
+            assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, market, period, asset_identifier, 10, 2));
+        }
+        assert_ok!(FedecomPSDemo::flexibility_purchase_decision(RuntimeOrigin::signed(2), 1, 100, 1000, 200, FLEXIBILITY_SELLING_STATE_CONFIRMED));
+
+        // Only governance and the operator of the market close its periods
+        assert_noop!(FedecomPSDemo::close_market_period(RuntimeOrigin::signed(1), 100, 1000), Error::<Test>::NotMarketOperator);
+        assert_noop!(FedecomPSDemo::close_market_period(RuntimeOrigin::signed(3), 102, 1000), Error::<Test>::MarketNotExists);
+
+        let post_info = FedecomPSDemo::close_market_period(RuntimeOrigin::signed(3), 100, 1000).unwrap();
+        System::assert_last_event(Event::MarketPeriodClosed {
+            flexibility_market_identifier: 100,
+            flexibility_market_timestamp: 1000,
+            removed: 1,
+            settled: 1,
+            remaining: 0,
+        }.into());
+        assert_eq!(post_info.actual_weight, Some(<() as WeightInfo>::close_market_period(2)));
+        assert_eq!(FedecomPSDemo::market_period_offers(100, 1000), vec![]);
+        assert_eq!(FedecomPSDemo::period_offer_count(100, 1000), 0);
+        assert_eq!(FedecomPSDemo::seller_offer_count((100u32, 1000u32, 1u64)), 0);
+        // The confirmed offer and the payments of the period are kept for the settlement
+        let confirmed = FlexibilitySellingData { sold_power: 10, change_fct_w: 2, state: FLEXIBILITY_SELLING_STATE_CONFIRMED };
+        assert_eq!(FedecomPSDemo::settled_period_offers(100, 1000), vec![(1, 2, 200, confirmed.clone())]);
+        assert_eq!(FedecomPSDemo::get_flexibility_selling(1, 2, 100, 1000, 200), confirmed);
+        assert_eq!(FedecomPSDemo::get_payment(2, 1, 1000), 20);
+
+        assert_eq!(FedecomPSDemo::market_period_offers(100, 1001).len(), 1);
+        assert_ok!(FedecomPSDemo::close_market_period(RuntimeOrigin::root(), 101, 1000));
+        assert_eq!(FedecomPSDemo::market_period_offers(101, 1000), vec![]);
+        assert_eq!(FedecomPSDemo::period_offer_count(100, 1001), 1);
+    });
+}
+
+#[test]
+fn closed_market_periods_take_no_more_offers_nor_decisions() {
+    market_test_ext().execute_with(|| {
+        assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1000, 200, 10, 2));
+        assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1000, 201, 10, 2));
+        assert_ok!(FedecomPSDemo::flexibility_purchase_decision(RuntimeOrigin::signed(2), 1, 100, 1000, 200, FLEXIBILITY_SELLING_STATE_CONFIRMED));
+        assert_ok!(FedecomPSDemo::close_market_period(RuntimeOrigin::signed(3), 100, 1000));
+        assert!(FedecomPSDemo::is_period_closed(100, 1000));
+
+        // A confirmed offer cannot be sold and confirmed again, which would pay it twice
+        assert_noop!(
+            FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1000, 200, 10, 2),
+            Error::<Test>::FlexibilityPeriodClosed
+        );
+        assert_noop!(
+            FedecomPSDemo::flexibility_purchase_decision(RuntimeOrigin::signed(2), 1, 100, 1000, 201, FLEXIBILITY_SELLING_STATE_CONFIRMED),
+            Error::<Test>::FlexibilityPeriodClosed
+        );
+        assert_noop!(FedecomPSDemo::close_market_period(RuntimeOrigin::signed(3), 100, 1000), Error::<Test>::FlexibilityPeriodClosed);
+        assert_eq!(FedecomPSDemo::get_payment(2, 1, 1000), 20);
+
+        // The delivery of the closed period is still confirmed, and the other periods are open
+        assert_ok!(FedecomPSDemo::add_confirmation(RuntimeOrigin::signed(1), 2, 1000, CONFIRMATION_OK));
+        assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1001, 200, 10, 2));
+        assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 101, 1000, 200, 10, 2));
+    });
+}
+
+#[test]
+fn confirmed_sales_of_a_period_add_up_to_one_payment() {
+    market_test_ext().execute_with(|| {
+        for (market, asset_identifier, sold_power) in [(100, 200, 10), (101, 200, 20), (100, 201, u32::MAX / 2)] {
+            assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, market, 1000, asset_identifier, sold_power, 2));
+        }
+        assert_ok!(FedecomPSDemo::flexibility_purchase_decision(RuntimeOrigin::signed(2), 1, 100, 1000, 200, FLEXIBILITY_SELLING_STATE_CONFIRMED));
+        assert_ok!(FedecomPSDemo::flexibility_purchase_decision(RuntimeOrigin::signed(2), 1, 101, 1000, 200, FLEXIBILITY_SELLING_STATE_CONFIRMED));
+        assert_eq!(FedecomPSDemo::get_payment(2, 1, 1000), 60);
+
+        // A sale whose payment does not fit in the payment of the period is not confirmed
+        assert_noop!(
+            FedecomPSDemo::flexibility_purchase_decision(RuntimeOrigin::signed(2), 1, 100, 1000, 201, FLEXIBILITY_SELLING_STATE_CONFIRMED),
+            Error::<Test>::StorageOverflow
+        );
+        assert_eq!(FedecomPSDemo::get_payment(2, 1, 1000), 60);
+    });
+}
+
+#[test]
+fn the_payment_of_a_confirmed_delivery_is_not_changed() {
+    market_test_ext().execute_with(|| {
+        for asset_identifier in [200, 201] {
+            assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1000, asset_identifier, 10, 2));
+        }
+        assert_ok!(FedecomPSDemo::flexibility_purchase_decision(RuntimeOrigin::signed(2), 1, 100, 1000, 200, FLEXIBILITY_SELLING_STATE_CONFIRMED));
+        assert_ok!(FedecomPSDemo::add_confirmation(RuntimeOrigin::signed(1), 2, 1000, CONFIRMATION_OK));
+
+        assert_noop!(
+            FedecomPSDemo::flexibility_purchase_decision(RuntimeOrigin::signed(2), 1, 100, 1000, 201, FLEXIBILITY_SELLING_STATE_CONFIRMED),
+            Error::<Test>::ConfirmationAlreadyExists
+        );
+        assert_eq!(FedecomPSDemo::get_payment(2, 1, 1000), 20);
+        // The sale can still be rejected
+        assert_ok!(FedecomPSDemo::flexibility_purchase_decision(RuntimeOrigin::signed(2), 1, 100, 1000, 201, FLEXIBILITY_SELLING_STATE_REJECTED));
+    });
+}
+
+fn legacy_key(seller: u64, buyer: u64, market: u32, period: u32, asset: u32) -> (u64, u64, u32, u32, u32) {
+    (seller, buyer, market, period, asset)
+}
+
+fn legacy_offer(sold_power: u32) -> FlexibilitySellingData {
+    FlexibilitySellingData { sold_power, change_fct_w: 2, state: FLEXIBILITY_SELLING_STATE_NOT_DECIDED }
+}
+
+#[test]
+fn migration_keys_the_offers_by_market_period() {
+    market_test_ext().execute_with(|| {
+        FlexibilityMarketLedger::<Test>::insert(legacy_key(1, 2, 100, 1000, 200), legacy_offer(10));
+        FlexibilityMarketLedger::<Test>::insert(legacy_key(4, 2, 100, 1000, 210), legacy_offer(20));
+        FlexibilityMarketLedger::<Test>::insert(legacy_key(1, 2, 101, 1000, 200), legacy_offer(30));
+        FlexibilityMarketLedger::<Test, crate::Instance2>::insert(legacy_key(1, 2, 100, 1000, 200), legacy_offer(40));
+        assert_eq!(FedecomPSDemo::on_chain_storage_version(), StorageVersion::new(0));
+
+        // The upgrade block only flags the migration
+        MigrateToMarketScopedLedger::<Test>::on_runtime_upgrade();
+        assert_eq!(FedecomPSDemo::on_chain_storage_version(), StorageVersion::new(1));
+        assert!(FedecomPSDemo::migrating_ledger());
+        assert_eq!(FlexibilityMarketLedger::<Test>::iter_keys().count(), 3);
+
+        // The market calls wait for the offers to be moved
+        assert_noop!(
+            FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1000, 201, 10, 2),
+            Error::<Test>::LedgerMigrating
+        );
+        assert_noop!(
+            FedecomPSDemo::flexibility_purchase_decision(RuntimeOrigin::signed(2), 1, 100, 1000, 200, FLEXIBILITY_SELLING_STATE_CONFIRMED),
+            Error::<Test>::LedgerMigrating
+        );
+        assert_noop!(FedecomPSDemo::close_market_period(RuntimeOrigin::root(), 100, 1000), Error::<Test>::LedgerMigrating);
+
+        FedecomPSDemo::on_initialize(2);
+        System::assert_last_event(Event::LedgerMigrated().into());
+        assert!(!FedecomPSDemo::migrating_ledger());
+        assert_eq!(FlexibilityMarketLedger::<Test>::iter_keys().count(), 0);
+        assert_eq!(FedecomPSDemo::get_flexibility_selling(1, 2, 100, 1000, 200), legacy_offer(10));
+        assert_eq!(FedecomPSDemo::get_flexibility_selling(4, 2, 100, 1000, 210), legacy_offer(20));
+        assert_eq!(FedecomPSDemo::get_flexibility_selling(1, 2, 101, 1000, 200), legacy_offer(30));
+        assert_eq!(FedecomPSDemo::period_offer_count(100, 1000), 2);
+        assert_eq!(FedecomPSDemo::period_offer_count(101, 1000), 1);
+        assert_eq!(FedecomPSDemo::seller_offer_count((100u32, 1000u32, 1u64)), 1);
+        assert_eq!(FedecomPSDemo::seller_offer_count((100u32, 1000u32, 4u64)), 1);
+        assert_ok!(FedecomPSDemo::flexibility_selling(RuntimeOrigin::signed(1), 2, 100, 1000, 201, 10, 2));
+        // Every instance is migrated on its own
+        assert_eq!(FlexibilityMarketLedger::<Test, crate::Instance2>::iter_keys().count(), 1);
+        assert_eq!(FedecomPSDemo2::on_chain_storage_version(), StorageVersion::new(0));
+
+        // The migration only runs once
+        FlexibilityMarketLedger::<Test>::insert(legacy_key(1, 2, 100, 1000, 202), legacy_offer(50));
+        MigrateToMarketScopedLedger::<Test>::on_runtime_upgrade();
+        assert!(!FedecomPSDemo::migrating_ledger());
+        FedecomPSDemo::on_initialize(3);
+        assert_eq!(FedecomPSDemo::period_offer_count(100, 1000), 3);
+    });
+}
+
+#[test]
+fn migrated_periods_above_the_bound_take_several_closings() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        let offers = MaxOffersPerPeriod::get() + 2;
+        for asset_identifier in 0..offers {
+            FlexibilityMarketLedger::<Test>::insert(legacy_key(1, 2, 100, 1000, asset_identifier), legacy_offer(10));
+        }
+        MigrateToMarketScopedLedger::<Test>::on_runtime_upgrade();
+
+        // Every block moves a bounded number of offers
+        migrate_offers::<Test, ()>(MaxOffersPerPeriod::get());
+        assert!(FedecomPSDemo::migrating_ledger());
+        assert_eq!(FedecomPSDemo::period_offer_count(100, 1000), MaxOffersPerPeriod::get());
+        migrate_offers::<Test, ()>(MaxOffersPerPeriod::get());
+        assert!(!FedecomPSDemo::migrating_ledger());
+        System::assert_has_event(Event::MigratedPeriodAboveBound {
+            flexibility_market_identifier: 100,
+            flexibility_market_timestamp: 1000,
+        }.into());
+        assert_eq!(FedecomPSDemo::period_offer_count(100, 1000), offers);
+        assert_eq!(FedecomPSDemo::seller_offer_count((100u32, 1000u32, 1u64)), offers);
+
+        assert_ok!(FedecomPSDemo::close_market_period(RuntimeOrigin::root(), 100, 1000));
+        assert_eq!(FedecomPSDemo::period_offer_count(100, 1000), 2);
+        assert_eq!(FedecomPSDemo::market_period_offers(100, 1000).len(), 2);
+        assert_ok!(FedecomPSDemo::close_market_period(RuntimeOrigin::root(), 100, 1000));
+        assert_eq!(FedecomPSDemo::period_offer_count(100, 1000), 0);
+        assert_eq!(FedecomPSDemo::seller_offer_count((100u32, 1000u32, 1u64)), 0);
+        assert_eq!(FedecomPSDemo::market_period_offers(100, 1000), vec![]);
+        assert_noop!(FedecomPSDemo::close_market_period(RuntimeOrigin::root(), 100, 1000), Error::<Test>::FlexibilityPeriodClosed);
+    });
+}
@@ -0,0 +1,163 @@
+//! Traits for other pallets to react to the settlement of the flexibility market and to inspect
+//! its ledger, e.g. incentives, certificates or accounting.
+//!
+//! The settlement handlers are called once the pallet has written its storage and deposited its
+//! events. They return the weight they consumed, bounded by their `weight`, which the calls
+//! charge ahead and refund once the handler has run. Tuples of handlers call every handler in
+//! order, `()` is the handler doing nothing.
+
+use crate::FlexibilitySellingData;
+use frame_support::{sp_std::prelude::*, weights::Weight, RuntimeDebug};
+
+/// Key of an offer of the flexibility market ledger.
+#[derive(Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct OfferKey<AccountId> {
+	pub seller: AccountId,
+	pub buyer: AccountId,
+	pub flexibility_market_identifier: u32,
+	pub flexibility_market_timestamp: u32,
+	pub asset_identifier: u32,
+}
+
+/// Deviation of a delivered flexibility reported by the receiver of its payment.
+#[derive(Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum DeliveryDeviation {
+	OverEstimation,
+	UnderEstimation,
+}
+
+/// Handler of the flexibility sales confirmed by their buyer.
+pub trait OnFlexibilityConfirmed<AccountId> {
+	/// The buyer confirmed the `offer`, whose `payment` is recorded from the buyer to the seller.
+	fn on_flexibility_confirmed(
+		offer: &OfferKey<AccountId>,
+		sale: &FlexibilitySellingData,
+		payment: u32,
+	) -> Weight;
+
+	/// Maximum weight of [`Self::on_flexibility_confirmed`].
+	fn weight() -> Weight;
+}
+
+#[impl_trait_for_tuples::impl_for_tuples(30)]
+impl<AccountId> OnFlexibilityConfirmed<AccountId> for Tuple {
+	fn on_flexibility_confirmed(
+		offer: &OfferKey<AccountId>,
+		sale: &FlexibilitySellingData,
+		payment: u32,
+	) -> Weight {
+		let mut weight = Weight::zero();
+		for_tuples!( #(
+			weight = weight.saturating_add(Tuple::on_flexibility_confirmed(offer, sale, payment));
+		)* );
+		weight
+	}
+
+	fn weight() -> Weight {
+		let mut weight = Weight::zero();
+		for_tuples!( #( weight = weight.saturating_add(Tuple::weight()); )* );
+		weight
+	}
+}
+
+/// Handler of the payments whose delivery is confirmed by their receiver.
+pub trait OnPaymentConfirmed<AccountId> {
+	/// The `receiver` confirmed the delivery paid `value` by `sender` at `ts`.
+	fn on_payment_confirmed(
+		sender: &AccountId,
+		receiver: &AccountId,
+		ts: u32,
+		value: u32,
+	) -> Weight;
+
+	/// Maximum weight of [`Self::on_payment_confirmed`].
+	fn weight() -> Weight;
+}
+
+#[impl_trait_for_tuples::impl_for_tuples(30)]
+impl<AccountId> OnPaymentConfirmed<AccountId> for Tuple {
+	fn on_payment_confirmed(
+		sender: &AccountId,
+		receiver: &AccountId,
+		ts: u32,
+		value: u32,
+	) -> Weight {
+		let mut weight = Weight::zero();
+		for_tuples!( #(
+			weight = weight
+				.saturating_add(Tuple::on_payment_confirmed(sender, receiver, ts, value));
+		)* );
+		weight
+	}
+
+	fn weight() -> Weight {
+		let mut weight = Weight::zero();
+		for_tuples!( #( weight = weight.saturating_add(Tuple::weight()); )* );
+		weight
+	}
+}
+
+/// Handler of the deliveries disputed by the receiver of their payment.
+pub trait OnDeliveryDisputed<AccountId> {
+	/// The `receiver` reported the `deviation` of the delivery paid by `sender` at `ts`.
+	fn on_delivery_disputed(
+		sender: &AccountId,
+		receiver: &AccountId,
+		ts: u32,
+		deviation: DeliveryDeviation,
+	) -> Weight;
+
+	/// Maximum weight of [`Self::on_delivery_disputed`].
+	fn weight() -> Weight;
+}
+
+#[impl_trait_for_tuples::impl_for_tuples(30)]
+impl<AccountId> OnDeliveryDisputed<AccountId> for Tuple {
+	fn on_delivery_disputed(
+		sender: &AccountId,
+		receiver: &AccountId,
+		ts: u32,
+		deviation: DeliveryDeviation,
+	) -> Weight {
+		let mut weight = Weight::zero();
+		for_tuples!( #(
+			weight = weight
+				.saturating_add(Tuple::on_delivery_disputed(sender, receiver, ts, deviation));
+		)* );
+		weight
+	}
+
+	fn weight() -> Weight {
+		let mut weight = Weight::zero();
+		for_tuples!( #( weight = weight.saturating_add(Tuple::weight()); )* );
+		weight
+	}
+}
+
+/// Read access to the flexibility market ledger and registry, for the other pallets.
+pub trait FlexibilityLedger<AccountId> {
+	/// Offer of the ledger with the given key.
+	fn offer(key: &OfferKey<AccountId>) -> Option<FlexibilitySellingData>;
+
+	/// Offers of a market period, at most `MaxOffersPerPeriod` unless migrated from an older
+	/// ledger. Once the period is closed, only its confirmed offers are kept.
+	fn offers_in_period(
+		flexibility_market_identifier: u32,
+		flexibility_market_timestamp: u32,
+	) -> Vec<(OfferKey<AccountId>, FlexibilitySellingData)>;
+
+	/// Payment from `sender` to `receiver` at `ts`.
+	fn payment(sender: &AccountId, receiver: &AccountId, ts: u32) -> Option<u32>;
+
+	/// Confirmation status of the payment from `sender` to `receiver` at `ts`.
+	fn confirmation(sender: &AccountId, receiver: &AccountId, ts: u32) -> Option<u32>;
+
+	/// Role of a registered market participant.
+	fn participant_role(participant: &AccountId) -> Option<u32>;
+
+	/// Operator of a registered flexibility market.
+	fn market_operator(flexibility_market_identifier: u32) -> Option<AccountId>;
+
+	/// Owner of a registered asset/flexibility.
+	fn asset_owner(asset_identifier: u32) -> Option<AccountId>;
+}
@@ -1,29 +1,12 @@
-
-//! Autogenerated weights for pallet_template
+//! Weights for pallet_fedecom_ps_demo
 //!
-//! THIS FILE WAS AUTO-GENERATED USING THE SUBSTRATE BENCHMARK CLI VERSION 4.0.0-dev
-//! DATE: 2023-04-06, STEPS: `50`, REPEAT: `20`, LOW RANGE: `[]`, HIGH RANGE: `[]`
-//! WORST CASE MAP SIZE: `1000000`
-//! HOSTNAME: `Alexs-MacBook-Pro-2.local`, CPU: `<UNKNOWN>`
-//! EXECUTION: Some(Wasm), WASM-EXECUTION: Compiled, CHAIN: Some("dev"), DB CACHE: 1024
-
-// Executed Command:
-// ../../target/release/node-template
-// benchmark
-// pallet
-// --chain
-// dev
-// --pallet
-// pallet_template
-// --extrinsic
-// *
-// --steps=50
-// --repeat=20
-// --wasm-execution=compiled
-// --output
-// pallets/template/src/weights.rs
-// --template
-// ../../.maintain/frame-weight-template.hbs
+//! Estimated by hand until the pallet is benchmarked on reference hardware: the reads and writes
+//! follow the storage accesses of every call, and the proof sizes the `MaxEncodedLen` bound of the
+//! read items with a 32-byte `AccountId`, as the benchmark CLI would record them. Replace this file
+//! with the output of:
+//!
+//! ./target/release/node-template benchmark pallet --chain dev --pallet pallet_fedecom_ps_demo
+//!   --extrinsic '*' --steps 50 --repeat 20 --output pallets/fedecom-ps-demo/src/weights.rs
 
 #![cfg_attr(rustfmt, rustfmt_skip)]
 #![allow(unused_parens)]
@@ -32,7 +15,7 @@
 use frame_support::{traits::Get, weights::{Weight, constants::RocksDbWeight}};
 use core::marker::PhantomData;
 
-/// Weight functions needed for pallet_template.
+/// Weight functions needed for pallet_fedecom_ps_demo.
 pub trait WeightInfo {
 	fn exist_call() -> Weight;
 	fn check_payment_call() -> Weight;
@@ -44,8 +27,23 @@ pub trait WeightInfo {
 	fn remove_confirmation() -> Weight;
 	fn flexibility_selling() -> Weight;
 	fn flexibility_purchase() -> Weight;
+	fn register_participant() -> Weight;
+	fn remove_participant() -> Weight;
+	fn register_market() -> Weight;
+	fn remove_market() -> Weight;
+	fn register_asset() -> Weight;
+	fn pause_pallet() -> Weight;
+	fn unpause_pallet() -> Weight;
+	fn pause_call() -> Weight;
+	fn unpause_call() -> Weight;
+	fn pause_market() -> Weight;
+	fn unpause_market() -> Weight;
+	fn close_market_period(n: u32, ) -> Weight;
+	fn clear_legacy_period(n: u32, m: u32, ) -> Weight;
+	fn migrate_offer() -> Weight;
 }
 
+/// Weights for pallet_fedecom_ps_demo using the Substrate node and recommended hardware.
 pub struct SubstrateWeight<T>(PhantomData<T>);
 impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
 	fn exist_call() -> Weight {
@@ -60,34 +58,214 @@ impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
 		Weight::from_parts(0, 0)
 			.saturating_add(T::DbWeight::get().writes(1_u64))
 	}
+	/// Storage: FedecomPSDemo Payments (r:1 w:1)
+	/// Proof: FedecomPSDemo Payments (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo Confirmations (r:1 w:0)
+	/// Proof: FedecomPSDemo Confirmations (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
 	fn modify_payment() -> Weight {
-		Weight::from_parts(9_000_000, 0)
+		Weight::from_parts(12_000_000, 5174)
+			.saturating_add(T::DbWeight::get().reads(2_u64))
 			.saturating_add(T::DbWeight::get().writes(1_u64))
 	}
+	/// Storage: FedecomPSDemo Payments (r:1 w:1)
+	/// Proof: FedecomPSDemo Payments (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
 	fn add_payment() -> Weight {
-		Weight::from_parts(9_000_000, 0)
+		Weight::from_parts(10_000_000, 2587)
+			.saturating_add(T::DbWeight::get().reads(1_u64))
 			.saturating_add(T::DbWeight::get().writes(1_u64))
 	}
+	/// Storage: FedecomPSDemo Payments (r:1 w:1)
+	/// Proof: FedecomPSDemo Payments (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo Confirmations (r:1 w:0)
+	/// Proof: FedecomPSDemo Confirmations (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
 	fn remove_payment() -> Weight {
-		Weight::from_parts(9_000_000, 0)
+		Weight::from_parts(12_000_000, 5174)
+			.saturating_add(T::DbWeight::get().reads(2_u64))
 			.saturating_add(T::DbWeight::get().writes(1_u64))
 	}
+	/// Storage: FedecomPSDemo Payments (r:1 w:0)
+	/// Proof: FedecomPSDemo Payments (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo Confirmations (r:1 w:1)
+	/// Proof: FedecomPSDemo Confirmations (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
 	fn add_confirmation() -> Weight {
-		Weight::from_parts(9_000_000, 0)
+		Weight::from_parts(12_000_000, 5174)
+			.saturating_add(T::DbWeight::get().reads(2_u64))
 			.saturating_add(T::DbWeight::get().writes(1_u64))
 	}
+	/// Storage: FedecomPSDemo Confirmations (r:1 w:1)
+	/// Proof: FedecomPSDemo Confirmations (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
 	fn remove_confirmation() -> Weight {
-		Weight::from_parts(9_000_000, 0)
+		Weight::from_parts(10_000_000, 2587)
+			.saturating_add(T::DbWeight::get().reads(1_u64))
 			.saturating_add(T::DbWeight::get().writes(1_u64))
 	}
+	/// Storage: FedecomPSDemo Participants (r:2 w:0)
+	/// Proof: FedecomPSDemo Participants (max_values: None, max_size: Some(52), added: 2527, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo Markets (r:1 w:0)
+	/// Proof: FedecomPSDemo Markets (max_values: None, max_size: Some(44), added: 2519, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo Assets (r:1 w:0)
+	/// Proof: FedecomPSDemo Assets (max_values: None, max_size: Some(44), added: 2519, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo MigratingLedger (r:1 w:0)
+	/// Proof: FedecomPSDemo MigratingLedger (max_values: Some(1), max_size: Some(1), added: 496, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo ClosedPeriods (r:1 w:0)
+	/// Proof: FedecomPSDemo ClosedPeriods (max_values: None, max_size: Some(24), added: 2499, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo FlexibilityOffers (r:1 w:1)
+	/// Proof: FedecomPSDemo FlexibilityOffers (max_values: None, max_size: Some(144), added: 2619, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo PeriodOffers (r:1 w:1)
+	/// Proof: FedecomPSDemo PeriodOffers (max_values: None, max_size: Some(28), added: 2503, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo SellerOffers (r:1 w:1)
+	/// Proof: FedecomPSDemo SellerOffers (max_values: None, max_size: Some(76), added: 2551, mode: MaxEncodedLen)
 	fn flexibility_selling() -> Weight {
+		Weight::from_parts(24_000_000, 20760)
+			.saturating_add(T::DbWeight::get().reads(9_u64))
+			.saturating_add(T::DbWeight::get().writes(3_u64))
+	}
+	/// Storage: FedecomPSDemo Participants (r:2 w:0)
+	/// Proof: FedecomPSDemo Participants (max_values: None, max_size: Some(52), added: 2527, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo Markets (r:1 w:0)
+	/// Proof: FedecomPSDemo Markets (max_values: None, max_size: Some(44), added: 2519, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo Assets (r:1 w:0)
+	/// Proof: FedecomPSDemo Assets (max_values: None, max_size: Some(44), added: 2519, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo MigratingLedger (r:1 w:0)
+	/// Proof: FedecomPSDemo MigratingLedger (max_values: Some(1), max_size: Some(1), added: 496, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo ClosedPeriods (r:1 w:0)
+	/// Proof: FedecomPSDemo ClosedPeriods (max_values: None, max_size: Some(24), added: 2499, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo FlexibilityOffers (r:1 w:1)
+	/// Proof: FedecomPSDemo FlexibilityOffers (max_values: None, max_size: Some(144), added: 2619, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo Confirmations (r:1 w:0)
+	/// Proof: FedecomPSDemo Confirmations (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo Payments (r:1 w:1)
+	/// Proof: FedecomPSDemo Payments (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
+	fn flexibility_purchase() -> Weight {
+		Weight::from_parts(25_000_000, 20880)
+			.saturating_add(T::DbWeight::get().reads(9_u64))
+			.saturating_add(T::DbWeight::get().writes(2_u64))
+	}
+	/// Storage: FedecomPSDemo Participants (r:0 w:1)
+	/// Proof: FedecomPSDemo Participants (max_values: None, max_size: Some(52), added: 2527, mode: MaxEncodedLen)
+	fn register_participant() -> Weight {
 		Weight::from_parts(9_000_000, 0)
 			.saturating_add(T::DbWeight::get().writes(1_u64))
 	}
-	fn flexibility_purchase() -> Weight {
+	/// Storage: FedecomPSDemo Participants (r:1 w:1)
+	/// Proof: FedecomPSDemo Participants (max_values: None, max_size: Some(52), added: 2527, mode: MaxEncodedLen)
+	fn remove_participant() -> Weight {
+		Weight::from_parts(10_000_000, 2527)
+			.saturating_add(T::DbWeight::get().reads(1_u64))
+			.saturating_add(T::DbWeight::get().writes(1_u64))
+	}
+	/// Storage: FedecomPSDemo Markets (r:0 w:1)
+	/// Proof: FedecomPSDemo Markets (max_values: None, max_size: Some(44), added: 2519, mode: MaxEncodedLen)
+	fn register_market() -> Weight {
+		Weight::from_parts(9_000_000, 0)
+			.saturating_add(T::DbWeight::get().writes(1_u64))
+	}
+	/// Storage: FedecomPSDemo Markets (r:1 w:1)
+	/// Proof: FedecomPSDemo Markets (max_values: None, max_size: Some(44), added: 2519, mode: MaxEncodedLen)
+	fn remove_market() -> Weight {
+		Weight::from_parts(10_000_000, 2519)
+			.saturating_add(T::DbWeight::get().reads(1_u64))
+			.saturating_add(T::DbWeight::get().writes(1_u64))
+	}
+	/// Storage: FedecomPSDemo Assets (r:0 w:1)
+	/// Proof: FedecomPSDemo Assets (max_values: None, max_size: Some(44), added: 2519, mode: MaxEncodedLen)
+	fn register_asset() -> Weight {
 		Weight::from_parts(9_000_000, 0)
 			.saturating_add(T::DbWeight::get().writes(1_u64))
 	}
+	/// Storage: FedecomPSDemo PalletPaused (r:1 w:1)
+	/// Proof: FedecomPSDemo PalletPaused (max_values: Some(1), max_size: Some(1), added: 496, mode: MaxEncodedLen)
+	fn pause_pallet() -> Weight {
+		Weight::from_parts(9_000_000, 496)
+			.saturating_add(T::DbWeight::get().reads(1_u64))
+			.saturating_add(T::DbWeight::get().writes(1_u64))
+	}
+	/// Storage: FedecomPSDemo PalletPaused (r:1 w:1)
+	/// Proof: FedecomPSDemo PalletPaused (max_values: Some(1), max_size: Some(1), added: 496, mode: MaxEncodedLen)
+	fn unpause_pallet() -> Weight {
+		Weight::from_parts(9_000_000, 496)
+			.saturating_add(T::DbWeight::get().reads(1_u64))
+			.saturating_add(T::DbWeight::get().writes(1_u64))
+	}
+	/// Storage: FedecomPSDemo PausedCalls (r:1 w:1)
+	/// Proof: FedecomPSDemo PausedCalls (max_values: None, max_size: Some(81), added: 2556, mode: MaxEncodedLen)
+	fn pause_call() -> Weight {
+		Weight::from_parts(9_000_000, 2556)
+			.saturating_add(T::DbWeight::get().reads(1_u64))
+			.saturating_add(T::DbWeight::get().writes(1_u64))
+	}
+	/// Storage: FedecomPSDemo PausedCalls (r:1 w:1)
+	/// Proof: FedecomPSDemo PausedCalls (max_values: None, max_size: Some(81), added: 2556, mode: MaxEncodedLen)
+	fn unpause_call() -> Weight {
+		Weight::from_parts(9_000_000, 2556)
+			.saturating_add(T::DbWeight::get().reads(1_u64))
+			.saturating_add(T::DbWeight::get().writes(1_u64))
+	}
+	/// Storage: FedecomPSDemo PausedMarkets (r:1 w:1)
+	/// Proof: FedecomPSDemo PausedMarkets (max_values: None, max_size: Some(12), added: 2487, mode: MaxEncodedLen)
+	fn pause_market() -> Weight {
+		Weight::from_parts(9_000_000, 2487)
+			.saturating_add(T::DbWeight::get().reads(1_u64))
+			.saturating_add(T::DbWeight::get().writes(1_u64))
+	}
+	/// Storage: FedecomPSDemo PausedMarkets (r:1 w:1)
+	/// Proof: FedecomPSDemo PausedMarkets (max_values: None, max_size: Some(12), added: 2487, mode: MaxEncodedLen)
+	fn unpause_market() -> Weight {
+		Weight::from_parts(9_000_000, 2487)
+			.saturating_add(T::DbWeight::get().reads(1_u64))
+			.saturating_add(T::DbWeight::get().writes(1_u64))
+	}
+	/// Storage: FedecomPSDemo Markets (r:1 w:0)
+	/// Proof: FedecomPSDemo Markets (max_values: None, max_size: Some(44), added: 2519, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo MigratingLedger (r:1 w:0)
+	/// Proof: FedecomPSDemo MigratingLedger (max_values: Some(1), max_size: Some(1), added: 496, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo PeriodOffers (r:1 w:1)
+	/// Proof: FedecomPSDemo PeriodOffers (max_values: None, max_size: Some(28), added: 2503, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo ClosedPeriods (r:1 w:1)
+	/// Proof: FedecomPSDemo ClosedPeriods (max_values: None, max_size: Some(24), added: 2499, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo FlexibilityOffers (r:1 w:1)
+	/// Proof: FedecomPSDemo FlexibilityOffers (max_values: None, max_size: Some(144), added: 2619, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo SellerOffers (r:1 w:1)
+	/// Proof: FedecomPSDemo SellerOffers (max_values: None, max_size: Some(76), added: 2551, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo SettledOffers (r:0 w:1)
+	/// Proof: FedecomPSDemo SettledOffers (max_values: None, max_size: Some(144), added: 2619, mode: MaxEncodedLen)
+	/// The range of component `n` is `[0, 1000]`.
+	fn close_market_period(n: u32, ) -> Weight {
+		Weight::from_parts(12_000_000, 8017)
+			.saturating_add(Weight::from_parts(6_000_000, 0).saturating_mul(n.into()))
+			.saturating_add(T::DbWeight::get().reads(4_u64))
+			.saturating_add(T::DbWeight::get().reads((2_u64).saturating_mul(n.into())))
+			.saturating_add(T::DbWeight::get().writes(2_u64))
+			.saturating_add(T::DbWeight::get().writes((3_u64).saturating_mul(n.into())))
+			.saturating_add(Weight::from_parts(0, 5170).saturating_mul(n.into()))
+	}
+	/// Storage: FedecomPSDemo FlexibilityMarketLedger (r:1 w:1)
+	/// Proof: FedecomPSDemo FlexibilityMarketLedger (max_values: None, max_size: Some(144), added: 2619, mode: MaxEncodedLen)
+	/// The range of component `n` is `[0, 1000]`.
+	/// The range of component `m` is `[0, 1000]`.
+	fn clear_legacy_period(n: u32, m: u32, ) -> Weight {
+		Weight::from_parts(9_000_000, 0)
+			.saturating_add(Weight::from_parts(3_000_000, 0).saturating_mul(n.into()))
+			.saturating_add(Weight::from_parts(2_000_000, 0).saturating_mul(m.into()))
+			.saturating_add(T::DbWeight::get().reads((1_u64).saturating_mul(n.into())))
+			.saturating_add(T::DbWeight::get().reads((1_u64).saturating_mul(m.into())))
+			.saturating_add(T::DbWeight::get().writes((1_u64).saturating_mul(n.into())))
+			.saturating_add(Weight::from_parts(0, 2619).saturating_mul(n.into()))
+			.saturating_add(Weight::from_parts(0, 2619).saturating_mul(m.into()))
+	}
+	/// Storage: FedecomPSDemo FlexibilityMarketLedger (r:1 w:1)
+	/// Proof: FedecomPSDemo FlexibilityMarketLedger (max_values: None, max_size: Some(144), added: 2619, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo PeriodOffers (r:1 w:1)
+	/// Proof: FedecomPSDemo PeriodOffers (max_values: None, max_size: Some(28), added: 2503, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo SellerOffers (r:1 w:1)
+	/// Proof: FedecomPSDemo SellerOffers (max_values: None, max_size: Some(76), added: 2551, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo FlexibilityOffers (r:0 w:1)
+	/// Proof: FedecomPSDemo FlexibilityOffers (max_values: None, max_size: Some(144), added: 2619, mode: MaxEncodedLen)
+	fn migrate_offer() -> Weight {
+		Weight::from_parts(15_000_000, 7673)
+			.saturating_add(T::DbWeight::get().reads(3_u64))
+			.saturating_add(T::DbWeight::get().writes(4_u64))
+	}
 }
 
 // For backwards compatibility and tests
@@ -104,32 +282,212 @@ impl WeightInfo for () {
 		Weight::from_parts(0, 0)
 			.saturating_add(RocksDbWeight::get().writes(1_u64))
 	}
+	/// Storage: FedecomPSDemo Payments (r:1 w:1)
+	/// Proof: FedecomPSDemo Payments (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo Confirmations (r:1 w:0)
+	/// Proof: FedecomPSDemo Confirmations (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
 	fn modify_payment() -> Weight {
-		Weight::from_parts(9_000_000, 0)
+		Weight::from_parts(12_000_000, 5174)
+			.saturating_add(RocksDbWeight::get().reads(2_u64))
 			.saturating_add(RocksDbWeight::get().writes(1_u64))
 	}
+	/// Storage: FedecomPSDemo Payments (r:1 w:1)
+	/// Proof: FedecomPSDemo Payments (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
 	fn add_payment() -> Weight {
-		Weight::from_parts(9_000_000, 0)
+		Weight::from_parts(10_000_000, 2587)
+			.saturating_add(RocksDbWeight::get().reads(1_u64))
 			.saturating_add(RocksDbWeight::get().writes(1_u64))
 	}
+	/// Storage: FedecomPSDemo Payments (r:1 w:1)
+	/// Proof: FedecomPSDemo Payments (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo Confirmations (r:1 w:0)
+	/// Proof: FedecomPSDemo Confirmations (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
 	fn remove_payment() -> Weight {
-		Weight::from_parts(9_000_000, 0)
+		Weight::from_parts(12_000_000, 5174)
+			.saturating_add(RocksDbWeight::get().reads(2_u64))
 			.saturating_add(RocksDbWeight::get().writes(1_u64))
 	}
+	/// Storage: FedecomPSDemo Payments (r:1 w:0)
+	/// Proof: FedecomPSDemo Payments (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo Confirmations (r:1 w:1)
+	/// Proof: FedecomPSDemo Confirmations (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
 	fn add_confirmation() -> Weight {
-		Weight::from_parts(9_000_000, 0)
+		Weight::from_parts(12_000_000, 5174)
+			.saturating_add(RocksDbWeight::get().reads(2_u64))
 			.saturating_add(RocksDbWeight::get().writes(1_u64))
 	}
+	/// Storage: FedecomPSDemo Confirmations (r:1 w:1)
+	/// Proof: FedecomPSDemo Confirmations (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
 	fn remove_confirmation() -> Weight {
-		Weight::from_parts(9_000_000, 0)
+		Weight::from_parts(10_000_000, 2587)
+			.saturating_add(RocksDbWeight::get().reads(1_u64))
 			.saturating_add(RocksDbWeight::get().writes(1_u64))
 	}
+	/// Storage: FedecomPSDemo Participants (r:2 w:0)
+	/// Proof: FedecomPSDemo Participants (max_values: None, max_size: Some(52), added: 2527, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo Markets (r:1 w:0)
+	/// Proof: FedecomPSDemo Markets (max_values: None, max_size: Some(44), added: 2519, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo Assets (r:1 w:0)
+	/// Proof: FedecomPSDemo Assets (max_values: None, max_size: Some(44), added: 2519, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo MigratingLedger (r:1 w:0)
+	/// Proof: FedecomPSDemo MigratingLedger (max_values: Some(1), max_size: Some(1), added: 496, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo ClosedPeriods (r:1 w:0)
+	/// Proof: FedecomPSDemo ClosedPeriods (max_values: None, max_size: Some(24), added: 2499, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo FlexibilityOffers (r:1 w:1)
+	/// Proof: FedecomPSDemo FlexibilityOffers (max_values: None, max_size: Some(144), added: 2619, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo PeriodOffers (r:1 w:1)
+	/// Proof: FedecomPSDemo PeriodOffers (max_values: None, max_size: Some(28), added: 2503, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo SellerOffers (r:1 w:1)
+	/// Proof: FedecomPSDemo SellerOffers (max_values: None, max_size: Some(76), added: 2551, mode: MaxEncodedLen)
 	fn flexibility_selling() -> Weight {
+		Weight::from_parts(24_000_000, 20760)
+			.saturating_add(RocksDbWeight::get().reads(9_u64))
+			.saturating_add(RocksDbWeight::get().writes(3_u64))
+	}
+	/// Storage: FedecomPSDemo Participants (r:2 w:0)
+	/// Proof: FedecomPSDemo Participants (max_values: None, max_size: Some(52), added: 2527, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo Markets (r:1 w:0)
+	/// Proof: FedecomPSDemo Markets (max_values: None, max_size: Some(44), added: 2519, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo Assets (r:1 w:0)
+	/// Proof: FedecomPSDemo Assets (max_values: None, max_size: Some(44), added: 2519, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo MigratingLedger (r:1 w:0)
+	/// Proof: FedecomPSDemo MigratingLedger (max_values: Some(1), max_size: Some(1), added: 496, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo ClosedPeriods (r:1 w:0)
+	/// Proof: FedecomPSDemo ClosedPeriods (max_values: None, max_size: Some(24), added: 2499, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo FlexibilityOffers (r:1 w:1)
+	/// Proof: FedecomPSDemo FlexibilityOffers (max_values: None, max_size: Some(144), added: 2619, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo Confirmations (r:1 w:0)
+	/// Proof: FedecomPSDemo Confirmations (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo Payments (r:1 w:1)
+	/// Proof: FedecomPSDemo Payments (max_values: None, max_size: Some(112), added: 2587, mode: MaxEncodedLen)
+	fn flexibility_purchase() -> Weight {
+		Weight::from_parts(25_000_000, 20880)
+			.saturating_add(RocksDbWeight::get().reads(9_u64))
+			.saturating_add(RocksDbWeight::get().writes(2_u64))
+	}
+	/// Storage: FedecomPSDemo Participants (r:0 w:1)
+	/// Proof: FedecomPSDemo Participants (max_values: None, max_size: Some(52), added: 2527, mode: MaxEncodedLen)
+	fn register_participant() -> Weight {
 		Weight::from_parts(9_000_000, 0)
 			.saturating_add(RocksDbWeight::get().writes(1_u64))
 	}
-	fn flexibility_purchase() -> Weight {
+	/// Storage: FedecomPSDemo Participants (r:1 w:1)
+	/// Proof: FedecomPSDemo Participants (max_values: None, max_size: Some(52), added: 2527, mode: MaxEncodedLen)
+	fn remove_participant() -> Weight {
+		Weight::from_parts(10_000_000, 2527)
+			.saturating_add(RocksDbWeight::get().reads(1_u64))
+			.saturating_add(RocksDbWeight::get().writes(1_u64))
+	}
+	/// Storage: FedecomPSDemo Markets (r:0 w:1)
+	/// Proof: FedecomPSDemo Markets (max_values: None, max_size: Some(44), added: 2519, mode: MaxEncodedLen)
+	fn register_market() -> Weight {
+		Weight::from_parts(9_000_000, 0)
+			.saturating_add(RocksDbWeight::get().writes(1_u64))
+	}
+	/// Storage: FedecomPSDemo Markets (r:1 w:1)
+	/// Proof: FedecomPSDemo Markets (max_values: None, max_size: Some(44), added: 2519, mode: MaxEncodedLen)
+	fn remove_market() -> Weight {
+		Weight::from_parts(10_000_000, 2519)
+			.saturating_add(RocksDbWeight::get().reads(1_u64))
+			.saturating_add(RocksDbWeight::get().writes(1_u64))
+	}
+	/// Storage: FedecomPSDemo Assets (r:0 w:1)
+	/// Proof: FedecomPSDemo Assets (max_values: None, max_size: Some(44), added: 2519, mode: MaxEncodedLen)
+	fn register_asset() -> Weight {
 		Weight::from_parts(9_000_000, 0)
 			.saturating_add(RocksDbWeight::get().writes(1_u64))
 	}
+	/// Storage: FedecomPSDemo PalletPaused (r:1 w:1)
+	/// Proof: FedecomPSDemo PalletPaused (max_values: Some(1), max_size: Some(1), added: 496, mode: MaxEncodedLen)
+	fn pause_pallet() -> Weight {
+		Weight::from_parts(9_000_000, 496)
+			.saturating_add(RocksDbWeight::get().reads(1_u64))
+			.saturating_add(RocksDbWeight::get().writes(1_u64))
+	}
+	/// Storage: FedecomPSDemo PalletPaused (r:1 w:1)
+	/// Proof: FedecomPSDemo PalletPaused (max_values: Some(1), max_size: Some(1), added: 496, mode: MaxEncodedLen)
+	fn unpause_pallet() -> Weight {
+		Weight::from_parts(9_000_000, 496)
+			.saturating_add(RocksDbWeight::get().reads(1_u64))
+			.saturating_add(RocksDbWeight::get().writes(1_u64))
+	}
+	/// Storage: FedecomPSDemo PausedCalls (r:1 w:1)
+	/// Proof: FedecomPSDemo PausedCalls (max_values: None, max_size: Some(81), added: 2556, mode: MaxEncodedLen)
+	fn pause_call() -> Weight {
+		Weight::from_parts(9_000_000, 2556)
+			.saturating_add(RocksDbWeight::get().reads(1_u64))
+			.saturating_add(RocksDbWeight::get().writes(1_u64))
+	}
+	/// Storage: FedecomPSDemo PausedCalls (r:1 w:1)
+	/// Proof: FedecomPSDemo PausedCalls (max_values: None, max_size: Some(81), added: 2556, mode: MaxEncodedLen)
+	fn unpause_call() -> Weight {
+		Weight::from_parts(9_000_000, 2556)
+			.saturating_add(RocksDbWeight::get().reads(1_u64))
+			.saturating_add(RocksDbWeight::get().writes(1_u64))
+	}
+	/// Storage: FedecomPSDemo PausedMarkets (r:1 w:1)
+	/// Proof: FedecomPSDemo PausedMarkets (max_values: None, max_size: Some(12), added: 2487, mode: MaxEncodedLen)
+	fn pause_market() -> Weight {
+		Weight::from_parts(9_000_000, 2487)
+			.saturating_add(RocksDbWeight::get().reads(1_u64))
+			.saturating_add(RocksDbWeight::get().writes(1_u64))
+	}
+	/// Storage: FedecomPSDemo PausedMarkets (r:1 w:1)
+	/// Proof: FedecomPSDemo PausedMarkets (max_values: None, max_size: Some(12), added: 2487, mode: MaxEncodedLen)
+	fn unpause_market() -> Weight {
+		Weight::from_parts(9_000_000, 2487)
+			.saturating_add(RocksDbWeight::get().reads(1_u64))
+			.saturating_add(RocksDbWeight::get().writes(1_u64))
+	}
+	/// Storage: FedecomPSDemo Markets (r:1 w:0)
+	/// Proof: FedecomPSDemo Markets (max_values: None, max_size: Some(44), added: 2519, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo MigratingLedger (r:1 w:0)
+	/// Proof: FedecomPSDemo MigratingLedger (max_values: Some(1), max_size: Some(1), added: 496, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo PeriodOffers (r:1 w:1)
+	/// Proof: FedecomPSDemo PeriodOffers (max_values: None, max_size: Some(28), added: 2503, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo ClosedPeriods (r:1 w:1)
+	/// Proof: FedecomPSDemo ClosedPeriods (max_values: None, max_size: Some(24), added: 2499, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo FlexibilityOffers (r:1 w:1)
+	/// Proof: FedecomPSDemo FlexibilityOffers (max_values: None, max_size: Some(144), added: 2619, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo SellerOffers (r:1 w:1)
+	/// Proof: FedecomPSDemo SellerOffers (max_values: None, max_size: Some(76), added: 2551, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo SettledOffers (r:0 w:1)
+	/// Proof: FedecomPSDemo SettledOffers (max_values: None, max_size: Some(144), added: 2619, mode: MaxEncodedLen)
+	/// The range of component `n` is `[0, 1000]`.
+	fn close_market_period(n: u32, ) -> Weight {
+		Weight::from_parts(12_000_000, 8017)
+			.saturating_add(Weight::from_parts(6_000_000, 0).saturating_mul(n.into()))
+			.saturating_add(RocksDbWeight::get().reads(4_u64))
+			.saturating_add(RocksDbWeight::get().reads((2_u64).saturating_mul(n.into())))
+			.saturating_add(RocksDbWeight::get().writes(2_u64))
+			.saturating_add(RocksDbWeight::get().writes((3_u64).saturating_mul(n.into())))
+			.saturating_add(Weight::from_parts(0, 5170).saturating_mul(n.into()))
+	}
+	/// Storage: FedecomPSDemo FlexibilityMarketLedger (r:1 w:1)
+	/// Proof: FedecomPSDemo FlexibilityMarketLedger (max_values: None, max_size: Some(144), added: 2619, mode: MaxEncodedLen)
+	/// The range of component `n` is `[0, 1000]`.
+	/// The range of component `m` is `[0, 1000]`.
+	fn clear_legacy_period(n: u32, m: u32, ) -> Weight {
+		Weight::from_parts(9_000_000, 0)
+			.saturating_add(Weight::from_parts(3_000_000, 0).saturating_mul(n.into()))
+			.saturating_add(Weight::from_parts(2_000_000, 0).saturating_mul(m.into()))
+			.saturating_add(RocksDbWeight::get().reads((1_u64).saturating_mul(n.into())))
+			.saturating_add(RocksDbWeight::get().reads((1_u64).saturating_mul(m.into())))
+			.saturating_add(RocksDbWeight::get().writes((1_u64).saturating_mul(n.into())))
+			.saturating_add(Weight::from_parts(0, 2619).saturating_mul(n.into()))
+			.saturating_add(Weight::from_parts(0, 2619).saturating_mul(m.into()))
+	}
+	/// Storage: FedecomPSDemo FlexibilityMarketLedger (r:1 w:1)
+	/// Proof: FedecomPSDemo FlexibilityMarketLedger (max_values: None, max_size: Some(144), added: 2619, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo PeriodOffers (r:1 w:1)
+	/// Proof: FedecomPSDemo PeriodOffers (max_values: None, max_size: Some(28), added: 2503, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo SellerOffers (r:1 w:1)
+	/// Proof: FedecomPSDemo SellerOffers (max_values: None, max_size: Some(76), added: 2551, mode: MaxEncodedLen)
+	/// Storage: FedecomPSDemo FlexibilityOffers (r:0 w:1)
+	/// Proof: FedecomPSDemo FlexibilityOffers (max_values: None, max_size: Some(144), added: 2619, mode: MaxEncodedLen)
+	fn migrate_offer() -> Weight {
+		Weight::from_parts(15_000_000, 7673)
+			.saturating_add(RocksDbWeight::get().reads(3_u64))
+			.saturating_add(RocksDbWeight::get().writes(4_u64))
+	}
 }
@@ -0,0 +1,52 @@
+use crate::{mock::*, Event};
+use frame_support::{assert_noop, assert_ok};
+use sp_runtime::DispatchError;
+
+fn set_storage_call() -> Box<RuntimeCall> {
+	Box::new(RuntimeCall::System(frame_system::Call::set_storage {
+		items: vec![(b"key".to_vec(), b"value".to_vec())],
+	}))
+}
+
+#[test]
+fn council_dispatches_root_calls() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		assert_ok!(Governance::dispatch_as_root(RuntimeOrigin::signed(1), set_storage_call()));
+
+		System::assert_last_event(Event::DispatchedAsRoot { result: Ok(()) }.into());
+		assert_eq!(sp_io::storage::get(b"key").as_deref(), Some(&b"value"[..]));
+	});
+}
+
+#[test]
+fn others_cannot_dispatch_root_calls() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(
+			Governance::dispatch_as_root(RuntimeOrigin::signed(2), set_storage_call()),
+			DispatchError::BadOrigin
+		);
+		assert_noop!(
+			Governance::dispatch_as_root(RuntimeOrigin::none(), set_storage_call()),
+			DispatchError::BadOrigin
+		);
+		assert_eq!(sp_io::storage::get(b"key"), None);
+	});
+}
+
+#[test]
+fn failed_call_is_reported() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		// `remark_with_event` requires a signed origin
+		let call = Box::new(RuntimeCall::System(frame_system::Call::remark_with_event {
+			remark: vec![],
+		}));
+
+		assert_ok!(Governance::dispatch_as_root(RuntimeOrigin::signed(1), call));
+
+		System::assert_last_event(
+			Event::DispatchedAsRoot { result: Err(DispatchError::BadOrigin) }.into(),
+		);
+	});
+}
@@ -0,0 +1,30 @@
+//! Weights for pallet_governance
+//!
+//! Estimated by hand until the pallet is benchmarked on reference hardware. The weight of the
+//! dispatched call is added on top.
+
+#![cfg_attr(rustfmt, rustfmt_skip)]
+#![allow(unused_parens)]
+#![allow(unused_imports)]
+
+use frame_support::{traits::Get, weights::{Weight, constants::RocksDbWeight}};
+use core::marker::PhantomData;
+
+/// Weight functions needed for pallet_governance.
+pub trait WeightInfo {
+	fn dispatch_as_root() -> Weight;
+}
+
+pub struct SubstrateWeight<T>(PhantomData<T>);
+impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
+	fn dispatch_as_root() -> Weight {
+		Weight::from_parts(10_000_000, 0)
+	}
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+	fn dispatch_as_root() -> Weight {
+		Weight::from_parts(10_000_000, 0)
+	}
+}
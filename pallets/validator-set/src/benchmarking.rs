@@ -0,0 +1,53 @@
+//! Benchmarking setup for pallet-validator-set
+#![cfg(feature = "runtime-benchmarks")]
+use super::*;
+
+#[allow(unused)]
+use crate::Pallet as ValidatorSet;
+use frame_benchmarking::v2::*;
+use frame_support::{
+	traits::{EnsureOrigin, Get},
+	BoundedVec,
+};
+
+#[benchmarks]
+mod benchmarks {
+	use super::*;
+
+	fn fill_validators<T: Config>(count: u32) {
+		let validators: Vec<T::AccountId> =
+			(0..count).map(|i| account("validator", i, 0)).collect();
+		Validators::<T>::put(BoundedVec::truncate_from(validators));
+	}
+
+	#[benchmark]
+	fn add_validator() -> Result<(), BenchmarkError> {
+		fill_validators::<T>(T::MaxValidators::get() - 1);
+		let origin =
+			T::AddRemoveOrigin::try_successful_origin().map_err(|_| BenchmarkError::Weightless)?;
+		let validator: T::AccountId = account("new", 0, 0);
+
+		#[extrinsic_call]
+		_(origin as T::RuntimeOrigin, validator.clone());
+
+		assert!(Validators::<T>::get().contains(&validator));
+		Ok(())
+	}
+
+	#[benchmark]
+	fn remove_validator() -> Result<(), BenchmarkError> {
+		fill_validators::<T>(T::MaxValidators::get());
+		let origin =
+			T::AddRemoveOrigin::try_successful_origin().map_err(|_| BenchmarkError::Weightless)?;
+		// Worst case: the last validator of a full set
+		let validator: T::AccountId = account("validator", T::MaxValidators::get() - 1, 0);
+
+		#[extrinsic_call]
+		_(origin as T::RuntimeOrigin, validator.clone());
+
+		assert!(!Validators::<T>::get().contains(&validator));
+		Ok(())
+	}
+
+	impl_benchmark_test_suite!(ValidatorSet, crate::mock::new_test_ext(), crate::mock::Test);
+}
@@ -0,0 +1,255 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+//! Permissioned validator set of the consortium.
+//!
+//! Validators are added and removed by `AddRemoveOrigin` (e.g. root or a governance body). The
+//! pallet acts as `pallet_session::SessionManager`, so that changes only take effect when the
+//! session pallet rotates to a new session.
+//!
+//! It also handles the offences reported through `pallet_offences`: offending validators are
+//! disabled for the rest of the session and stay in the set, removing them is left to
+//! `AddRemoveOrigin`.
+pub use pallet::*;
+
+use frame_support::{
+	sp_runtime::{traits::Convert, Perbill},
+	traits::Get,
+	weights::Weight,
+};
+use sp_staking::{
+	offence::{DisableStrategy, OffenceDetails, OnOffenceHandler},
+	SessionIndex,
+};
+use sp_std::{marker::PhantomData, prelude::*};
+
+#[cfg(test)]
+mod mock;
+
+#[cfg(test)]
+mod tests;
+
+#[cfg(feature = "runtime-benchmarks")]
+mod benchmarking;
+pub mod weights;
+pub use weights::*;
+
+#[frame_support::pallet]
+pub mod pallet {
+	use super::*;
+	use frame_support::pallet_prelude::*;
+	use frame_system::pallet_prelude::*;
+
+	#[pallet::pallet]
+	pub struct Pallet<T>(_);
+
+	/// Configure the pallet by specifying the parameters and types on which it depends.
+	#[pallet::config]
+	pub trait Config: frame_system::Config {
+		/// Because this pallet emits events, it depends on the runtime's definition of an event.
+		type RuntimeEvent: From<Event<Self>> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
+		/// Origin allowed to add or remove validators.
+		type AddRemoveOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+		/// Minimum number of validators that must be left in the set.
+		#[pallet::constant]
+		type MinAuthorities: Get<u32>;
+		/// Maximum number of validators in the set.
+		#[pallet::constant]
+		type MaxValidators: Get<u32>;
+		/// Type representing the weight of this pallet
+		type WeightInfo: WeightInfo;
+	}
+
+	/// The validators of the next sessions.
+	#[pallet::storage]
+	#[pallet::getter(fn validators)]
+	pub type Validators<T: Config> =
+		StorageValue<_, BoundedVec<T::AccountId, T::MaxValidators>, ValueQuery>;
+
+	/// Whether the validator set changed since the last session rotation.
+	#[pallet::storage]
+	pub type ValidatorsChanged<T> = StorageValue<_, bool, ValueQuery>;
+
+	#[pallet::genesis_config]
+	#[derive(frame_support::DefaultNoBound)]
+	pub struct GenesisConfig<T: Config> {
+		/// Validators of the first session.
+		pub initial_validators: Vec<T::AccountId>,
+	}
+
+	#[pallet::genesis_build]
+	impl<T: Config> BuildGenesisConfig for GenesisConfig<T> {
+		fn build(&self) {
+			let validators: BoundedVec<_, T::MaxValidators> = self
+				.initial_validators
+				.clone()
+				.try_into()
+				.expect("Too many initial validators");
+			Validators::<T>::put(validators);
+		}
+	}
+
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config> {
+		/// A validator was added, it becomes active at the next session rotation. [validator]
+		ValidatorAdded { validator: T::AccountId },
+		/// A validator was removed, it leaves at the next session rotation. [validator]
+		ValidatorRemoved { validator: T::AccountId },
+		/// A validator committed an offence in `session` and was disabled until the end of the
+		/// current session. [validator, session]
+		ValidatorOffended { validator: T::AccountId, session: SessionIndex },
+	}
+
+	#[pallet::error]
+	pub enum Error<T> {
+		/// The account is already a validator.
+		Duplicate,
+		/// The account is not a validator.
+		NotAValidator,
+		/// Removing the validator would leave less than `MinAuthorities` validators.
+		TooLowValidatorCount,
+		/// The validator set already contains `MaxValidators` validators.
+		TooManyValidators,
+	}
+
+	#[pallet::call]
+	impl<T: Config> Pallet<T> {
+		/// Add `validator` to the set. It starts validating once its session keys are registered
+		/// and a new session begins.
+		#[pallet::call_index(0)]
+		#[pallet::weight(T::WeightInfo::add_validator())]
+		pub fn add_validator(origin: OriginFor<T>, validator: T::AccountId) -> DispatchResult {
+			T::AddRemoveOrigin::ensure_origin(origin)?;
+
+			Validators::<T>::try_mutate(|validators| -> DispatchResult {
+				ensure!(!validators.contains(&validator), Error::<T>::Duplicate);
+				validators.try_push(validator.clone()).map_err(|_| Error::<T>::TooManyValidators)?;
+				Ok(())
+			})?;
+			ValidatorsChanged::<T>::put(true);
+
+			Self::deposit_event(Event::ValidatorAdded { validator });
+			Ok(())
+		}
+
+		/// Remove `validator` from the set. It stops validating once a new session begins.
+		#[pallet::call_index(1)]
+		#[pallet::weight(T::WeightInfo::remove_validator())]
+		pub fn remove_validator(origin: OriginFor<T>, validator: T::AccountId) -> DispatchResult {
+			T::AddRemoveOrigin::ensure_origin(origin)?;
+
+			Self::do_remove_validator(&validator)?;
+
+			Self::deposit_event(Event::ValidatorRemoved { validator });
+			Ok(())
+		}
+	}
+
+	impl<T: Config> Pallet<T> {
+		/// Remove `validator` from the set, keeping at least `MinAuthorities` validators.
+		pub fn do_remove_validator(validator: &T::AccountId) -> DispatchResult {
+			Validators::<T>::try_mutate(|validators| -> DispatchResult {
+				let index = validators
+					.iter()
+					.position(|v| v == validator)
+					.ok_or(Error::<T>::NotAValidator)?;
+				ensure!(
+					validators.len() > T::MinAuthorities::get() as usize,
+					Error::<T>::TooLowValidatorCount
+				);
+				validators.remove(index);
+				Ok(())
+			})?;
+			ValidatorsChanged::<T>::put(true);
+			Ok(())
+		}
+	}
+}
+
+impl<T: Config> pallet_session::SessionManager<T::AccountId> for Pallet<T> {
+	fn new_session(_new_index: u32) -> Option<Vec<T::AccountId>> {
+		// Only report a new set when it changed, otherwise GRANDPA would enact a new authority
+		// set at every session.
+		if ValidatorsChanged::<T>::take() {
+			Some(Validators::<T>::get().into_inner())
+		} else {
+			None
+		}
+	}
+
+	fn new_session_genesis(_new_index: u32) -> Option<Vec<T::AccountId>> {
+		Some(Validators::<T>::get().into_inner())
+	}
+
+	fn end_session(_end_index: u32) {}
+
+	fn start_session(_start_index: u32) {}
+}
+
+impl<T: Config> pallet_session::historical::SessionManager<T::AccountId, ()> for Pallet<T> {
+	fn new_session(new_index: u32) -> Option<Vec<(T::AccountId, ())>> {
+		<Self as pallet_session::SessionManager<_>>::new_session(new_index)
+			.map(|validators| validators.into_iter().map(|v| (v, ())).collect())
+	}
+
+	fn new_session_genesis(new_index: u32) -> Option<Vec<(T::AccountId, ())>> {
+		<Self as pallet_session::SessionManager<_>>::new_session_genesis(new_index)
+			.map(|validators| validators.into_iter().map(|v| (v, ())).collect())
+	}
+
+	fn end_session(end_index: u32) {
+		<Self as pallet_session::SessionManager<_>>::end_session(end_index)
+	}
+
+	fn start_session(start_index: u32) {
+		<Self as pallet_session::SessionManager<_>>::start_session(start_index)
+	}
+}
+
+/// Full identification of a validator for `pallet_session::historical`.
+///
+/// Validators have no stake, so the identification carries no data besides the account.
+pub struct FullIdentificationOf<T>(PhantomData<T>);
+
+impl<T: Config> Convert<T::AccountId, Option<()>> for FullIdentificationOf<T> {
+	fn convert(_validator: T::AccountId) -> Option<()> {
+		Some(())
+	}
+}
+
+impl<T> OnOffenceHandler<T::AccountId, (T::AccountId, ()), Weight> for Pallet<T>
+where
+	T: Config + pallet_session::Config<ValidatorId = <T as frame_system::Config>::AccountId>,
+{
+	fn on_offence(
+		offenders: &[OffenceDetails<T::AccountId, (T::AccountId, ())>],
+		slash_fraction: &[Perbill],
+		session: SessionIndex,
+		disable_strategy: DisableStrategy,
+	) -> Weight {
+		let mut weight = Weight::zero();
+
+		for (details, fraction) in offenders.iter().zip(slash_fraction) {
+			let disable = match disable_strategy {
+				DisableStrategy::Never => false,
+				DisableStrategy::WhenSlashed => *fraction != Perbill::from_parts(0),
+				DisableStrategy::Always => true,
+			};
+			if !disable {
+				continue
+			}
+
+			let (validator, _) = &details.offender;
+			// Stop authoring blocks until the end of the session, Aura skips the slots of disabled
+			// validators. The validator is not removed from the set, which is up to governance.
+			weight = weight.saturating_add(T::DbWeight::get().reads_writes(2, 1));
+			if pallet_session::Pallet::<T>::disable(validator) {
+				Self::deposit_event(Event::ValidatorOffended {
+					validator: validator.clone(),
+					session,
+				});
+			}
+		}
+
+		weight
+	}
+}
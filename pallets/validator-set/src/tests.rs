@@ -0,0 +1,76 @@
+use crate::{mock::*, Error, Event};
+use frame_support::{assert_noop, assert_ok};
+use pallet_session::SessionManager;
+use sp_runtime::DispatchError;
+
+#[test]
+fn genesis_validators_start_the_first_session() {
+	new_test_ext().execute_with(|| {
+		assert_eq!(ValidatorSet::new_session_genesis(0), Some(vec![1, 2, 3]));
+		// Nothing changed since genesis
+		assert_eq!(ValidatorSet::new_session(1), None);
+	});
+}
+
+#[test]
+fn add_validator_takes_effect_at_next_session() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		assert_ok!(ValidatorSet::add_validator(RuntimeOrigin::root(), 4));
+		System::assert_last_event(Event::ValidatorAdded { validator: 4 }.into());
+
+		assert_eq!(ValidatorSet::validators().into_inner(), vec![1, 2, 3, 4]);
+		assert_eq!(ValidatorSet::new_session(1), Some(vec![1, 2, 3, 4]));
+		assert_eq!(ValidatorSet::new_session(2), None);
+	});
+}
+
+#[test]
+fn remove_validator_takes_effect_at_next_session() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		assert_ok!(ValidatorSet::remove_validator(RuntimeOrigin::root(), 2));
+		System::assert_last_event(Event::ValidatorRemoved { validator: 2 }.into());
+
+		assert_eq!(ValidatorSet::new_session(1), Some(vec![1, 3]));
+		assert_eq!(ValidatorSet::new_session(2), None);
+	});
+}
+
+#[test]
+fn only_add_remove_origin_can_change_the_set() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(
+			ValidatorSet::add_validator(RuntimeOrigin::signed(1), 4),
+			DispatchError::BadOrigin
+		);
+		assert_noop!(
+			ValidatorSet::remove_validator(RuntimeOrigin::signed(1), 3),
+			DispatchError::BadOrigin
+		);
+	});
+}
+
+#[test]
+fn invalid_changes_are_rejected() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(ValidatorSet::add_validator(RuntimeOrigin::root(), 1), Error::<Test>::Duplicate);
+		assert_noop!(
+			ValidatorSet::remove_validator(RuntimeOrigin::root(), 5),
+			Error::<Test>::NotAValidator
+		);
+
+		assert_ok!(ValidatorSet::add_validator(RuntimeOrigin::root(), 4));
+		assert_noop!(
+			ValidatorSet::add_validator(RuntimeOrigin::root(), 5),
+			Error::<Test>::TooManyValidators
+		);
+
+		assert_ok!(ValidatorSet::remove_validator(RuntimeOrigin::root(), 4));
+		assert_ok!(ValidatorSet::remove_validator(RuntimeOrigin::root(), 3));
+		assert_noop!(
+			ValidatorSet::remove_validator(RuntimeOrigin::root(), 2),
+			Error::<Test>::TooLowValidatorCount
+		);
+	});
+}
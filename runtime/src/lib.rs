@@ -7,15 +7,17 @@
 include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
 
 use pallet_grandpa::AuthorityId as GrandpaId;
+use pallet_session::historical as pallet_session_historical;
 use sp_api::impl_runtime_apis;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use sp_core::{crypto::KeyTypeId, OpaqueMetadata};
 use sp_runtime::{
 	create_runtime_str, generic, impl_opaque_keys,
 	traits::{
-		AccountIdLookup, BlakeTwo256, Block as BlockT, IdentifyAccount, NumberFor, One, Verify,
+		AccountIdLookup, BlakeTwo256, Block as BlockT, ConvertInto, IdentifyAccount, NumberFor,
+		One, OpaqueKeys, Verify,
 	},
-	transaction_validity::{TransactionSource, TransactionValidity},
+	transaction_validity::{InvalidTransaction, TransactionSource, TransactionValidity},
 	ApplyExtrinsicResult, MultiSignature,
 };
 use sp_std::prelude::*;
@@ -27,8 +29,8 @@ use sp_version::RuntimeVersion;
 pub use frame_support::{
 	construct_runtime, parameter_types,
 	traits::{
-		ConstBool, ConstU128, ConstU32, ConstU64, ConstU8, KeyOwnerProofSystem, Randomness,
-		StorageInfo,
+		ConstBool, ConstU128, ConstU32, ConstU64, ConstU8, Contains, EitherOfDiverse,
+		KeyOwnerProofSystem, PalletInfoAccess, Randomness, StorageInfo,
 	},
 	weights::{
 		constants::{
@@ -46,8 +48,12 @@ use pallet_transaction_payment::{ConstFeeMultiplier, CurrencyAdapter, Multiplier
 pub use sp_runtime::BuildStorage;
 pub use sp_runtime::{Perbill, Permill};
 
-/// Import the local pallet.
+/// Import the local pallets.
 pub use pallet_fedecom_ps_demo;
+pub use pallet_governance;
+pub use pallet_validator_set;
+
+pub mod migrations;
 
 /// An index to a block.
 pub type BlockNumber = u32;
@@ -104,7 +110,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	//   `spec_version`, and `authoring_version` are the same between Wasm and native.
 	// This value is set to 100 to notify Polkadot-JS App (https://polkadot.js.org/apps) to use
 	//   the compatible custom types.
-	spec_version: 100,
+	spec_version: 101,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
 	transaction_version: 1,
@@ -150,11 +156,24 @@ parameter_types! {
 	pub const SS58Prefix: u8 = 42;
 }
 
+/// Filters out the calls of the flexibility market paused by its circuit breaker.
+pub struct BaseFilter;
+
+impl Contains<RuntimeCall> for BaseFilter {
+	fn contains(call: &RuntimeCall) -> bool {
+		match call {
+			RuntimeCall::FedecomPSDemo(call) => !FedecomPSDemo::is_paused(call),
+			RuntimeCall::FedecomPSDemo2(call) => !FedecomPSDemo2::is_paused(call),
+			_ => true,
+		}
+	}
+}
+
 // Configure FRAME pallets to include in runtime.
 
 impl frame_system::Config for Runtime {
 	/// The basic call filter to use in dispatchable.
-	type BaseCallFilter = frame_support::traits::Everything;
+	type BaseCallFilter = BaseFilter;
 	/// The block type for the runtime.
 	type Block = Block;
 	/// Block & extrinsics weights: base values and limits.
@@ -202,22 +221,96 @@ impl frame_system::Config for Runtime {
 	type MaxConsumers = frame_support::traits::ConstU32<16>;
 }
 
+parameter_types! {
+	/// Validator set changes are enacted at the next session rotation.
+	pub const SessionPeriod: BlockNumber = 10 * MINUTES;
+	pub const SessionOffset: BlockNumber = 0;
+}
+
+impl pallet_session::Config for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+	type ValidatorId = <Self as frame_system::Config>::AccountId;
+	type ValidatorIdOf = ConvertInto;
+	type ShouldEndSession = pallet_session::PeriodicSessions<SessionPeriod, SessionOffset>;
+	type NextSessionRotation = pallet_session::PeriodicSessions<SessionPeriod, SessionOffset>;
+	type SessionManager = pallet_session::historical::NoteHistoricalRoot<Self, ValidatorSet>;
+	type SessionHandler = <opaque::SessionKeys as OpaqueKeys>::KeyTypeIdProviders;
+	type Keys = opaque::SessionKeys;
+	type WeightInfo = pallet_session::weights::SubstrateWeight<Runtime>;
+}
+
+impl pallet_session::historical::Config for Runtime {
+	type FullIdentification = ();
+	type FullIdentificationOf = pallet_validator_set::FullIdentificationOf<Runtime>;
+}
+
+impl pallet_offences::Config for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+	type IdentificationTuple = pallet_session::historical::IdentificationTuple<Self>;
+	type OnOffenceHandler = ValidatorSet;
+}
+
+parameter_types! {
+	pub const MaxWellKnownNodes: u32 = 64;
+	pub const MaxPeerIdLength: u32 = 128;
+}
+
+/// Peer-to-peer connections are restricted to the nodes of the consortium members.
+///
+/// Well-known nodes are managed by the council, each one is owned by the member operating it, who can
+/// additionally allow connections from and to other nodes.
+impl pallet_node_authorization::Config for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+	type MaxWellKnownNodes = MaxWellKnownNodes;
+	type MaxPeerIdLength = MaxPeerIdLength;
+	type AddOrigin = EnsureRootOrTwoThirdsCouncil;
+	type RemoveOrigin = EnsureRootOrTwoThirdsCouncil;
+	type SwapOrigin = EnsureRootOrTwoThirdsCouncil;
+	type ResetOrigin = EnsureRootOrTwoThirdsCouncil;
+	type WeightInfo = ();
+}
+
+impl pallet_validator_set::Config for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+	type AddRemoveOrigin = EnsureRootOrTwoThirdsCouncil;
+	type MinAuthorities = ConstU32<1>;
+	type MaxValidators = ConstU32<32>;
+	type WeightInfo = pallet_validator_set::weights::SubstrateWeight<Runtime>;
+}
+
 impl pallet_aura::Config for Runtime {
 	type AuthorityId = AuraId;
-	type DisabledValidators = ();
+	type DisabledValidators = Session;
 	type MaxAuthorities = ConstU32<32>;
 	type AllowMultipleBlocksPerSlot = ConstBool<false>;
 }
 
+parameter_types! {
+	/// Number of sessions for which the GRANDPA set id to session mapping is kept, i.e. how far
+	/// back equivocations can be reported (one week).
+	pub const MaxSetIdSessionEntries: u64 = 7 * DAYS as u64 / SessionPeriod::get() as u64;
+	/// Longevity of an equivocation report in the transaction pool, in blocks.
+	pub const ReportLongevity: u64 = 7 * DAYS as u64;
+}
+
 impl pallet_grandpa::Config for Runtime {
 	type RuntimeEvent = RuntimeEvent;
 
 	type WeightInfo = ();
 	type MaxAuthorities = ConstU32<32>;
-	type MaxSetIdSessionEntries = ConstU64<0>;
+	type MaxSetIdSessionEntries = MaxSetIdSessionEntries;
 
-	type KeyOwnerProof = sp_core::Void;
-	type EquivocationReportSystem = ();
+	type KeyOwnerProof = <Historical as KeyOwnerProofSystem<(KeyTypeId, GrandpaId)>>::Proof;
+	type EquivocationReportSystem =
+		pallet_grandpa::EquivocationReportSystem<Self, Offences, Historical, ReportLongevity>;
+}
+
+impl<C> frame_system::offchain::SendTransactionTypes<C> for Runtime
+where
+	RuntimeCall: From<C>,
+{
+	type Extrinsic = UncheckedExtrinsic;
+	type OverarchingCall = RuntimeCall;
 }
 
 impl pallet_timestamp::Config for Runtime {
@@ -262,29 +355,122 @@ impl pallet_transaction_payment::Config for Runtime {
 	type FeeMultiplierUpdate = ConstFeeMultiplier<FeeMultiplier>;
 }
 
-impl pallet_sudo::Config for Runtime {
+parameter_types! {
+	pub const CouncilMotionDuration: BlockNumber = 3 * DAYS;
+	pub const CouncilMaxProposals: u32 = 100;
+	pub const CouncilMaxMembers: u32 = 100;
+	pub MaxProposalWeight: Weight = Perbill::from_percent(50) * BlockWeights::get().max_block;
+}
+
+/// The council of the consortium members (DSOs), which replaces the sudo key.
+pub type CouncilCollective = pallet_collective::Instance1;
+
+/// Root or a two-thirds majority of the council.
+pub type EnsureRootOrTwoThirdsCouncil = EitherOfDiverse<
+	frame_system::EnsureRoot<AccountId>,
+	pallet_collective::EnsureProportionAtLeast<AccountId, CouncilCollective, 2, 3>,
+>;
+
+impl pallet_collective::Config<CouncilCollective> for Runtime {
+	type RuntimeOrigin = RuntimeOrigin;
+	type Proposal = RuntimeCall;
+	type RuntimeEvent = RuntimeEvent;
+	type MotionDuration = CouncilMotionDuration;
+	type MaxProposals = CouncilMaxProposals;
+	type MaxMembers = CouncilMaxMembers;
+	type DefaultVote = pallet_collective::PrimeDefaultVote;
+	type WeightInfo = pallet_collective::weights::SubstrateWeight<Runtime>;
+	type SetMembersOrigin = EnsureRootOrTwoThirdsCouncil;
+	type MaxProposalWeight = MaxProposalWeight;
+}
+
+/// Calls requiring the root origin, e.g. runtime upgrades, are dispatched by council motions.
+impl pallet_governance::Config for Runtime {
 	type RuntimeEvent = RuntimeEvent;
 	type RuntimeCall = RuntimeCall;
-	type WeightInfo = pallet_sudo::weights::SubstrateWeight<Runtime>;
+	type DispatchRootOrigin =
+		pallet_collective::EnsureProportionAtLeast<AccountId, CouncilCollective, 2, 3>;
+	type WeightInfo = pallet_governance::weights::SubstrateWeight<Runtime>;
 }
 
 impl pallet_fedecom_ps_demo::Config for Runtime {
 	type RuntimeEvent = RuntimeEvent;
 	type WeightInfo = pallet_fedecom_ps_demo::weights::SubstrateWeight<Runtime>;
+	type GovernanceOrigin = EnsureRootOrTwoThirdsCouncil;
+	// Any council member can trip the circuit breaker in an emergency, without waiting for a vote
+	type PauseOrigin = EitherOfDiverse<
+		frame_system::EnsureRoot<AccountId>,
+		pallet_collective::EnsureMember<AccountId, CouncilCollective>,
+	>;
+	type OnFlexibilityConfirmed = ();
+	type OnPaymentConfirmed = ();
+	type OnDeliveryDisputed = ();
 	type MaxLength = ();
+	type MaxOffersPerPeriod = ConstU32<1_000>;
+	type MaxOffersPerSeller = ConstU32<50>;
+}
+
+/// The administrators of the second flexibility market, appointed by the council.
+pub type MarketCouncilCollective = pallet_collective::Instance2;
+
+impl pallet_collective::Config<MarketCouncilCollective> for Runtime {
+	type RuntimeOrigin = RuntimeOrigin;
+	type Proposal = RuntimeCall;
+	type RuntimeEvent = RuntimeEvent;
+	type MotionDuration = CouncilMotionDuration;
+	type MaxProposals = CouncilMaxProposals;
+	type MaxMembers = CouncilMaxMembers;
+	type DefaultVote = pallet_collective::PrimeDefaultVote;
+	type WeightInfo = pallet_collective::weights::SubstrateWeight<Runtime>;
+	type SetMembersOrigin = EnsureRootOrTwoThirdsCouncil;
+	type MaxProposalWeight = MaxProposalWeight;
+}
+
+/// Second flexibility market, with its own registry, ledger and circuit breaker, governed by the
+/// market council instead of the consortium council.
+impl pallet_fedecom_ps_demo::Config<pallet_fedecom_ps_demo::Instance2> for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+	type WeightInfo = pallet_fedecom_ps_demo::weights::SubstrateWeight<Runtime>;
+	type GovernanceOrigin = EitherOfDiverse<
+		frame_system::EnsureRoot<AccountId>,
+		pallet_collective::EnsureProportionAtLeast<AccountId, MarketCouncilCollective, 1, 2>,
+	>;
+	type PauseOrigin = EitherOfDiverse<
+		frame_system::EnsureRoot<AccountId>,
+		pallet_collective::EnsureMember<AccountId, MarketCouncilCollective>,
+	>;
+	type OnFlexibilityConfirmed = ();
+	type OnPaymentConfirmed = ();
+	type OnDeliveryDisputed = ();
+	type MaxLength = ConstU32<64>;
+	type MaxOffersPerPeriod = ConstU32<1_000>;
+	type MaxOffersPerSeller = ConstU32<50>;
 }
 
 // Create the runtime by composing the FRAME pallets that were previously configured.
+//
+// The pallet indices are part of the encoding of calls and events, they must not change when
+// pallets are removed (index 6 was `pallet_sudo`).
 construct_runtime!(
 	pub struct Runtime {
-		System: frame_system,
-		Timestamp: pallet_timestamp,
-		Aura: pallet_aura,
-		Grandpa: pallet_grandpa,
-		Balances: pallet_balances,
-		TransactionPayment: pallet_transaction_payment,
-		Sudo: pallet_sudo,
-		FedecomPSDemo: pallet_fedecom_ps_demo
+		System: frame_system = 0,
+		Timestamp: pallet_timestamp = 1,
+		Aura: pallet_aura = 2,
+		Grandpa: pallet_grandpa = 3,
+		Balances: pallet_balances = 4,
+		TransactionPayment: pallet_transaction_payment = 5,
+		FedecomPSDemo: pallet_fedecom_ps_demo = 7,
+		// `ValidatorSet` must come before `Session`, which hands the session keys to Aura and
+		// GRANDPA at genesis.
+		ValidatorSet: pallet_validator_set = 8,
+		Session: pallet_session = 9,
+		Historical: pallet_session_historical = 10,
+		Offences: pallet_offences = 11,
+		NodeAuthorization: pallet_node_authorization = 12,
+		Council: pallet_collective::<Instance1> = 13,
+		Governance: pallet_governance = 14,
+		FedecomPSDemo2: pallet_fedecom_ps_demo::<Instance2> = 15,
+		MarketCouncil: pallet_collective::<Instance2> = 16,
 	}
 );
 
@@ -318,6 +504,7 @@ pub type Executive = frame_executive::Executive<
 	frame_system::ChainContext<Runtime>,
 	Runtime,
 	AllPalletsWithSystem,
+	migrations::Migrations,
 >;
 
 #[cfg(feature = "runtime-benchmarks")]
@@ -331,8 +518,10 @@ mod benches {
 		[frame_system, SystemBench::<Runtime>]
 		[pallet_balances, Balances]
 		[pallet_timestamp, Timestamp]
-		[pallet_sudo, Sudo]
 		[pallet_fedecom_ps_demo, FedecomPSDemo]
+		[pallet_validator_set, ValidatorSet]
+		[pallet_collective, Council]
+		[pallet_governance, Governance]
 	);
 }
 
@@ -392,10 +581,38 @@ impl_runtime_apis! {
 			tx: <Block as BlockT>::Extrinsic,
 			block_hash: <Block as BlockT>::Hash,
 		) -> TransactionValidity {
+			// Calls paused by the circuit breaker would only fail at dispatch, keep them out of
+			// the pool.
+			if !BaseFilter::contains(&tx.function) {
+				return InvalidTransaction::Call.into()
+			}
+
 			Executive::validate_transaction(source, tx, block_hash)
 		}
 	}
 
+	impl pallet_fedecom_ps_demo_runtime_api::FedecomApi<Block> for Runtime {
+		fn circuit_breaker_status() -> pallet_fedecom_ps_demo::CircuitBreakerStatus {
+			FedecomPSDemo::circuit_breaker_status()
+		}
+
+		fn instances() -> Vec<Vec<u8>> {
+			vec![FedecomPSDemo::name().into(), FedecomPSDemo2::name().into()]
+		}
+
+		fn instance_circuit_breaker_status(
+			instance: Vec<u8>,
+		) -> Option<pallet_fedecom_ps_demo::CircuitBreakerStatus> {
+			if instance == FedecomPSDemo::name().as_bytes() {
+				Some(FedecomPSDemo::circuit_breaker_status())
+			} else if instance == FedecomPSDemo2::name().as_bytes() {
+				Some(FedecomPSDemo2::circuit_breaker_status())
+			} else {
+				None
+			}
+		}
+	}
+
 	impl sp_offchain::OffchainWorkerApi<Block> for Runtime {
 		fn offchain_worker(header: &<Block as BlockT>::Header) {
 			Executive::offchain_worker(header)
@@ -434,23 +651,26 @@ impl_runtime_apis! {
 		}
 
 		fn submit_report_equivocation_unsigned_extrinsic(
-			_equivocation_proof: sp_consensus_grandpa::EquivocationProof<
+			equivocation_proof: sp_consensus_grandpa::EquivocationProof<
 				<Block as BlockT>::Hash,
 				NumberFor<Block>,
 			>,
-			_key_owner_proof: sp_consensus_grandpa::OpaqueKeyOwnershipProof,
+			key_owner_proof: sp_consensus_grandpa::OpaqueKeyOwnershipProof,
 		) -> Option<()> {
-			None
+			let key_owner_proof = key_owner_proof.decode()?;
+
+			Grandpa::submit_unsigned_equivocation_report(equivocation_proof, key_owner_proof)
 		}
 
 		fn generate_key_ownership_proof(
 			_set_id: sp_consensus_grandpa::SetId,
-			_authority_id: GrandpaId,
+			authority_id: GrandpaId,
 		) -> Option<sp_consensus_grandpa::OpaqueKeyOwnershipProof> {
-			// NOTE: this is the only implementation possible since we've
-			// defined our key owner proof type as a bottom type (i.e. a type
-			// with no values).
-			None
+			use codec::Encode;
+
+			Historical::prove((sp_consensus_grandpa::KEY_TYPE, authority_id))
+				.map(|p| p.encode())
+				.map(sp_consensus_grandpa::OpaqueKeyOwnershipProof::new)
 		}
 	}
 
@@ -0,0 +1,231 @@
+//! Storage migrations of the runtime upgrades.
+
+use crate::{
+	opaque::SessionKeys, AccountId, Aura, Council, FedecomPSDemo2, Grandpa, Historical,
+	MarketCouncil, Offences, Runtime, RuntimeOrigin, Session, ValidatorSet, Weight,
+};
+use pallet_fedecom_ps_demo::migrations::v1::MigrateToMarketScopedLedger;
+use frame_support::{
+	migrations::RemovePallet,
+	parameter_types,
+	storage::migration::get_storage_value,
+	traits::{
+		Get, GetStorageVersion, InitializeMembers, OnRuntimeUpgrade, PalletInfoAccess,
+		StorageVersion,
+	},
+	weights::constants::RocksDbWeight,
+	BoundedVec,
+};
+use pallet_session::{historical::NoteHistoricalRoot, SessionManager, WeightInfo};
+use pallet_node_authorization::{Owners, WellKnownNodes};
+use sp_core::{sr25519, OpaquePeerId};
+use sp_std::{collections::btree_set::BTreeSet, marker::PhantomData, prelude::*};
+
+parameter_types! {
+	pub const SudoPalletName: &'static str = "Sudo";
+}
+
+/// Make the holder of the sudo key the only member of the council.
+///
+/// The new member then sets up the actual council with a `council.setMembers` motion. Nothing
+/// is done if there is no sudo key or if the council already has members, e.g. on chains started
+/// with a council at genesis.
+pub struct SudoKeyToCouncil;
+
+impl OnRuntimeUpgrade for SudoKeyToCouncil {
+	fn on_runtime_upgrade() -> Weight {
+		let db_weight = <Runtime as frame_system::Config>::DbWeight::get();
+
+		match get_storage_value::<AccountId>(SudoPalletName::get().as_bytes(), b"Key", &[]) {
+			Some(key) if Council::members().is_empty() => {
+				<Council as InitializeMembers<AccountId>>::initialize_members(&[key]);
+				db_weight.reads_writes(2, 1)
+			},
+			_ => db_weight.reads(2),
+		}
+	}
+}
+
+/// Set the storage version of a pallet added by a runtime upgrade.
+///
+/// The storage version of a pallet is only written at genesis, a pallet added to a running chain
+/// would otherwise keep the version 0 and later run migrations that do not apply to its storage.
+pub struct InitializeStorageVersion<P>(PhantomData<P>);
+
+impl<P: GetStorageVersion + PalletInfoAccess> OnRuntimeUpgrade for InitializeStorageVersion<P> {
+	fn on_runtime_upgrade() -> Weight {
+		let db_weight = <Runtime as frame_system::Config>::DbWeight::get();
+
+		if P::on_chain_storage_version() == StorageVersion::default() {
+			P::current_storage_version().put::<P>();
+			db_weight.reads_writes(1, 1)
+		} else {
+			db_weight.reads(1)
+		}
+	}
+}
+
+/// Make the Aura and GRANDPA authorities of a chain started without a validator set its
+/// validators.
+///
+/// The authorities of such a chain are development accounts, whose account is their Aura key: each
+/// one becomes a validator, with its Aura and GRANDPA keys, taken in the same order, as session
+/// keys, and the current session is recorded like at genesis, so that the authorities are kept at
+/// the next session rotation and their GRANDPA equivocations can be proven. Nothing is done if the
+/// validator set already has members, e.g. on chains started with a validator set at genesis, or
+/// if the Aura and GRANDPA authorities do not pair up.
+pub struct SeedValidatorsFromAuthorities;
+
+impl OnRuntimeUpgrade for SeedValidatorsFromAuthorities {
+	fn on_runtime_upgrade() -> Weight {
+		let db_weight = <Runtime as frame_system::Config>::DbWeight::get();
+
+		if !ValidatorSet::validators().is_empty() {
+			return db_weight.reads(1)
+		}
+		let aura = Aura::authorities();
+		let grandpa = Grandpa::grandpa_authorities();
+		if aura.is_empty() || aura.len() != grandpa.len() {
+			return db_weight.reads(3)
+		}
+
+		let keys: Vec<(AccountId, SessionKeys)> = aura
+			.into_iter()
+			.zip(grandpa)
+			.map(|(aura, (grandpa, _))| {
+				let validator = AccountId::from(sr25519::Public::from(aura.clone()));
+				(validator, SessionKeys { aura, grandpa })
+			})
+			.collect();
+		let validators: Vec<AccountId> =
+			keys.iter().map(|(validator, _)| validator.clone()).collect();
+		// Aura takes as many authorities as the validator set
+		pallet_validator_set::Validators::<Runtime>::put(BoundedVec::truncate_from(
+			validators.clone(),
+		));
+
+		for (validator, session_keys) in &keys {
+			// The session keys need a provider of the account, as at genesis
+			if !frame_system::Pallet::<Runtime>::account_exists(validator) {
+				frame_system::Pallet::<Runtime>::inc_providers(validator);
+			}
+			// Only fails if the keys are already used by another validator, which then keeps them
+			let _ = Session::set_keys(
+				RuntimeOrigin::signed(validator.clone()),
+				session_keys.clone(),
+				Vec::new(),
+			);
+		}
+		pallet_session::Validators::<Runtime>::put(validators);
+		pallet_session::QueuedKeys::<Runtime>::put(&keys);
+		NoteHistoricalRoot::<Runtime, ValidatorSet>::new_session_genesis(Session::current_index());
+
+		let set_keys = <Runtime as pallet_session::Config>::WeightInfo::set_keys();
+		db_weight
+			.reads_writes(5, 5)
+			.saturating_add(set_keys.saturating_mul(keys.len() as u64))
+	}
+}
+
+/// Prefix of the peer id of a node with an ed25519 node key, followed by its public key: the
+/// identity multihash of the protobuf encoding of the key.
+const ED25519_PEER_ID_PREFIX: [u8; 6] = [0x00, 0x24, 0x08, 0x01, 0x12, 0x20];
+
+/// Public node keys of the nodes of the development validators, Alice and Bob, started with the
+/// node keys `0x00..01` and `0x00..02`, with the sr25519 public key of their owner.
+const DEVELOPMENT_NODES: [([u8; 32], [u8; 32]); 2] = [
+	(
+		[
+			0x4c, 0xb5, 0xab, 0xf6, 0xad, 0x79, 0xfb, 0xf5, 0xab, 0xbc, 0xca, 0xfc, 0xc2, 0x69,
+			0xd8, 0x5c, 0xd2, 0x65, 0x1e, 0xd4, 0xb8, 0x85, 0xb5, 0x86, 0x9f, 0x24, 0x1a, 0xed,
+			0xf0, 0xa5, 0xba, 0x29,
+		],
+		[
+			0xd4, 0x35, 0x93, 0xc7, 0x15, 0xfd, 0xd3, 0x1c, 0x61, 0x14, 0x1a, 0xbd, 0x04, 0xa9,
+			0x9f, 0xd6, 0x82, 0x2c, 0x85, 0x58, 0x85, 0x4c, 0xcd, 0xe3, 0x9a, 0x56, 0x84, 0xe7,
+			0xa5, 0x6d, 0xa2, 0x7d,
+		],
+	),
+	(
+		[
+			0x74, 0x22, 0xb9, 0x88, 0x75, 0x98, 0x06, 0x8e, 0x32, 0xc4, 0x44, 0x8a, 0x94, 0x9a,
+			0xdb, 0x29, 0x0d, 0x0f, 0x4e, 0x35, 0xb9, 0xe0, 0x1b, 0x0e, 0xe5, 0xf1, 0xa1, 0xe6,
+			0x00, 0xfe, 0x26, 0x74,
+		],
+		[
+			0x8e, 0xaf, 0x04, 0x15, 0x16, 0x87, 0x73, 0x63, 0x26, 0xc9, 0xfe, 0xa1, 0x7e, 0x25,
+			0xfc, 0x52, 0x87, 0x61, 0x36, 0x93, 0xc9, 0x12, 0x90, 0x9c, 0xb2, 0x26, 0xaa, 0x47,
+			0x94, 0xf2, 0x6a, 0x48,
+		],
+	),
+];
+
+/// Peer id of the node with the ed25519 public node key `public`.
+pub fn ed25519_peer_id(public: &[u8; 32]) -> OpaquePeerId {
+	OpaquePeerId([&ED25519_PEER_ID_PREFIX[..], &public[..]].concat())
+}
+
+/// Authorize the nodes of the validators of a chain started without node authorization.
+///
+/// Without well-known nodes, the nodes of such a chain would drop all their peers once the upgrade
+/// adding `pallet_node_authorization` is applied. The nodes of the development validators, started
+/// with the node keys of the local testnet, become the well-known nodes of their owners among the
+/// validators, who then authorize the other nodes of the chain with a council motion. Nothing is
+/// done if there are well-known nodes already, e.g. on chains started with them at genesis.
+pub struct SeedWellKnownNodes;
+
+impl OnRuntimeUpgrade for SeedWellKnownNodes {
+	fn on_runtime_upgrade() -> Weight {
+		let db_weight = <Runtime as frame_system::Config>::DbWeight::get();
+
+		if !WellKnownNodes::<Runtime>::get().is_empty() {
+			return db_weight.reads(1)
+		}
+		let validators = ValidatorSet::validators();
+		let mut nodes = BTreeSet::new();
+		for (node_key, owner) in DEVELOPMENT_NODES.iter() {
+			let owner = AccountId::new(*owner);
+			if validators.contains(&owner) {
+				let node = ed25519_peer_id(node_key);
+				Owners::<Runtime>::insert(&node, owner);
+				nodes.insert(node);
+			}
+		}
+		let writes = nodes.len() as u64 + 1;
+		WellKnownNodes::<Runtime>::put(nodes);
+
+		db_weight.reads_writes(2, writes)
+	}
+}
+
+/// Migrations of the runtime upgrades:
+///
+/// - replacing `pallet_sudo` with the council: the holder of the sudo key becomes the only member
+///   of the council, before the storage of `pallet_sudo` is removed, and the storage version of
+///   the council is initialized. Once removed, there is no sudo key left, so the migration does
+///   nothing on later upgrades;
+/// - adding the validator set and the session keys: the authorities of chains started without
+///   them become the validators;
+/// - restricting the peer-to-peer connections: the nodes of the development validators are
+///   authorized on chains started without authorized nodes;
+/// - reporting the GRANDPA equivocations: the storage versions of the added pallets are
+///   initialized;
+/// - keying the offers of the flexibility market by market and period: the ledger of the existing
+///   market is migrated;
+/// - adding the second flexibility market and its council: the default instance of the
+///   instantiable `pallet_fedecom_ps_demo` keeps the name, index and storage prefix
+///   `FedecomPSDemo`, so the existing market storage already is the storage of the default
+///   instance. The second market starts empty, with the keyed ledger, so only the storage versions
+///   of the added pallets are initialized.
+pub type Migrations = (
+	SudoKeyToCouncil,
+	RemovePallet<SudoPalletName, RocksDbWeight>,
+	InitializeStorageVersion<Council>,
+	SeedValidatorsFromAuthorities,
+	InitializeStorageVersion<Historical>,
+	InitializeStorageVersion<Offences>,
+	SeedWellKnownNodes,
+	MigrateToMarketScopedLedger<Runtime>,
+	InitializeStorageVersion<FedecomPSDemo2>,
+	InitializeStorageVersion<MarketCouncil>,
+);
@@ -0,0 +1,354 @@
+//! Integration tests against the full `node_template_runtime::Runtime`.
+//!
+//! Unlike the pallet tests, which use a bare mock runtime, these tests build real signed
+//! `UncheckedExtrinsic`s carrying the runtime's `SignedExtra` and apply them through `Executive`,
+//! so that the signed extensions, the fee payment and the pallet wiring are exercised together.
+
+use codec::Encode;
+use frame_support::dispatch::GetDispatchInfo;
+use frame_support::{assert_ok, traits::Contains};
+use node_template_runtime::{
+	AccountId, Balance, BalancesConfig, BaseFilter, Executive, FedecomPSDemo, FedecomPSDemoConfig,
+	Header, Nonce, Runtime, RuntimeCall, RuntimeEvent, RuntimeGenesisConfig, RuntimeOrigin,
+	Signature, SignedExtra, SignedPayload, System, TransactionPayment, UncheckedExtrinsic,
+};
+use pallet_fedecom_ps_demo::{
+	FlexibilitySellingData, FLEXIBILITY_SELLING_STATE_CONFIRMED,
+	FLEXIBILITY_SELLING_STATE_NOT_DECIDED, PARTICIPANT_ROLE_DSO, PARTICIPANT_ROLE_MARKET_OPERATOR,
+	PARTICIPANT_ROLE_PROSUMER,
+};
+use sp_keyring::AccountKeyring;
+use sp_runtime::{
+	generic::Era,
+	traits::Header as HeaderT,
+	transaction_validity::{InvalidTransaction, TransactionValidityError},
+	BuildStorage, DispatchError,
+};
+
+const INITIAL_BALANCE: Balance = 1 << 60;
+
+fn new_test_ext() -> sp_io::TestExternalities {
+	let alice = AccountKeyring::Alice.to_account_id();
+	let charlie = AccountKeyring::Charlie.to_account_id();
+	let storage = RuntimeGenesisConfig {
+		balances: BalancesConfig {
+			balances: vec![
+				(AccountKeyring::Alice.to_account_id(), INITIAL_BALANCE),
+				(AccountKeyring::Bob.to_account_id(), INITIAL_BALANCE),
+			],
+		},
+		// Alice sells the flexibility of her asset 7 to Bob on the markets 1 and 2
+		fedecom_ps_demo: FedecomPSDemoConfig {
+			participants: vec![
+				(alice.clone(), PARTICIPANT_ROLE_PROSUMER),
+				(AccountKeyring::Bob.to_account_id(), PARTICIPANT_ROLE_DSO),
+				(charlie.clone(), PARTICIPANT_ROLE_MARKET_OPERATOR),
+			],
+			markets: vec![(1, charlie.clone()), (2, charlie)],
+			assets: vec![(7, alice)],
+			..Default::default()
+		},
+		..Default::default()
+	}
+	.build_storage()
+	.unwrap();
+
+	let mut ext = sp_io::TestExternalities::new(storage);
+	ext.execute_with(|| {
+		let header = Header::new(
+			1,
+			Default::default(),
+			Default::default(),
+			System::block_hash(0),
+			Default::default(),
+		);
+		Executive::initialize_block(&header);
+	});
+	ext
+}
+
+fn signed_extra(nonce: Nonce) -> SignedExtra {
+	(
+		frame_system::CheckNonZeroSender::<Runtime>::new(),
+		frame_system::CheckSpecVersion::<Runtime>::new(),
+		frame_system::CheckTxVersion::<Runtime>::new(),
+		frame_system::CheckGenesis::<Runtime>::new(),
+		frame_system::CheckEra::<Runtime>::from(Era::Immortal),
+		frame_system::CheckNonce::<Runtime>::from(nonce),
+		frame_system::CheckWeight::<Runtime>::new(),
+		pallet_transaction_payment::ChargeTransactionPayment::<Runtime>::from(0),
+	)
+}
+
+/// Build an extrinsic signed by `signer`, as a wallet would do.
+fn sign(signer: AccountKeyring, call: RuntimeCall, nonce: Nonce) -> UncheckedExtrinsic {
+	let extra = signed_extra(nonce);
+	let payload = SignedPayload::new(call.clone(), extra.clone()).unwrap();
+	let signature = payload.using_encoded(|e| signer.sign(e));
+
+	UncheckedExtrinsic::new_signed(
+		call,
+		signer.to_account_id().into(),
+		Signature::Sr25519(signature),
+		extra,
+	)
+}
+
+/// Apply `xt` and return the fee it is expected to have been charged.
+fn apply(xt: UncheckedExtrinsic) -> (Result<(), DispatchError>, Balance) {
+	let len = xt.encoded_size() as u32;
+	let fee = TransactionPayment::compute_fee(len, &xt.get_dispatch_info(), 0);
+	let result = Executive::apply_extrinsic(xt).expect("extrinsic is valid");
+	(result, fee)
+}
+
+fn free_balance(who: &AccountId) -> Balance {
+	System::account(who).data.free
+}
+
+fn add_payment_call(receiver: &AccountId, ts: u32, value: u32) -> RuntimeCall {
+	pallet_fedecom_ps_demo::Call::add_payment { key_receiver: receiver.clone(), ts, value }.into()
+}
+
+#[test]
+fn add_payment_charges_fee_and_updates_storage() {
+	new_test_ext().execute_with(|| {
+		let alice = AccountKeyring::Alice.to_account_id();
+		let bob = AccountKeyring::Bob.to_account_id();
+
+		let (result, fee) = apply(sign(AccountKeyring::Alice, add_payment_call(&bob, 10, 100), 0));
+
+		assert_eq!(result, Ok(()));
+		assert!(fee > 0);
+		assert_eq!(free_balance(&alice), INITIAL_BALANCE - fee);
+		assert_eq!(free_balance(&bob), INITIAL_BALANCE);
+		assert_eq!(System::account_nonce(&alice), 1);
+		assert_eq!(FedecomPSDemo::get_payment(alice.clone(), bob.clone(), 10), 100);
+		assert!(System::events().iter().any(|record| matches!(
+			&record.event,
+			RuntimeEvent::FedecomPSDemo(pallet_fedecom_ps_demo::Event::AddedToPayments {
+				key_sender,
+				value: 100,
+				..
+			}) if *key_sender == alice
+		)));
+	});
+}
+
+#[test]
+fn failed_dispatch_still_pays_fee_and_bumps_nonce() {
+	new_test_ext().execute_with(|| {
+		let alice = AccountKeyring::Alice.to_account_id();
+		let bob = AccountKeyring::Bob.to_account_id();
+
+		let (result, first_fee) =
+			apply(sign(AccountKeyring::Alice, add_payment_call(&bob, 10, 100), 0));
+		assert_eq!(result, Ok(()));
+
+		// The same payment cannot be added twice, but the extrinsic is still included
+		let (result, second_fee) =
+			apply(sign(AccountKeyring::Alice, add_payment_call(&bob, 10, 200), 1));
+		assert_eq!(
+			result,
+			Err(pallet_fedecom_ps_demo::Error::<Runtime>::PaymentAlreadyExists.into())
+		);
+
+		assert_eq!(free_balance(&alice), INITIAL_BALANCE - first_fee - second_fee);
+		assert_eq!(System::account_nonce(&alice), 2);
+		assert_eq!(FedecomPSDemo::get_payment(alice, bob, 10), 100);
+	});
+}
+
+#[test]
+fn nonce_is_checked() {
+	new_test_ext().execute_with(|| {
+		let bob = AccountKeyring::Bob.to_account_id();
+
+		assert_eq!(
+			Executive::apply_extrinsic(sign(
+				AccountKeyring::Alice,
+				add_payment_call(&bob, 10, 100),
+				1
+			)),
+			Err(TransactionValidityError::Invalid(InvalidTransaction::Future))
+		);
+
+		assert!(Executive::apply_extrinsic(sign(
+			AccountKeyring::Alice,
+			add_payment_call(&bob, 10, 100),
+			0
+		))
+		.is_ok());
+
+		assert_eq!(
+			Executive::apply_extrinsic(sign(
+				AccountKeyring::Alice,
+				add_payment_call(&bob, 11, 100),
+				0
+			)),
+			Err(TransactionValidityError::Invalid(InvalidTransaction::Stale))
+		);
+	});
+}
+
+#[test]
+fn bad_signature_is_rejected() {
+	new_test_ext().execute_with(|| {
+		let alice = AccountKeyring::Alice.to_account_id();
+		let bob = AccountKeyring::Bob.to_account_id();
+
+		// Signed by Bob but claiming to come from Alice
+		let xt = sign(AccountKeyring::Bob, add_payment_call(&bob, 10, 100), 0);
+		let xt = UncheckedExtrinsic::new_signed(
+			xt.function,
+			alice.clone().into(),
+			xt.signature.unwrap().1,
+			signed_extra(0),
+		);
+
+		assert_eq!(
+			Executive::apply_extrinsic(xt),
+			Err(TransactionValidityError::Invalid(InvalidTransaction::BadProof))
+		);
+		assert_eq!(free_balance(&alice), INITIAL_BALANCE);
+		assert_eq!(System::account_nonce(&alice), 0);
+	});
+}
+
+#[test]
+fn unfunded_account_cannot_pay_fees() {
+	new_test_ext().execute_with(|| {
+		let charlie = AccountKeyring::Charlie.to_account_id();
+		let bob = AccountKeyring::Bob.to_account_id();
+
+		assert_eq!(
+			Executive::apply_extrinsic(sign(
+				AccountKeyring::Charlie,
+				add_payment_call(&bob, 10, 100),
+				0
+			)),
+			Err(TransactionValidityError::Invalid(InvalidTransaction::Payment))
+		);
+		assert!(!FedecomPSDemo::check_payment(charlie, bob, 10));
+	});
+}
+
+#[test]
+fn flexibility_selling_and_purchase_through_executive() {
+	new_test_ext().execute_with(|| {
+		let seller = AccountKeyring::Alice.to_account_id();
+		let buyer = AccountKeyring::Bob.to_account_id();
+		let (market, market_ts, asset, sold_power, change_fct_w) = (1, 1_000, 7, 10, 2);
+
+		let (result, seller_fee) = apply(sign(
+			AccountKeyring::Alice,
+			pallet_fedecom_ps_demo::Call::flexibility_selling {
+				buyer: buyer.clone(),
+				flexibility_market_identifier: market,
+				flexibility_market_timestamp: market_ts,
+				asset_identifier: asset,
+				sold_power,
+				change_fct_w,
+			}
+			.into(),
+			0,
+		));
+		assert_eq!(result, Ok(()));
+		assert_eq!(
+			FedecomPSDemo::get_flexibility_selling(
+				seller.clone(),
+				buyer.clone(),
+				market,
+				market_ts,
+				asset
+			),
+			FlexibilitySellingData {
+				sold_power,
+				change_fct_w,
+				state: FLEXIBILITY_SELLING_STATE_NOT_DECIDED
+			}
+		);
+
+		let (result, buyer_fee) = apply(sign(
+			AccountKeyring::Bob,
+			pallet_fedecom_ps_demo::Call::flexibility_purchase_decision {
+				seller: seller.clone(),
+				flexibility_market_identifier: market,
+				flexibility_market_timestamp: market_ts,
+				asset_identifier: asset,
+				new_state: FLEXIBILITY_SELLING_STATE_CONFIRMED,
+			}
+			.into(),
+			0,
+		));
+		assert_eq!(result, Ok(()));
+
+		assert_eq!(
+			FedecomPSDemo::get_flexibility_selling(
+				seller.clone(),
+				buyer.clone(),
+				market,
+				market_ts,
+				asset
+			)
+			.state,
+			FLEXIBILITY_SELLING_STATE_CONFIRMED
+		);
+		assert_eq!(
+			FedecomPSDemo::get_payment(buyer.clone(), seller.clone(), market_ts),
+			sold_power * change_fct_w
+		);
+		assert_eq!(free_balance(&seller), INITIAL_BALANCE - seller_fee);
+		assert_eq!(free_balance(&buyer), INITIAL_BALANCE - buyer_fee);
+	});
+}
+
+fn flexibility_selling_call(buyer: &AccountId, market: u32) -> RuntimeCall {
+	pallet_fedecom_ps_demo::Call::flexibility_selling {
+		buyer: buyer.clone(),
+		flexibility_market_identifier: market,
+		flexibility_market_timestamp: 1_000,
+		asset_identifier: 7,
+		sold_power: 10,
+		change_fct_w: 2,
+	}
+	.into()
+}
+
+#[test]
+fn paused_market_calls_are_filtered() {
+	new_test_ext().execute_with(|| {
+		let alice = AccountKeyring::Alice.to_account_id();
+		let bob = AccountKeyring::Bob.to_account_id();
+		assert_ok!(FedecomPSDemo::pause_market(RuntimeOrigin::root(), 1));
+
+		assert!(!BaseFilter::contains(&flexibility_selling_call(&bob, 1)));
+		assert!(BaseFilter::contains(&flexibility_selling_call(&bob, 2)));
+
+		// Included, the fee is paid but the call is not dispatched
+		let (result, fee) = apply(sign(AccountKeyring::Alice, flexibility_selling_call(&bob, 1), 0));
+		assert_eq!(result, Err(frame_system::Error::<Runtime>::CallFiltered.into()));
+		assert_eq!(free_balance(&alice), INITIAL_BALANCE - fee);
+		assert_eq!(
+			FedecomPSDemo::get_flexibility_selling(alice.clone(), bob.clone(), 1, 1_000, 7),
+			FlexibilitySellingData::default()
+		);
+
+		let (result, _) = apply(sign(AccountKeyring::Alice, flexibility_selling_call(&bob, 2), 1));
+		assert_eq!(result, Ok(()));
+	});
+}
+
+#[test]
+fn pausing_the_pallet_keeps_other_pallets_available() {
+	new_test_ext().execute_with(|| {
+		let bob = AccountKeyring::Bob.to_account_id();
+		assert_ok!(FedecomPSDemo::pause_pallet(RuntimeOrigin::root()));
+
+		assert!(!BaseFilter::contains(&add_payment_call(&bob, 10, 100)));
+		assert!(BaseFilter::contains(&RuntimeCall::System(frame_system::Call::remark {
+			remark: vec![]
+		})));
+		assert!(BaseFilter::contains(&pallet_fedecom_ps_demo::Call::unpause_pallet {}.into()));
+	});
+}
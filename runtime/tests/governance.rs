@@ -0,0 +1,184 @@
+//! Integration tests of the council governance of the full `node_template_runtime::Runtime`.
+
+use codec::Encode;
+use frame_support::{
+	assert_ok,
+	dispatch::GetDispatchInfo,
+	storage::migration::{get_storage_value, put_storage_value},
+	traits::OnRuntimeUpgrade,
+};
+use node_template_runtime::{
+	migrations::Migrations, pallet_fedecom_ps_demo::PARTICIPANT_ROLE_PROSUMER, AccountId,
+	Council, CouncilConfig, FedecomPSDemo, RuntimeCall, RuntimeEvent, RuntimeGenesisConfig,
+	RuntimeOrigin, System, ValidatorSet, ValidatorSetConfig,
+};
+use sp_keyring::AccountKeyring;
+use sp_runtime::{
+	traits::{BlakeTwo256, Hash},
+	BuildStorage,
+};
+
+fn new_test_ext(council: Vec<AccountKeyring>) -> sp_io::TestExternalities {
+	let storage = RuntimeGenesisConfig {
+		council: CouncilConfig {
+			members: council.iter().map(|k| k.to_account_id()).collect(),
+			..Default::default()
+		},
+		validator_set: ValidatorSetConfig {
+			initial_validators: vec![AccountKeyring::Alice.to_account_id()],
+		},
+		..Default::default()
+	}
+	.build_storage()
+	.unwrap();
+
+	let mut ext = sp_io::TestExternalities::new(storage);
+	ext.execute_with(|| System::set_block_number(1));
+	ext
+}
+
+fn council() -> Vec<AccountKeyring> {
+	vec![AccountKeyring::Alice, AccountKeyring::Bob, AccountKeyring::Charlie]
+}
+
+/// Propose `call` with `threshold` to the council as Alice, then get it approved by `voters` and
+/// close the motion.
+fn motion(call: RuntimeCall, threshold: u32, voters: &[AccountKeyring]) {
+	let alice = AccountKeyring::Alice.to_account_id();
+	let len = call.encoded_size() as u32;
+	let weight = call.get_dispatch_info().weight;
+	let hash = BlakeTwo256::hash_of(&call);
+	let index = Council::proposal_count();
+
+	assert_ok!(Council::propose(
+		RuntimeOrigin::signed(alice.clone()),
+		threshold,
+		Box::new(call),
+		len
+	));
+	for voter in voters {
+		assert_ok!(Council::vote(RuntimeOrigin::signed(voter.to_account_id()), hash, index, true));
+	}
+	assert_ok!(Council::close(RuntimeOrigin::signed(alice), hash, index, weight, len));
+}
+
+fn add_validator_call(validator: &AccountId) -> RuntimeCall {
+	pallet_validator_set::Call::add_validator { validator: validator.clone() }.into()
+}
+
+#[test]
+fn two_thirds_of_the_council_manage_validators() {
+	new_test_ext(council()).execute_with(|| {
+		let dave = AccountKeyring::Dave.to_account_id();
+
+		motion(add_validator_call(&dave), 2, &[AccountKeyring::Alice, AccountKeyring::Bob]);
+
+		assert!(ValidatorSet::validators().contains(&dave));
+	});
+}
+
+#[test]
+fn a_single_member_cannot_act_for_the_council() {
+	new_test_ext(council()).execute_with(|| {
+		let dave = AccountKeyring::Dave.to_account_id();
+
+		// Executed right away with a single approval, but the origin is not two thirds
+		assert_ok!(Council::propose(
+			RuntimeOrigin::signed(AccountKeyring::Alice.to_account_id()),
+			1,
+			Box::new(add_validator_call(&dave)),
+			100
+		));
+		assert!(!ValidatorSet::validators().contains(&dave));
+
+		assert!(ValidatorSet::add_validator(
+			RuntimeOrigin::signed(AccountKeyring::Alice.to_account_id()),
+			dave
+		)
+		.is_err());
+	});
+}
+
+#[test]
+fn council_registers_market_participants() {
+	new_test_ext(council()).execute_with(|| {
+		let eve = AccountKeyring::Eve.to_account_id();
+
+		motion(
+			pallet_fedecom_ps_demo::Call::register_participant {
+				participant: eve.clone(),
+				role: PARTICIPANT_ROLE_PROSUMER,
+			}
+			.into(),
+			2,
+			&[AccountKeyring::Alice, AccountKeyring::Charlie],
+		);
+
+		assert_eq!(FedecomPSDemo::participants(eve), Some(PARTICIPANT_ROLE_PROSUMER));
+	});
+}
+
+#[test]
+fn council_dispatches_root_calls() {
+	new_test_ext(council()).execute_with(|| {
+		let set_heap_pages: RuntimeCall = frame_system::Call::set_heap_pages { pages: 128 }.into();
+
+		motion(
+			pallet_governance::Call::dispatch_as_root { call: Box::new(set_heap_pages) }.into(),
+			2,
+			&[AccountKeyring::Alice, AccountKeyring::Bob],
+		);
+
+		assert!(System::events().iter().any(|record| matches!(
+			record.event,
+			RuntimeEvent::Governance(pallet_governance::Event::DispatchedAsRoot { result: Ok(()) })
+		)));
+		assert_eq!(
+			sp_io::storage::get(sp_core::storage::well_known_keys::HEAP_PAGES),
+			Some(128u64.encode().into())
+		);
+	});
+}
+
+#[test]
+fn sudo_key_is_handed_over_to_the_council() {
+	new_test_ext(vec![]).execute_with(|| {
+		let alice = AccountKeyring::Alice.to_account_id();
+		let eve = AccountKeyring::Eve.to_account_id();
+		put_storage_value(b"Sudo", b"Key", &[], alice.clone());
+
+		Migrations::on_runtime_upgrade();
+
+		assert_eq!(Council::members(), vec![alice.clone()]);
+		assert_eq!(get_storage_value::<AccountId>(b"Sudo", b"Key", &[]), None);
+
+		// The former sudo key holder governs the chain through the council
+		let call: RuntimeCall = pallet_fedecom_ps_demo::Call::register_participant {
+			participant: eve.clone(),
+			role: PARTICIPANT_ROLE_PROSUMER,
+		}
+		.into();
+		let len = call.encoded_size() as u32;
+		assert_ok!(Council::propose(RuntimeOrigin::signed(alice), 1, Box::new(call), len));
+		assert_eq!(FedecomPSDemo::participants(eve), Some(PARTICIPANT_ROLE_PROSUMER));
+
+		// Nothing is left to hand over on the next upgrade
+		Migrations::on_runtime_upgrade();
+		assert_eq!(Council::members(), vec![AccountKeyring::Alice.to_account_id()]);
+	});
+}
+
+#[test]
+fn existing_council_is_kept_by_the_migration() {
+	new_test_ext(council()).execute_with(|| {
+		let dave = AccountKeyring::Dave.to_account_id();
+		put_storage_value(b"Sudo", b"Key", &[], dave);
+
+		Migrations::on_runtime_upgrade();
+
+		let mut members: Vec<AccountId> = council().iter().map(|k| k.to_account_id()).collect();
+		members.sort();
+		assert_eq!(Council::members(), members);
+		assert_eq!(get_storage_value::<AccountId>(b"Sudo", b"Key", &[]), None);
+	});
+}
@@ -0,0 +1,149 @@
+//! Integration tests of the offence handling of the full `node_template_runtime::Runtime`.
+//!
+//! Offences reported through `pallet_offences` (e.g. GRANDPA equivocations) are handed to the
+//! validator set, which disables the offender in `pallet_session` (and therefore in Aura) until the
+//! end of the session. The offender stays in the set, removing it is left to governance.
+
+use frame_support::weights::Weight;
+use node_template_runtime::{
+	opaque::SessionKeys, AccountId, Perbill, Runtime, RuntimeEvent, RuntimeGenesisConfig,
+	Session, SessionConfig, System, ValidatorSet, ValidatorSetConfig,
+};
+use sp_keyring::{AccountKeyring, Ed25519Keyring};
+use sp_runtime::BuildStorage;
+use sp_staking::offence::{DisableStrategy, OffenceDetails, OnOffenceHandler};
+
+const VALIDATORS: [(AccountKeyring, Ed25519Keyring); 3] = [
+	(AccountKeyring::Alice, Ed25519Keyring::Alice),
+	(AccountKeyring::Bob, Ed25519Keyring::Bob),
+	(AccountKeyring::Charlie, Ed25519Keyring::Charlie),
+];
+
+fn new_test_ext() -> sp_io::TestExternalities {
+	let storage = RuntimeGenesisConfig {
+		validator_set: ValidatorSetConfig {
+			initial_validators: VALIDATORS.iter().map(|(sr, _)| sr.to_account_id()).collect(),
+		},
+		session: SessionConfig {
+			keys: VALIDATORS
+				.iter()
+				.map(|(sr, ed)| {
+					let keys =
+						SessionKeys { aura: sr.public().into(), grandpa: ed.public().into() };
+					(sr.to_account_id(), sr.to_account_id(), keys)
+				})
+				.collect(),
+		},
+		..Default::default()
+	}
+	.build_storage()
+	.unwrap();
+
+	let mut ext = sp_io::TestExternalities::new(storage);
+	ext.execute_with(|| System::set_block_number(1));
+	ext
+}
+
+fn report(offender: &AccountId, fraction: Perbill, strategy: DisableStrategy) -> Weight {
+	<ValidatorSet as OnOffenceHandler<AccountId, (AccountId, ()), Weight>>::on_offence(
+		&[OffenceDetails { offender: (offender.clone(), ()), reporters: vec![] }],
+		&[fraction],
+		Session::current_index(),
+		strategy,
+	)
+}
+
+/// Number of `ValidatorOffended` events of `offender`.
+fn offended(offender: &AccountId) -> usize {
+	System::events()
+		.iter()
+		.filter(|record| {
+			matches!(
+				&record.event,
+				RuntimeEvent::ValidatorSet(pallet_validator_set::Event::ValidatorOffended {
+					validator,
+					..
+				}) if validator == offender
+			)
+		})
+		.count()
+}
+
+fn session_index_of(validator: &AccountId) -> u32 {
+	Session::validators().iter().position(|v| v == validator).unwrap() as u32
+}
+
+#[test]
+fn offender_is_disabled_and_stays_in_the_set() {
+	new_test_ext().execute_with(|| {
+		let bob = AccountKeyring::Bob.to_account_id();
+
+		report(&bob, Perbill::from_percent(100), DisableStrategy::WhenSlashed);
+
+		assert_eq!(Session::disabled_validators(), vec![session_index_of(&bob)]);
+		assert!(ValidatorSet::validators().contains(&bob));
+		assert_eq!(offended(&bob), 1);
+	});
+}
+
+#[test]
+fn offender_is_not_disabled_when_not_slashed() {
+	new_test_ext().execute_with(|| {
+		let bob = AccountKeyring::Bob.to_account_id();
+
+		report(&bob, Perbill::zero(), DisableStrategy::WhenSlashed);
+		report(&bob, Perbill::from_percent(100), DisableStrategy::Never);
+
+		assert!(Session::disabled_validators().is_empty());
+		assert!(ValidatorSet::validators().contains(&bob));
+		assert_eq!(offended(&bob), 0);
+	});
+}
+
+#[test]
+fn offender_is_disabled_when_always_required() {
+	new_test_ext().execute_with(|| {
+		let charlie = AccountKeyring::Charlie.to_account_id();
+
+		report(&charlie, Perbill::zero(), DisableStrategy::Always);
+
+		assert_eq!(Session::disabled_validators(), vec![session_index_of(&charlie)]);
+		assert!(ValidatorSet::validators().contains(&charlie));
+	});
+}
+
+#[test]
+fn offence_is_only_reported_when_the_offender_gets_disabled() {
+	new_test_ext().execute_with(|| {
+		let bob = AccountKeyring::Bob.to_account_id();
+		let dave = AccountKeyring::Dave.to_account_id();
+
+		report(&bob, Perbill::zero(), DisableStrategy::Always);
+		// Already disabled in this session
+		report(&bob, Perbill::zero(), DisableStrategy::Always);
+		// Not a validator
+		report(&dave, Perbill::zero(), DisableStrategy::Always);
+
+		assert_eq!(offended(&bob), 1);
+		assert_eq!(offended(&dave), 0);
+		assert_eq!(Session::disabled_validators(), vec![session_index_of(&bob)]);
+	});
+}
+
+#[test]
+fn historical_session_trie_proves_grandpa_keys() {
+	use frame_support::traits::KeyOwnerProofSystem;
+	use node_template_runtime::Historical;
+
+	new_test_ext().execute_with(|| {
+		let grandpa_key: (_, pallet_grandpa::AuthorityId) =
+			(sp_consensus_grandpa::KEY_TYPE, Ed25519Keyring::Bob.public().into());
+
+		let proof = Historical::prove(grandpa_key.clone()).expect("Bob is a validator");
+
+		assert_eq!(
+			Historical::check_proof(grandpa_key, proof),
+			Some((AccountKeyring::Bob.to_account_id(), ()))
+		);
+	});
+}
@@ -0,0 +1,179 @@
+//! Integration tests of the runtime upgrade of chains started with the runtime of the node
+//! template, without a validator set, session keys, authorized nodes or council.
+
+use codec::Encode;
+use frame_support::{
+	assert_ok,
+	storage::migration::{get_storage_value, put_storage_value},
+	traits::{GetStorageVersion, KeyOwnerProofSystem, OnRuntimeUpgrade},
+};
+use node_template_runtime::{
+	migrations::{ed25519_peer_id, Migrations},
+	opaque::SessionKeys,
+	AccountId, Aura, Balance, Council, CouncilCollective, FedecomPSDemo2, Historical,
+	MarketCouncil, Runtime, RuntimeCall, RuntimeOrigin, Session, System, ValidatorSet,
+};
+use pallet_node_authorization::{Owners, WellKnownNodes};
+use sp_core::{ed25519, OpaquePeerId, Pair};
+use sp_keyring::{AccountKeyring, Ed25519Keyring};
+use sp_runtime::BuildStorage;
+use std::collections::BTreeSet;
+
+const INITIAL_BALANCE: Balance = 1 << 60;
+
+const AUTHORITIES: [(AccountKeyring, Ed25519Keyring); 2] = [
+	(AccountKeyring::Alice, Ed25519Keyring::Alice),
+	(AccountKeyring::Bob, Ed25519Keyring::Bob),
+];
+
+/// Externalities of a chain started with the genesis of the node template, whose Aura and GRANDPA
+/// `authorities` are set at genesis.
+fn template_test_ext(authorities: &[(AccountKeyring, Ed25519Keyring)]) -> sp_io::TestExternalities {
+	let mut storage = frame_system::GenesisConfig::<Runtime>::default().build_storage().unwrap();
+	pallet_balances::GenesisConfig::<Runtime> {
+		balances: authorities.iter().map(|(sr, _)| (sr.to_account_id(), INITIAL_BALANCE)).collect(),
+	}
+	.assimilate_storage(&mut storage)
+	.unwrap();
+	pallet_aura::GenesisConfig::<Runtime> {
+		authorities: authorities.iter().map(|(sr, _)| sr.public().into()).collect(),
+	}
+	.assimilate_storage(&mut storage)
+	.unwrap();
+	pallet_grandpa::GenesisConfig::<Runtime> {
+		authorities: authorities.iter().map(|(_, ed)| (ed.public().into(), 1)).collect(),
+		..Default::default()
+	}
+	.assimilate_storage(&mut storage)
+	.unwrap();
+
+	let mut ext = sp_io::TestExternalities::new(storage);
+	ext.execute_with(|| System::set_block_number(1));
+	ext
+}
+
+fn session_keys(sr: AccountKeyring, ed: Ed25519Keyring) -> SessionKeys {
+	SessionKeys { aura: sr.public().into(), grandpa: ed.public().into() }
+}
+
+/// Peer id of the node of the local testnet started with the node key of 31 zero bytes followed
+/// by `index`.
+fn testnet_node(index: u8) -> OpaquePeerId {
+	let mut node_key = [0; 32];
+	node_key[31] = index;
+	ed25519_peer_id(&ed25519::Pair::from_seed(&node_key).public().0)
+}
+
+#[test]
+fn authorities_become_the_validators() {
+	template_test_ext(&AUTHORITIES).execute_with(|| {
+		let validators: Vec<AccountId> =
+			AUTHORITIES.iter().map(|(sr, _)| sr.to_account_id()).collect();
+		let keys: Vec<(AccountId, SessionKeys)> = AUTHORITIES
+			.iter()
+			.map(|(sr, ed)| (sr.to_account_id(), session_keys(*sr, *ed)))
+			.collect();
+		let authorities = Aura::authorities();
+
+		Migrations::on_runtime_upgrade();
+
+		assert_eq!(ValidatorSet::validators().into_inner(), validators);
+		assert_eq!(Session::validators(), validators);
+		assert_eq!(Session::queued_keys(), keys);
+		for (validator, validator_keys) in &keys {
+			let next_keys = pallet_session::NextKeys::<Runtime>::get(validator);
+			assert_eq!(next_keys.as_ref(), Some(validator_keys));
+		}
+		// GRANDPA equivocations of the current session can be proven
+		let bob_grandpa: sp_consensus_grandpa::AuthorityId = Ed25519Keyring::Bob.public().into();
+		assert!(Historical::prove((sp_consensus_grandpa::KEY_TYPE, bob_grandpa)).is_some());
+
+		// The authorities are kept at the next session
+		Session::rotate_session();
+		assert_eq!(Session::current_index(), 1);
+		assert_eq!(Session::validators(), validators);
+		assert_eq!(Aura::authorities(), authorities);
+	});
+}
+
+#[test]
+fn validators_are_only_seeded_once() {
+	template_test_ext(&AUTHORITIES).execute_with(|| {
+		let charlie = AccountKeyring::Charlie.to_account_id();
+		Migrations::on_runtime_upgrade();
+		pallet_validator_set::Validators::<Runtime>::mutate(|validators| {
+			validators.try_push(charlie.clone()).unwrap()
+		});
+
+		Migrations::on_runtime_upgrade();
+
+		let validators = ValidatorSet::validators();
+		assert_eq!(validators.len(), AUTHORITIES.len() + 1);
+		assert!(validators.contains(&charlie));
+	});
+}
+
+#[test]
+fn nodes_of_the_validators_are_authorized() {
+	template_test_ext(&AUTHORITIES).execute_with(|| {
+		Migrations::on_runtime_upgrade();
+
+		let nodes = BTreeSet::from([testnet_node(1), testnet_node(2)]);
+		assert_eq!(WellKnownNodes::<Runtime>::get(), nodes);
+		for (index, (owner, _)) in AUTHORITIES.iter().enumerate() {
+			let node = testnet_node(index as u8 + 1);
+			assert_eq!(Owners::<Runtime>::get(node), Some(owner.to_account_id()));
+		}
+	});
+
+	// Only the nodes of the validators are authorized
+	template_test_ext(&AUTHORITIES[..1]).execute_with(|| {
+		Migrations::on_runtime_upgrade();
+
+		assert_eq!(WellKnownNodes::<Runtime>::get(), BTreeSet::from([testnet_node(1)]));
+	});
+}
+
+#[test]
+fn sudo_key_holder_sets_up_the_council() {
+	template_test_ext(&AUTHORITIES).execute_with(|| {
+		let alice = AccountKeyring::Alice.to_account_id();
+		put_storage_value(b"Sudo", b"Key", &[], alice.clone());
+
+		Migrations::on_runtime_upgrade();
+
+		assert_eq!(Council::members(), vec![alice.clone()]);
+		assert_eq!(get_storage_value::<AccountId>(b"Sudo", b"Key", &[]), None);
+
+		// The only member approves its motions alone
+		let mut members: Vec<AccountId> =
+			AUTHORITIES.iter().map(|(sr, _)| sr.to_account_id()).collect();
+		let call: RuntimeCall = pallet_collective::Call::<Runtime, CouncilCollective>::set_members {
+			new_members: members.clone(),
+			prime: None,
+			old_count: 1,
+		}
+		.into();
+		let len = call.encoded_size() as u32;
+		assert_ok!(Council::propose(RuntimeOrigin::signed(alice), 1, Box::new(call), len));
+
+		members.sort();
+		assert_eq!(Council::members(), members);
+	});
+}
+
+#[test]
+fn storage_versions_of_the_second_market_are_initialized() {
+	template_test_ext(&AUTHORITIES).execute_with(|| {
+		Migrations::on_runtime_upgrade();
+
+		assert_eq!(
+			FedecomPSDemo2::on_chain_storage_version(),
+			FedecomPSDemo2::current_storage_version()
+		);
+		assert_eq!(
+			MarketCouncil::on_chain_storage_version(),
+			MarketCouncil::current_storage_version()
+		);
+	});
+}